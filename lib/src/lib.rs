@@ -0,0 +1,114 @@
+#![warn(unsafe_op_in_unsafe_fn)]
+#![allow(clippy::missing_safety_doc)]
+/*!
+This crate provides a C API for TaskChampion, for use by applications written in C or C++.
+
+The API is described in the generated `taskchampion.h`, which is regenerated with `cargo xtask
+codegen`.  Rust users should use the `taskchampion` crate directly.
+
+# Conventions
+
+ * All types and functions are prefixed with `TC` and `tc_`, respectively.
+ * Replicas, tasks, and other complex values are "opaque handles": pointers to values allocated
+   by this library, which must be freed with the corresponding `tc_.._free` function.
+ * Functions which can fail return a `TCResult`, or NULL for functions returning a pointer.  The
+   error message is available from the object on which the function was called, such as with
+   `tc_replica_error`.
+ * Strings passed into the library are NUL-terminated UTF-8 and remain owned by the caller.
+   Strings returned from the library are owned by the caller and must be freed with
+   `tc_string_free`.
+ */
+
+ffizz_header::snippet! {
+#[ffizz(name="intro", order=0)]
+/// TaskChampion
+///
+/// This file defines the C interface to libtaskchampion.  This is a thin wrapper around the Rust
+/// `taskchampion` crate.  Refer to the documentation for that crate at
+/// https://docs.rs/taskchampion/latest/taskchampion/ for API details.
+///
+/// ## Handles
+///
+/// Replicas, tasks, and other complex values are represented as opaque pointers ("handles") to
+/// values allocated by this library.  Each handle must be freed exactly once, with the
+/// corresponding `tc_.._free` function, and must not be used after it is freed.  Unless stated
+/// otherwise, handles passed to functions are borrowed and not freed by the function.
+///
+/// ## Errors
+///
+/// Functions that can fail return a `TCResult` or a NULL pointer.  The corresponding error message
+/// can be retrieved from the object on which the function was called, with functions like
+/// `tc_replica_error`.  Constructors take an `error_out` argument which, if not NULL, receives the
+/// error message on failure.
+///
+/// ## Strings
+///
+/// Strings passed into the library are NUL-terminated UTF-8 strings, and remain owned by the
+/// caller.  Strings returned from the library are owned by the caller, and must be freed with
+/// `tc_string_free`.
+///
+/// ## Thread Safety
+///
+/// Handles must only be used from one thread at a time.
+///
+/// ```c
+/// #ifndef TASKCHAMPION_H
+/// #define TASKCHAMPION_H
+///
+/// #include <stdbool.h>
+/// #include <stddef.h>
+/// #include <stdint.h>
+///
+/// #ifdef __cplusplus
+/// #define EXTERN_C extern "C"
+/// #else
+/// #define EXTERN_C
+/// #endif // __cplusplus
+/// ```
+}
+
+ffizz_header::snippet! {
+#[ffizz(name="bottomatter", order=10000)]
+/// ```c
+/// #endif /* TASKCHAMPION_H */
+/// ```
+}
+
+mod operations;
+mod replica;
+mod result;
+mod server;
+mod status;
+mod string;
+mod task;
+mod taskdata;
+mod uuid;
+mod workingset;
+
+pub use operations::*;
+pub use replica::*;
+pub use result::*;
+pub use server::*;
+pub use status::*;
+pub use string::*;
+pub use task::*;
+pub use taskdata::*;
+pub use uuid::*;
+pub use workingset::*;
+
+/// Generate the C header for this library.
+#[cfg(debug_assertions)]
+pub fn generate_header() -> String {
+    ffizz_header::generate()
+}
+
+#[cfg(all(test, debug_assertions))]
+mod test {
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn header_is_up_to_date() {
+        // run `cargo xtask codegen` to regenerate the header
+        assert_eq!(super::generate_header(), include_str!("../taskchampion.h"));
+    }
+}
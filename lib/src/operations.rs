@@ -0,0 +1,83 @@
+use taskchampion::{Operation, Operations};
+
+#[ffizz_header::item]
+#[ffizz(order = 50)]
+/// ***** TCOperations *****
+///
+/// TCOperations is a sequence of operations, built up by the functions which modify tasks and
+/// then committed to a replica with `tc_replica_commit_operations`.
+///
+/// ```c
+/// typedef struct TCOperations TCOperations;
+/// ```
+pub struct TCOperations(pub(crate) Operations);
+
+#[ffizz_header::item]
+#[ffizz(order = 500)]
+/// Create a new, empty TCOperations.  The result must be freed with `tc_operations_free`.
+///
+/// ```c
+/// EXTERN_C TCOperations *tc_operations_new(void);
+/// ```
+#[no_mangle]
+pub extern "C" fn tc_operations_new() -> *mut TCOperations {
+    Box::into_raw(Box::new(TCOperations(Operations::new())))
+}
+
+#[ffizz_header::item]
+#[ffizz(order = 501)]
+/// Get the number of operations in the TCOperations.
+///
+/// ```c
+/// EXTERN_C size_t tc_operations_len(const TCOperations *ops);
+/// ```
+#[no_mangle]
+pub unsafe extern "C" fn tc_operations_len(ops: *const TCOperations) -> usize {
+    debug_assert!(!ops.is_null());
+    // SAFETY: caller guarantees ops is a valid TCOperations
+    unsafe { &*ops }.0.len()
+}
+
+#[ffizz_header::item]
+#[ffizz(order = 502)]
+/// Add an undo point to the TCOperations.  When the operations are committed, a subsequent
+/// undo will revert to this point.
+///
+/// ```c
+/// EXTERN_C void tc_operations_add_undo_point(TCOperations *ops);
+/// ```
+#[no_mangle]
+pub unsafe extern "C" fn tc_operations_add_undo_point(ops: *mut TCOperations) {
+    debug_assert!(!ops.is_null());
+    // SAFETY: caller guarantees ops is a valid TCOperations
+    unsafe { &mut *ops }.0.push(Operation::UndoPoint);
+}
+
+#[ffizz_header::item]
+#[ffizz(order = 503)]
+/// Free a TCOperations.  The TCOperations must not be used after this call.
+///
+/// ```c
+/// EXTERN_C void tc_operations_free(TCOperations *ops);
+/// ```
+#[no_mangle]
+pub unsafe extern "C" fn tc_operations_free(ops: *mut TCOperations) {
+    if !ops.is_null() {
+        // SAFETY: ops was allocated by tc_operations_new, and will not be used again
+        drop(unsafe { Box::from_raw(ops) });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn undo_point() {
+        let ops = tc_operations_new();
+        assert_eq!(unsafe { tc_operations_len(ops) }, 0);
+        unsafe { tc_operations_add_undo_point(ops) };
+        assert_eq!(unsafe { tc_operations_len(ops) }, 1);
+        unsafe { tc_operations_free(ops) };
+    }
+}
@@ -0,0 +1,548 @@
+use crate::string::{borrow_str, return_string, set_error_out};
+use crate::task::TCTaskList;
+use crate::{
+    TCOperations, TCResult, TCServer, TCTask, TCTaskData, TCUuid, TCUuidList, TCWorkingSet,
+};
+use libc::c_char;
+use taskchampion::storage::{InMemoryStorage, SqliteStorage};
+use taskchampion::Replica;
+
+#[ffizz_header::item]
+#[ffizz(order = 50)]
+/// ***** TCReplica *****
+///
+/// A replica represents an instance of a user's task data, providing an easy interface
+/// for querying and modifying that data.
+///
+/// When a `tc_replica_..` function that returns a TCResult returns `TC_RESULT_ERROR`, or a
+/// function returning a pointer returns NULL, then `tc_replica_error` will return the error
+/// message.
+///
+/// ```c
+/// typedef struct TCReplica TCReplica;
+/// ```
+pub struct TCReplica {
+    inner: Replica,
+    error: Option<String>,
+}
+
+impl TCReplica {
+    fn new(inner: Replica) -> *mut TCReplica {
+        Box::into_raw(Box::new(TCReplica { inner, error: None }))
+    }
+}
+
+/// Call `f` with the replica, recording any error and returning `err_value` in that case.
+unsafe fn wrap<T, F>(rep: *mut TCReplica, f: F, err_value: T) -> T
+where
+    F: FnOnce(&mut Replica) -> anyhow::Result<T>,
+{
+    debug_assert!(!rep.is_null());
+    // SAFETY: caller guarantees rep is a valid TCReplica, not in use elsewhere
+    let rep = unsafe { &mut *rep };
+    rep.error = None;
+    match f(&mut rep.inner) {
+        Ok(v) => v,
+        Err(e) => {
+            rep.error = Some(e.to_string());
+            err_value
+        }
+    }
+}
+
+#[ffizz_header::item]
+#[ffizz(order = 700)]
+/// Create a new TCReplica with an in-memory database.  The contents of the database will be
+/// lost when it is freed with `tc_replica_free`.
+///
+/// ```c
+/// EXTERN_C TCReplica *tc_replica_new_in_memory(void);
+/// ```
+#[no_mangle]
+pub extern "C" fn tc_replica_new_in_memory() -> *mut TCReplica {
+    TCReplica::new(Replica::new(Box::new(InMemoryStorage::new())))
+}
+
+#[ffizz_header::item]
+#[ffizz(order = 701)]
+/// Create a new TCReplica with an on-disk database in the given directory, creating it if
+/// `create_if_missing` is true.  Returns NULL on error, in which case the error message is
+/// written to `error_out` if it is not NULL.
+///
+/// ```c
+/// EXTERN_C TCReplica *tc_replica_new_on_disk(const char *path,
+///                                            bool create_if_missing,
+///                                            char **error_out);
+/// ```
+#[no_mangle]
+pub unsafe extern "C" fn tc_replica_new_on_disk(
+    path: *const c_char,
+    create_if_missing: bool,
+    error_out: *mut *mut c_char,
+) -> *mut TCReplica {
+    // SAFETY: caller guarantees path is a valid string
+    let storage = unsafe { borrow_str(path) }
+        .and_then(|path| Ok(SqliteStorage::new(path, create_if_missing)?));
+    match storage {
+        Ok(storage) => TCReplica::new(Replica::new(Box::new(storage))),
+        Err(e) => {
+            // SAFETY: caller guarantees error_out is valid or NULL
+            unsafe { set_error_out(error_out, e) };
+            std::ptr::null_mut()
+        }
+    }
+}
+
+#[ffizz_header::item]
+#[ffizz(order = 702)]
+/// Get the latest error for a replica, or NULL if the last operation succeeded.  The returned
+/// string must be freed with `tc_string_free`.
+///
+/// ```c
+/// EXTERN_C char *tc_replica_error(TCReplica *rep);
+/// ```
+#[no_mangle]
+pub unsafe extern "C" fn tc_replica_error(rep: *mut TCReplica) -> *mut c_char {
+    debug_assert!(!rep.is_null());
+    // SAFETY: caller guarantees rep is a valid TCReplica
+    match unsafe { &mut *rep }.error.take() {
+        Some(e) => return_string(e),
+        None => std::ptr::null_mut(),
+    }
+}
+
+#[ffizz_header::item]
+#[ffizz(order = 703)]
+/// Free a replica.  The replica must not be used after this call, but tasks and other values
+/// retrieved from it remain valid until they are freed.
+///
+/// ```c
+/// EXTERN_C void tc_replica_free(TCReplica *rep);
+/// ```
+#[no_mangle]
+pub unsafe extern "C" fn tc_replica_free(rep: *mut TCReplica) {
+    if !rep.is_null() {
+        // SAFETY: rep was allocated by this library, and will not be used again
+        drop(unsafe { Box::from_raw(rep) });
+    }
+}
+
+#[ffizz_header::item]
+#[ffizz(order = 710)]
+/// Create a new task with the given UUID, adding the necessary operations to `ops`.  If the task
+/// already exists, it is returned.  Returns NULL on error.
+///
+/// The result must be freed with `tc_task_free`.
+///
+/// ```c
+/// EXTERN_C TCTask *tc_replica_create_task(TCReplica *rep, TCUuid uuid, TCOperations *ops);
+/// ```
+#[no_mangle]
+pub unsafe extern "C" fn tc_replica_create_task(
+    rep: *mut TCReplica,
+    uuid: TCUuid,
+    ops: *mut TCOperations,
+) -> *mut TCTask {
+    debug_assert!(!ops.is_null());
+    // SAFETY: caller guarantees ops is a valid TCOperations
+    let ops = unsafe { &mut *ops };
+    // SAFETY: caller guarantees rep is valid
+    unsafe {
+        wrap(
+            rep,
+            |rep| {
+                Ok(TCTask::return_ptr(
+                    rep.create_task(uuid.into(), &mut ops.0)?,
+                ))
+            },
+            std::ptr::null_mut(),
+        )
+    }
+}
+
+#[ffizz_header::item]
+#[ffizz(order = 711)]
+/// Get an existing task by its UUID.  Returns NULL if the task does not exist, or on error, in
+/// which case `tc_replica_error` returns the error message.
+///
+/// The result must be freed with `tc_task_free`.
+///
+/// ```c
+/// EXTERN_C TCTask *tc_replica_get_task(TCReplica *rep, TCUuid uuid);
+/// ```
+#[no_mangle]
+pub unsafe extern "C" fn tc_replica_get_task(rep: *mut TCReplica, uuid: TCUuid) -> *mut TCTask {
+    // SAFETY: caller guarantees rep is valid
+    unsafe {
+        wrap(
+            rep,
+            |rep| {
+                Ok(rep
+                    .get_task(uuid.into())?
+                    .map(TCTask::return_ptr)
+                    .unwrap_or(std::ptr::null_mut()))
+            },
+            std::ptr::null_mut(),
+        )
+    }
+}
+
+#[ffizz_header::item]
+#[ffizz(order = 712)]
+/// Get the data for an existing task by its UUID.  Returns NULL if the task does not exist, or
+/// on error, in which case `tc_replica_error` returns the error message.
+///
+/// The result must be freed with `tc_task_data_free`.
+///
+/// ```c
+/// EXTERN_C TCTaskData *tc_replica_get_task_data(TCReplica *rep, TCUuid uuid);
+/// ```
+#[no_mangle]
+pub unsafe extern "C" fn tc_replica_get_task_data(
+    rep: *mut TCReplica,
+    uuid: TCUuid,
+) -> *mut TCTaskData {
+    // SAFETY: caller guarantees rep is valid
+    unsafe {
+        wrap(
+            rep,
+            |rep| {
+                Ok(rep
+                    .get_task_data(uuid.into())?
+                    .map(TCTaskData::return_ptr)
+                    .unwrap_or(std::ptr::null_mut()))
+            },
+            std::ptr::null_mut(),
+        )
+    }
+}
+
+#[ffizz_header::item]
+#[ffizz(order = 713)]
+/// Get a list of all tasks in the replica.  On error, the list is empty and `tc_replica_error`
+/// returns the error message.
+///
+/// The result must be freed with `tc_task_list_free`.
+///
+/// ```c
+/// EXTERN_C TCTaskList tc_replica_all_tasks(TCReplica *rep);
+/// ```
+#[no_mangle]
+pub unsafe extern "C" fn tc_replica_all_tasks(rep: *mut TCReplica) -> TCTaskList {
+    // SAFETY: caller guarantees rep is valid
+    unsafe {
+        wrap(
+            rep,
+            |rep| Ok(rep.all_tasks()?.into_values().collect::<Vec<_>>().into()),
+            TCTaskList::empty(),
+        )
+    }
+}
+
+#[ffizz_header::item]
+#[ffizz(order = 714)]
+/// Get a list of the UUIDs of all tasks in the replica.  On error, the list is empty and
+/// `tc_replica_error` returns the error message.
+///
+/// The result must be freed with `tc_uuid_list_free`.
+///
+/// ```c
+/// EXTERN_C TCUuidList tc_replica_all_task_uuids(TCReplica *rep);
+/// ```
+#[no_mangle]
+pub unsafe extern "C" fn tc_replica_all_task_uuids(rep: *mut TCReplica) -> TCUuidList {
+    // SAFETY: caller guarantees rep is valid
+    unsafe {
+        wrap(
+            rep,
+            |rep| Ok(rep.all_task_uuids()?.into()),
+            TCUuidList::empty(),
+        )
+    }
+}
+
+#[ffizz_header::item]
+#[ffizz(order = 715)]
+/// Commit the given operations to the replica.  On success, `ops` is left empty and may be
+/// reused.
+///
+/// ```c
+/// EXTERN_C TCResult tc_replica_commit_operations(TCReplica *rep, TCOperations *ops);
+/// ```
+#[no_mangle]
+pub unsafe extern "C" fn tc_replica_commit_operations(
+    rep: *mut TCReplica,
+    ops: *mut TCOperations,
+) -> TCResult {
+    debug_assert!(!ops.is_null());
+    // SAFETY: caller guarantees ops is a valid TCOperations
+    let ops = unsafe { &mut *ops };
+    // SAFETY: caller guarantees rep is valid
+    unsafe {
+        wrap(
+            rep,
+            |rep| {
+                rep.commit_operations(std::mem::take(&mut ops.0))?;
+                Ok(TCResult::Ok)
+            },
+            TCResult::Error,
+        )
+    }
+}
+
+#[ffizz_header::item]
+#[ffizz(order = 716)]
+/// Undo local operations until the most recent undo point.  If `undone_out` is not NULL, it is
+/// set to true if any operations were undone.
+///
+/// ```c
+/// EXTERN_C TCResult tc_replica_undo(TCReplica *rep, bool *undone_out);
+/// ```
+#[no_mangle]
+pub unsafe extern "C" fn tc_replica_undo(rep: *mut TCReplica, undone_out: *mut bool) -> TCResult {
+    // SAFETY: caller guarantees rep is valid
+    unsafe {
+        wrap(
+            rep,
+            |rep| {
+                let ops = rep.get_undo_operations()?;
+                let undone = rep.commit_reversed_operations(ops)?;
+                if !undone_out.is_null() {
+                    // SAFETY: caller guarantees undone_out is valid or NULL
+                    *undone_out = undone;
+                }
+                Ok(TCResult::Ok)
+            },
+            TCResult::Error,
+        )
+    }
+}
+
+#[ffizz_header::item]
+#[ffizz(order = 717)]
+/// Get the number of local operations that have not yet been synchronized, or -1 on error.
+///
+/// ```c
+/// EXTERN_C int64_t tc_replica_num_local_operations(TCReplica *rep);
+/// ```
+#[no_mangle]
+pub unsafe extern "C" fn tc_replica_num_local_operations(rep: *mut TCReplica) -> i64 {
+    // SAFETY: caller guarantees rep is valid
+    unsafe { wrap(rep, |rep| Ok(rep.num_local_operations()? as i64), -1) }
+}
+
+#[ffizz_header::item]
+#[ffizz(order = 718)]
+/// Get the number of undo points available, or -1 on error.
+///
+/// ```c
+/// EXTERN_C int64_t tc_replica_num_undo_points(TCReplica *rep);
+/// ```
+#[no_mangle]
+pub unsafe extern "C" fn tc_replica_num_undo_points(rep: *mut TCReplica) -> i64 {
+    // SAFETY: caller guarantees rep is valid
+    unsafe { wrap(rep, |rep| Ok(rep.num_undo_points()? as i64), -1) }
+}
+
+#[ffizz_header::item]
+#[ffizz(order = 720)]
+/// Get the current working set for this replica.  Returns NULL on error.
+///
+/// The result must be freed with `tc_working_set_free`.
+///
+/// ```c
+/// EXTERN_C TCWorkingSet *tc_replica_working_set(TCReplica *rep);
+/// ```
+#[no_mangle]
+pub unsafe extern "C" fn tc_replica_working_set(rep: *mut TCReplica) -> *mut TCWorkingSet {
+    // SAFETY: caller guarantees rep is valid
+    unsafe {
+        wrap(
+            rep,
+            |rep| Ok(TCWorkingSet::return_ptr(rep.working_set()?)),
+            std::ptr::null_mut(),
+        )
+    }
+}
+
+#[ffizz_header::item]
+#[ffizz(order = 721)]
+/// Rebuild the working set, removing tasks that are no longer pending and adding pending tasks.
+/// If `renumber` is true, the remaining tasks are renumbered to fill gaps.
+///
+/// ```c
+/// EXTERN_C TCResult tc_replica_rebuild_working_set(TCReplica *rep, bool renumber);
+/// ```
+#[no_mangle]
+pub unsafe extern "C" fn tc_replica_rebuild_working_set(
+    rep: *mut TCReplica,
+    renumber: bool,
+) -> TCResult {
+    // SAFETY: caller guarantees rep is valid
+    unsafe {
+        wrap(
+            rep,
+            |rep| {
+                rep.rebuild_working_set(renumber)?;
+                Ok(TCResult::Ok)
+            },
+            TCResult::Error,
+        )
+    }
+}
+
+#[ffizz_header::item]
+#[ffizz(order = 722)]
+/// Remove deleted tasks that have not been modified for some time from the replica.
+///
+/// ```c
+/// EXTERN_C TCResult tc_replica_expire_tasks(TCReplica *rep);
+/// ```
+#[no_mangle]
+pub unsafe extern "C" fn tc_replica_expire_tasks(rep: *mut TCReplica) -> TCResult {
+    // SAFETY: caller guarantees rep is valid
+    unsafe {
+        wrap(
+            rep,
+            |rep| {
+                rep.expire_tasks()?;
+                Ok(TCResult::Ok)
+            },
+            TCResult::Error,
+        )
+    }
+}
+
+#[ffizz_header::item]
+#[ffizz(order = 730)]
+/// Synchronize this replica with a server.  If `avoid_snapshots` is true, the replica will not
+/// upload a snapshot even if the server requests one; this is useful on slow or metered
+/// connections.
+///
+/// ```c
+/// EXTERN_C TCResult tc_replica_sync(TCReplica *rep, TCServer *server, bool avoid_snapshots);
+/// ```
+#[no_mangle]
+pub unsafe extern "C" fn tc_replica_sync(
+    rep: *mut TCReplica,
+    server: *mut TCServer,
+    avoid_snapshots: bool,
+) -> TCResult {
+    debug_assert!(!server.is_null());
+    // SAFETY: caller guarantees server is a valid TCServer
+    let server = unsafe { &mut *server };
+    // SAFETY: caller guarantees rep is valid
+    unsafe {
+        wrap(
+            rep,
+            |rep| {
+                rep.sync(&mut server.0, avoid_snapshots)?;
+                Ok(TCResult::Ok)
+            },
+            TCResult::Error,
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::*;
+    use pretty_assertions::assert_eq;
+    use std::ffi::CString;
+    use tempfile::TempDir;
+
+    #[test]
+    fn create_and_get() {
+        let rep = tc_replica_new_in_memory();
+        let ops = tc_operations_new();
+        let uuid = tc_uuid_new_v4();
+        unsafe {
+            let task = tc_replica_create_task(rep, uuid, ops);
+            assert!(!task.is_null());
+            tc_task_free(task);
+            assert_eq!(tc_replica_commit_operations(rep, ops), TCResult::Ok);
+            assert_eq!(tc_operations_len(ops), 0);
+
+            let task = tc_replica_get_task(rep, uuid);
+            assert!(!task.is_null());
+            assert_eq!(tc_task_get_uuid(task), uuid);
+            tc_task_free(task);
+
+            let mut uuids = tc_replica_all_task_uuids(rep);
+            assert_eq!(uuids.len, 1);
+            assert_eq!(*uuids.items, uuid);
+            tc_uuid_list_free(&mut uuids);
+
+            assert!(tc_replica_get_task(rep, tc_uuid_new_v4()).is_null());
+            assert!(tc_replica_error(rep).is_null());
+
+            tc_operations_free(ops);
+            tc_replica_free(rep);
+        }
+    }
+
+    #[test]
+    fn undo() {
+        let rep = tc_replica_new_in_memory();
+        let ops = tc_operations_new();
+        unsafe {
+            tc_operations_add_undo_point(ops);
+            tc_task_free(tc_replica_create_task(rep, tc_uuid_new_v4(), ops));
+            assert_eq!(tc_replica_commit_operations(rep, ops), TCResult::Ok);
+            assert_eq!(tc_replica_num_undo_points(rep), 1);
+
+            let mut undone = false;
+            assert_eq!(tc_replica_undo(rep, &mut undone), TCResult::Ok);
+            assert!(undone);
+            assert_eq!(tc_replica_all_task_uuids(rep).len, 0);
+
+            tc_operations_free(ops);
+            tc_replica_free(rep);
+        }
+    }
+
+    #[test]
+    fn on_disk_error() {
+        let tmp_dir = TempDir::new().unwrap();
+        let path = CString::new(tmp_dir.path().join("missing").to_str().unwrap()).unwrap();
+        let mut error = std::ptr::null_mut();
+        unsafe {
+            let rep = tc_replica_new_on_disk(path.as_ptr(), false, &mut error);
+            assert!(rep.is_null());
+            assert!(!error.is_null());
+            tc_string_free(error);
+        }
+    }
+
+    #[test]
+    fn sync() {
+        let tmp_dir = TempDir::new().unwrap();
+        let server_dir = CString::new(tmp_dir.path().to_str().unwrap()).unwrap();
+        unsafe {
+            let config = tc_server_config_new_local(server_dir.as_ptr(), std::ptr::null_mut());
+            let server = tc_server_new(config, std::ptr::null_mut());
+            let rep1 = tc_replica_new_in_memory();
+            let rep2 = tc_replica_new_in_memory();
+
+            let ops = tc_operations_new();
+            let uuid = tc_uuid_new_v4();
+            tc_task_free(tc_replica_create_task(rep1, uuid, ops));
+            assert_eq!(tc_replica_commit_operations(rep1, ops), TCResult::Ok);
+
+            assert_eq!(tc_replica_sync(rep1, server, false), TCResult::Ok);
+            assert_eq!(tc_replica_sync(rep2, server, false), TCResult::Ok);
+            assert_eq!(tc_replica_num_local_operations(rep1), 0);
+
+            let task = tc_replica_get_task(rep2, uuid);
+            assert!(!task.is_null());
+            tc_task_free(task);
+
+            tc_operations_free(ops);
+            tc_replica_free(rep1);
+            tc_replica_free(rep2);
+            tc_server_free(server);
+            tc_server_config_free(config);
+        }
+    }
+}
@@ -0,0 +1,28 @@
+#[ffizz_header::item]
+#[ffizz(order = 50)]
+/// ***** TCResult *****
+///
+/// A result from a TC operation.  On failure, the error message is available from the object on
+/// which the operation was performed.
+///
+/// ```c
+/// typedef enum TCResult {
+///   TC_RESULT_ERROR = -1,
+///   TC_RESULT_OK = 0,
+/// } TCResult;
+/// ```
+#[repr(i32)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum TCResult {
+    Error = -1,
+    Ok = 0,
+}
+
+impl<T, E> From<Result<T, E>> for TCResult {
+    fn from(res: Result<T, E>) -> TCResult {
+        match res {
+            Ok(_) => TCResult::Ok,
+            Err(_) => TCResult::Error,
+        }
+    }
+}
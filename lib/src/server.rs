@@ -0,0 +1,252 @@
+use crate::string::{borrow_optional_str, borrow_str, set_error_out};
+use crate::TCUuid;
+use libc::c_char;
+use std::path::PathBuf;
+use std::slice;
+use taskchampion::{Server, ServerConfig};
+
+#[ffizz_header::item]
+#[ffizz(order = 50)]
+/// ***** TCServerConfig *****
+///
+/// TCServerConfig describes how to connect to a sync server.  Create a server from it with
+/// `tc_server_new`.
+///
+/// ```c
+/// typedef struct TCServerConfig TCServerConfig;
+/// ```
+pub struct TCServerConfig(ServerConfig);
+
+#[ffizz_header::item]
+#[ffizz(order = 50)]
+/// ***** TCServer *****
+///
+/// TCServer is a connection to a sync server, used with `tc_replica_sync`.
+///
+/// ```c
+/// typedef struct TCServer TCServer;
+/// ```
+pub struct TCServer(pub(crate) Box<dyn Server>);
+
+fn return_config(config: ServerConfig) -> *mut TCServerConfig {
+    Box::into_raw(Box::new(TCServerConfig(config)))
+}
+
+/// Borrow an encryption secret passed from C.
+unsafe fn borrow_secret(secret: *const u8, secret_len: usize) -> Vec<u8> {
+    if secret_len == 0 {
+        return vec![];
+    }
+    debug_assert!(!secret.is_null());
+    // SAFETY: caller guarantees secret points to secret_len bytes
+    unsafe { slice::from_raw_parts(secret, secret_len) }.to_vec()
+}
+
+#[ffizz_header::item]
+#[ffizz(order = 600)]
+/// Create a configuration for a local server, storing its data in the given directory.  This is
+/// useful for situations with a single replica.  Returns NULL on error, in which case the error
+/// message is written to `error_out` if it is not NULL.
+///
+/// The result must be freed with `tc_server_config_free`.
+///
+/// ```c
+/// EXTERN_C TCServerConfig *tc_server_config_new_local(const char *server_dir, char **error_out);
+/// ```
+#[no_mangle]
+pub unsafe extern "C" fn tc_server_config_new_local(
+    server_dir: *const c_char,
+    error_out: *mut *mut c_char,
+) -> *mut TCServerConfig {
+    // SAFETY: caller guarantees server_dir is a valid string
+    match unsafe { borrow_str(server_dir) } {
+        Ok(server_dir) => return_config(ServerConfig::Local {
+            server_dir: PathBuf::from(server_dir),
+        }),
+        Err(e) => {
+            // SAFETY: caller guarantees error_out is valid or NULL
+            unsafe { set_error_out(error_out, e) };
+            std::ptr::null_mut()
+        }
+    }
+}
+
+#[ffizz_header::item]
+#[ffizz(order = 601)]
+/// Create a configuration for a remote taskchampion-sync-server at the given URL.  The client ID
+/// identifies this replica to the server, and the encryption secret (of `secret_len` bytes) is
+/// used to encrypt all data sent to the server.  Returns NULL on error, in which case the error
+/// message is written to `error_out` if it is not NULL.
+///
+/// The result must be freed with `tc_server_config_free`.
+///
+/// ```c
+/// EXTERN_C TCServerConfig *tc_server_config_new_remote(const char *url,
+///                                                      TCUuid client_id,
+///                                                      const uint8_t *secret,
+///                                                      size_t secret_len,
+///                                                      char **error_out);
+/// ```
+#[no_mangle]
+pub unsafe extern "C" fn tc_server_config_new_remote(
+    url: *const c_char,
+    client_id: TCUuid,
+    secret: *const u8,
+    secret_len: usize,
+    error_out: *mut *mut c_char,
+) -> *mut TCServerConfig {
+    // SAFETY: caller guarantees url is a valid string
+    match unsafe { borrow_str(url) } {
+        Ok(url) => return_config(ServerConfig::Remote {
+            url: url.into(),
+            client_id: client_id.into(),
+            // SAFETY: caller guarantees secret is valid for secret_len bytes
+            encryption_secret: unsafe { borrow_secret(secret, secret_len) },
+        }),
+        Err(e) => {
+            // SAFETY: caller guarantees error_out is valid or NULL
+            unsafe { set_error_out(error_out, e) };
+            std::ptr::null_mut()
+        }
+    }
+}
+
+#[ffizz_header::item]
+#[ffizz(order = 602)]
+/// Create a configuration for a Google Cloud Platform storage bucket.  If `credential_path` is
+/// NULL, Application Default Credentials are used; otherwise it must be the path to a service
+/// account key.  The encryption secret (of `secret_len` bytes) is used to encrypt all data sent
+/// to the server.  Returns NULL on error, in which case the error message is written to
+/// `error_out` if it is not NULL.
+///
+/// The result must be freed with `tc_server_config_free`.
+///
+/// ```c
+/// EXTERN_C TCServerConfig *tc_server_config_new_gcp(const char *bucket,
+///                                                   const char *credential_path,
+///                                                   const uint8_t *secret,
+///                                                   size_t secret_len,
+///                                                   char **error_out);
+/// ```
+#[no_mangle]
+pub unsafe extern "C" fn tc_server_config_new_gcp(
+    bucket: *const c_char,
+    credential_path: *const c_char,
+    secret: *const u8,
+    secret_len: usize,
+    error_out: *mut *mut c_char,
+) -> *mut TCServerConfig {
+    // SAFETY: caller guarantees bucket and credential_path are valid strings (or NULL for
+    // credential_path)
+    let args = unsafe { borrow_str(bucket) }
+        .and_then(|b| Ok((b, unsafe { borrow_optional_str(credential_path) }?)));
+    match args {
+        Ok((bucket, credential_path)) => return_config(ServerConfig::Gcp {
+            bucket: bucket.into(),
+            credential_path: credential_path.map(Into::into),
+            // SAFETY: caller guarantees secret is valid for secret_len bytes
+            encryption_secret: unsafe { borrow_secret(secret, secret_len) },
+        }),
+        Err(e) => {
+            // SAFETY: caller guarantees error_out is valid or NULL
+            unsafe { set_error_out(error_out, e) };
+            std::ptr::null_mut()
+        }
+    }
+}
+
+#[ffizz_header::item]
+#[ffizz(order = 603)]
+/// Free a TCServerConfig.  The TCServerConfig must not be used after this call.
+///
+/// ```c
+/// EXTERN_C void tc_server_config_free(TCServerConfig *config);
+/// ```
+#[no_mangle]
+pub unsafe extern "C" fn tc_server_config_free(config: *mut TCServerConfig) {
+    if !config.is_null() {
+        // SAFETY: config was allocated by this library, and will not be used again
+        drop(unsafe { Box::from_raw(config) });
+    }
+}
+
+#[ffizz_header::item]
+#[ffizz(order = 610)]
+/// Create a server from the given configuration.  The configuration is not freed, and may be
+/// used again.  Returns NULL on error, in which case the error message is written to `error_out`
+/// if it is not NULL.
+///
+/// The result must be freed with `tc_server_free`.
+///
+/// ```c
+/// EXTERN_C TCServer *tc_server_new(const TCServerConfig *config, char **error_out);
+/// ```
+#[no_mangle]
+pub unsafe extern "C" fn tc_server_new(
+    config: *const TCServerConfig,
+    error_out: *mut *mut c_char,
+) -> *mut TCServer {
+    debug_assert!(!config.is_null());
+    // SAFETY: caller guarantees config is a valid TCServerConfig
+    let config = unsafe { &*config }.0.clone();
+    match config.into_server() {
+        Ok(server) => Box::into_raw(Box::new(TCServer(server))),
+        Err(e) => {
+            // SAFETY: caller guarantees error_out is valid or NULL
+            unsafe { set_error_out(error_out, e) };
+            std::ptr::null_mut()
+        }
+    }
+}
+
+#[ffizz_header::item]
+#[ffizz(order = 611)]
+/// Free a TCServer.  The TCServer must not be used after this call.
+///
+/// ```c
+/// EXTERN_C void tc_server_free(TCServer *server);
+/// ```
+#[no_mangle]
+pub unsafe extern "C" fn tc_server_free(server: *mut TCServer) {
+    if !server.is_null() {
+        // SAFETY: server was allocated by this library, and will not be used again
+        drop(unsafe { Box::from_raw(server) });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{tc_string_free, tc_uuid_new_v4};
+    use std::ffi::CString;
+    use tempfile::TempDir;
+
+    #[test]
+    fn local_server() {
+        let tmp_dir = TempDir::new().unwrap();
+        let dir = CString::new(tmp_dir.path().to_str().unwrap()).unwrap();
+        let config = unsafe { tc_server_config_new_local(dir.as_ptr(), std::ptr::null_mut()) };
+        assert!(!config.is_null());
+        let server = unsafe { tc_server_new(config, std::ptr::null_mut()) };
+        assert!(!server.is_null());
+        unsafe { tc_server_free(server) };
+        unsafe { tc_server_config_free(config) };
+    }
+
+    #[test]
+    fn remote_config_error() {
+        let mut error = std::ptr::null_mut();
+        let config = unsafe {
+            tc_server_config_new_remote(
+                std::ptr::null(),
+                tc_uuid_new_v4(),
+                std::ptr::null(),
+                0,
+                &mut error,
+            )
+        };
+        assert!(config.is_null());
+        assert!(!error.is_null());
+        unsafe { tc_string_free(error) };
+    }
+}
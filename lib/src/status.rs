@@ -0,0 +1,51 @@
+use taskchampion::Status;
+
+#[ffizz_header::item]
+#[ffizz(order = 50)]
+/// ***** TCStatus *****
+///
+/// The status of a task.  Statuses not recognized by this library are represented as
+/// `TC_STATUS_UNKNOWN`.
+///
+/// ```c
+/// typedef enum TCStatus {
+///   TC_STATUS_PENDING,
+///   TC_STATUS_COMPLETED,
+///   TC_STATUS_DELETED,
+///   TC_STATUS_RECURRING,
+///   TC_STATUS_UNKNOWN,
+/// } TCStatus;
+/// ```
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TCStatus {
+    Pending,
+    Completed,
+    Deleted,
+    Recurring,
+    Unknown,
+}
+
+impl From<TCStatus> for Status {
+    fn from(status: TCStatus) -> Status {
+        match status {
+            TCStatus::Pending => Status::Pending,
+            TCStatus::Completed => Status::Completed,
+            TCStatus::Deleted => Status::Deleted,
+            TCStatus::Recurring => Status::Recurring,
+            TCStatus::Unknown => Status::Unknown("unknown".to_string()),
+        }
+    }
+}
+
+impl From<Status> for TCStatus {
+    fn from(status: Status) -> TCStatus {
+        match status {
+            Status::Pending => TCStatus::Pending,
+            Status::Completed => TCStatus::Completed,
+            Status::Deleted => TCStatus::Deleted,
+            Status::Recurring => TCStatus::Recurring,
+            _ => TCStatus::Unknown,
+        }
+    }
+}
@@ -0,0 +1,83 @@
+use libc::c_char;
+use std::ffi::{CStr, CString};
+
+/// Borrow a string passed from C.  The pointer must be non-NULL and point to a NUL-terminated
+/// string that remains valid for the lifetime `'a`.
+pub(crate) unsafe fn borrow_str<'a>(ptr: *const c_char) -> anyhow::Result<&'a str> {
+    if ptr.is_null() {
+        anyhow::bail!("unexpected NULL string");
+    }
+    // SAFETY: pointer is not NULL, and the caller guarantees it is NUL-terminated and valid
+    // for 'a.
+    Ok(unsafe { CStr::from_ptr(ptr) }.to_str()?)
+}
+
+/// Borrow a string passed from C which may be NULL.
+pub(crate) unsafe fn borrow_optional_str<'a>(
+    ptr: *const c_char,
+) -> anyhow::Result<Option<&'a str>> {
+    if ptr.is_null() {
+        Ok(None)
+    } else {
+        // SAFETY: see borrow_str
+        Ok(Some(unsafe { borrow_str(ptr) }?))
+    }
+}
+
+/// Convert a Rust string into a newly-allocated C string, to be freed by `tc_string_free`.
+/// Any embedded NUL characters are removed.
+pub(crate) fn return_string(s: impl Into<String>) -> *mut c_char {
+    let mut s = s.into();
+    s.retain(|c| c != '\0');
+    CString::new(s)
+        .expect("string has no embedded NULs")
+        .into_raw()
+}
+
+/// Write an error message into an `error_out` argument, if it is not NULL.
+pub(crate) unsafe fn set_error_out(error_out: *mut *mut c_char, err: impl ToString) {
+    if !error_out.is_null() {
+        // SAFETY: caller guarantees error_out is valid if not NULL
+        unsafe { *error_out = return_string(err.to_string()) };
+    }
+}
+
+#[ffizz_header::item]
+#[ffizz(order = 200)]
+/// Free a string returned from this library.  The string must not be used after this call.  If
+/// the pointer is NULL, this function does nothing.
+///
+/// ```c
+/// EXTERN_C void tc_string_free(char *string);
+/// ```
+#[no_mangle]
+pub unsafe extern "C" fn tc_string_free(string: *mut c_char) {
+    if !string.is_null() {
+        // SAFETY: the string was allocated by return_string, and the caller will not use it
+        // again.
+        drop(unsafe { CString::from_raw(string) });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn round_trip() {
+        let s = return_string("a\0b");
+        assert_eq!(unsafe { borrow_str(s) }.unwrap(), "ab");
+        unsafe { tc_string_free(s) };
+    }
+
+    #[test]
+    fn null_string() {
+        assert!(unsafe { borrow_str(std::ptr::null()) }.is_err());
+        assert_eq!(
+            unsafe { borrow_optional_str(std::ptr::null()) }.unwrap(),
+            None
+        );
+        unsafe { tc_string_free(std::ptr::null_mut()) };
+    }
+}
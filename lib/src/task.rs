@@ -0,0 +1,498 @@
+use crate::string::{borrow_optional_str, borrow_str, return_string};
+use crate::{TCOperations, TCResult, TCStatus, TCTaskData, TCUuid};
+use libc::c_char;
+use taskchampion::{Tag, Task};
+
+#[ffizz_header::item]
+#[ffizz(order = 50)]
+/// ***** TCTask *****
+///
+/// A task, with a high-level interface for reading and modifying its properties.  Modifications
+/// add operations to a TCOperations, and take effect when those operations are committed to the
+/// replica with `tc_replica_commit_operations`.
+///
+/// A TCTask is a snapshot of the task at the time it was retrieved from the replica, updated by
+/// modifications made through it.
+///
+/// When a `tc_task_..` function that returns a TCResult returns `TC_RESULT_ERROR`, then
+/// `tc_task_error` will return the error message.
+///
+/// ```c
+/// typedef struct TCTask TCTask;
+/// ```
+pub struct TCTask {
+    inner: Task,
+    error: Option<String>,
+}
+
+impl TCTask {
+    pub(crate) fn return_ptr(task: Task) -> *mut TCTask {
+        Box::into_raw(Box::new(TCTask {
+            inner: task,
+            error: None,
+        }))
+    }
+}
+
+/// Borrow a TCTask passed from C.
+unsafe fn task_ref<'a>(task: *const TCTask) -> &'a Task {
+    debug_assert!(!task.is_null());
+    // SAFETY: caller guarantees task is a valid TCTask
+    &unsafe { &*task }.inner
+}
+
+/// Call `f` with the task and operations, recording any error.
+unsafe fn wrap_mut<F>(task: *mut TCTask, ops: *mut TCOperations, f: F) -> TCResult
+where
+    F: FnOnce(&mut Task, &mut taskchampion::Operations) -> anyhow::Result<()>,
+{
+    debug_assert!(!task.is_null());
+    debug_assert!(!ops.is_null());
+    // SAFETY: caller guarantees task and ops are valid and not in use elsewhere
+    let (task, ops) = unsafe { (&mut *task, &mut *ops) };
+    task.error = None;
+    match f(&mut task.inner, &mut ops.0) {
+        Ok(()) => TCResult::Ok,
+        Err(e) => {
+            task.error = Some(e.to_string());
+            TCResult::Error
+        }
+    }
+}
+
+#[ffizz_header::item]
+#[ffizz(order = 51)]
+/// ***** TCTaskList *****
+///
+/// TCTaskList is a list of tasks, returned from the library.  It must be freed with
+/// `tc_task_list_free`, which also frees the tasks it contains.
+///
+/// ```c
+/// typedef struct TCTaskList {
+///   // number of tasks in items
+///   size_t len;
+///   // array of pointers to tasks
+///   TCTask **items;
+/// } TCTaskList;
+/// ```
+#[repr(C)]
+pub struct TCTaskList {
+    pub len: usize,
+    pub items: *mut *mut TCTask,
+}
+
+impl From<Vec<Task>> for TCTaskList {
+    fn from(tasks: Vec<Task>) -> TCTaskList {
+        let items: Box<[*mut TCTask]> = tasks.into_iter().map(TCTask::return_ptr).collect();
+        TCTaskList {
+            len: items.len(),
+            items: Box::into_raw(items) as *mut *mut TCTask,
+        }
+    }
+}
+
+impl TCTaskList {
+    /// An empty list, returned on error.
+    pub(crate) fn empty() -> TCTaskList {
+        Vec::new().into()
+    }
+}
+
+#[ffizz_header::item]
+#[ffizz(order = 800)]
+/// Get a task's UUID.
+///
+/// ```c
+/// EXTERN_C TCUuid tc_task_get_uuid(const TCTask *task);
+/// ```
+#[no_mangle]
+pub unsafe extern "C" fn tc_task_get_uuid(task: *const TCTask) -> TCUuid {
+    // SAFETY: caller guarantees task is valid
+    unsafe { task_ref(task) }.get_uuid().into()
+}
+
+#[ffizz_header::item]
+#[ffizz(order = 801)]
+/// Get a task's status.
+///
+/// ```c
+/// EXTERN_C TCStatus tc_task_get_status(const TCTask *task);
+/// ```
+#[no_mangle]
+pub unsafe extern "C" fn tc_task_get_status(task: *const TCTask) -> TCStatus {
+    // SAFETY: caller guarantees task is valid
+    unsafe { task_ref(task) }.get_status().into()
+}
+
+#[ffizz_header::item]
+#[ffizz(order = 802)]
+/// Get a task's description.  The result must be freed with `tc_string_free`.
+///
+/// ```c
+/// EXTERN_C char *tc_task_get_description(const TCTask *task);
+/// ```
+#[no_mangle]
+pub unsafe extern "C" fn tc_task_get_description(task: *const TCTask) -> *mut c_char {
+    // SAFETY: caller guarantees task is valid
+    return_string(unsafe { task_ref(task) }.get_description())
+}
+
+#[ffizz_header::item]
+#[ffizz(order = 803)]
+/// Get the value of an arbitrary property of a task, or NULL if the property is not set or the
+/// property name is not a valid string.  The result must be freed with `tc_string_free`.
+///
+/// ```c
+/// EXTERN_C char *tc_task_get_value(const TCTask *task, const char *property);
+/// ```
+#[no_mangle]
+pub unsafe extern "C" fn tc_task_get_value(
+    task: *const TCTask,
+    property: *const c_char,
+) -> *mut c_char {
+    // SAFETY: caller guarantees task and property are valid
+    let (task, property) = unsafe { (task_ref(task), borrow_str(property)) };
+    match property.ok().and_then(|p| task.get_value(p)) {
+        Some(value) => return_string(value),
+        None => std::ptr::null_mut(),
+    }
+}
+
+#[ffizz_header::item]
+#[ffizz(order = 804)]
+/// Determine whether the task has the given tag.  Synthetic tags such as `PENDING` are
+/// supported.  Returns false if the tag is not valid.
+///
+/// ```c
+/// EXTERN_C bool tc_task_has_tag(const TCTask *task, const char *tag);
+/// ```
+#[no_mangle]
+pub unsafe extern "C" fn tc_task_has_tag(task: *const TCTask, tag: *const c_char) -> bool {
+    // SAFETY: caller guarantees task and tag are valid
+    let (task, tag) = unsafe { (task_ref(task), borrow_str(tag)) };
+    match tag.ok().and_then(|t| Tag::try_from(t).ok()) {
+        Some(tag) => task.has_tag(&tag),
+        None => false,
+    }
+}
+
+#[ffizz_header::item]
+#[ffizz(order = 805)]
+/// Determine whether the task is active (started and not stopped).
+///
+/// ```c
+/// EXTERN_C bool tc_task_is_active(const TCTask *task);
+/// ```
+#[no_mangle]
+pub unsafe extern "C" fn tc_task_is_active(task: *const TCTask) -> bool {
+    // SAFETY: caller guarantees task is valid
+    unsafe { task_ref(task) }.is_active()
+}
+
+#[ffizz_header::item]
+#[ffizz(order = 810)]
+/// Set a task's status, adding the necessary operations to `ops`.
+///
+/// ```c
+/// EXTERN_C TCResult tc_task_set_status(TCTask *task, TCStatus status, TCOperations *ops);
+/// ```
+#[no_mangle]
+pub unsafe extern "C" fn tc_task_set_status(
+    task: *mut TCTask,
+    status: TCStatus,
+    ops: *mut TCOperations,
+) -> TCResult {
+    // SAFETY: caller guarantees task and ops are valid
+    unsafe {
+        wrap_mut(task, ops, |task, ops| {
+            Ok(task.set_status(status.into(), ops)?)
+        })
+    }
+}
+
+#[ffizz_header::item]
+#[ffizz(order = 811)]
+/// Set a task's description, adding the necessary operations to `ops`.
+///
+/// ```c
+/// EXTERN_C TCResult tc_task_set_description(TCTask *task,
+///                                           const char *description,
+///                                           TCOperations *ops);
+/// ```
+#[no_mangle]
+pub unsafe extern "C" fn tc_task_set_description(
+    task: *mut TCTask,
+    description: *const c_char,
+    ops: *mut TCOperations,
+) -> TCResult {
+    // SAFETY: caller guarantees task, description, and ops are valid
+    unsafe {
+        wrap_mut(task, ops, |task, ops| {
+            let description = borrow_str(description)?;
+            Ok(task.set_description(description.into(), ops)?)
+        })
+    }
+}
+
+#[ffizz_header::item]
+#[ffizz(order = 812)]
+/// Set an arbitrary property of a task, adding the necessary operations to `ops`.  If `value` is
+/// NULL, the property is removed.
+///
+/// ```c
+/// EXTERN_C TCResult tc_task_set_value(TCTask *task,
+///                                     const char *property,
+///                                     const char *value,
+///                                     TCOperations *ops);
+/// ```
+#[no_mangle]
+pub unsafe extern "C" fn tc_task_set_value(
+    task: *mut TCTask,
+    property: *const c_char,
+    value: *const c_char,
+    ops: *mut TCOperations,
+) -> TCResult {
+    // SAFETY: caller guarantees task, property, value, and ops are valid
+    unsafe {
+        wrap_mut(task, ops, |task, ops| {
+            let property = borrow_str(property)?;
+            let value = borrow_optional_str(value)?;
+            Ok(task.set_value(property, value.map(Into::into), ops)?)
+        })
+    }
+}
+
+#[ffizz_header::item]
+#[ffizz(order = 813)]
+/// Add a tag to a task, adding the necessary operations to `ops`.
+///
+/// ```c
+/// EXTERN_C TCResult tc_task_add_tag(TCTask *task, const char *tag, TCOperations *ops);
+/// ```
+#[no_mangle]
+pub unsafe extern "C" fn tc_task_add_tag(
+    task: *mut TCTask,
+    tag: *const c_char,
+    ops: *mut TCOperations,
+) -> TCResult {
+    // SAFETY: caller guarantees task, tag, and ops are valid
+    unsafe {
+        wrap_mut(task, ops, |task, ops| {
+            let tag = Tag::try_from(borrow_str(tag)?)?;
+            Ok(task.add_tag(&tag, ops)?)
+        })
+    }
+}
+
+#[ffizz_header::item]
+#[ffizz(order = 814)]
+/// Remove a tag from a task, adding the necessary operations to `ops`.
+///
+/// ```c
+/// EXTERN_C TCResult tc_task_remove_tag(TCTask *task, const char *tag, TCOperations *ops);
+/// ```
+#[no_mangle]
+pub unsafe extern "C" fn tc_task_remove_tag(
+    task: *mut TCTask,
+    tag: *const c_char,
+    ops: *mut TCOperations,
+) -> TCResult {
+    // SAFETY: caller guarantees task, tag, and ops are valid
+    unsafe {
+        wrap_mut(task, ops, |task, ops| {
+            let tag = Tag::try_from(borrow_str(tag)?)?;
+            Ok(task.remove_tag(&tag, ops)?)
+        })
+    }
+}
+
+#[ffizz_header::item]
+#[ffizz(order = 815)]
+/// Start a task, adding the necessary operations to `ops`.
+///
+/// ```c
+/// EXTERN_C TCResult tc_task_start(TCTask *task, TCOperations *ops);
+/// ```
+#[no_mangle]
+pub unsafe extern "C" fn tc_task_start(task: *mut TCTask, ops: *mut TCOperations) -> TCResult {
+    // SAFETY: caller guarantees task and ops are valid
+    unsafe { wrap_mut(task, ops, |task, ops| Ok(task.start(ops)?)) }
+}
+
+#[ffizz_header::item]
+#[ffizz(order = 816)]
+/// Stop a task, adding the necessary operations to `ops`.
+///
+/// ```c
+/// EXTERN_C TCResult tc_task_stop(TCTask *task, TCOperations *ops);
+/// ```
+#[no_mangle]
+pub unsafe extern "C" fn tc_task_stop(task: *mut TCTask, ops: *mut TCOperations) -> TCResult {
+    // SAFETY: caller guarantees task and ops are valid
+    unsafe { wrap_mut(task, ops, |task, ops| Ok(task.stop(ops)?)) }
+}
+
+#[ffizz_header::item]
+#[ffizz(order = 817)]
+/// Mark a task as done, adding the necessary operations to `ops`.
+///
+/// ```c
+/// EXTERN_C TCResult tc_task_done(TCTask *task, TCOperations *ops);
+/// ```
+#[no_mangle]
+pub unsafe extern "C" fn tc_task_done(task: *mut TCTask, ops: *mut TCOperations) -> TCResult {
+    // SAFETY: caller guarantees task and ops are valid
+    unsafe { wrap_mut(task, ops, |task, ops| Ok(task.done(ops)?)) }
+}
+
+#[ffizz_header::item]
+#[ffizz(order = 820)]
+/// Get a copy of the task's underlying data.  The result must be freed with
+/// `tc_task_data_free`.
+///
+/// ```c
+/// EXTERN_C TCTaskData *tc_task_to_task_data(const TCTask *task);
+/// ```
+#[no_mangle]
+pub unsafe extern "C" fn tc_task_to_task_data(task: *const TCTask) -> *mut TCTaskData {
+    // SAFETY: caller guarantees task is valid
+    let task = unsafe { task_ref(task) }.clone();
+    TCTaskData::return_ptr(task.into_task_data())
+}
+
+#[ffizz_header::item]
+#[ffizz(order = 821)]
+/// Get the latest error for a task, or NULL if the last operation succeeded.  The returned
+/// string must be freed with `tc_string_free`.
+///
+/// ```c
+/// EXTERN_C char *tc_task_error(TCTask *task);
+/// ```
+#[no_mangle]
+pub unsafe extern "C" fn tc_task_error(task: *mut TCTask) -> *mut c_char {
+    debug_assert!(!task.is_null());
+    // SAFETY: caller guarantees task is valid
+    match unsafe { &mut *task }.error.take() {
+        Some(e) => return_string(e),
+        None => std::ptr::null_mut(),
+    }
+}
+
+#[ffizz_header::item]
+#[ffizz(order = 822)]
+/// Free a task.  The task must not be used after this call.
+///
+/// ```c
+/// EXTERN_C void tc_task_free(TCTask *task);
+/// ```
+#[no_mangle]
+pub unsafe extern "C" fn tc_task_free(task: *mut TCTask) {
+    if !task.is_null() {
+        // SAFETY: task was allocated by this library, and will not be used again
+        drop(unsafe { Box::from_raw(task) });
+    }
+}
+
+#[ffizz_header::item]
+#[ffizz(order = 823)]
+/// Free a TCTaskList, including all of the tasks it contains.  The list's items must not be used
+/// after this call, and the list is left empty.
+///
+/// ```c
+/// EXTERN_C void tc_task_list_free(TCTaskList *list);
+/// ```
+#[no_mangle]
+pub unsafe extern "C" fn tc_task_list_free(list: *mut TCTaskList) {
+    debug_assert!(!list.is_null());
+    // SAFETY: caller guarantees list is a valid TCTaskList
+    let list = unsafe { &mut *list };
+    if list.items.is_null() {
+        return;
+    }
+    // SAFETY: items and len were created from a boxed slice in From<Vec<Task>>
+    let items = unsafe { Box::from_raw(std::ptr::slice_from_raw_parts_mut(list.items, list.len)) };
+    for task in items.iter() {
+        // SAFETY: each task was allocated by TCTask::return_ptr
+        unsafe { tc_task_free(*task) };
+    }
+    list.items = std::ptr::null_mut();
+    list.len = 0;
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::*;
+    use pretty_assertions::assert_eq;
+    use std::ffi::{CStr, CString};
+
+    /// Take ownership of a string returned from the library.
+    unsafe fn take_string(s: *mut c_char) -> String {
+        assert!(!s.is_null());
+        let res = unsafe { CStr::from_ptr(s) }.to_str().unwrap().to_string();
+        unsafe { tc_string_free(s) };
+        res
+    }
+
+    #[test]
+    fn modify_task() {
+        let rep = tc_replica_new_in_memory();
+        let ops = tc_operations_new();
+        let description = CString::new("do the thing").unwrap();
+        let tag = CString::new("work").unwrap();
+        let prop = CString::new("project").unwrap();
+        let value = CString::new("home").unwrap();
+        unsafe {
+            let task = tc_replica_create_task(rep, tc_uuid_new_v4(), ops);
+            assert_eq!(
+                tc_task_set_status(task, TCStatus::Pending, ops),
+                TCResult::Ok
+            );
+            assert_eq!(
+                tc_task_set_description(task, description.as_ptr(), ops),
+                TCResult::Ok
+            );
+            assert_eq!(tc_task_add_tag(task, tag.as_ptr(), ops), TCResult::Ok);
+            assert_eq!(
+                tc_task_set_value(task, prop.as_ptr(), value.as_ptr(), ops),
+                TCResult::Ok
+            );
+            assert_eq!(tc_task_start(task, ops), TCResult::Ok);
+            assert_eq!(tc_replica_commit_operations(rep, ops), TCResult::Ok);
+            tc_task_free(task);
+
+            let mut tasks = tc_replica_all_tasks(rep);
+            assert_eq!(tasks.len, 1);
+            let task = *tasks.items;
+            assert_eq!(tc_task_get_status(task), TCStatus::Pending);
+            assert_eq!(take_string(tc_task_get_description(task)), "do the thing");
+            assert!(tc_task_has_tag(task, tag.as_ptr()));
+            assert!(tc_task_is_active(task));
+            assert_eq!(take_string(tc_task_get_value(task, prop.as_ptr())), "home");
+            tc_task_list_free(&mut tasks);
+            assert!(tasks.items.is_null());
+
+            tc_operations_free(ops);
+            tc_replica_free(rep);
+        }
+    }
+
+    #[test]
+    fn task_error() {
+        let rep = tc_replica_new_in_memory();
+        let ops = tc_operations_new();
+        let bad_tag = CString::new("bad tag").unwrap();
+        unsafe {
+            let task = tc_replica_create_task(rep, tc_uuid_new_v4(), ops);
+            assert_eq!(
+                tc_task_add_tag(task, bad_tag.as_ptr(), ops),
+                TCResult::Error
+            );
+            assert!(take_string(tc_task_error(task)).contains("invalid tag"));
+            assert!(tc_task_error(task).is_null());
+            tc_task_free(task);
+            tc_operations_free(ops);
+            tc_replica_free(rep);
+        }
+    }
+}
@@ -0,0 +1,211 @@
+use crate::string::{borrow_optional_str, borrow_str, return_string};
+use crate::{TCOperations, TCUuid};
+use libc::c_char;
+use taskchampion::TaskData;
+
+#[ffizz_header::item]
+#[ffizz(order = 50)]
+/// ***** TCTaskData *****
+///
+/// TCTaskData is the low-level representation of a task, as a key/value map.  Modifications add
+/// operations to a TCOperations, and take effect when those operations are committed to the
+/// replica with `tc_replica_commit_operations`.
+///
+/// ```c
+/// typedef struct TCTaskData TCTaskData;
+/// ```
+pub struct TCTaskData(TaskData);
+
+impl TCTaskData {
+    pub(crate) fn return_ptr(data: TaskData) -> *mut TCTaskData {
+        Box::into_raw(Box::new(TCTaskData(data)))
+    }
+}
+
+/// Borrow a TCTaskData passed from C.
+unsafe fn data_ref<'a>(data: *const TCTaskData) -> &'a TaskData {
+    debug_assert!(!data.is_null());
+    // SAFETY: caller guarantees data is a valid TCTaskData
+    &unsafe { &*data }.0
+}
+
+#[ffizz_header::item]
+#[ffizz(order = 900)]
+/// Create a new task with the given UUID, adding the necessary operation to `ops`.  This does
+/// not check whether the task already exists.
+///
+/// The result must be freed with `tc_task_data_free`.
+///
+/// ```c
+/// EXTERN_C TCTaskData *tc_task_data_create(TCUuid uuid, TCOperations *ops);
+/// ```
+#[no_mangle]
+pub unsafe extern "C" fn tc_task_data_create(
+    uuid: TCUuid,
+    ops: *mut TCOperations,
+) -> *mut TCTaskData {
+    debug_assert!(!ops.is_null());
+    // SAFETY: caller guarantees ops is a valid TCOperations
+    let ops = unsafe { &mut *ops };
+    TCTaskData::return_ptr(TaskData::create(uuid.into(), &mut ops.0))
+}
+
+#[ffizz_header::item]
+#[ffizz(order = 901)]
+/// Get the UUID of the task.
+///
+/// ```c
+/// EXTERN_C TCUuid tc_task_data_get_uuid(const TCTaskData *data);
+/// ```
+#[no_mangle]
+pub unsafe extern "C" fn tc_task_data_get_uuid(data: *const TCTaskData) -> TCUuid {
+    // SAFETY: caller guarantees data is valid
+    unsafe { data_ref(data) }.get_uuid().into()
+}
+
+#[ffizz_header::item]
+#[ffizz(order = 902)]
+/// Get the value of a property of the task, or NULL if the property is not set or the property
+/// name is not a valid string.  The result must be freed with `tc_string_free`.
+///
+/// ```c
+/// EXTERN_C char *tc_task_data_get(const TCTaskData *data, const char *property);
+/// ```
+#[no_mangle]
+pub unsafe extern "C" fn tc_task_data_get(
+    data: *const TCTaskData,
+    property: *const c_char,
+) -> *mut c_char {
+    // SAFETY: caller guarantees data and property are valid
+    let (data, property) = unsafe { (data_ref(data), borrow_str(property)) };
+    match property.ok().and_then(|p| data.get(p)) {
+        Some(value) => return_string(value),
+        None => std::ptr::null_mut(),
+    }
+}
+
+#[ffizz_header::item]
+#[ffizz(order = 903)]
+/// Determine whether the task has the given property.
+///
+/// ```c
+/// EXTERN_C bool tc_task_data_has(const TCTaskData *data, const char *property);
+/// ```
+#[no_mangle]
+pub unsafe extern "C" fn tc_task_data_has(
+    data: *const TCTaskData,
+    property: *const c_char,
+) -> bool {
+    // SAFETY: caller guarantees data and property are valid
+    let (data, property) = unsafe { (data_ref(data), borrow_str(property)) };
+    property.map(|p| data.has(p)).unwrap_or(false)
+}
+
+#[ffizz_header::item]
+#[ffizz(order = 904)]
+/// Set a property of the task, adding the necessary operation to `ops`.  If `value` is NULL, the
+/// property is removed.  Returns false if the property or value is not a valid string.
+///
+/// ```c
+/// EXTERN_C bool tc_task_data_update(TCTaskData *data,
+///                                   const char *property,
+///                                   const char *value,
+///                                   TCOperations *ops);
+/// ```
+#[no_mangle]
+pub unsafe extern "C" fn tc_task_data_update(
+    data: *mut TCTaskData,
+    property: *const c_char,
+    value: *const c_char,
+    ops: *mut TCOperations,
+) -> bool {
+    debug_assert!(!data.is_null());
+    debug_assert!(!ops.is_null());
+    // SAFETY: caller guarantees all arguments are valid
+    let (data, ops) = unsafe { (&mut *data, &mut *ops) };
+    // SAFETY: caller guarantees property and value are valid strings (or NULL for value)
+    let args = unsafe { borrow_str(property) }
+        .and_then(|p| Ok((p, unsafe { borrow_optional_str(value) }?)));
+    match args {
+        Ok((property, value)) => {
+            data.0.update(property, value.map(Into::into), &mut ops.0);
+            true
+        }
+        Err(_) => false,
+    }
+}
+
+#[ffizz_header::item]
+#[ffizz(order = 905)]
+/// Delete the task, adding the necessary operation to `ops`.  This is the final purge of the
+/// task, not a change to its status.
+///
+/// ```c
+/// EXTERN_C void tc_task_data_delete(TCTaskData *data, TCOperations *ops);
+/// ```
+#[no_mangle]
+pub unsafe extern "C" fn tc_task_data_delete(data: *mut TCTaskData, ops: *mut TCOperations) {
+    debug_assert!(!data.is_null());
+    debug_assert!(!ops.is_null());
+    // SAFETY: caller guarantees data and ops are valid
+    let (data, ops) = unsafe { (&mut *data, &mut *ops) };
+    data.0.delete(&mut ops.0);
+}
+
+#[ffizz_header::item]
+#[ffizz(order = 906)]
+/// Free a TCTaskData.  The TCTaskData must not be used after this call.
+///
+/// ```c
+/// EXTERN_C void tc_task_data_free(TCTaskData *data);
+/// ```
+#[no_mangle]
+pub unsafe extern "C" fn tc_task_data_free(data: *mut TCTaskData) {
+    if !data.is_null() {
+        // SAFETY: data was allocated by this library, and will not be used again
+        drop(unsafe { Box::from_raw(data) });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::*;
+    use pretty_assertions::assert_eq;
+    use std::ffi::{CStr, CString};
+
+    #[test]
+    fn update_and_delete() {
+        let rep = tc_replica_new_in_memory();
+        let ops = tc_operations_new();
+        let prop = CString::new("description").unwrap();
+        let value = CString::new("hello").unwrap();
+        let uuid = tc_uuid_new_v4();
+        unsafe {
+            let data = tc_task_data_create(uuid, ops);
+            assert!(tc_task_data_update(
+                data,
+                prop.as_ptr(),
+                value.as_ptr(),
+                ops
+            ));
+            assert_eq!(tc_replica_commit_operations(rep, ops), TCResult::Ok);
+            tc_task_data_free(data);
+
+            let data = tc_replica_get_task_data(rep, uuid);
+            assert_eq!(tc_task_data_get_uuid(data), uuid);
+            assert!(tc_task_data_has(data, prop.as_ptr()));
+            let s = tc_task_data_get(data, prop.as_ptr());
+            assert_eq!(CStr::from_ptr(s).to_str().unwrap(), "hello");
+            tc_string_free(s);
+
+            tc_task_data_delete(data, ops);
+            assert_eq!(tc_replica_commit_operations(rep, ops), TCResult::Ok);
+            tc_task_data_free(data);
+            assert!(tc_replica_get_task_data(rep, uuid).is_null());
+
+            tc_operations_free(ops);
+            tc_replica_free(rep);
+        }
+    }
+}
@@ -0,0 +1,184 @@
+use crate::string::{borrow_str, return_string};
+use crate::TCResult;
+use libc::c_char;
+use taskchampion::Uuid;
+
+#[ffizz_header::item]
+#[ffizz(order = 50)]
+/// ***** TCUuid *****
+///
+/// TCUuid is a task UUID, in its 16-byte binary form.  It is passed by value.
+///
+/// ```c
+/// typedef struct TCUuid {
+///   uint8_t bytes[16];
+/// } TCUuid;
+/// ```
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct TCUuid {
+    pub bytes: [u8; 16],
+}
+
+impl From<Uuid> for TCUuid {
+    fn from(uuid: Uuid) -> TCUuid {
+        TCUuid {
+            bytes: *uuid.as_bytes(),
+        }
+    }
+}
+
+impl From<TCUuid> for Uuid {
+    fn from(tcuuid: TCUuid) -> Uuid {
+        Uuid::from_bytes(tcuuid.bytes)
+    }
+}
+
+#[ffizz_header::item]
+#[ffizz(order = 51)]
+/// ***** TCUuidList *****
+///
+/// TCUuidList is a list of UUIDs, returned from the library.  It must be freed with
+/// `tc_uuid_list_free`.
+///
+/// ```c
+/// typedef struct TCUuidList {
+///   // number of UUIDs in items
+///   size_t len;
+///   // array of UUIDs
+///   TCUuid *items;
+/// } TCUuidList;
+/// ```
+#[repr(C)]
+pub struct TCUuidList {
+    pub len: usize,
+    pub items: *mut TCUuid,
+}
+
+impl From<Vec<Uuid>> for TCUuidList {
+    fn from(uuids: Vec<Uuid>) -> TCUuidList {
+        let items: Box<[TCUuid]> = uuids.into_iter().map(TCUuid::from).collect();
+        TCUuidList {
+            len: items.len(),
+            items: Box::into_raw(items) as *mut TCUuid,
+        }
+    }
+}
+
+impl TCUuidList {
+    /// An empty list, returned on error.
+    pub(crate) fn empty() -> TCUuidList {
+        Vec::new().into()
+    }
+}
+
+#[ffizz_header::item]
+#[ffizz(order = 300)]
+/// Create a new, randomly-generated UUID.
+///
+/// ```c
+/// EXTERN_C TCUuid tc_uuid_new_v4(void);
+/// ```
+#[no_mangle]
+pub extern "C" fn tc_uuid_new_v4() -> TCUuid {
+    Uuid::new_v4().into()
+}
+
+#[ffizz_header::item]
+#[ffizz(order = 301)]
+/// Create a new UUID with the nil value.
+///
+/// ```c
+/// EXTERN_C TCUuid tc_uuid_nil(void);
+/// ```
+#[no_mangle]
+pub extern "C" fn tc_uuid_nil() -> TCUuid {
+    Uuid::nil().into()
+}
+
+#[ffizz_header::item]
+#[ffizz(order = 302)]
+/// Format the given UUID in its hyphenated string form.  The resulting string must be freed with
+/// `tc_string_free`.
+///
+/// ```c
+/// EXTERN_C char *tc_uuid_to_str(TCUuid uuid);
+/// ```
+#[no_mangle]
+pub extern "C" fn tc_uuid_to_str(uuid: TCUuid) -> *mut c_char {
+    return_string(Uuid::from(uuid).to_string())
+}
+
+#[ffizz_header::item]
+#[ffizz(order = 303)]
+/// Parse the given string as a UUID, writing the result to `uuid_out`.  Returns
+/// `TC_RESULT_ERROR` if the string is not a valid UUID.
+///
+/// ```c
+/// EXTERN_C TCResult tc_uuid_from_str(const char *s, TCUuid *uuid_out);
+/// ```
+#[no_mangle]
+pub unsafe extern "C" fn tc_uuid_from_str(s: *const c_char, uuid_out: *mut TCUuid) -> TCResult {
+    debug_assert!(!uuid_out.is_null());
+    // SAFETY: caller guarantees s is a valid string
+    let Ok(s) = (unsafe { borrow_str(s) }) else {
+        return TCResult::Error;
+    };
+    match Uuid::parse_str(s) {
+        Ok(uuid) => {
+            // SAFETY: caller guarantees uuid_out is valid
+            unsafe { *uuid_out = uuid.into() };
+            TCResult::Ok
+        }
+        Err(_) => TCResult::Error,
+    }
+}
+
+#[ffizz_header::item]
+#[ffizz(order = 304)]
+/// Free a TCUuidList.  The list's items must not be used after this call, and the list is
+/// left empty.
+///
+/// ```c
+/// EXTERN_C void tc_uuid_list_free(TCUuidList *list);
+/// ```
+#[no_mangle]
+pub unsafe extern "C" fn tc_uuid_list_free(list: *mut TCUuidList) {
+    debug_assert!(!list.is_null());
+    // SAFETY: caller guarantees list is a valid TCUuidList
+    let list = unsafe { &mut *list };
+    if list.items.is_null() {
+        return;
+    }
+    // SAFETY: items and len were created from a boxed slice in From<Vec<Uuid>>
+    drop(unsafe { Box::from_raw(std::ptr::slice_from_raw_parts_mut(list.items, list.len)) });
+    list.items = std::ptr::null_mut();
+    list.len = 0;
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::tc_string_free;
+    use pretty_assertions::assert_eq;
+    use std::ffi::CString;
+
+    #[test]
+    fn round_trip() {
+        let uuid = tc_uuid_new_v4();
+        let s = tc_uuid_to_str(uuid);
+        let mut parsed = tc_uuid_nil();
+        assert_eq!(unsafe { tc_uuid_from_str(s, &mut parsed) }, TCResult::Ok);
+        assert_eq!(parsed, uuid);
+        unsafe { tc_string_free(s) };
+    }
+
+    #[test]
+    fn invalid() {
+        let mut parsed = tc_uuid_nil();
+        assert_eq!(
+            unsafe { tc_uuid_from_str(CString::new("not-a-uuid").unwrap().as_ptr(), &mut parsed) },
+            TCResult::Error
+        );
+    }
+}
@@ -0,0 +1,142 @@
+use crate::TCUuid;
+use taskchampion::WorkingSet;
+
+#[ffizz_header::item]
+#[ffizz(order = 50)]
+/// ***** TCWorkingSet *****
+///
+/// A TCWorkingSet represents a snapshot of the working set for a replica.  It is not
+/// automatically updated based on changes in the replica.
+///
+/// ```c
+/// typedef struct TCWorkingSet TCWorkingSet;
+/// ```
+pub struct TCWorkingSet(WorkingSet);
+
+impl TCWorkingSet {
+    pub(crate) fn return_ptr(ws: WorkingSet) -> *mut TCWorkingSet {
+        Box::into_raw(Box::new(TCWorkingSet(ws)))
+    }
+}
+
+/// Borrow a TCWorkingSet passed from C.
+unsafe fn ws_ref<'a>(ws: *const TCWorkingSet) -> &'a WorkingSet {
+    debug_assert!(!ws.is_null());
+    // SAFETY: caller guarantees ws is a valid TCWorkingSet
+    &unsafe { &*ws }.0
+}
+
+#[ffizz_header::item]
+#[ffizz(order = 1000)]
+/// Get the working set's length, or the number of UUIDs it contains.
+///
+/// ```c
+/// EXTERN_C size_t tc_working_set_len(const TCWorkingSet *ws);
+/// ```
+#[no_mangle]
+pub unsafe extern "C" fn tc_working_set_len(ws: *const TCWorkingSet) -> usize {
+    // SAFETY: caller guarantees ws is valid
+    unsafe { ws_ref(ws) }.len()
+}
+
+#[ffizz_header::item]
+#[ffizz(order = 1001)]
+/// Get the working set's largest index.
+///
+/// ```c
+/// EXTERN_C size_t tc_working_set_largest_index(const TCWorkingSet *ws);
+/// ```
+#[no_mangle]
+pub unsafe extern "C" fn tc_working_set_largest_index(ws: *const TCWorkingSet) -> usize {
+    // SAFETY: caller guarantees ws is valid
+    unsafe { ws_ref(ws) }.largest_index()
+}
+
+#[ffizz_header::item]
+#[ffizz(order = 1002)]
+/// Get the UUID for the task at the given index.  Returns true and writes the UUID to `uuid_out`
+/// if there is a task at that index; otherwise returns false.
+///
+/// ```c
+/// EXTERN_C bool tc_working_set_by_index(const TCWorkingSet *ws, size_t index, TCUuid *uuid_out);
+/// ```
+#[no_mangle]
+pub unsafe extern "C" fn tc_working_set_by_index(
+    ws: *const TCWorkingSet,
+    index: usize,
+    uuid_out: *mut TCUuid,
+) -> bool {
+    debug_assert!(!uuid_out.is_null());
+    // SAFETY: caller guarantees ws is valid
+    match unsafe { ws_ref(ws) }.by_index(index) {
+        Some(uuid) => {
+            // SAFETY: caller guarantees uuid_out is valid
+            unsafe { *uuid_out = uuid.into() };
+            true
+        }
+        None => false,
+    }
+}
+
+#[ffizz_header::item]
+#[ffizz(order = 1003)]
+/// Get the working set index for the task with the given UUID.  Returns 0 if the task is not in
+/// the working set.
+///
+/// ```c
+/// EXTERN_C size_t tc_working_set_by_uuid(const TCWorkingSet *ws, TCUuid uuid);
+/// ```
+#[no_mangle]
+pub unsafe extern "C" fn tc_working_set_by_uuid(ws: *const TCWorkingSet, uuid: TCUuid) -> usize {
+    // SAFETY: caller guarantees ws is valid
+    unsafe { ws_ref(ws) }.by_uuid(uuid.into()).unwrap_or(0)
+}
+
+#[ffizz_header::item]
+#[ffizz(order = 1004)]
+/// Free a TCWorkingSet.  The TCWorkingSet must not be used after this call.
+///
+/// ```c
+/// EXTERN_C void tc_working_set_free(TCWorkingSet *ws);
+/// ```
+#[no_mangle]
+pub unsafe extern "C" fn tc_working_set_free(ws: *mut TCWorkingSet) {
+    if !ws.is_null() {
+        // SAFETY: ws was allocated by this library, and will not be used again
+        drop(unsafe { Box::from_raw(ws) });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn working_set() {
+        let rep = tc_replica_new_in_memory();
+        let ops = tc_operations_new();
+        let uuid = tc_uuid_new_v4();
+        unsafe {
+            let task = tc_replica_create_task(rep, uuid, ops);
+            tc_task_set_status(task, TCStatus::Pending, ops);
+            tc_task_free(task);
+            assert_eq!(tc_replica_commit_operations(rep, ops), TCResult::Ok);
+
+            let ws = tc_replica_working_set(rep);
+            assert_eq!(tc_working_set_len(ws), 1);
+            assert_eq!(tc_working_set_largest_index(ws), 1);
+            let mut found = tc_uuid_nil();
+            assert!(tc_working_set_by_index(ws, 1, &mut found));
+            assert_eq!(found, uuid);
+            assert!(!tc_working_set_by_index(ws, 2, &mut found));
+            assert_eq!(tc_working_set_by_uuid(ws, uuid), 1);
+            assert_eq!(tc_working_set_by_uuid(ws, tc_uuid_new_v4()), 0);
+            tc_working_set_free(ws);
+
+            tc_operations_free(ops);
+            tc_replica_free(rep);
+        }
+    }
+}
@@ -0,0 +1,418 @@
+// TaskChampion
+//
+// This file defines the C interface to libtaskchampion.  This is a thin wrapper around the Rust
+// `taskchampion` crate.  Refer to the documentation for that crate at
+// https://docs.rs/taskchampion/latest/taskchampion/ for API details.
+//
+// ## Handles
+//
+// Replicas, tasks, and other complex values are represented as opaque pointers ("handles") to
+// values allocated by this library.  Each handle must be freed exactly once, with the
+// corresponding `tc_.._free` function, and must not be used after it is freed.  Unless stated
+// otherwise, handles passed to functions are borrowed and not freed by the function.
+//
+// ## Errors
+//
+// Functions that can fail return a `TCResult` or a NULL pointer.  The corresponding error message
+// can be retrieved from the object on which the function was called, with functions like
+// `tc_replica_error`.  Constructors take an `error_out` argument which, if not NULL, receives the
+// error message on failure.
+//
+// ## Strings
+//
+// Strings passed into the library are NUL-terminated UTF-8 strings, and remain owned by the
+// caller.  Strings returned from the library are owned by the caller, and must be freed with
+// `tc_string_free`.
+//
+// ## Thread Safety
+//
+// Handles must only be used from one thread at a time.
+#ifndef TASKCHAMPION_H
+#define TASKCHAMPION_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+#define EXTERN_C extern "C"
+#else
+#define EXTERN_C
+#endif // __cplusplus
+
+// ***** TCOperations *****
+//
+// TCOperations is a sequence of operations, built up by the functions which modify tasks and
+// then committed to a replica with `tc_replica_commit_operations`.
+typedef struct TCOperations TCOperations;
+
+// ***** TCReplica *****
+//
+// A replica represents an instance of a user's task data, providing an easy interface
+// for querying and modifying that data.
+//
+// When a `tc_replica_..` function that returns a TCResult returns `TC_RESULT_ERROR`, or a
+// function returning a pointer returns NULL, then `tc_replica_error` will return the error
+// message.
+typedef struct TCReplica TCReplica;
+
+// ***** TCResult *****
+//
+// A result from a TC operation.  On failure, the error message is available from the object on
+// which the operation was performed.
+typedef enum TCResult {
+  TC_RESULT_ERROR = -1,
+  TC_RESULT_OK = 0,
+} TCResult;
+
+// ***** TCServer *****
+//
+// TCServer is a connection to a sync server, used with `tc_replica_sync`.
+typedef struct TCServer TCServer;
+
+// ***** TCServerConfig *****
+//
+// TCServerConfig describes how to connect to a sync server.  Create a server from it with
+// `tc_server_new`.
+typedef struct TCServerConfig TCServerConfig;
+
+// ***** TCStatus *****
+//
+// The status of a task.  Statuses not recognized by this library are represented as
+// `TC_STATUS_UNKNOWN`.
+typedef enum TCStatus {
+  TC_STATUS_PENDING,
+  TC_STATUS_COMPLETED,
+  TC_STATUS_DELETED,
+  TC_STATUS_RECURRING,
+  TC_STATUS_UNKNOWN,
+} TCStatus;
+
+// ***** TCTask *****
+//
+// A task, with a high-level interface for reading and modifying its properties.  Modifications
+// add operations to a TCOperations, and take effect when those operations are committed to the
+// replica with `tc_replica_commit_operations`.
+//
+// A TCTask is a snapshot of the task at the time it was retrieved from the replica, updated by
+// modifications made through it.
+//
+// When a `tc_task_..` function that returns a TCResult returns `TC_RESULT_ERROR`, then
+// `tc_task_error` will return the error message.
+typedef struct TCTask TCTask;
+
+// ***** TCTaskData *****
+//
+// TCTaskData is the low-level representation of a task, as a key/value map.  Modifications add
+// operations to a TCOperations, and take effect when those operations are committed to the
+// replica with `tc_replica_commit_operations`.
+typedef struct TCTaskData TCTaskData;
+
+// ***** TCUuid *****
+//
+// TCUuid is a task UUID, in its 16-byte binary form.  It is passed by value.
+typedef struct TCUuid {
+  uint8_t bytes[16];
+} TCUuid;
+
+// ***** TCWorkingSet *****
+//
+// A TCWorkingSet represents a snapshot of the working set for a replica.  It is not
+// automatically updated based on changes in the replica.
+typedef struct TCWorkingSet TCWorkingSet;
+
+// ***** TCTaskList *****
+//
+// TCTaskList is a list of tasks, returned from the library.  It must be freed with
+// `tc_task_list_free`, which also frees the tasks it contains.
+typedef struct TCTaskList {
+  // number of tasks in items
+  size_t len;
+  // array of pointers to tasks
+  TCTask **items;
+} TCTaskList;
+
+// ***** TCUuidList *****
+//
+// TCUuidList is a list of UUIDs, returned from the library.  It must be freed with
+// `tc_uuid_list_free`.
+typedef struct TCUuidList {
+  // number of UUIDs in items
+  size_t len;
+  // array of UUIDs
+  TCUuid *items;
+} TCUuidList;
+
+// Free a string returned from this library.  The string must not be used after this call.  If
+// the pointer is NULL, this function does nothing.
+EXTERN_C void tc_string_free(char *string);
+
+// Create a new, randomly-generated UUID.
+EXTERN_C TCUuid tc_uuid_new_v4(void);
+
+// Create a new UUID with the nil value.
+EXTERN_C TCUuid tc_uuid_nil(void);
+
+// Format the given UUID in its hyphenated string form.  The resulting string must be freed with
+// `tc_string_free`.
+EXTERN_C char *tc_uuid_to_str(TCUuid uuid);
+
+// Parse the given string as a UUID, writing the result to `uuid_out`.  Returns
+// `TC_RESULT_ERROR` if the string is not a valid UUID.
+EXTERN_C TCResult tc_uuid_from_str(const char *s, TCUuid *uuid_out);
+
+// Free a TCUuidList.  The list's items must not be used after this call, and the list is
+// left empty.
+EXTERN_C void tc_uuid_list_free(TCUuidList *list);
+
+// Create a new, empty TCOperations.  The result must be freed with `tc_operations_free`.
+EXTERN_C TCOperations *tc_operations_new(void);
+
+// Get the number of operations in the TCOperations.
+EXTERN_C size_t tc_operations_len(const TCOperations *ops);
+
+// Add an undo point to the TCOperations.  When the operations are committed, a subsequent
+// undo will revert to this point.
+EXTERN_C void tc_operations_add_undo_point(TCOperations *ops);
+
+// Free a TCOperations.  The TCOperations must not be used after this call.
+EXTERN_C void tc_operations_free(TCOperations *ops);
+
+// Create a configuration for a local server, storing its data in the given directory.  This is
+// useful for situations with a single replica.  Returns NULL on error, in which case the error
+// message is written to `error_out` if it is not NULL.
+//
+// The result must be freed with `tc_server_config_free`.
+EXTERN_C TCServerConfig *tc_server_config_new_local(const char *server_dir, char **error_out);
+
+// Create a configuration for a remote taskchampion-sync-server at the given URL.  The client ID
+// identifies this replica to the server, and the encryption secret (of `secret_len` bytes) is
+// used to encrypt all data sent to the server.  Returns NULL on error, in which case the error
+// message is written to `error_out` if it is not NULL.
+//
+// The result must be freed with `tc_server_config_free`.
+EXTERN_C TCServerConfig *tc_server_config_new_remote(const char *url,
+                                                     TCUuid client_id,
+                                                     const uint8_t *secret,
+                                                     size_t secret_len,
+                                                     char **error_out);
+
+// Create a configuration for a Google Cloud Platform storage bucket.  If `credential_path` is
+// NULL, Application Default Credentials are used; otherwise it must be the path to a service
+// account key.  The encryption secret (of `secret_len` bytes) is used to encrypt all data sent
+// to the server.  Returns NULL on error, in which case the error message is written to
+// `error_out` if it is not NULL.
+//
+// The result must be freed with `tc_server_config_free`.
+EXTERN_C TCServerConfig *tc_server_config_new_gcp(const char *bucket,
+                                                  const char *credential_path,
+                                                  const uint8_t *secret,
+                                                  size_t secret_len,
+                                                  char **error_out);
+
+// Free a TCServerConfig.  The TCServerConfig must not be used after this call.
+EXTERN_C void tc_server_config_free(TCServerConfig *config);
+
+// Create a server from the given configuration.  The configuration is not freed, and may be
+// used again.  Returns NULL on error, in which case the error message is written to `error_out`
+// if it is not NULL.
+//
+// The result must be freed with `tc_server_free`.
+EXTERN_C TCServer *tc_server_new(const TCServerConfig *config, char **error_out);
+
+// Free a TCServer.  The TCServer must not be used after this call.
+EXTERN_C void tc_server_free(TCServer *server);
+
+// Create a new TCReplica with an in-memory database.  The contents of the database will be
+// lost when it is freed with `tc_replica_free`.
+EXTERN_C TCReplica *tc_replica_new_in_memory(void);
+
+// Create a new TCReplica with an on-disk database in the given directory, creating it if
+// `create_if_missing` is true.  Returns NULL on error, in which case the error message is
+// written to `error_out` if it is not NULL.
+EXTERN_C TCReplica *tc_replica_new_on_disk(const char *path,
+                                           bool create_if_missing,
+                                           char **error_out);
+
+// Get the latest error for a replica, or NULL if the last operation succeeded.  The returned
+// string must be freed with `tc_string_free`.
+EXTERN_C char *tc_replica_error(TCReplica *rep);
+
+// Free a replica.  The replica must not be used after this call, but tasks and other values
+// retrieved from it remain valid until they are freed.
+EXTERN_C void tc_replica_free(TCReplica *rep);
+
+// Create a new task with the given UUID, adding the necessary operations to `ops`.  If the task
+// already exists, it is returned.  Returns NULL on error.
+//
+// The result must be freed with `tc_task_free`.
+EXTERN_C TCTask *tc_replica_create_task(TCReplica *rep, TCUuid uuid, TCOperations *ops);
+
+// Get an existing task by its UUID.  Returns NULL if the task does not exist, or on error, in
+// which case `tc_replica_error` returns the error message.
+//
+// The result must be freed with `tc_task_free`.
+EXTERN_C TCTask *tc_replica_get_task(TCReplica *rep, TCUuid uuid);
+
+// Get the data for an existing task by its UUID.  Returns NULL if the task does not exist, or
+// on error, in which case `tc_replica_error` returns the error message.
+//
+// The result must be freed with `tc_task_data_free`.
+EXTERN_C TCTaskData *tc_replica_get_task_data(TCReplica *rep, TCUuid uuid);
+
+// Get a list of all tasks in the replica.  On error, the list is empty and `tc_replica_error`
+// returns the error message.
+//
+// The result must be freed with `tc_task_list_free`.
+EXTERN_C TCTaskList tc_replica_all_tasks(TCReplica *rep);
+
+// Get a list of the UUIDs of all tasks in the replica.  On error, the list is empty and
+// `tc_replica_error` returns the error message.
+//
+// The result must be freed with `tc_uuid_list_free`.
+EXTERN_C TCUuidList tc_replica_all_task_uuids(TCReplica *rep);
+
+// Commit the given operations to the replica.  On success, `ops` is left empty and may be
+// reused.
+EXTERN_C TCResult tc_replica_commit_operations(TCReplica *rep, TCOperations *ops);
+
+// Undo local operations until the most recent undo point.  If `undone_out` is not NULL, it is
+// set to true if any operations were undone.
+EXTERN_C TCResult tc_replica_undo(TCReplica *rep, bool *undone_out);
+
+// Get the number of local operations that have not yet been synchronized, or -1 on error.
+EXTERN_C int64_t tc_replica_num_local_operations(TCReplica *rep);
+
+// Get the number of undo points available, or -1 on error.
+EXTERN_C int64_t tc_replica_num_undo_points(TCReplica *rep);
+
+// Get the current working set for this replica.  Returns NULL on error.
+//
+// The result must be freed with `tc_working_set_free`.
+EXTERN_C TCWorkingSet *tc_replica_working_set(TCReplica *rep);
+
+// Rebuild the working set, removing tasks that are no longer pending and adding pending tasks.
+// If `renumber` is true, the remaining tasks are renumbered to fill gaps.
+EXTERN_C TCResult tc_replica_rebuild_working_set(TCReplica *rep, bool renumber);
+
+// Remove deleted tasks that have not been modified for some time from the replica.
+EXTERN_C TCResult tc_replica_expire_tasks(TCReplica *rep);
+
+// Synchronize this replica with a server.  If `avoid_snapshots` is true, the replica will not
+// upload a snapshot even if the server requests one; this is useful on slow or metered
+// connections.
+EXTERN_C TCResult tc_replica_sync(TCReplica *rep, TCServer *server, bool avoid_snapshots);
+
+// Get a task's UUID.
+EXTERN_C TCUuid tc_task_get_uuid(const TCTask *task);
+
+// Get a task's status.
+EXTERN_C TCStatus tc_task_get_status(const TCTask *task);
+
+// Get a task's description.  The result must be freed with `tc_string_free`.
+EXTERN_C char *tc_task_get_description(const TCTask *task);
+
+// Get the value of an arbitrary property of a task, or NULL if the property is not set or the
+// property name is not a valid string.  The result must be freed with `tc_string_free`.
+EXTERN_C char *tc_task_get_value(const TCTask *task, const char *property);
+
+// Determine whether the task has the given tag.  Synthetic tags such as `PENDING` are
+// supported.  Returns false if the tag is not valid.
+EXTERN_C bool tc_task_has_tag(const TCTask *task, const char *tag);
+
+// Determine whether the task is active (started and not stopped).
+EXTERN_C bool tc_task_is_active(const TCTask *task);
+
+// Set a task's status, adding the necessary operations to `ops`.
+EXTERN_C TCResult tc_task_set_status(TCTask *task, TCStatus status, TCOperations *ops);
+
+// Set a task's description, adding the necessary operations to `ops`.
+EXTERN_C TCResult tc_task_set_description(TCTask *task,
+                                          const char *description,
+                                          TCOperations *ops);
+
+// Set an arbitrary property of a task, adding the necessary operations to `ops`.  If `value` is
+// NULL, the property is removed.
+EXTERN_C TCResult tc_task_set_value(TCTask *task,
+                                    const char *property,
+                                    const char *value,
+                                    TCOperations *ops);
+
+// Add a tag to a task, adding the necessary operations to `ops`.
+EXTERN_C TCResult tc_task_add_tag(TCTask *task, const char *tag, TCOperations *ops);
+
+// Remove a tag from a task, adding the necessary operations to `ops`.
+EXTERN_C TCResult tc_task_remove_tag(TCTask *task, const char *tag, TCOperations *ops);
+
+// Start a task, adding the necessary operations to `ops`.
+EXTERN_C TCResult tc_task_start(TCTask *task, TCOperations *ops);
+
+// Stop a task, adding the necessary operations to `ops`.
+EXTERN_C TCResult tc_task_stop(TCTask *task, TCOperations *ops);
+
+// Mark a task as done, adding the necessary operations to `ops`.
+EXTERN_C TCResult tc_task_done(TCTask *task, TCOperations *ops);
+
+// Get a copy of the task's underlying data.  The result must be freed with
+// `tc_task_data_free`.
+EXTERN_C TCTaskData *tc_task_to_task_data(const TCTask *task);
+
+// Get the latest error for a task, or NULL if the last operation succeeded.  The returned
+// string must be freed with `tc_string_free`.
+EXTERN_C char *tc_task_error(TCTask *task);
+
+// Free a task.  The task must not be used after this call.
+EXTERN_C void tc_task_free(TCTask *task);
+
+// Free a TCTaskList, including all of the tasks it contains.  The list's items must not be used
+// after this call, and the list is left empty.
+EXTERN_C void tc_task_list_free(TCTaskList *list);
+
+// Create a new task with the given UUID, adding the necessary operation to `ops`.  This does
+// not check whether the task already exists.
+//
+// The result must be freed with `tc_task_data_free`.
+EXTERN_C TCTaskData *tc_task_data_create(TCUuid uuid, TCOperations *ops);
+
+// Get the UUID of the task.
+EXTERN_C TCUuid tc_task_data_get_uuid(const TCTaskData *data);
+
+// Get the value of a property of the task, or NULL if the property is not set or the property
+// name is not a valid string.  The result must be freed with `tc_string_free`.
+EXTERN_C char *tc_task_data_get(const TCTaskData *data, const char *property);
+
+// Determine whether the task has the given property.
+EXTERN_C bool tc_task_data_has(const TCTaskData *data, const char *property);
+
+// Set a property of the task, adding the necessary operation to `ops`.  If `value` is NULL, the
+// property is removed.  Returns false if the property or value is not a valid string.
+EXTERN_C bool tc_task_data_update(TCTaskData *data,
+                                  const char *property,
+                                  const char *value,
+                                  TCOperations *ops);
+
+// Delete the task, adding the necessary operation to `ops`.  This is the final purge of the
+// task, not a change to its status.
+EXTERN_C void tc_task_data_delete(TCTaskData *data, TCOperations *ops);
+
+// Free a TCTaskData.  The TCTaskData must not be used after this call.
+EXTERN_C void tc_task_data_free(TCTaskData *data);
+
+// Get the working set's length, or the number of UUIDs it contains.
+EXTERN_C size_t tc_working_set_len(const TCWorkingSet *ws);
+
+// Get the working set's largest index.
+EXTERN_C size_t tc_working_set_largest_index(const TCWorkingSet *ws);
+
+// Get the UUID for the task at the given index.  Returns true and writes the UUID to `uuid_out`
+// if there is a task at that index; otherwise returns false.
+EXTERN_C bool tc_working_set_by_index(const TCWorkingSet *ws, size_t index, TCUuid *uuid_out);
+
+// Get the working set index for the task with the given UUID.  Returns 0 if the task is not in
+// the working set.
+EXTERN_C size_t tc_working_set_by_uuid(const TCWorkingSet *ws, TCUuid uuid);
+
+// Free a TCWorkingSet.  The TCWorkingSet must not be used after this call.
+EXTERN_C void tc_working_set_free(TCWorkingSet *ws);
+
+#endif /* TASKCHAMPION_H */
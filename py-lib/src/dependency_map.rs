@@ -1,8 +1,9 @@
 use pyo3::prelude::*;
+use std::sync::Arc;
 use taskchampion::{DependencyMap as TCDependencyMap, Uuid};
 
 #[pyclass]
-pub struct DependencyMap(pub(crate) TCDependencyMap);
+pub struct DependencyMap(pub(crate) Arc<TCDependencyMap>);
 
 #[pymethods]
 impl DependencyMap {
@@ -0,0 +1,36 @@
+// `create_exception!` in this version of pyo3 refers to a `gil-refs` feature it does not declare
+#![allow(unexpected_cfgs)]
+use pyo3::create_exception;
+use pyo3::exceptions::PyRuntimeError;
+use pyo3::prelude::*;
+use taskchampion::Error as TCError;
+
+create_exception!(
+    taskchampion,
+    OutOfSyncError,
+    PyRuntimeError,
+    "The local replica cannot be synchronized with the server."
+);
+create_exception!(
+    taskchampion,
+    ServerError,
+    PyRuntimeError,
+    "An error communicating with the sync server."
+);
+create_exception!(
+    taskchampion,
+    UsageError,
+    PyRuntimeError,
+    "An invalid use of the TaskChampion API."
+);
+
+/// Convert a TaskChampion error into the corresponding Python exception.  Errors without a
+/// more specific exception become a `RuntimeError`, as with other `anyhow` errors.
+pub(crate) fn into_pyerr(err: TCError) -> PyErr {
+    match err {
+        TCError::OutOfSync => OutOfSyncError::new_err(err.to_string()),
+        TCError::Server(_) => ServerError::new_err(err.to_string()),
+        TCError::Usage(_) => UsageError::new_err(err.to_string()),
+        _ => PyRuntimeError::new_err(err.to_string()),
+    }
+}
@@ -9,6 +9,10 @@ pub mod dependency_map;
 use dependency_map::*;
 pub mod operation;
 use operation::*;
+pub mod server;
+use server::*;
+mod errors;
+use errors::{OutOfSyncError, ServerError, UsageError};
 mod task;
 use task::{Annotation, Status, Tag, Task};
 
@@ -24,6 +28,11 @@ fn taskchampion(m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_class::<SqliteStorage>()?;
     m.add_class::<DependencyMap>()?;
     m.add_class::<Operation>()?;
+    m.add_class::<ServerConfig>()?;
+    m.add_class::<Server>()?;
+    m.add("OutOfSyncError", m.py().get_type_bound::<OutOfSyncError>())?;
+    m.add("ServerError", m.py().get_type_bound::<ServerError>())?;
+    m.add("UsageError", m.py().get_type_bound::<UsageError>())?;
 
     Ok(())
 }
@@ -1,10 +1,13 @@
 use std::collections::HashMap;
-use std::rc::Rc;
 
+use crate::errors::into_pyerr;
+use crate::storage::{PyStorage, Storage};
 use crate::task::TaskData;
-use crate::{DependencyMap, Operation, Task, WorkingSet};
+use crate::{DependencyMap, InMemoryStorage, Operation, Server, SqliteStorage, Task, WorkingSet};
 use pyo3::prelude::*;
-use taskchampion::storage::{InMemoryStorage, SqliteStorage};
+use taskchampion::storage::{
+    InMemoryStorage as TCInMemoryStorage, SqliteStorage as TCSqliteStorage, Storage as TCStorage,
+};
 use taskchampion::{Operations as TCOperations, Replica as TCReplica, Uuid};
 
 #[pyclass]
@@ -12,39 +15,53 @@ use taskchampion::{Operations as TCOperations, Replica as TCReplica, Uuid};
 /// for querying and modifying that data.
 pub struct Replica(TCReplica);
 
-unsafe impl Send for Replica {}
 #[pymethods]
 impl Replica {
     #[new]
+    #[pyo3(signature = (storage, create_if_missing=false))]
     /// Instantiates the Replica
     ///
     /// Args:
-    ///     path (str): path to the directory with the database
-    ///     create_if_missing (bool): create the database if it does not exist
+    ///     storage (str | InMemoryStorage | SqliteStorage | object): path to the directory with
+    ///         the database, a storage object, or a Python object implementing the storage
+    ///         protocol (an object with a `txn()` method returning a transaction)
+    ///     create_if_missing (bool): create the database if it does not exist, when `storage`
+    ///         is a path
     /// Raises:
     ///     RuntimeError: if database does not exist, and create_if_missing is false
-    pub fn new(path: String, create_if_missing: bool) -> anyhow::Result<Replica> {
-        let storage = SqliteStorage::new(path, create_if_missing)?;
+    ///     UsageError: if the storage object is already in use by another Replica
+    pub fn new(storage: &Bound<'_, PyAny>, create_if_missing: bool) -> anyhow::Result<Replica> {
+        let storage: Box<dyn TCStorage> = if let Ok(path) = storage.extract::<String>() {
+            Box::new(TCSqliteStorage::new(path, create_if_missing)?)
+        } else if let Ok(s) = storage.downcast::<InMemoryStorage>() {
+            s.borrow_mut().take_storage()?
+        } else if let Ok(s) = storage.downcast::<SqliteStorage>() {
+            s.borrow_mut().take_storage()?
+        } else {
+            Box::new(PyStorage(storage.clone().unbind()))
+        };
 
-        Ok(Replica(TCReplica::new(Box::new(storage))))
+        Ok(Replica(TCReplica::new(storage)))
     }
 
     #[staticmethod]
     pub fn new_inmemory() -> Self {
-        let storage = InMemoryStorage::new();
+        let storage = TCInMemoryStorage::new();
 
         Replica(TCReplica::new(Box::new(storage)))
     }
     /// Create a new task
     /// The task must not already exist.
-
     pub fn create_task(&mut self, uuid: String) -> anyhow::Result<(Task, Operation)> {
         let mut ops = TCOperations::new();
         let task = self
             .0
             .create_task(Uuid::parse_str(&uuid)?, &mut ops)
-            .map(|t| Task(t))?;
-        Ok((task, Operation(ops.get(0).expect("Invalid create").clone())))
+            .map(Task)?;
+        Ok((
+            task,
+            Operation(ops.first().expect("Invalid create").clone()),
+        ))
     }
 
     /// Get a list of all tasks in the replica.
@@ -74,39 +91,41 @@ impl Replica {
     }
 
     pub fn working_set(&mut self) -> anyhow::Result<WorkingSet> {
-        Ok(self.0.working_set().map(|ws| WorkingSet(ws))?)
+        Ok(self.0.working_set().map(WorkingSet)?)
     }
 
     pub fn dependency_map(&mut self, force: bool) -> anyhow::Result<DependencyMap> {
-        // TODO: kinda spaghetti here, it will do for now
-        let s = self
-            .0
-            .dependency_map(force)
-            .map(|rc| {
-                // TODO: better error handling here
-                Rc::into_inner(rc).unwrap()
-            })
-            .map(|dm| DependencyMap(dm))?;
-
-        Ok(s)
+        Ok(DependencyMap(self.0.dependency_map(force)?))
     }
 
     pub fn get_task(&mut self, uuid: String) -> anyhow::Result<Option<Task>> {
         Ok(self
             .0
             .get_task(Uuid::parse_str(&uuid).unwrap())
-            .map(|opt| opt.map(|t| Task(t)))?)
+            .map(|opt| opt.map(Task))?)
     }
 
     pub fn get_task_data(&mut self, uuid: String) -> anyhow::Result<Option<TaskData>> {
         Ok(self
             .0
             .get_task_data(Uuid::parse_str(&uuid)?)
-            .map(|opt| opt.map(|td| TaskData(td)))?)
+            .map(|opt| opt.map(TaskData))?)
     }
 
-    pub fn sync(&self, _avoid_snapshots: bool) {
-        todo!()
+    /// Synchronize this replica with a server.
+    ///
+    /// Args:
+    ///     server (Server): the server to synchronize with
+    ///     avoid_snapshots (bool): avoid uploading snapshots, e.g. on a slow or metered connection
+    /// Raises:
+    ///     OutOfSyncError: if the replica cannot be synchronized with the server
+    ///     ServerError: if communication with the server fails
+    pub fn sync(&mut self, server: &mut Server, avoid_snapshots: bool) -> anyhow::Result<()> {
+        // a PyErr wrapped in an anyhow::Error is raised as-is
+        Ok(self
+            .0
+            .sync(&mut server.0, avoid_snapshots)
+            .map_err(into_pyerr)?)
     }
     pub fn commit_operations(&mut self, operations: Vec<Operation>) -> anyhow::Result<()> {
         let ops = operations.iter().map(|op| op.0.clone()).collect();
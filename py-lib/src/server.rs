@@ -0,0 +1,154 @@
+use crate::errors::into_pyerr;
+use pyo3::prelude::*;
+use std::path::PathBuf;
+use taskchampion::{AwsCredentials, Server as TCServer, ServerConfig as TCServerConfig, Uuid};
+
+#[pyclass]
+#[derive(Clone)]
+/// The configuration for a replica's access to a sync server.  Use one of the static
+/// constructors to create a configuration, then `into_server` to connect to the server.
+pub struct ServerConfig(TCServerConfig);
+
+#[pymethods]
+impl ServerConfig {
+    #[staticmethod]
+    /// A local task database, for situations with a single replica.
+    ///
+    /// Args:
+    ///     server_dir (str): path to the directory containing the server's database
+    pub fn local(server_dir: String) -> ServerConfig {
+        ServerConfig(TCServerConfig::Local {
+            server_dir: PathBuf::from(server_dir),
+        })
+    }
+
+    #[staticmethod]
+    /// A remote taskchampion-sync-server instance.
+    ///
+    /// Args:
+    ///     url (str): the base URL of the sync server
+    ///     client_id (str): UUID identifying this replica to the server
+    ///     encryption_secret (bytes): secret used to encrypt all data sent to the server
+    pub fn remote(
+        url: String,
+        client_id: String,
+        encryption_secret: Vec<u8>,
+    ) -> anyhow::Result<ServerConfig> {
+        Ok(ServerConfig(TCServerConfig::Remote {
+            url,
+            client_id: Uuid::parse_str(&client_id)?,
+            encryption_secret,
+        }))
+    }
+
+    #[staticmethod]
+    #[pyo3(signature = (bucket, encryption_secret, credential_path=None))]
+    /// A Google Cloud Platform storage bucket.
+    ///
+    /// Args:
+    ///     bucket (str): bucket in which to store the task data
+    ///     encryption_secret (bytes): secret used to encrypt all data sent to the server
+    ///     credential_path (Optional[str]): path to a service account key; if omitted,
+    ///         Application Default Credentials are used
+    pub fn gcp(
+        bucket: String,
+        encryption_secret: Vec<u8>,
+        credential_path: Option<String>,
+    ) -> ServerConfig {
+        ServerConfig(TCServerConfig::Gcp {
+            bucket,
+            credential_path,
+            encryption_secret,
+        })
+    }
+
+    #[staticmethod]
+    #[pyo3(signature = (region, bucket, encryption_secret, access_key_id=None, secret_access_key=None, profile_name=None, endpoint=None))]
+    #[allow(clippy::too_many_arguments)]
+    /// An AWS S3 bucket, or a bucket in a service implementing the S3 API.
+    ///
+    /// Credentials are given either as an access key ID and secret access key, or as a profile
+    /// name.  If neither is given, the default AWS credential chain is used.
+    ///
+    /// Args:
+    ///     region (str): region in which the bucket is located
+    ///     bucket (str): bucket in which to store the task data
+    ///     encryption_secret (bytes): secret used to encrypt all data sent to the server
+    ///     access_key_id (Optional[str]): access key ID
+    ///     secret_access_key (Optional[str]): secret access key
+    ///     profile_name (Optional[str]): name of a profile in the AWS configuration files
+    ///     endpoint (Optional[str]): URL of an S3-compatible service, such as MinIO
+    pub fn aws(
+        region: String,
+        bucket: String,
+        encryption_secret: Vec<u8>,
+        access_key_id: Option<String>,
+        secret_access_key: Option<String>,
+        profile_name: Option<String>,
+        endpoint: Option<String>,
+    ) -> anyhow::Result<ServerConfig> {
+        let credentials = match (access_key_id, secret_access_key, profile_name) {
+            (Some(access_key_id), Some(secret_access_key), None) => AwsCredentials::AccessKey {
+                access_key_id,
+                secret_access_key,
+            },
+            (None, None, Some(profile_name)) => AwsCredentials::Profile { profile_name },
+            (None, None, None) => AwsCredentials::Default,
+            _ => anyhow::bail!(
+                "give both access_key_id and secret_access_key, or profile_name, or neither"
+            ),
+        };
+        Ok(ServerConfig(TCServerConfig::Aws {
+            region,
+            bucket,
+            endpoint,
+            credentials,
+            encryption_secret,
+        }))
+    }
+
+    #[staticmethod]
+    #[pyo3(signature = (url, encryption_secret, username=None, password=None))]
+    /// A WebDAV collection, such as a folder in Nextcloud.
+    ///
+    /// Args:
+    ///     url (str): URL of the collection in which to store the task data
+    ///     encryption_secret (bytes): secret used to encrypt all data sent to the server
+    ///     username (Optional[str]): username for HTTP basic authentication
+    ///     password (Optional[str]): password for HTTP basic authentication
+    pub fn webdav(
+        url: String,
+        encryption_secret: Vec<u8>,
+        username: Option<String>,
+        password: Option<String>,
+    ) -> ServerConfig {
+        ServerConfig(TCServerConfig::WebDav {
+            url,
+            username,
+            password,
+            encryption_secret,
+        })
+    }
+
+    #[staticmethod]
+    /// A directory on a filesystem shared between replicas.
+    ///
+    /// Args:
+    ///     path (str): directory in which to store the task data
+    ///     encryption_secret (bytes): secret used to encrypt all data stored in the directory
+    pub fn directory(path: String, encryption_secret: Vec<u8>) -> ServerConfig {
+        ServerConfig(TCServerConfig::Directory {
+            path: PathBuf::from(path),
+            encryption_secret,
+        })
+    }
+
+    /// Get a server based on this configuration.
+    pub fn into_server(&self) -> anyhow::Result<Server> {
+        Ok(Server(self.0.clone().into_server().map_err(into_pyerr)?))
+    }
+}
+
+#[pyclass]
+/// A connection to a sync server, created with `ServerConfig.into_server`.
+pub struct Server(pub(crate) Box<dyn TCServer>);
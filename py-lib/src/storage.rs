@@ -1,38 +1,210 @@
+use crate::errors::UsageError;
+use crate::Operation;
 use pyo3::prelude::*;
 use taskchampion::storage::{
-    InMemoryStorage as TCInMemoryStorage, SqliteStorage as TCSqliteStorage,
+    InMemoryStorage as TCInMemoryStorage, SqliteStorage as TCSqliteStorage, Storage as TCStorage,
+    StorageTxn as TCStorageTxn, TaskMap, VersionId,
 };
-// TODO: actually make the storage usable and extensible, rn it just exists /shrug
-pub trait Storage {}
+use taskchampion::{Error as TCError, Operation as TCOperation, Uuid};
+
+type TCResult<T> = Result<T, TCError>;
+
+/// A storage implementation that can be handed to a Replica.
+pub trait Storage {
+    /// Take the underlying storage, for use by a new Replica.  A storage object can only be
+    /// used by one Replica.
+    fn take_storage(&mut self) -> PyResult<Box<dyn TCStorage>>;
+}
+
+fn already_used() -> PyErr {
+    UsageError::new_err("This storage is already in use by a Replica")
+}
 
-#[allow(dead_code)]
 #[pyclass]
-pub struct InMemoryStorage(TCInMemoryStorage);
+pub struct InMemoryStorage(Option<TCInMemoryStorage>);
 
 #[pymethods]
+#[allow(clippy::new_without_default)]
 impl InMemoryStorage {
     #[new]
     pub fn new() -> InMemoryStorage {
-        InMemoryStorage(TCInMemoryStorage::new())
+        InMemoryStorage(Some(TCInMemoryStorage::new()))
     }
 }
 
-impl Storage for InMemoryStorage {}
+impl Storage for InMemoryStorage {
+    fn take_storage(&mut self) -> PyResult<Box<dyn TCStorage>> {
+        Ok(Box::new(self.0.take().ok_or_else(already_used)?))
+    }
+}
 
-#[allow(dead_code)]
 #[pyclass]
-pub struct SqliteStorage(TCSqliteStorage);
+pub struct SqliteStorage(Option<TCSqliteStorage>);
 
 #[pymethods]
 impl SqliteStorage {
     #[new]
     pub fn new(path: String, create_if_missing: bool) -> anyhow::Result<Self> {
-        // TODO: kinda ugly, prettify;
-        Ok(SqliteStorage(TCSqliteStorage::new(
+        Ok(SqliteStorage(Some(TCSqliteStorage::new(
             path,
             create_if_missing,
-        )?))
+        )?)))
     }
 }
 
-impl Storage for SqliteStorage {}
+impl Storage for SqliteStorage {
+    fn take_storage(&mut self) -> PyResult<Box<dyn TCStorage>> {
+        Ok(Box::new(self.0.take().ok_or_else(already_used)?))
+    }
+}
+
+/// A storage implemented in Python.
+///
+/// The Python object must have a `txn()` method returning a transaction object, which must
+/// implement the methods of `taskchampion::storage::StorageTxn`, using strings for UUIDs,
+/// `dict[str, str]` for task maps, and `Operation` for operations:
+///
+/// - `get_task(uuid) -> Optional[dict[str, str]]`
+/// - `create_task(uuid) -> bool`
+/// - `set_task(uuid, task)`
+/// - `delete_task(uuid) -> bool`
+/// - `all_tasks() -> list[tuple[str, dict[str, str]]]`
+/// - `all_task_uuids() -> list[str]`
+/// - `base_version() -> str`
+/// - `set_base_version(version)`
+/// - `operations() -> list[Operation]`
+/// - `num_operations() -> int`
+/// - `add_operation(op)`
+/// - `set_operations(ops)`
+/// - `get_working_set() -> list[Optional[str]]`
+/// - `add_to_working_set(uuid) -> int`
+/// - `set_working_set_item(index, uuid)`
+/// - `clear_working_set()`
+/// - `commit()`
+///
+/// Changes made in a transaction that is not committed must not be visible to later
+/// transactions.  Exceptions raised by these methods are propagated to the caller.
+pub(crate) struct PyStorage(pub(crate) PyObject);
+
+impl TCStorage for PyStorage {
+    fn txn<'a>(&'a mut self) -> TCResult<Box<dyn TCStorageTxn + 'a>> {
+        let txn = Python::with_gil(|py| self.0.call_method0(py, "txn")).map_err(pyerr)?;
+        Ok(Box::new(PyStorageTxn(txn)))
+    }
+}
+
+struct PyStorageTxn(PyObject);
+
+impl PyStorageTxn {
+    /// Call a method on the Python transaction object and extract the result.
+    fn call<A, R>(&self, method: &str, args: A) -> TCResult<R>
+    where
+        A: IntoPy<Py<pyo3::types::PyTuple>>,
+        R: for<'py> FromPyObject<'py>,
+    {
+        Python::with_gil(|py| self.0.call_method1(py, method, args)?.extract(py)).map_err(pyerr)
+    }
+
+    /// Call a method on the Python transaction object, ignoring its result.
+    fn invoke<A>(&self, method: &str, args: A) -> TCResult<()>
+    where
+        A: IntoPy<Py<pyo3::types::PyTuple>>,
+    {
+        Python::with_gil(|py| self.0.call_method1(py, method, args))
+            .map(|_| ())
+            .map_err(pyerr)
+    }
+}
+
+fn pyerr(err: PyErr) -> TCError {
+    TCError::Other(err.into())
+}
+
+fn parse_uuid(uuid: &str) -> TCResult<Uuid> {
+    Uuid::parse_str(uuid).map_err(|e| TCError::Database(format!("invalid UUID {uuid:?}: {e}")))
+}
+
+impl TCStorageTxn for PyStorageTxn {
+    fn get_task(&mut self, uuid: Uuid) -> TCResult<Option<TaskMap>> {
+        self.call("get_task", (uuid.to_string(),))
+    }
+
+    fn create_task(&mut self, uuid: Uuid) -> TCResult<bool> {
+        self.call("create_task", (uuid.to_string(),))
+    }
+
+    fn set_task(&mut self, uuid: Uuid, task: TaskMap) -> TCResult<()> {
+        self.invoke("set_task", (uuid.to_string(), task))
+    }
+
+    fn delete_task(&mut self, uuid: Uuid) -> TCResult<bool> {
+        self.call("delete_task", (uuid.to_string(),))
+    }
+
+    fn all_tasks(&mut self) -> TCResult<Vec<(Uuid, TaskMap)>> {
+        let tasks: Vec<(String, TaskMap)> = self.call("all_tasks", ())?;
+        tasks
+            .into_iter()
+            .map(|(uuid, task)| Ok((parse_uuid(&uuid)?, task)))
+            .collect()
+    }
+
+    fn all_task_uuids(&mut self) -> TCResult<Vec<Uuid>> {
+        let uuids: Vec<String> = self.call("all_task_uuids", ())?;
+        uuids.iter().map(|uuid| parse_uuid(uuid)).collect()
+    }
+
+    fn base_version(&mut self) -> TCResult<VersionId> {
+        let version: String = self.call("base_version", ())?;
+        parse_uuid(&version)
+    }
+
+    fn set_base_version(&mut self, version: VersionId) -> TCResult<()> {
+        self.invoke("set_base_version", (version.to_string(),))
+    }
+
+    fn operations(&mut self) -> TCResult<Vec<TCOperation>> {
+        let ops: Vec<Operation> = self.call("operations", ())?;
+        Ok(ops.into_iter().map(|op| op.0).collect())
+    }
+
+    fn num_operations(&mut self) -> TCResult<usize> {
+        self.call("num_operations", ())
+    }
+
+    fn add_operation(&mut self, op: TCOperation) -> TCResult<()> {
+        self.invoke("add_operation", (Operation(op),))
+    }
+
+    fn set_operations(&mut self, ops: Vec<TCOperation>) -> TCResult<()> {
+        let ops: Vec<Operation> = ops.into_iter().map(Operation).collect();
+        self.invoke("set_operations", (ops,))
+    }
+
+    fn get_working_set(&mut self) -> TCResult<Vec<Option<Uuid>>> {
+        let working_set: Vec<Option<String>> = self.call("get_working_set", ())?;
+        working_set
+            .iter()
+            .map(|uuid| uuid.as_deref().map(parse_uuid).transpose())
+            .collect()
+    }
+
+    fn add_to_working_set(&mut self, uuid: Uuid) -> TCResult<usize> {
+        self.call("add_to_working_set", (uuid.to_string(),))
+    }
+
+    fn set_working_set_item(&mut self, index: usize, uuid: Option<Uuid>) -> TCResult<()> {
+        self.invoke(
+            "set_working_set_item",
+            (index, uuid.map(|uuid| uuid.to_string())),
+        )
+    }
+
+    fn clear_working_set(&mut self) -> TCResult<()> {
+        self.invoke("clear_working_set", ())
+    }
+
+    fn commit(&mut self) -> TCResult<()> {
+        self.invoke("commit", ())
+    }
+}
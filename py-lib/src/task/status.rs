@@ -14,24 +14,24 @@ pub enum Status {
 
 impl From<TCStatus> for Status {
     fn from(status: TCStatus) -> Self {
-        return match status {
+        match status {
             TCStatus::Pending => Status::Pending,
             TCStatus::Completed => Status::Completed,
             TCStatus::Deleted => Status::Deleted,
             TCStatus::Recurring => Status::Recurring,
             _ => Status::Unknown,
-        };
+        }
     }
 }
 
 impl From<Status> for TCStatus {
     fn from(status: Status) -> Self {
-        return match status {
+        match status {
             Status::Pending => TCStatus::Pending,
             Status::Completed => TCStatus::Completed,
             Status::Deleted => TCStatus::Deleted,
             Status::Recurring => TCStatus::Recurring,
             Status::Unknown => TCStatus::Unknown("unknown status".to_string()),
-        };
+        }
     }
 }
@@ -7,9 +7,8 @@ use taskchampion::{Operation as TCOperation, Task as TCTask, Uuid};
 #[pyclass]
 pub struct Task(pub(crate) TCTask);
 
-unsafe impl Send for Task {}
-
 #[pymethods]
+#[allow(clippy::wrong_self_convention)]
 impl Task {
     pub fn into_task_data(&self) -> TaskData {
         TaskData(self.0.clone().into_task_data())
@@ -103,18 +102,14 @@ impl Task {
     /// Returns:
     ///     list[str]: list of tags
     pub fn get_tags(&self) -> Vec<Tag> {
-        self.0.get_tags().into_iter().map(|v| Tag(v)).collect()
+        self.0.get_tags().map(Tag).collect()
     }
     /// Get task annotations
     ///
     /// Returns:
     ///     list[Annotation]: list of task annotations
     pub fn get_annotations(&self) -> Vec<Annotation> {
-        self.0
-            .get_annotations()
-            .into_iter()
-            .map(|annotation| Annotation(annotation))
-            .collect()
+        self.0.get_annotations().map(Annotation).collect()
     }
 
     /// Get a task UDA
@@ -165,7 +160,6 @@ impl Task {
     pub fn get_dependencies(&self) -> Vec<String> {
         self.0
             .get_dependencies()
-            .into_iter()
             .map(|uuid| uuid.to_string())
             .collect()
     }
@@ -183,7 +177,7 @@ impl Task {
 
         self.0.set_status(status.into(), &mut ops).expect("");
 
-        Ok(ops.get(0).map(|op| Operation(op.clone())).unwrap())
+        Ok(ops.first().map(|op| Operation(op.clone())).unwrap())
     }
 
     pub fn set_description(&mut self, description: String) -> anyhow::Result<Operation> {
@@ -191,7 +185,7 @@ impl Task {
 
         self.0.set_description(description, &mut ops).expect("");
 
-        Ok(ops.get(0).map(|op| Operation(op.clone())).unwrap())
+        Ok(ops.first().map(|op| Operation(op.clone())).unwrap())
     }
 
     pub fn set_priority(&mut self, priority: String) -> anyhow::Result<Operation> {
@@ -199,7 +193,7 @@ impl Task {
 
         self.0.set_priority(priority, &mut ops).expect("");
 
-        Ok(ops.get(0).map(|op| Operation(op.clone())).unwrap())
+        Ok(ops.first().map(|op| Operation(op.clone())).unwrap())
     }
 
     #[pyo3(signature=(entry=None))]
@@ -214,7 +208,7 @@ impl Task {
 
         self.0.set_entry(timestamp, &mut ops).expect("");
 
-        Ok(ops.get(0).map(|op| Operation(op.clone())).unwrap())
+        Ok(ops.first().map(|op| Operation(op.clone())).unwrap())
     }
 
     #[pyo3(signature=(wait=None))]
@@ -229,7 +223,7 @@ impl Task {
 
         self.0.set_wait(timestamp, &mut ops).expect("");
 
-        Ok(ops.get(0).map(|op| Operation(op.clone())).unwrap())
+        Ok(ops.first().map(|op| Operation(op.clone())).unwrap())
     }
 
     #[pyo3(signature=(modified=None))]
@@ -244,7 +238,7 @@ impl Task {
 
         self.0.set_wait(timestamp, &mut ops).expect("");
 
-        Ok(ops.get(0).map(|op| Operation(op.clone())).unwrap())
+        Ok(ops.first().map(|op| Operation(op.clone())).unwrap())
     }
 
     #[pyo3(signature=(property, value=None))]
@@ -256,7 +250,7 @@ impl Task {
         let mut ops: Vec<TCOperation> = Vec::new();
         self.0.set_value(property, value, &mut ops).expect("");
 
-        Ok(ops.get(0).map(|op| Operation(op.clone())).unwrap())
+        Ok(ops.first().map(|op| Operation(op.clone())).unwrap())
     }
 
     pub fn start(&mut self) -> anyhow::Result<Operation> {
@@ -264,7 +258,7 @@ impl Task {
 
         self.0.start(&mut ops).expect("");
 
-        Ok(ops.get(0).map(|op| Operation(op.clone())).unwrap())
+        Ok(ops.first().map(|op| Operation(op.clone())).unwrap())
     }
 
     pub fn stop(&mut self) -> anyhow::Result<Operation> {
@@ -272,7 +266,7 @@ impl Task {
 
         self.0.stop(&mut ops).expect("");
 
-        Ok(ops.get(0).map(|op| Operation(op.clone())).unwrap())
+        Ok(ops.first().map(|op| Operation(op.clone())).unwrap())
     }
 
     pub fn done(&mut self) -> anyhow::Result<Operation> {
@@ -280,7 +274,7 @@ impl Task {
 
         self.0.done(&mut ops).expect("");
 
-        Ok(ops.get(0).map(|op| Operation(op.clone())).unwrap())
+        Ok(ops.first().map(|op| Operation(op.clone())).unwrap())
     }
 
     pub fn add_tag(&mut self, tag: &Tag) -> anyhow::Result<Operation> {
@@ -288,7 +282,7 @@ impl Task {
 
         self.0.add_tag(&tag.0, &mut ops).expect("");
 
-        Ok(ops.get(0).map(|op| Operation(op.clone())).unwrap())
+        Ok(ops.first().map(|op| Operation(op.clone())).unwrap())
     }
 
     pub fn remove_tag(&mut self, tag: &Tag) -> anyhow::Result<Operation> {
@@ -296,7 +290,7 @@ impl Task {
 
         self.0.remove_tag(&tag.0, &mut ops).expect("");
 
-        Ok(ops.get(0).map(|op| Operation(op.clone())).unwrap())
+        Ok(ops.first().map(|op| Operation(op.clone())).unwrap())
     }
 
     pub fn add_annotation(&mut self, ann: &Annotation) -> anyhow::Result<Operation> {
@@ -308,7 +302,7 @@ impl Task {
 
         self.0.add_annotation(annotation.0, &mut ops).expect("");
 
-        Ok(ops.get(0).map(|op| Operation(op.clone())).unwrap())
+        Ok(ops.first().map(|op| Operation(op.clone())).unwrap())
     }
 
     pub fn remove_annotation(&mut self, timestamp: String) -> anyhow::Result<Operation> {
@@ -319,7 +313,7 @@ impl Task {
             .with_timezone(&chrono::Utc);
         self.0.remove_annotation(time, &mut ops).expect("");
 
-        Ok(ops.get(0).map(|op| Operation(op.clone())).unwrap())
+        Ok(ops.first().map(|op| Operation(op.clone())).unwrap())
     }
 
     #[pyo3(signature=(due=None))]
@@ -334,7 +328,7 @@ impl Task {
 
         self.0.set_due(timestamp, &mut ops).expect("");
 
-        Ok(ops.get(0).map(|op| Operation(op.clone())).unwrap())
+        Ok(ops.first().map(|op| Operation(op.clone())).unwrap())
     }
 
     pub fn set_uda(
@@ -347,7 +341,7 @@ impl Task {
 
         self.0.set_uda(namespace, key, value, &mut ops).expect("");
 
-        Ok(ops.get(0).map(|op| Operation(op.clone())).unwrap())
+        Ok(ops.first().map(|op| Operation(op.clone())).unwrap())
     }
 
     pub fn remove_uda(&mut self, namespace: String, key: String) -> anyhow::Result<Operation> {
@@ -355,7 +349,7 @@ impl Task {
 
         self.0.remove_uda(namespace, key, &mut ops).expect("");
 
-        Ok(ops.get(0).map(|op| Operation(op.clone())).unwrap())
+        Ok(ops.first().map(|op| Operation(op.clone())).unwrap())
     }
 
     pub fn set_legacy_uda(&mut self, key: String, value: String) -> anyhow::Result<Operation> {
@@ -363,7 +357,7 @@ impl Task {
 
         self.0.set_legacy_uda(key, value, &mut ops).expect("");
 
-        Ok(ops.get(0).map(|op| Operation(op.clone())).unwrap())
+        Ok(ops.first().map(|op| Operation(op.clone())).unwrap())
     }
 
     pub fn remove_legacy_uda(&mut self, key: String) -> anyhow::Result<Operation> {
@@ -371,7 +365,7 @@ impl Task {
 
         self.0.remove_legacy_uda(key, &mut ops).expect("");
 
-        Ok(ops.get(0).map(|op| Operation(op.clone())).unwrap())
+        Ok(ops.first().map(|op| Operation(op.clone())).unwrap())
     }
 
     pub fn add_dependency(&mut self, dep: String) -> anyhow::Result<Operation> {
@@ -380,7 +374,7 @@ impl Task {
 
         self.0.add_dependency(dep_uuid, &mut ops).expect("");
 
-        Ok(ops.get(0).map(|op| Operation(op.clone())).unwrap())
+        Ok(ops.first().map(|op| Operation(op.clone())).unwrap())
     }
 
     pub fn remove_dependency(&mut self, dep: String) -> anyhow::Result<Operation> {
@@ -389,6 +383,6 @@ impl Task {
 
         self.0.remove_dependency(dep_uuid, &mut ops).expect("");
 
-        Ok(ops.get(0).map(|op| Operation(op.clone())).unwrap())
+        Ok(ops.first().map(|op| Operation(op.clone())).unwrap())
     }
 }
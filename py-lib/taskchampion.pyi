@@ -1,9 +1,45 @@
-from typing import Optional
+from typing import Optional, Protocol, Union
 from enum import Enum
 
 
-class Replica:
+class StorageTxn(Protocol):
+    def get_task(self, uuid: str) -> Optional[dict[str, str]]: ...
+    def create_task(self, uuid: str) -> bool: ...
+    def set_task(self, uuid: str, task: dict[str, str]) -> None: ...
+    def delete_task(self, uuid: str) -> bool: ...
+    def all_tasks(self) -> list[tuple[str, dict[str, str]]]: ...
+    def all_task_uuids(self) -> list[str]: ...
+    def base_version(self) -> str: ...
+    def set_base_version(self, version: str) -> None: ...
+    def operations(self) -> list["Operation"]: ...
+    def num_operations(self) -> int: ...
+    def add_operation(self, op: "Operation") -> None: ...
+    def set_operations(self, ops: list["Operation"]) -> None: ...
+    def get_working_set(self) -> list[Optional[str]]: ...
+    def add_to_working_set(self, uuid: str) -> int: ...
+    def set_working_set_item(self, index: int, uuid: Optional[str]) -> None: ...
+    def clear_working_set(self) -> None: ...
+    def commit(self) -> None: ...
+
+
+class Storage(Protocol):
+    def txn(self) -> StorageTxn: ...
+
+
+class InMemoryStorage:
+    def __init__(self): ...
+
+
+class SqliteStorage:
     def __init__(self, path: str, create_if_missing: bool): ...
+
+
+class Replica:
+    def __init__(
+        self,
+        storage: Union[str, InMemoryStorage, SqliteStorage, Storage],
+        create_if_missing: bool = False,
+    ): ...
     def create_task(
         self, uuid: str) -> Optional[tuple["Task", "Operation"]]: ...
 
@@ -17,7 +53,7 @@ class Replica:
     def dependency_map(self, force: bool) -> "DependencyMap": ...
     def get_task(self, uuid: str) -> Optional["Task"]: ...
     def import_task_with_uuid(self, uuid: str) -> "Task": ...
-    def sync(self): ...
+    def sync(self, server: "Server", avoid_snapshots: bool) -> None: ...
     def rebuild_working_set(self, renumber: bool): ...
     def add_undo_point(self, force: bool) -> None: ...
     def num_local_operations(self) -> int: ...
@@ -25,6 +61,54 @@ class Replica:
     def commit_operations(self, operations: list["Operation"]) -> None: ...
 
 
+class ServerConfig:
+    @staticmethod
+    def local(server_dir: str) -> "ServerConfig": ...
+    @staticmethod
+    def remote(url: str, client_id: str,
+               encryption_secret: bytes) -> "ServerConfig": ...
+
+    @staticmethod
+    def gcp(
+        bucket: str, encryption_secret: bytes, credential_path: Optional[str] = None
+    ) -> "ServerConfig": ...
+
+    @staticmethod
+    def aws(
+        region: str,
+        bucket: str,
+        encryption_secret: bytes,
+        access_key_id: Optional[str] = None,
+        secret_access_key: Optional[str] = None,
+        profile_name: Optional[str] = None,
+        endpoint: Optional[str] = None,
+    ) -> "ServerConfig": ...
+
+    @staticmethod
+    def webdav(
+        url: str,
+        encryption_secret: bytes,
+        username: Optional[str] = None,
+        password: Optional[str] = None,
+    ) -> "ServerConfig": ...
+
+    @staticmethod
+    def directory(path: str, encryption_secret: bytes) -> "ServerConfig": ...
+    def into_server(self) -> "Server": ...
+
+
+class Server: ...
+
+
+class OutOfSyncError(RuntimeError): ...
+
+
+class ServerError(RuntimeError): ...
+
+
+class UsageError(RuntimeError): ...
+
+
 class Operation:
     @staticmethod
     def Create(uuid: str) -> "Operation": ...
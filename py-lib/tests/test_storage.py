@@ -0,0 +1,149 @@
+import uuid
+from pathlib import Path
+from typing import Optional
+
+import pytest
+from taskchampion import (
+    InMemoryStorage,
+    Operation,
+    Replica,
+    SqliteStorage,
+    UsageError,
+)
+
+NIL_UUID = "00000000-0000-0000-0000-000000000000"
+
+
+class DictStorage:
+    """A storage backed by Python dictionaries, to exercise the storage protocol."""
+
+    def __init__(self):
+        self.state = {
+            "tasks": {},
+            "base_version": NIL_UUID,
+            "operations": [],
+            "working_set": [None],
+        }
+        self.commits = 0
+
+    def txn(self) -> "DictTxn":
+        return DictTxn(self)
+
+
+class DictTxn:
+    def __init__(self, storage: DictStorage):
+        self.storage = storage
+        state = storage.state
+        self.state = {
+            "tasks": {u: dict(t) for u, t in state["tasks"].items()},
+            "base_version": state["base_version"],
+            "operations": list(state["operations"]),
+            "working_set": list(state["working_set"]),
+        }
+
+    def get_task(self, uuid: str) -> Optional[dict[str, str]]:
+        return self.state["tasks"].get(uuid)
+
+    def create_task(self, uuid: str) -> bool:
+        if uuid in self.state["tasks"]:
+            return False
+        self.state["tasks"][uuid] = {}
+        return True
+
+    def set_task(self, uuid: str, task: dict[str, str]):
+        self.state["tasks"][uuid] = task
+
+    def delete_task(self, uuid: str) -> bool:
+        return self.state["tasks"].pop(uuid, None) is not None
+
+    def all_tasks(self) -> list[tuple[str, dict[str, str]]]:
+        return list(self.state["tasks"].items())
+
+    def all_task_uuids(self) -> list[str]:
+        return list(self.state["tasks"].keys())
+
+    def base_version(self) -> str:
+        return self.state["base_version"]
+
+    def set_base_version(self, version: str):
+        self.state["base_version"] = version
+
+    def operations(self) -> list[Operation]:
+        return list(self.state["operations"])
+
+    def num_operations(self) -> int:
+        return len(self.state["operations"])
+
+    def add_operation(self, op: Operation):
+        self.state["operations"].append(op)
+
+    def set_operations(self, ops: list[Operation]):
+        self.state["operations"] = list(ops)
+
+    def get_working_set(self) -> list[Optional[str]]:
+        return list(self.state["working_set"])
+
+    def add_to_working_set(self, uuid: str) -> int:
+        self.state["working_set"].append(uuid)
+        return len(self.state["working_set"]) - 1
+
+    def set_working_set_item(self, index: int, uuid: Optional[str]):
+        self.state["working_set"][index] = uuid
+
+    def clear_working_set(self):
+        self.state["working_set"] = [None]
+
+    def commit(self):
+        self.storage.state = self.state
+        self.storage.commits += 1
+
+
+def add_task(r: Replica) -> str:
+    u = str(uuid.uuid4())
+    result = r.create_task(u)
+    assert result is not None
+    _, op = result
+    r.commit_operations([op])
+    return u
+
+
+def test_inmemory_storage():
+    r = Replica(InMemoryStorage())
+    u = add_task(r)
+    assert r.all_task_uuids() == [u]
+
+
+def test_sqlite_storage(tmp_path: Path):
+    r = Replica(SqliteStorage(str(tmp_path), True))
+    u = add_task(r)
+    assert r.all_task_uuids() == [u]
+
+    # a fresh replica on the same database sees the task
+    assert Replica(str(tmp_path)).all_task_uuids() == [u]
+
+
+def test_storage_used_twice():
+    storage = InMemoryStorage()
+    Replica(storage)
+    with pytest.raises(UsageError):
+        Replica(storage)
+
+
+def test_python_storage():
+    storage = DictStorage()
+    r = Replica(storage)
+    u = add_task(r)
+
+    assert r.all_task_uuids() == [u]
+    assert list(storage.state["tasks"].keys()) == [u]
+    assert storage.commits > 0
+
+
+def test_python_storage_exception():
+    class BrokenStorage:
+        def txn(self):
+            raise ValueError("no database")
+
+    r = Replica(BrokenStorage())
+    with pytest.raises(RuntimeError):
+        r.all_task_uuids()
@@ -0,0 +1,61 @@
+import uuid
+from pathlib import Path
+
+import pytest
+from taskchampion import Replica, ServerConfig, UsageError
+
+
+def test_sync_local(tmp_path: Path):
+    (tmp_path / "server").mkdir()
+    server = ServerConfig.local(str(tmp_path / "server")).into_server()
+    r1 = Replica(str(tmp_path / "r1"), True)
+    r2 = Replica(str(tmp_path / "r2"), True)
+
+    u = str(uuid.uuid4())
+    result = r1.create_task(u)
+    assert result is not None
+    _, op = result
+    r1.commit_operations([op])
+
+    r1.sync(server, False)
+    r2.sync(server, False)
+
+    assert r2.all_task_uuids() == [u]
+    assert r1.num_local_operations() == 0
+
+
+def test_sync_directory(tmp_path: Path):
+    config = ServerConfig.directory(str(tmp_path / "shared"), b"secret")
+    r1 = Replica(str(tmp_path / "r1"), True)
+    r2 = Replica(str(tmp_path / "r2"), True)
+
+    u = str(uuid.uuid4())
+    result = r1.create_task(u)
+    assert result is not None
+    _, op = result
+    r1.commit_operations([op])
+
+    r1.sync(config.into_server(), False)
+    r2.sync(config.into_server(), False)
+
+    assert r2.all_task_uuids() == [u]
+
+
+def test_server_config_reusable(tmp_path: Path):
+    config = ServerConfig.local(str(tmp_path))
+    config.into_server()
+    config.into_server()
+
+
+def test_remote_invalid_client_id():
+    with pytest.raises(RuntimeError):
+        ServerConfig.remote("http://localhost:8080", "not-a-uuid", b"secret")
+
+
+def test_aws_conflicting_credentials():
+    with pytest.raises(RuntimeError):
+        ServerConfig.aws("us-east-1", "bucket", b"secret", access_key_id="abc")
+
+
+def test_usage_error_is_runtime_error():
+    assert issubclass(UsageError, RuntimeError)
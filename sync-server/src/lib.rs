@@ -0,0 +1,34 @@
+#![deny(clippy::all)]
+/*!
+
+This crate implements a sync server for TaskChampion, speaking the HTTP protocol used by
+`ServerConfig::Remote` in the `taskchampion` crate.
+
+The server stores, for each client, a chain of encrypted versions and the latest encrypted
+snapshot.  It cannot read task data.  Clients are identified by the `X-Client-Id` header, and
+each client's data is isolated from that of other clients.
+
+Storage is pluggable via the [`storage::Storage`] trait, with in-memory and SQLite
+implementations provided.
+
+# Example
+
+```no_run
+use taskchampion_sync_server::{storage::SqliteStorage, ServerConfig, WebServer};
+# fn main() -> anyhow::Result<()> {
+let storage = SqliteStorage::new("/var/lib/taskchampion-sync-server")?;
+WebServer::new(ServerConfig::default(), Box::new(storage)).serve("0.0.0.0:8080")?;
+# Ok(())
+# }
+```
+
+For tests, [`WebServer::spawn`] runs the server in a background thread.
+
+ */
+
+mod server;
+pub mod storage;
+mod web;
+
+pub use server::ServerConfig;
+pub use web::{RunningServer, WebServer};
@@ -0,0 +1,53 @@
+//! The `taskchampion-sync-server` executable.
+//!
+//! At the moment its argument parsing is very simple, but if this grows more options then it
+//! will be sensible to use `clap` or another similar library.
+
+use std::env;
+use taskchampion_sync_server::storage::{InMemoryStorage, SqliteStorage, Storage};
+use taskchampion_sync_server::{ServerConfig, WebServer};
+
+const USAGE: &str = "Usage: taskchampion-sync-server [--listen ADDRESS] [--data-dir DIR]
+    [--snapshot-days DAYS] [--snapshot-versions VERSIONS]
+
+Options:
+    --listen ADDRESS        Address and port on which to listen (default 127.0.0.1:8080)
+    --data-dir DIR          Directory in which to store data (default: in memory)
+    --snapshot-days DAYS    Target number of days between snapshots (default 14)
+    --snapshot-versions VERSIONS
+                            Target number of versions between snapshots (default 100)";
+
+fn main() -> anyhow::Result<()> {
+    let mut listen = String::from("127.0.0.1:8080");
+    let mut data_dir = None;
+    let mut config = ServerConfig::default();
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .ok_or_else(|| anyhow::anyhow!("{arg} requires a value\n\n{USAGE}"))
+        };
+        match arg.as_str() {
+            "--listen" => listen = value()?,
+            "--data-dir" => data_dir = Some(value()?),
+            "--snapshot-days" => config.snapshot_days = value()?.parse()?,
+            "--snapshot-versions" => config.snapshot_versions = value()?.parse()?,
+            "-h" | "--help" => {
+                println!("{USAGE}");
+                return Ok(());
+            }
+            _ => anyhow::bail!("Unknown argument {arg}\n\n{USAGE}"),
+        }
+    }
+
+    let storage: Box<dyn Storage> = match data_dir {
+        Some(dir) => Box::new(SqliteStorage::new(dir)?),
+        None => {
+            eprintln!("No --data-dir given; data will be lost when the server exits");
+            Box::new(InMemoryStorage::new())
+        }
+    };
+    eprintln!("Serving on {listen}");
+    WebServer::new(config, storage).serve(listen)
+}
@@ -0,0 +1,418 @@
+//! The protocol logic of the sync server, independent of HTTP.  See the "Server-Replica
+//! Protocol" chapter of the TaskChampion book for details.
+
+use crate::storage::{Snapshot, Storage};
+use chrono::Utc;
+use log::{info, warn};
+use uuid::Uuid;
+
+/// The nil version ID, used as the parent of the first version.
+pub(crate) const NIL_VERSION_ID: Uuid = Uuid::nil();
+
+/// Number of versions to search back from the latest to find the version for a newly-added
+/// snapshot.  Snapshots for versions older than this are rejected.
+const SNAPSHOT_SEARCH_LEN: usize = 5;
+
+/// Configuration for the server's snapshot requests.
+#[derive(Clone, Debug)]
+pub struct ServerConfig {
+    /// Target number of days between snapshots.
+    pub snapshot_days: i64,
+
+    /// Target number of versions between snapshots.
+    pub snapshot_versions: u32,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            snapshot_days: 14,
+            snapshot_versions: 100,
+        }
+    }
+}
+
+/// Response to add_version.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub(crate) enum AddVersionResult {
+    /// The version was added, with the given ID.
+    Ok(Uuid),
+    /// The version was not added, because the parent version is not the latest.
+    ExpectedParentVersion(Uuid),
+}
+
+/// Urgency of a snapshot request, sent with the response to add_version.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Debug, Clone, Copy)]
+pub(crate) enum SnapshotUrgency {
+    None,
+    Low,
+    High,
+}
+
+impl SnapshotUrgency {
+    /// Calculate the urgency for a snapshot based on its age in days.
+    fn for_days(config: &ServerConfig, days: i64) -> Self {
+        if days >= config.snapshot_days * 3 / 2 {
+            SnapshotUrgency::High
+        } else if days >= config.snapshot_days {
+            SnapshotUrgency::Low
+        } else {
+            SnapshotUrgency::None
+        }
+    }
+
+    /// Calculate the urgency for a snapshot based on the number of versions since it was made.
+    fn for_versions_since(config: &ServerConfig, versions_since: u32) -> Self {
+        if versions_since >= config.snapshot_versions * 3 / 2 {
+            SnapshotUrgency::High
+        } else if versions_since >= config.snapshot_versions {
+            SnapshotUrgency::Low
+        } else {
+            SnapshotUrgency::None
+        }
+    }
+}
+
+/// Response to get_child_version.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub(crate) enum GetVersionResult {
+    /// There is no child version; the client is up to date.
+    NotFound,
+    /// The parent version is no longer available, so the client cannot continue from it.
+    Gone,
+    Success {
+        version_id: Uuid,
+        parent_version_id: Uuid,
+        history_segment: Vec<u8>,
+    },
+}
+
+/// The sync server's protocol implementation, combining configuration and storage.
+pub(crate) struct Server {
+    config: ServerConfig,
+    storage: Box<dyn Storage>,
+}
+
+impl Server {
+    pub(crate) fn new(config: ServerConfig, storage: Box<dyn Storage>) -> Self {
+        Server { config, storage }
+    }
+
+    /// Implementation of the AddVersion protocol transaction.  The client is created if it does
+    /// not exist.
+    pub(crate) fn add_version(
+        &mut self,
+        client_id: Uuid,
+        parent_version_id: Uuid,
+        history_segment: Vec<u8>,
+    ) -> anyhow::Result<(AddVersionResult, SnapshotUrgency)> {
+        let mut txn = self.storage.txn()?;
+        let client = match txn.get_client(client_id)? {
+            Some(client) => client,
+            None => {
+                info!("creating new client {}", client_id);
+                txn.new_client(client_id, NIL_VERSION_ID)?;
+                txn.get_client(client_id)?
+                    .expect("client should exist after creation")
+            }
+        };
+
+        // check if this version is acceptable, under the protection of the transaction
+        if client.latest_version_id != NIL_VERSION_ID
+            && parent_version_id != client.latest_version_id
+        {
+            return Ok((
+                AddVersionResult::ExpectedParentVersion(client.latest_version_id),
+                SnapshotUrgency::None,
+            ));
+        }
+
+        let version_id = Uuid::new_v4();
+        txn.add_version(client_id, version_id, parent_version_id, history_segment)?;
+        txn.commit()?;
+
+        let urgency = match client.snapshot {
+            None => SnapshotUrgency::High,
+            Some(Snapshot {
+                timestamp,
+                versions_since,
+                ..
+            }) => {
+                let days = (Utc::now() - timestamp).num_days();
+                // this version is not yet counted in the client's versions_since
+                SnapshotUrgency::for_days(&self.config, days).max(
+                    SnapshotUrgency::for_versions_since(&self.config, versions_since + 1),
+                )
+            }
+        };
+
+        Ok((AddVersionResult::Ok(version_id), urgency))
+    }
+
+    /// Implementation of the GetChildVersion protocol transaction.
+    pub(crate) fn get_child_version(
+        &mut self,
+        client_id: Uuid,
+        parent_version_id: Uuid,
+    ) -> anyhow::Result<GetVersionResult> {
+        let mut txn = self.storage.txn()?;
+        if let Some(version) = txn.get_version_by_parent(client_id, parent_version_id)? {
+            return Ok(GetVersionResult::Success {
+                version_id: version.version_id,
+                parent_version_id: version.parent_version_id,
+                history_segment: version.history_segment,
+            });
+        }
+
+        let Some(client) = txn.get_client(client_id)? else {
+            return Ok(GetVersionResult::NotFound);
+        };
+
+        // the client is up to date
+        if parent_version_id == client.latest_version_id {
+            return Ok(GetVersionResult::NotFound);
+        }
+
+        // a client starting from scratch, when the history begins with a snapshot, must use the
+        // snapshot
+        if parent_version_id == NIL_VERSION_ID {
+            return Ok(if client.snapshot.is_some() {
+                GetVersionResult::Gone
+            } else {
+                GetVersionResult::NotFound
+            });
+        }
+
+        // the parent version is unknown, or has no child, so the client cannot continue from it
+        Ok(GetVersionResult::Gone)
+    }
+
+    /// Implementation of the AddSnapshot protocol transaction.  Returns false if the snapshot
+    /// was rejected because its version is not a recent version.
+    pub(crate) fn add_snapshot(
+        &mut self,
+        client_id: Uuid,
+        version_id: Uuid,
+        data: Vec<u8>,
+    ) -> anyhow::Result<bool> {
+        let mut txn = self.storage.txn()?;
+        let Some(client) = txn.get_client(client_id)? else {
+            return Ok(false);
+        };
+        let last_snapshot = client.snapshot.map(|snap| snap.version_id);
+        if version_id == NIL_VERSION_ID || Some(version_id) == last_snapshot {
+            // a snapshot at the nil version is meaningless, and an existing snapshot need not
+            // be replaced
+            return Ok(true);
+        }
+
+        // search backward from the latest version, to verify that version_id is recent and
+        // newer than the existing snapshot
+        let mut vid = client.latest_version_id;
+        let mut found = false;
+        for _ in 0..SNAPSHOT_SEARCH_LEN {
+            if vid == version_id {
+                found = true;
+                break;
+            }
+            if Some(vid) == last_snapshot || vid == NIL_VERSION_ID {
+                break;
+            }
+            match txn.get_version(client_id, vid)? {
+                Some(version) => vid = version.parent_version_id,
+                None => break,
+            }
+        }
+        if !found {
+            warn!(
+                "rejecting snapshot for version {} of client {}",
+                version_id, client_id
+            );
+            return Ok(false);
+        }
+
+        txn.set_snapshot(
+            client_id,
+            Snapshot {
+                version_id,
+                timestamp: Utc::now(),
+                versions_since: 0,
+            },
+            data,
+        )?;
+        txn.commit()?;
+        Ok(true)
+    }
+
+    /// Implementation of the GetSnapshot protocol transaction.
+    pub(crate) fn get_snapshot(
+        &mut self,
+        client_id: Uuid,
+    ) -> anyhow::Result<Option<(Uuid, Vec<u8>)>> {
+        let mut txn = self.storage.txn()?;
+        let Some(snapshot) = txn.get_client(client_id)?.and_then(|c| c.snapshot) else {
+            return Ok(None);
+        };
+        Ok(txn
+            .get_snapshot_data(client_id, snapshot.version_id)?
+            .map(|data| (snapshot.version_id, data)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::storage::InMemoryStorage;
+    use pretty_assertions::assert_eq;
+
+    fn server() -> Server {
+        Server::new(ServerConfig::default(), Box::new(InMemoryStorage::new()))
+    }
+
+    /// Add a version and return its ID
+    fn add(server: &mut Server, client_id: Uuid, parent: Uuid) -> Uuid {
+        match server.add_version(client_id, parent, vec![1]).unwrap().0 {
+            AddVersionResult::Ok(v) => v,
+            r => panic!("unexpected {r:?}"),
+        }
+    }
+
+    #[test]
+    fn add_version_chain() {
+        let mut server = server();
+        let client_id = Uuid::new_v4();
+        let (res, urgency) = server
+            .add_version(client_id, NIL_VERSION_ID, vec![1])
+            .unwrap();
+        let AddVersionResult::Ok(v1) = res else {
+            panic!("unexpected {res:?}");
+        };
+        // no snapshot yet, so a snapshot is urgently requested
+        assert_eq!(urgency, SnapshotUrgency::High);
+
+        // adding with the wrong parent conflicts
+        assert_eq!(
+            server
+                .add_version(client_id, NIL_VERSION_ID, vec![2])
+                .unwrap(),
+            (
+                AddVersionResult::ExpectedParentVersion(v1),
+                SnapshotUrgency::None
+            )
+        );
+
+        let v2 = add(&mut server, client_id, v1);
+        assert_eq!(
+            server.get_child_version(client_id, v1).unwrap(),
+            GetVersionResult::Success {
+                version_id: v2,
+                parent_version_id: v1,
+                history_segment: vec![1],
+            }
+        );
+        assert_eq!(
+            server.get_child_version(client_id, v2).unwrap(),
+            GetVersionResult::NotFound
+        );
+        assert_eq!(
+            server.get_child_version(client_id, Uuid::new_v4()).unwrap(),
+            GetVersionResult::Gone
+        );
+    }
+
+    #[test]
+    fn clients_are_isolated() {
+        let mut server = server();
+        let (client1, client2) = (Uuid::new_v4(), Uuid::new_v4());
+        let v1 = add(&mut server, client1, NIL_VERSION_ID);
+        assert_eq!(
+            server.get_child_version(client2, NIL_VERSION_ID).unwrap(),
+            GetVersionResult::NotFound
+        );
+        // client2 can start its own history
+        let v2 = add(&mut server, client2, NIL_VERSION_ID);
+        assert_ne!(v1, v2);
+        assert_eq!(server.get_snapshot(client2).unwrap(), None);
+    }
+
+    #[test]
+    fn snapshots() {
+        let mut server = server();
+        let client_id = Uuid::new_v4();
+        assert!(!server
+            .add_snapshot(client_id, Uuid::new_v4(), vec![])
+            .unwrap());
+
+        let mut versions = vec![];
+        let mut parent = NIL_VERSION_ID;
+        for _ in 0..7 {
+            parent = add(&mut server, client_id, parent);
+            versions.push(parent);
+        }
+
+        // too old, or unknown
+        assert!(!server
+            .add_snapshot(client_id, versions[0], vec![0])
+            .unwrap());
+        assert!(!server
+            .add_snapshot(client_id, Uuid::new_v4(), vec![0])
+            .unwrap());
+        assert_eq!(server.get_snapshot(client_id).unwrap(), None);
+
+        assert!(server
+            .add_snapshot(client_id, versions[5], vec![5])
+            .unwrap());
+        assert_eq!(
+            server.get_snapshot(client_id).unwrap(),
+            Some((versions[5], vec![5]))
+        );
+        // older than the existing snapshot
+        assert!(!server
+            .add_snapshot(client_id, versions[4], vec![4])
+            .unwrap());
+        assert_eq!(
+            server.get_snapshot(client_id).unwrap(),
+            Some((versions[5], vec![5]))
+        );
+
+        // with a recent snapshot, no further snapshot is requested
+        let (_, urgency) = server.add_version(client_id, versions[6], vec![]).unwrap();
+        assert_eq!(urgency, SnapshotUrgency::None);
+
+        // versions remain available after a snapshot
+        assert_eq!(
+            server.get_child_version(client_id, NIL_VERSION_ID).unwrap(),
+            GetVersionResult::Success {
+                version_id: versions[0],
+                parent_version_id: NIL_VERSION_ID,
+                history_segment: vec![1],
+            }
+        );
+    }
+
+    #[test]
+    fn urgency() {
+        let config = ServerConfig {
+            snapshot_days: 10,
+            snapshot_versions: 4,
+        };
+        assert_eq!(SnapshotUrgency::for_days(&config, 9), SnapshotUrgency::None);
+        assert_eq!(SnapshotUrgency::for_days(&config, 10), SnapshotUrgency::Low);
+        assert_eq!(
+            SnapshotUrgency::for_days(&config, 15),
+            SnapshotUrgency::High
+        );
+        assert_eq!(
+            SnapshotUrgency::for_versions_since(&config, 3),
+            SnapshotUrgency::None
+        );
+        assert_eq!(
+            SnapshotUrgency::for_versions_since(&config, 4),
+            SnapshotUrgency::Low
+        );
+        assert_eq!(
+            SnapshotUrgency::for_versions_since(&config, 6),
+            SnapshotUrgency::High
+        );
+    }
+}
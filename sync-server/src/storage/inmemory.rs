@@ -0,0 +1,179 @@
+use super::{Client, Snapshot, Storage, StorageTxn, Version};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+#[derive(Clone, Default)]
+struct Data {
+    /// Clients, keyed by client ID
+    clients: HashMap<Uuid, Client>,
+
+    /// Snapshot data, keyed by client ID
+    snapshots: HashMap<Uuid, Vec<u8>>,
+
+    /// Versions, keyed by (client_id, version_id)
+    versions: HashMap<(Uuid, Uuid), Version>,
+
+    /// Child version IDs, keyed by (client_id, parent_version_id)
+    children: HashMap<(Uuid, Uuid), Uuid>,
+}
+
+/// A storage implementation that keeps all data in memory.  This is useful for testing, but all
+/// data is lost when the server exits.
+#[derive(Default)]
+pub struct InMemoryStorage {
+    data: Data,
+}
+
+impl InMemoryStorage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+struct Txn<'t> {
+    storage: &'t mut InMemoryStorage,
+    new_data: Option<Data>,
+}
+
+impl<'t> Txn<'t> {
+    fn mut_data_ref(&mut self) -> &mut Data {
+        self.new_data
+            .get_or_insert_with(|| self.storage.data.clone())
+    }
+
+    fn data_ref(&self) -> &Data {
+        self.new_data.as_ref().unwrap_or(&self.storage.data)
+    }
+}
+
+impl Storage for InMemoryStorage {
+    fn txn<'a>(&'a mut self) -> anyhow::Result<Box<dyn StorageTxn + 'a>> {
+        Ok(Box::new(Txn {
+            storage: self,
+            new_data: None,
+        }))
+    }
+}
+
+impl<'t> StorageTxn for Txn<'t> {
+    fn get_client(&mut self, client_id: Uuid) -> anyhow::Result<Option<Client>> {
+        Ok(self.data_ref().clients.get(&client_id).cloned())
+    }
+
+    fn new_client(&mut self, client_id: Uuid, latest_version_id: Uuid) -> anyhow::Result<()> {
+        if self.data_ref().clients.contains_key(&client_id) {
+            anyhow::bail!("Client {} already exists", client_id);
+        }
+        self.mut_data_ref().clients.insert(
+            client_id,
+            Client {
+                latest_version_id,
+                snapshot: None,
+            },
+        );
+        Ok(())
+    }
+
+    fn set_snapshot(
+        &mut self,
+        client_id: Uuid,
+        snapshot: Snapshot,
+        data: Vec<u8>,
+    ) -> anyhow::Result<()> {
+        let data_ref = self.mut_data_ref();
+        let client = data_ref
+            .clients
+            .get_mut(&client_id)
+            .ok_or_else(|| anyhow::anyhow!("no such client"))?;
+        client.snapshot = Some(snapshot);
+        data_ref.snapshots.insert(client_id, data);
+        Ok(())
+    }
+
+    fn get_snapshot_data(
+        &mut self,
+        client_id: Uuid,
+        version_id: Uuid,
+    ) -> anyhow::Result<Option<Vec<u8>>> {
+        let data = self.data_ref();
+        let Some(client) = data.clients.get(&client_id) else {
+            return Ok(None);
+        };
+        if client.snapshot.as_ref().map(|s| s.version_id) != Some(version_id) {
+            return Ok(None);
+        }
+        Ok(data.snapshots.get(&client_id).cloned())
+    }
+
+    fn get_version_by_parent(
+        &mut self,
+        client_id: Uuid,
+        parent_version_id: Uuid,
+    ) -> anyhow::Result<Option<Version>> {
+        let data = self.data_ref();
+        Ok(data
+            .children
+            .get(&(client_id, parent_version_id))
+            .and_then(|version_id| data.versions.get(&(client_id, *version_id)))
+            .cloned())
+    }
+
+    fn get_version(
+        &mut self,
+        client_id: Uuid,
+        version_id: Uuid,
+    ) -> anyhow::Result<Option<Version>> {
+        Ok(self
+            .data_ref()
+            .versions
+            .get(&(client_id, version_id))
+            .cloned())
+    }
+
+    fn add_version(
+        &mut self,
+        client_id: Uuid,
+        version_id: Uuid,
+        parent_version_id: Uuid,
+        history_segment: Vec<u8>,
+    ) -> anyhow::Result<()> {
+        let data = self.mut_data_ref();
+        let client = data
+            .clients
+            .get_mut(&client_id)
+            .ok_or_else(|| anyhow::anyhow!("no such client"))?;
+        client.latest_version_id = version_id;
+        if let Some(ref mut snap) = client.snapshot {
+            snap.versions_since += 1;
+        }
+        data.children
+            .insert((client_id, parent_version_id), version_id);
+        data.versions.insert(
+            (client_id, version_id),
+            Version {
+                version_id,
+                parent_version_id,
+                history_segment,
+            },
+        );
+        Ok(())
+    }
+
+    fn commit(&mut self) -> anyhow::Result<()> {
+        if let Some(data) = self.new_data.take() {
+            self.storage.data = data;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::storage::test::storage_behavior;
+
+    #[test]
+    fn behavior() -> anyhow::Result<()> {
+        storage_behavior(&mut InMemoryStorage::new())
+    }
+}
@@ -0,0 +1,190 @@
+//! Storage for the sync server.
+//!
+//! The server stores, for each client, the chain of versions and the latest snapshot.  All
+//! access occurs in transactions, and the server performs each request in a single transaction,
+//! so a storage implementation must ensure that transactions are atomic and isolated.
+
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+mod inmemory;
+mod sqlite;
+
+pub use inmemory::InMemoryStorage;
+pub use sqlite::SqliteStorage;
+
+/// A client, as identified by the `X-Client-Id` header.  All replicas synchronizing the same task
+/// history share a client.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Client {
+    /// The latest version for this client (may be the nil version)
+    pub latest_version_id: Uuid,
+    /// Data about the latest snapshot for this client
+    pub snapshot: Option<Snapshot>,
+}
+
+/// Metadata about a snapshot.  The snapshot data itself is stored separately.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Snapshot {
+    /// ID of the version at which this snapshot was made
+    pub version_id: Uuid,
+    /// Timestamp at which this snapshot was set
+    pub timestamp: DateTime<Utc>,
+    /// Number of versions since this snapshot was made
+    pub versions_since: u32,
+}
+
+/// A version in a client's task history.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Version {
+    pub version_id: Uuid,
+    pub parent_version_id: Uuid,
+    /// The (encrypted) history segment, opaque to the server
+    pub history_segment: Vec<u8>,
+}
+
+/// A transaction in the storage backend.
+///
+/// Transactions must be sequentially consistent.  That is, the results of transactions performed
+/// in storage must be as if each were executed sequentially in some order.  In particular, the
+/// `Client.latest_version_id` must not change between a call to `get_client` and `add_version`.
+///
+/// Changes in a transaction that is dropped without calling `commit` must not appear in any other
+/// transaction.
+pub trait StorageTxn {
+    /// Get information about the given client
+    fn get_client(&mut self, client_id: Uuid) -> anyhow::Result<Option<Client>>;
+
+    /// Create a new client with the given latest_version_id
+    fn new_client(&mut self, client_id: Uuid, latest_version_id: Uuid) -> anyhow::Result<()>;
+
+    /// Set the client's most recent snapshot, replacing any existing snapshot.
+    fn set_snapshot(
+        &mut self,
+        client_id: Uuid,
+        snapshot: Snapshot,
+        data: Vec<u8>,
+    ) -> anyhow::Result<()>;
+
+    /// Get the data for the most recent snapshot.  The version_id is used to verify that the
+    /// snapshot is for the correct version.
+    fn get_snapshot_data(
+        &mut self,
+        client_id: Uuid,
+        version_id: Uuid,
+    ) -> anyhow::Result<Option<Vec<u8>>>;
+
+    /// Get a version, indexed by parent version id
+    fn get_version_by_parent(
+        &mut self,
+        client_id: Uuid,
+        parent_version_id: Uuid,
+    ) -> anyhow::Result<Option<Version>>;
+
+    /// Get a version, indexed by its own version id
+    fn get_version(&mut self, client_id: Uuid, version_id: Uuid)
+        -> anyhow::Result<Option<Version>>;
+
+    /// Add a version.  This must also set the client's `latest_version_id` to the new version,
+    /// and increment the `versions_since` count of its snapshot, if any.
+    fn add_version(
+        &mut self,
+        client_id: Uuid,
+        version_id: Uuid,
+        parent_version_id: Uuid,
+        history_segment: Vec<u8>,
+    ) -> anyhow::Result<()>;
+
+    /// Commit any changes made in the transaction.  It is an error to call this more than
+    /// once.
+    fn commit(&mut self) -> anyhow::Result<()>;
+}
+
+/// A trait for objects able to act as storage for the sync server.  Most of the interesting
+/// behavior is in the [`StorageTxn`] trait.
+pub trait Storage: Send {
+    /// Begin a transaction
+    fn txn<'a>(&'a mut self) -> anyhow::Result<Box<dyn StorageTxn + 'a>>;
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    /// Exercise a storage implementation.
+    pub(crate) fn storage_behavior(storage: &mut dyn Storage) -> anyhow::Result<()> {
+        let client_id = Uuid::new_v4();
+        let (v1, v2) = (Uuid::new_v4(), Uuid::new_v4());
+        let snapshot = Snapshot {
+            version_id: v1,
+            timestamp: DateTime::from_timestamp(1704110400, 0).unwrap(),
+            versions_since: 0,
+        };
+
+        {
+            let mut txn = storage.txn()?;
+            assert_eq!(txn.get_client(client_id)?, None);
+            txn.new_client(client_id, Uuid::nil())?;
+            txn.add_version(client_id, v1, Uuid::nil(), b"one".to_vec())?;
+            txn.commit()?;
+        }
+
+        {
+            // uncommitted changes are not visible
+            let mut txn = storage.txn()?;
+            txn.add_version(client_id, v2, v1, b"two".to_vec())?;
+            assert!(txn.get_version(client_id, v2)?.is_some());
+        }
+
+        {
+            let mut txn = storage.txn()?;
+            assert_eq!(
+                txn.get_client(client_id)?,
+                Some(Client {
+                    latest_version_id: v1,
+                    snapshot: None
+                })
+            );
+            assert_eq!(txn.get_version(client_id, v2)?, None);
+            let version = Version {
+                version_id: v1,
+                parent_version_id: Uuid::nil(),
+                history_segment: b"one".to_vec(),
+            };
+            assert_eq!(txn.get_version(client_id, v1)?, Some(version.clone()));
+            assert_eq!(
+                txn.get_version_by_parent(client_id, Uuid::nil())?,
+                Some(version)
+            );
+            // versions are isolated by client
+            assert_eq!(txn.get_version(Uuid::new_v4(), v1)?, None);
+            assert_eq!(
+                txn.get_version_by_parent(Uuid::new_v4(), Uuid::nil())?,
+                None
+            );
+
+            txn.set_snapshot(client_id, snapshot.clone(), b"snap".to_vec())?;
+            txn.add_version(client_id, v2, v1, b"two".to_vec())?;
+            txn.commit()?;
+        }
+
+        let mut txn = storage.txn()?;
+        assert_eq!(
+            txn.get_client(client_id)?,
+            Some(Client {
+                latest_version_id: v2,
+                snapshot: Some(Snapshot {
+                    versions_since: 1,
+                    ..snapshot
+                }),
+            })
+        );
+        assert_eq!(
+            txn.get_snapshot_data(client_id, v1)?,
+            Some(b"snap".to_vec())
+        );
+        assert_eq!(txn.get_snapshot_data(client_id, v2)?, None);
+        Ok(())
+    }
+}
@@ -0,0 +1,273 @@
+use super::{Client, Snapshot, Storage, StorageTxn, Version};
+use anyhow::Context;
+use chrono::{DateTime, TimeZone, Utc};
+use rusqlite::{params, Connection, OptionalExtension, TransactionBehavior};
+use std::path::Path;
+use uuid::Uuid;
+
+/// A storage implementation backed by an SQLite database, stored as
+/// `taskchampion-sync-server.sqlite3` in the given directory.
+pub struct SqliteStorage {
+    con: Connection,
+}
+
+impl SqliteStorage {
+    /// Open the database in the given directory, creating the directory and database if
+    /// necessary.
+    pub fn new<P: AsRef<Path>>(directory: P) -> anyhow::Result<SqliteStorage> {
+        std::fs::create_dir_all(&directory)?;
+        let db_file = directory.as_ref().join("taskchampion-sync-server.sqlite3");
+        let con = Connection::open(db_file)?;
+
+        con.query_row("PRAGMA journal_mode=WAL", [], |_row| Ok(()))
+            .context("Setting journal_mode=WAL")?;
+
+        let queries = vec![
+            "CREATE TABLE IF NOT EXISTS clients (
+                client_id STRING PRIMARY KEY,
+                latest_version_id STRING,
+                snapshot_version_id STRING,
+                versions_since_snapshot INTEGER,
+                snapshot_timestamp INTEGER,
+                snapshot BLOB);",
+            "CREATE TABLE IF NOT EXISTS versions (
+                version_id STRING PRIMARY KEY,
+                client_id STRING,
+                parent_version_id STRING,
+                history_segment BLOB);",
+            "CREATE UNIQUE INDEX IF NOT EXISTS versions_by_parent
+                ON versions (client_id, parent_version_id);",
+        ];
+        for q in queries {
+            con.execute(q, []).context("Creating table")?;
+        }
+
+        Ok(SqliteStorage { con })
+    }
+}
+
+impl Storage for SqliteStorage {
+    fn txn<'a>(&'a mut self) -> anyhow::Result<Box<dyn StorageTxn + 'a>> {
+        let txn = self
+            .con
+            .transaction_with_behavior(TransactionBehavior::Immediate)?;
+        Ok(Box::new(Txn { txn: Some(txn) }))
+    }
+}
+
+struct Txn<'t> {
+    txn: Option<rusqlite::Transaction<'t>>,
+}
+
+/// Parse a UUID stored as a string.
+fn parse_uuid(s: String) -> rusqlite::Result<Uuid> {
+    Uuid::parse_str(&s).map_err(|e| {
+        rusqlite::Error::FromSqlConversionFailure(0, rusqlite::types::Type::Text, Box::new(e))
+    })
+}
+
+impl<'t> Txn<'t> {
+    fn get_txn(&self) -> anyhow::Result<&rusqlite::Transaction<'t>> {
+        self.txn
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("Transaction already committed"))
+    }
+
+    fn get_version_where(
+        &self,
+        condition: &str,
+        client_id: Uuid,
+        id: Uuid,
+    ) -> anyhow::Result<Option<Version>> {
+        let query = format!(
+            "SELECT version_id, parent_version_id, history_segment FROM versions
+             WHERE client_id = ? AND {condition} = ?"
+        );
+        self.get_txn()?
+            .query_row(
+                &query,
+                params![client_id.to_string(), id.to_string()],
+                |r| {
+                    Ok(Version {
+                        version_id: parse_uuid(r.get(0)?)?,
+                        parent_version_id: parse_uuid(r.get(1)?)?,
+                        history_segment: r.get(2)?,
+                    })
+                },
+            )
+            .optional()
+            .context("Get version")
+    }
+}
+
+impl<'t> StorageTxn for Txn<'t> {
+    fn get_client(&mut self, client_id: Uuid) -> anyhow::Result<Option<Client>> {
+        self.get_txn()?
+            .query_row(
+                "SELECT latest_version_id, snapshot_version_id, versions_since_snapshot,
+                        snapshot_timestamp
+                 FROM clients WHERE client_id = ?",
+                [client_id.to_string()],
+                |r| {
+                    let latest_version_id = parse_uuid(r.get(0)?)?;
+                    let snapshot_version_id: Option<String> = r.get(1)?;
+                    let snapshot = match snapshot_version_id {
+                        Some(version_id) => Some(Snapshot {
+                            version_id: parse_uuid(version_id)?,
+                            versions_since: r.get(2)?,
+                            timestamp: Utc
+                                .timestamp_opt(r.get(3)?, 0)
+                                .single()
+                                .unwrap_or(DateTime::<Utc>::MIN_UTC),
+                        }),
+                        None => None,
+                    };
+                    Ok(Client {
+                        latest_version_id,
+                        snapshot,
+                    })
+                },
+            )
+            .optional()
+            .context("Get client")
+    }
+
+    fn new_client(&mut self, client_id: Uuid, latest_version_id: Uuid) -> anyhow::Result<()> {
+        self.get_txn()?
+            .execute(
+                "INSERT INTO clients (client_id, latest_version_id) VALUES (?, ?)",
+                params![client_id.to_string(), latest_version_id.to_string()],
+            )
+            .context("Create client")?;
+        Ok(())
+    }
+
+    fn set_snapshot(
+        &mut self,
+        client_id: Uuid,
+        snapshot: Snapshot,
+        data: Vec<u8>,
+    ) -> anyhow::Result<()> {
+        let updated = self
+            .get_txn()?
+            .execute(
+                "UPDATE clients SET snapshot_version_id = ?, snapshot_timestamp = ?,
+                    versions_since_snapshot = ?, snapshot = ?
+                 WHERE client_id = ?",
+                params![
+                    snapshot.version_id.to_string(),
+                    snapshot.timestamp.timestamp(),
+                    snapshot.versions_since,
+                    data,
+                    client_id.to_string(),
+                ],
+            )
+            .context("Set snapshot")?;
+        if updated == 0 {
+            anyhow::bail!("no such client");
+        }
+        Ok(())
+    }
+
+    fn get_snapshot_data(
+        &mut self,
+        client_id: Uuid,
+        version_id: Uuid,
+    ) -> anyhow::Result<Option<Vec<u8>>> {
+        self.get_txn()?
+            .query_row(
+                "SELECT snapshot FROM clients WHERE client_id = ? AND snapshot_version_id = ?",
+                params![client_id.to_string(), version_id.to_string()],
+                |r| r.get(0),
+            )
+            .optional()
+            .context("Get snapshot data")
+    }
+
+    fn get_version_by_parent(
+        &mut self,
+        client_id: Uuid,
+        parent_version_id: Uuid,
+    ) -> anyhow::Result<Option<Version>> {
+        self.get_version_where("parent_version_id", client_id, parent_version_id)
+    }
+
+    fn get_version(
+        &mut self,
+        client_id: Uuid,
+        version_id: Uuid,
+    ) -> anyhow::Result<Option<Version>> {
+        self.get_version_where("version_id", client_id, version_id)
+    }
+
+    fn add_version(
+        &mut self,
+        client_id: Uuid,
+        version_id: Uuid,
+        parent_version_id: Uuid,
+        history_segment: Vec<u8>,
+    ) -> anyhow::Result<()> {
+        let txn = self.get_txn()?;
+        txn.execute(
+            "INSERT INTO versions (version_id, client_id, parent_version_id, history_segment)
+             VALUES (?, ?, ?, ?)",
+            params![
+                version_id.to_string(),
+                client_id.to_string(),
+                parent_version_id.to_string(),
+                history_segment,
+            ],
+        )
+        .context("Add version")?;
+        let updated = txn
+            .execute(
+                "UPDATE clients SET latest_version_id = ?,
+                    versions_since_snapshot = versions_since_snapshot + 1
+                 WHERE client_id = ?",
+                params![version_id.to_string(), client_id.to_string()],
+            )
+            .context("Update latest version")?;
+        if updated == 0 {
+            anyhow::bail!("no such client");
+        }
+        Ok(())
+    }
+
+    fn commit(&mut self) -> anyhow::Result<()> {
+        let txn = self
+            .txn
+            .take()
+            .ok_or_else(|| anyhow::anyhow!("Transaction already committed"))?;
+        txn.commit().context("Committing transaction")?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::storage::test::storage_behavior;
+    use tempfile::TempDir;
+
+    #[test]
+    fn behavior() -> anyhow::Result<()> {
+        let tmp_dir = TempDir::new()?;
+        storage_behavior(&mut SqliteStorage::new(tmp_dir.path())?)
+    }
+
+    #[test]
+    fn reopen() -> anyhow::Result<()> {
+        let tmp_dir = TempDir::new()?;
+        let client_id = Uuid::new_v4();
+        {
+            let mut storage = SqliteStorage::new(tmp_dir.path())?;
+            let mut txn = storage.txn()?;
+            txn.new_client(client_id, Uuid::nil())?;
+            txn.commit()?;
+        }
+        let mut storage = SqliteStorage::new(tmp_dir.path())?;
+        let mut txn = storage.txn()?;
+        assert!(txn.get_client(client_id)?.is_some());
+        Ok(())
+    }
+}
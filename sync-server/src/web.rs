@@ -0,0 +1,471 @@
+//! The HTTP representation of the sync protocol, as described in the "HTTP Representation"
+//! chapter of the TaskChampion book.
+
+use crate::server::{AddVersionResult, GetVersionResult, Server, ServerConfig, SnapshotUrgency};
+use crate::storage::Storage;
+use log::{error, info};
+use std::io::{Cursor, Read};
+use std::net::{SocketAddr, ToSocketAddrs};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use tiny_http::{Header, Method, Request, Response, StatusCode};
+use uuid::Uuid;
+
+/// The content-type for history segments (opaque blobs of bytes)
+const HISTORY_SEGMENT_CONTENT_TYPE: &str = "application/vnd.taskchampion.history-segment";
+
+/// The content-type for snapshots (opaque blobs of bytes)
+const SNAPSHOT_CONTENT_TYPE: &str = "application/vnd.taskchampion.snapshot";
+
+/// The maximum size of a request body.
+const MAX_SIZE: u64 = 100 * 1024 * 1024;
+
+/// An HTTP sync server, implementing the protocol expected by `ServerConfig::Remote` in the
+/// `taskchampion` crate.
+///
+/// Requests are handled one at a time, so each is atomic with respect to the others.
+pub struct WebServer {
+    server: Server,
+}
+
+impl WebServer {
+    /// Create a new server with the given configuration and storage.
+    pub fn new(config: ServerConfig, storage: Box<dyn Storage>) -> WebServer {
+        WebServer {
+            server: Server::new(config, storage),
+        }
+    }
+
+    /// Listen on the given address and serve requests until the process exits.
+    pub fn serve(mut self, addr: impl ToSocketAddrs) -> anyhow::Result<()> {
+        let http = listen(addr)?;
+        info!("Serving on {:?}", http.server_addr().to_ip());
+        for request in http.incoming_requests() {
+            self.handle(request);
+        }
+        Ok(())
+    }
+
+    /// Listen on the given address and serve requests in a background thread, until the
+    /// returned [`RunningServer`] is dropped.  Use port 0 to pick an unused port, and
+    /// [`RunningServer::url`] to find the resulting URL.
+    pub fn spawn(mut self, addr: impl ToSocketAddrs) -> anyhow::Result<RunningServer> {
+        let http = Arc::new(listen(addr)?);
+        let addr = http
+            .server_addr()
+            .to_ip()
+            .ok_or_else(|| anyhow::anyhow!("Server is not listening on an IP address"))?;
+        let thread = {
+            let http = http.clone();
+            std::thread::spawn(move || {
+                for request in http.incoming_requests() {
+                    self.handle(request);
+                }
+            })
+        };
+        Ok(RunningServer {
+            http,
+            addr,
+            thread: Some(thread),
+        })
+    }
+
+    /// Handle a single request, logging any failure to respond.
+    fn handle(&mut self, mut request: Request) {
+        let response = match self.respond(&mut request) {
+            Ok(response) => response,
+            Err(e) => {
+                error!(
+                    "Error handling {} {}: {:#}",
+                    request.method(),
+                    request.url(),
+                    e
+                );
+                empty(500)
+            }
+        };
+        if let Err(e) = request.respond(response) {
+            error!("Error sending response: {}", e);
+        }
+    }
+
+    /// Determine the response to a request.
+    fn respond(&mut self, request: &mut Request) -> anyhow::Result<Response<Cursor<Vec<u8>>>> {
+        let path = request
+            .url()
+            .split('?')
+            .next()
+            .unwrap_or_default()
+            .to_owned();
+        let segments: Vec<&str> = path.trim_start_matches('/').split('/').collect();
+        let route = match segments.as_slice() {
+            ["v1", "client", "add-version", id] => Route::AddVersion(id),
+            ["v1", "client", "get-child-version", id] => Route::GetChildVersion(id),
+            ["v1", "client", "add-snapshot", id] => Route::AddSnapshot(id),
+            ["v1", "client", "snapshot"] => Route::GetSnapshot,
+            _ => return Ok(empty(404)),
+        };
+        let method = request.method().clone();
+        let Some(client_id) = header(request, "X-Client-Id").and_then(|v| Uuid::parse_str(v).ok())
+        else {
+            return Ok(empty(400));
+        };
+
+        Ok(match (route, method) {
+            (Route::AddVersion(id), Method::Post) => {
+                let Ok(parent_version_id) = Uuid::parse_str(id) else {
+                    return Ok(empty(400));
+                };
+                let body = match read_body(request, HISTORY_SEGMENT_CONTENT_TYPE)? {
+                    Ok(body) => body,
+                    Err(status) => return Ok(empty(status)),
+                };
+                match self
+                    .server
+                    .add_version(client_id, parent_version_id, body)?
+                {
+                    (AddVersionResult::Ok(version_id), urgency) => {
+                        let response =
+                            empty(200).with_header(uuid_header("X-Version-Id", version_id));
+                        match urgency {
+                            SnapshotUrgency::None => response,
+                            SnapshotUrgency::Low => response
+                                .with_header(make_header("X-Snapshot-Request", "urgency=low")),
+                            SnapshotUrgency::High => response
+                                .with_header(make_header("X-Snapshot-Request", "urgency=high")),
+                        }
+                    }
+                    (AddVersionResult::ExpectedParentVersion(parent_version_id), _) => empty(409)
+                        .with_header(uuid_header("X-Parent-Version-Id", parent_version_id)),
+                }
+            }
+            (Route::GetChildVersion(id), Method::Get) => {
+                let Ok(parent_version_id) = Uuid::parse_str(id) else {
+                    return Ok(empty(400));
+                };
+                match self
+                    .server
+                    .get_child_version(client_id, parent_version_id)?
+                {
+                    GetVersionResult::NotFound => empty(404),
+                    GetVersionResult::Gone => empty(410),
+                    GetVersionResult::Success {
+                        version_id,
+                        parent_version_id,
+                        history_segment,
+                    } => Response::from_data(history_segment)
+                        .with_header(make_header("Content-Type", HISTORY_SEGMENT_CONTENT_TYPE))
+                        .with_header(uuid_header("X-Version-Id", version_id))
+                        .with_header(uuid_header("X-Parent-Version-Id", parent_version_id)),
+                }
+            }
+            (Route::AddSnapshot(id), Method::Post) => {
+                let Ok(version_id) = Uuid::parse_str(id) else {
+                    return Ok(empty(400));
+                };
+                let body = match read_body(request, SNAPSHOT_CONTENT_TYPE)? {
+                    Ok(body) => body,
+                    Err(status) => return Ok(empty(status)),
+                };
+                if self.server.add_snapshot(client_id, version_id, body)? {
+                    empty(200)
+                } else {
+                    empty(400)
+                }
+            }
+            (Route::GetSnapshot, Method::Get) => match self.server.get_snapshot(client_id)? {
+                Some((version_id, data)) => Response::from_data(data)
+                    .with_header(make_header("Content-Type", SNAPSHOT_CONTENT_TYPE))
+                    .with_header(uuid_header("X-Version-Id", version_id)),
+                None => empty(404),
+            },
+            _ => empty(405),
+        })
+    }
+}
+
+/// A [`WebServer`] running in a background thread.  Dropping this value stops the server.
+pub struct RunningServer {
+    http: Arc<tiny_http::Server>,
+    addr: SocketAddr,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl RunningServer {
+    /// The address on which the server is listening.
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// The base URL of the server, suitable for `ServerConfig::Remote`.
+    pub fn url(&self) -> String {
+        format!("http://{}/", self.addr)
+    }
+}
+
+impl Drop for RunningServer {
+    fn drop(&mut self) {
+        self.http.unblock();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+enum Route<'a> {
+    AddVersion(&'a str),
+    GetChildVersion(&'a str),
+    AddSnapshot(&'a str),
+    GetSnapshot,
+}
+
+fn listen(addr: impl ToSocketAddrs) -> anyhow::Result<tiny_http::Server> {
+    tiny_http::Server::http(addr).map_err(|e| anyhow::anyhow!("Could not listen: {}", e))
+}
+
+fn empty(status: u16) -> Response<Cursor<Vec<u8>>> {
+    Response::from_data(vec![]).with_status_code(StatusCode(status))
+}
+
+fn make_header(name: &str, value: &str) -> Header {
+    Header::from_bytes(name, value).expect("header names and values are ASCII")
+}
+
+fn uuid_header(name: &str, value: Uuid) -> Header {
+    make_header(name, &value.to_string())
+}
+
+/// Get the value of a request header.
+fn header<'r>(request: &'r Request, name: &'static str) -> Option<&'r str> {
+    request
+        .headers()
+        .iter()
+        .find(|h| h.field.equiv(name))
+        .map(|h| h.value.as_str())
+}
+
+/// Read the body of a request, which must have the given content-type, decoding it according to
+/// any `Content-Encoding`.  Returns an HTTP status code if the request is unacceptable.
+fn read_body(request: &mut Request, content_type: &str) -> anyhow::Result<Result<Vec<u8>, u16>> {
+    let actual_type = header(request, "Content-Type")
+        .and_then(|v| v.split(';').next())
+        .map(str::trim);
+    if actual_type != Some(content_type) {
+        return Ok(Err(415));
+    }
+    let encoding = header(request, "Content-Encoding")
+        .unwrap_or("identity")
+        .to_ascii_lowercase();
+    let reader = request.as_reader().take(MAX_SIZE + 1);
+    let mut reader: Box<dyn Read> = match encoding.as_str() {
+        "identity" => Box::new(reader),
+        "gzip" | "x-gzip" => Box::new(flate2::read::GzDecoder::new(reader).take(MAX_SIZE + 1)),
+        "deflate" => Box::new(flate2::read::ZlibDecoder::new(reader).take(MAX_SIZE + 1)),
+        _ => return Ok(Err(415)),
+    };
+    let mut body = vec![];
+    if reader.read_to_end(&mut body).is_err() {
+        return Ok(Err(400));
+    }
+    if body.len() as u64 > MAX_SIZE {
+        return Ok(Err(413));
+    }
+    if body.is_empty() {
+        return Ok(Err(400));
+    }
+    Ok(Ok(body))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::storage::InMemoryStorage;
+    use pretty_assertions::assert_eq;
+
+    fn spawn() -> RunningServer {
+        WebServer::new(ServerConfig::default(), Box::new(InMemoryStorage::new()))
+            .spawn("127.0.0.1:0")
+            .unwrap()
+    }
+
+    fn status(result: Result<ureq::Response, ureq::Error>) -> u16 {
+        match result {
+            Ok(resp) => resp.status(),
+            Err(ureq::Error::Status(status, _)) => status,
+            Err(e) => panic!("{e}"),
+        }
+    }
+
+    #[test]
+    fn add_and_get_version() {
+        let server = spawn();
+        let client_id = Uuid::new_v4().to_string();
+        let nil = Uuid::nil();
+
+        let resp = ureq::post(&format!("{}v1/client/add-version/{nil}", server.url()))
+            .set("X-Client-Id", &client_id)
+            .set("Content-Type", HISTORY_SEGMENT_CONTENT_TYPE)
+            .send_bytes(b"abcd")
+            .unwrap();
+        assert_eq!(resp.header("X-Snapshot-Request"), Some("urgency=high"));
+        let version_id = resp.header("X-Version-Id").unwrap().to_owned();
+
+        let resp = ureq::get(&format!(
+            "{}v1/client/get-child-version/{nil}",
+            server.url()
+        ))
+        .set("X-Client-Id", &client_id)
+        .call()
+        .unwrap();
+        assert_eq!(resp.header("X-Version-Id"), Some(version_id.as_str()));
+        assert_eq!(
+            resp.header("X-Parent-Version-Id"),
+            Some(nil.to_string().as_str())
+        );
+        assert_eq!(
+            resp.header("Content-Type"),
+            Some(HISTORY_SEGMENT_CONTENT_TYPE)
+        );
+        assert_eq!(resp.into_string().unwrap(), "abcd");
+
+        // conflict
+        match ureq::post(&format!("{}v1/client/add-version/{nil}", server.url()))
+            .set("X-Client-Id", &client_id)
+            .set("Content-Type", HISTORY_SEGMENT_CONTENT_TYPE)
+            .send_bytes(b"efgh")
+        {
+            Err(ureq::Error::Status(409, resp)) => {
+                assert_eq!(
+                    resp.header("X-Parent-Version-Id"),
+                    Some(version_id.as_str())
+                );
+            }
+            r => panic!("unexpected {r:?}"),
+        }
+
+        // up to date
+        let result = ureq::get(&format!(
+            "{}v1/client/get-child-version/{version_id}",
+            server.url()
+        ))
+        .set("X-Client-Id", &client_id)
+        .call();
+        assert_eq!(status(result), 404);
+
+        // another client sees nothing
+        let result = ureq::get(&format!(
+            "{}v1/client/get-child-version/{nil}",
+            server.url()
+        ))
+        .set("X-Client-Id", &Uuid::new_v4().to_string())
+        .call();
+        assert_eq!(status(result), 404);
+    }
+
+    #[test]
+    fn snapshot() {
+        let server = spawn();
+        let client_id = Uuid::new_v4().to_string();
+        let nil = Uuid::nil();
+
+        let result = ureq::get(&format!("{}v1/client/snapshot", server.url()))
+            .set("X-Client-Id", &client_id)
+            .call();
+        assert_eq!(status(result), 404);
+
+        let resp = ureq::post(&format!("{}v1/client/add-version/{nil}", server.url()))
+            .set("X-Client-Id", &client_id)
+            .set("Content-Type", HISTORY_SEGMENT_CONTENT_TYPE)
+            .send_bytes(b"abcd")
+            .unwrap();
+        let version_id = resp.header("X-Version-Id").unwrap().to_owned();
+
+        let result = ureq::post(&format!(
+            "{}v1/client/add-snapshot/{version_id}",
+            server.url()
+        ))
+        .set("X-Client-Id", &client_id)
+        .set("Content-Type", SNAPSHOT_CONTENT_TYPE)
+        .send_bytes(b"snap");
+        assert_eq!(status(result), 200);
+
+        let resp = ureq::get(&format!("{}v1/client/snapshot", server.url()))
+            .set("X-Client-Id", &client_id)
+            .call()
+            .unwrap();
+        assert_eq!(resp.header("X-Version-Id"), Some(version_id.as_str()));
+        assert_eq!(resp.header("Content-Type"), Some(SNAPSHOT_CONTENT_TYPE));
+        assert_eq!(resp.into_string().unwrap(), "snap");
+
+        // unknown version
+        let result = ureq::post(&format!(
+            "{}v1/client/add-snapshot/{}",
+            server.url(),
+            Uuid::new_v4()
+        ))
+        .set("X-Client-Id", &client_id)
+        .set("Content-Type", SNAPSHOT_CONTENT_TYPE)
+        .send_bytes(b"snap");
+        assert_eq!(status(result), 400);
+    }
+
+    #[test]
+    fn bad_requests() {
+        let server = spawn();
+        let nil = Uuid::nil();
+        let url = format!("{}v1/client/add-version/{nil}", server.url());
+
+        // no client ID
+        let result = ureq::post(&url)
+            .set("Content-Type", HISTORY_SEGMENT_CONTENT_TYPE)
+            .send_bytes(b"abcd");
+        assert_eq!(status(result), 400);
+
+        // wrong content type
+        let result = ureq::post(&url)
+            .set("X-Client-Id", &Uuid::new_v4().to_string())
+            .set("Content-Type", "text/plain")
+            .send_bytes(b"abcd");
+        assert_eq!(status(result), 415);
+
+        // wrong method
+        let result = ureq::get(&url)
+            .set("X-Client-Id", &Uuid::new_v4().to_string())
+            .call();
+        assert_eq!(status(result), 405);
+
+        // unknown path
+        let result = ureq::get(&format!("{}v2/other", server.url()))
+            .set("X-Client-Id", &Uuid::new_v4().to_string())
+            .call();
+        assert_eq!(status(result), 404);
+    }
+
+    #[test]
+    fn gzip_body() {
+        use flate2::write::GzEncoder;
+        use std::io::Write;
+
+        let server = spawn();
+        let client_id = Uuid::new_v4().to_string();
+        let nil = Uuid::nil();
+        let mut encoder = GzEncoder::new(vec![], flate2::Compression::default());
+        encoder.write_all(b"compressed").unwrap();
+        let body = encoder.finish().unwrap();
+
+        ureq::post(&format!("{}v1/client/add-version/{nil}", server.url()))
+            .set("X-Client-Id", &client_id)
+            .set("Content-Type", HISTORY_SEGMENT_CONTENT_TYPE)
+            .set("Content-Encoding", "gzip")
+            .send_bytes(&body)
+            .unwrap();
+        let resp = ureq::get(&format!(
+            "{}v1/client/get-child-version/{nil}",
+            server.url()
+        ))
+        .set("X-Client-Id", &client_id)
+        .call()
+        .unwrap();
+        assert_eq!(resp.into_string().unwrap(), "compressed");
+    }
+}
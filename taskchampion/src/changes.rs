@@ -0,0 +1,291 @@
+use crate::operation::Operation;
+use crate::storage::TaskMap;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// The origin of a change to a task.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ChangeOrigin {
+    /// The change was made on this replica, via [`Replica::commit_operations`] or
+    /// [`Replica::commit_reversed_operations`].
+    ///
+    /// [`Replica::commit_operations`]: crate::Replica::commit_operations
+    /// [`Replica::commit_reversed_operations`]: crate::Replica::commit_reversed_operations
+    Local,
+    /// The change was made on another replica and applied to this replica during
+    /// [`Replica::sync`](crate::Replica::sync).
+    Remote,
+}
+
+/// The kind of a change to a task.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ChangeKind {
+    /// The task was created.
+    Created,
+    /// The task was deleted (purged from the replica, not just given status "deleted").
+    Deleted,
+    /// A property of the task was changed.  A value of `None` indicates that the property was
+    /// not set.
+    Updated {
+        property: String,
+        old_value: Option<String>,
+        value: Option<String>,
+    },
+}
+
+/// A change to a single task, as reported to observers registered with
+/// [`Replica::subscribe`](crate::Replica::subscribe).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TaskChange {
+    /// The task that changed.
+    pub uuid: Uuid,
+    /// What changed.
+    pub kind: ChangeKind,
+    /// Where the change came from.
+    pub origin: ChangeOrigin,
+}
+
+impl TaskChange {
+    pub(crate) fn created(uuid: Uuid, origin: ChangeOrigin) -> Self {
+        TaskChange {
+            uuid,
+            kind: ChangeKind::Created,
+            origin,
+        }
+    }
+
+    pub(crate) fn deleted(uuid: Uuid, origin: ChangeOrigin) -> Self {
+        TaskChange {
+            uuid,
+            kind: ChangeKind::Deleted,
+            origin,
+        }
+    }
+
+    pub(crate) fn updated(
+        uuid: Uuid,
+        property: String,
+        old_value: Option<String>,
+        value: Option<String>,
+        origin: ChangeOrigin,
+    ) -> Self {
+        TaskChange {
+            uuid,
+            kind: ChangeKind::Updated {
+                property,
+                old_value,
+                value,
+            },
+            origin,
+        }
+    }
+}
+
+/// A change along with the time at which it was made, if known.  Changes without a time are
+/// recorded in history as of the time they are applied.
+pub(crate) type TimedChange = (TaskChange, Option<DateTime<Utc>>);
+
+/// An entry in the history of a task, as returned from
+/// [`Replica::task_history`](crate::Replica::task_history).
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    /// The time at which the change was made.  For an update, this is the time of the update on
+    /// the replica that made it; for other changes, it is the time at which the change was applied
+    /// to this replica.
+    pub timestamp: DateTime<Utc>,
+    /// What changed.
+    pub kind: ChangeKind,
+    /// Where the change came from.
+    pub origin: ChangeOrigin,
+}
+
+impl HistoryEntry {
+    /// Create a history entry for the given change, made at the given time.
+    pub(crate) fn new(change: &TaskChange, timestamp: DateTime<Utc>) -> Self {
+        HistoryEntry {
+            timestamp,
+            kind: change.kind.clone(),
+            origin: change.origin,
+        }
+    }
+}
+
+/// An update to a task property that was overridden by a conflicting update to the same property
+/// during a sync, as returned from [`Replica::sync_conflicts`](crate::Replica::sync_conflicts).
+///
+/// When two replicas update the same property concurrently, the later update takes precedence and
+/// the earlier update is discarded.  The conflict is recorded by the replica that resolved it,
+/// which is the replica that synchronized second.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SyncConflict {
+    /// The task that was updated.
+    pub uuid: Uuid,
+    /// The property that was updated.
+    pub property: String,
+    /// The value set by the overridden update.  A value of `None` indicates that the update
+    /// removed the property.
+    pub losing_value: Option<String>,
+    /// The time of the overridden update.
+    pub losing_timestamp: DateTime<Utc>,
+    /// Where the overridden update came from.
+    pub losing_origin: ChangeOrigin,
+    /// The value set by the update that took precedence.
+    pub winning_value: Option<String>,
+    /// The time of the update that took precedence.
+    pub winning_timestamp: DateTime<Utc>,
+}
+
+/// Identifies an observer registered with [`Replica::subscribe`](crate::Replica::subscribe),
+/// for use with [`Replica::unsubscribe`](crate::Replica::unsubscribe).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct SubscriptionId(pub(crate) u64);
+
+/// Determine the changes made by committing the given local operations.
+pub(crate) fn local_changes(operations: &[Operation]) -> Vec<TaskChange> {
+    timed_local_changes(operations)
+        .into_iter()
+        .map(|(change, _)| change)
+        .collect()
+}
+
+/// Determine the changes made by committing the given local operations, each with the time of the
+/// operation, if it has one.
+pub(crate) fn timed_local_changes(operations: &[Operation]) -> Vec<TimedChange> {
+    operations
+        .iter()
+        .filter_map(|op| match op {
+            Operation::Create { uuid } => {
+                Some((TaskChange::created(*uuid, ChangeOrigin::Local), None))
+            }
+            Operation::Delete { uuid, .. } => {
+                Some((TaskChange::deleted(*uuid, ChangeOrigin::Local), None))
+            }
+            Operation::Update {
+                uuid,
+                property,
+                old_value,
+                value,
+                timestamp,
+            } => Some((
+                TaskChange::updated(
+                    *uuid,
+                    property.clone(),
+                    old_value.clone(),
+                    value.clone(),
+                    ChangeOrigin::Local,
+                ),
+                Some(*timestamp),
+            )),
+            Operation::UndoPoint => None,
+        })
+        .collect()
+}
+
+/// Determine the changes made by committing the reverse of the given local operations, as
+/// done by [`Replica::commit_reversed_operations`](crate::Replica::commit_reversed_operations).
+pub(crate) fn reversed_changes(operations: &[Operation]) -> Vec<TaskChange> {
+    let mut changes = vec![];
+    for op in operations.iter().rev() {
+        match op {
+            Operation::Create { uuid } => {
+                changes.push(TaskChange::deleted(*uuid, ChangeOrigin::Local));
+            }
+            Operation::Delete { uuid, old_task } => {
+                changes.push(TaskChange::created(*uuid, ChangeOrigin::Local));
+                changes.extend(created_properties(*uuid, old_task, ChangeOrigin::Local));
+            }
+            Operation::Update {
+                uuid,
+                property,
+                old_value,
+                value,
+                ..
+            } => changes.push(TaskChange::updated(
+                *uuid,
+                property.clone(),
+                value.clone(),
+                old_value.clone(),
+                ChangeOrigin::Local,
+            )),
+            Operation::UndoPoint => {}
+        }
+    }
+    changes
+}
+
+/// Generate `Updated` changes setting each property of a newly-created task, in a stable order.
+pub(crate) fn created_properties(
+    uuid: Uuid,
+    task: &TaskMap,
+    origin: ChangeOrigin,
+) -> Vec<TaskChange> {
+    let mut props: Vec<_> = task.iter().collect();
+    props.sort();
+    props
+        .into_iter()
+        .map(|(p, v)| TaskChange::updated(uuid, p.clone(), None, Some(v.clone()), origin))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chrono::Utc;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn local_and_reversed_changes() {
+        let uuid = Uuid::new_v4();
+        let ops = vec![
+            Operation::UndoPoint,
+            Operation::Create { uuid },
+            Operation::Update {
+                uuid,
+                property: "title".into(),
+                old_value: None,
+                value: Some("x".into()),
+                timestamp: Utc::now(),
+            },
+            Operation::Delete {
+                uuid,
+                old_task: [("title".to_string(), "x".to_string())].into(),
+            },
+        ];
+        assert_eq!(
+            local_changes(&ops),
+            vec![
+                TaskChange::created(uuid, ChangeOrigin::Local),
+                TaskChange::updated(
+                    uuid,
+                    "title".into(),
+                    None,
+                    Some("x".into()),
+                    ChangeOrigin::Local
+                ),
+                TaskChange::deleted(uuid, ChangeOrigin::Local),
+            ]
+        );
+        assert_eq!(
+            reversed_changes(&ops),
+            vec![
+                TaskChange::created(uuid, ChangeOrigin::Local),
+                TaskChange::updated(
+                    uuid,
+                    "title".into(),
+                    None,
+                    Some("x".into()),
+                    ChangeOrigin::Local
+                ),
+                TaskChange::updated(
+                    uuid,
+                    "title".into(),
+                    Some("x".into()),
+                    None,
+                    ChangeOrigin::Local
+                ),
+                TaskChange::deleted(uuid, ChangeOrigin::Local),
+            ]
+        );
+    }
+}
@@ -16,6 +16,14 @@ pub enum Error {
     /// other irrecoverable error.
     #[error("Local replica is out of sync with the server")]
     OutOfSync,
+    /// A sync was cancelled with a [`CancellationToken`](crate::CancellationToken).
+    #[error("Synchronization was cancelled")]
+    Cancelled,
+    /// Data from the server could not be decrypted.  This typically means that the encryption
+    /// secret is incorrect, or that another replica has changed it with
+    /// [`Replica::rotate_encryption_secret`](crate::Replica::rotate_encryption_secret).
+    #[error("Could not decrypt data from the server: the encryption secret is incorrect or has been changed")]
+    WrongEncryptionSecret,
     /// A usage error
     #[error("Usage Error: {0}")]
     Usage(String),
@@ -42,9 +50,20 @@ other_error!(crate::storage::sqlite::SqliteError);
 other_error!(google_cloud_storage::http::Error);
 #[cfg(feature = "server-gcp")]
 other_error!(google_cloud_storage::client::google_cloud_auth::error::Error);
+/// Convert AWS SDK errors to Error::Server, including the details of the underlying error.
+#[cfg(feature = "server-aws")]
+impl<E, R> From<aws_sdk_s3::error::SdkError<E, R>> for Error
+where
+    E: std::error::Error + Send + Sync + 'static,
+    R: std::fmt::Debug + Send + Sync + 'static,
+{
+    fn from(err: aws_sdk_s3::error::SdkError<E, R>) -> Self {
+        Self::Server(aws_sdk_s3::error::DisplayErrorContext(err).to_string())
+    }
+}
 
 /// Convert ureq errors more carefully
-#[cfg(feature = "server-sync")]
+#[cfg(any(feature = "server-sync", feature = "server-webdav"))]
 impl From<ureq::Error> for Error {
     fn from(ureq_err: ureq::Error) -> Self {
         match ureq_err {
@@ -0,0 +1,125 @@
+use crate::task::Timestamp;
+use chrono::{DateTime, Datelike, Duration, NaiveDate, NaiveDateTime, TimeZone, Utc};
+
+/// Parse a date as it appears in a filter, relative to the given "now".
+///
+/// This supports a subset of Taskwarrior's date formats: the named dates `now`, `today`, `sod`,
+/// `eod`, `yesterday`, `tomorrow`, `sow`, `eow`, `som`, `eom`, `soy` and `eoy`; ISO 8601 dates
+/// and date-times; and integer UNIX epoch timestamps.  All calculations are performed in UTC.
+pub(super) fn parse_date(value: &str, now: Timestamp) -> Option<Timestamp> {
+    if let Some(ts) = named_date(value, now) {
+        return Some(ts);
+    }
+
+    if !value.is_empty() && value.chars().all(|c| c.is_ascii_digit()) {
+        return value.parse().ok().and_then(from_epoch);
+    }
+
+    if let Ok(dt) = DateTime::parse_from_rfc3339(value) {
+        return Some(dt.with_timezone(&Utc));
+    }
+
+    if let Ok(ndt) = NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M:%S") {
+        return Some(Utc.from_utc_datetime(&ndt));
+    }
+
+    if let Ok(nd) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        return Some(Utc.from_utc_datetime(&nd.and_hms_opt(0, 0, 0)?));
+    }
+
+    None
+}
+
+/// Convert a UNIX epoch timestamp to a Timestamp, or None if it is out of range.
+pub(super) fn from_epoch(secs: i64) -> Option<Timestamp> {
+    Utc.timestamp_opt(secs, 0).single()
+}
+
+/// Determine whether the two timestamps fall on the same (UTC) day.
+pub(super) fn same_day(a: Timestamp, b: Timestamp) -> bool {
+    a.date_naive() == b.date_naive()
+}
+
+fn named_date(value: &str, now: Timestamp) -> Option<Timestamp> {
+    let one_sec = Duration::seconds(1);
+    let sod = start_of_day(now.date_naive())?;
+    Some(match value {
+        "now" => now,
+        "today" | "sod" => sod,
+        "eod" => sod + Duration::days(1) - one_sec,
+        "yesterday" => sod - Duration::days(1),
+        "tomorrow" => sod + Duration::days(1),
+        "sow" => sod - Duration::days(now.weekday().num_days_from_monday().into()),
+        "eow" => {
+            sod - Duration::days(now.weekday().num_days_from_monday().into()) + Duration::days(7)
+                - one_sec
+        }
+        "som" => start_of_day(NaiveDate::from_ymd_opt(now.year(), now.month(), 1)?)?,
+        "eom" => {
+            let (y, m) = if now.month() == 12 {
+                (now.year() + 1, 1)
+            } else {
+                (now.year(), now.month() + 1)
+            };
+            start_of_day(NaiveDate::from_ymd_opt(y, m, 1)?)? - one_sec
+        }
+        "soy" => start_of_day(NaiveDate::from_ymd_opt(now.year(), 1, 1)?)?,
+        "eoy" => start_of_day(NaiveDate::from_ymd_opt(now.year() + 1, 1, 1)?)? - one_sec,
+        _ => return None,
+    })
+}
+
+fn start_of_day(date: NaiveDate) -> Option<Timestamp> {
+    Some(Utc.from_utc_datetime(&date.and_hms_opt(0, 0, 0)?))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::task::utc_timestamp;
+    use pretty_assertions::assert_eq;
+    use rstest::rstest;
+
+    fn ts(y: i32, mo: u32, d: u32, h: u32, mi: u32, s: u32) -> Timestamp {
+        Utc.with_ymd_and_hms(y, mo, d, h, mi, s).unwrap()
+    }
+
+    // 2024-02-14 is a Wednesday
+    #[rstest]
+    #[case::now("now", ts(2024, 2, 14, 13, 30, 0))]
+    #[case::today("today", ts(2024, 2, 14, 0, 0, 0))]
+    #[case::sod("sod", ts(2024, 2, 14, 0, 0, 0))]
+    #[case::eod("eod", ts(2024, 2, 14, 23, 59, 59))]
+    #[case::yesterday("yesterday", ts(2024, 2, 13, 0, 0, 0))]
+    #[case::tomorrow("tomorrow", ts(2024, 2, 15, 0, 0, 0))]
+    #[case::sow("sow", ts(2024, 2, 12, 0, 0, 0))]
+    #[case::eow("eow", ts(2024, 2, 18, 23, 59, 59))]
+    #[case::som("som", ts(2024, 2, 1, 0, 0, 0))]
+    #[case::eom("eom", ts(2024, 2, 29, 23, 59, 59))]
+    #[case::soy("soy", ts(2024, 1, 1, 0, 0, 0))]
+    #[case::eoy("eoy", ts(2024, 12, 31, 23, 59, 59))]
+    #[case::epoch("1700000000", utc_timestamp(1700000000))]
+    #[case::date("2023-06-01", ts(2023, 6, 1, 0, 0, 0))]
+    #[case::datetime("2023-06-01T12:34:56", ts(2023, 6, 1, 12, 34, 56))]
+    #[case::rfc3339("2023-06-01T12:34:56+02:00", ts(2023, 6, 1, 10, 34, 56))]
+    fn test_parse_date(#[case] value: &'static str, #[case] expected: Timestamp) {
+        let now = ts(2024, 2, 14, 13, 30, 0);
+        assert_eq!(parse_date(value, now), Some(expected));
+    }
+
+    #[test]
+    fn test_parse_date_eom_december() {
+        let now = ts(2024, 12, 3, 0, 0, 0);
+        assert_eq!(parse_date("eom", now), Some(ts(2024, 12, 31, 23, 59, 59)));
+    }
+
+    #[rstest]
+    #[case::empty("")]
+    #[case::word("someday")]
+    #[case::bad_date("2023-13-01")]
+    #[case::epoch_out_of_range("99999999999999999")]
+    #[case::epoch_too_large("99999999999999999999")]
+    fn test_parse_date_invalid(#[case] value: &'static str) {
+        assert_eq!(parse_date(value, Utc::now()), None);
+    }
+}
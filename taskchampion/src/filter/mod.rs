@@ -0,0 +1,377 @@
+mod date;
+mod parse;
+
+use crate::errors::{Error, Result};
+use crate::task::Timestamp;
+use crate::{Tag, Task};
+use chrono::Utc;
+use std::convert::TryFrom;
+use std::str::FromStr;
+
+/// A parsed filter expression, used to select tasks with
+/// [`Replica::query`](crate::Replica::query) or [`Filter::matches`].
+///
+/// The filter language is compatible with the filters accepted by Taskwarrior.
+///
+/// A filter is a sequence of terms, optionally combined with the operators `and`, `or`, `xor` and
+/// `not` (or `!`) and grouped with parentheses.  Adjacent terms are implicitly combined with `and`.
+/// The supported terms are:
+///
+///  * `+tag` and `-tag` match tasks with and without the given tag, including synthetic tags such
+///    as `+PENDING` or `+BLOCKED`.
+///  * `attribute:value` matches tasks whose attribute has the given value.  An empty value
+///    matches tasks where the attribute is not set.  Any task property can be used as an
+///    attribute, including UDAs.
+///  * `attribute.modifier:value` applies a modifier to the comparison, one of `is` (`equals`),
+///    `isnt` (`not`), `before` (`below`, `under`), `after` (`above`, `over`), `by`, `none`, `any`,
+///    `has` (`contains`), `hasnt`, `startswith` (`left`), `endswith` (`right`), `word` and `noword`.
+///  * Any other word matches tasks whose description or annotations contain that word.
+///
+/// The attributes `due`, `wait`, `scheduled`, `until`, `entry`, `modified`, `start` and `end` are
+/// compared as dates.  Dates may be given as ISO 8601 dates or date-times, as UNIX timestamps, or
+/// as one of the named dates `now`, `today`, `sod`, `eod`, `yesterday`, `tomorrow`, `sow`, `eow`,
+/// `som`, `eom`, `soy` and `eoy`, all interpreted in UTC.  An unmodified date comparison such as
+/// `due:today` matches any time on the given day.
+///
+/// The `project` attribute is hierarchical: `project:home` matches both `home` and `home.garden`.
+///
+/// Filters are parsed from strings:
+///
+/// ```
+/// # use taskchampion::Filter;
+/// let filter: Filter = "status:pending +work due.before:eom and (priority:H or +urgent)"
+///     .parse()
+///     .unwrap();
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Filter {
+    expr: Expr,
+}
+
+impl Filter {
+    /// Determine whether the given task matches this filter.
+    pub fn matches(&self, task: &Task) -> bool {
+        self.expr.eval(task, Utc::now())
+    }
+}
+
+impl FromStr for Filter {
+    type Err = Error;
+
+    fn from_str(value: &str) -> Result<Filter> {
+        Ok(Filter {
+            expr: parse::parse(value)?,
+        })
+    }
+}
+
+impl TryFrom<&str> for Filter {
+    type Error = Error;
+
+    fn try_from(value: &str) -> Result<Filter> {
+        Self::from_str(value)
+    }
+}
+
+impl Default for Filter {
+    /// The default filter matches all tasks.
+    fn default() -> Self {
+        Filter { expr: Expr::All }
+    }
+}
+
+/// A comparison applied to an attribute in a filter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, strum_macros::EnumString)]
+#[strum(serialize_all = "lowercase")]
+enum Modifier {
+    #[strum(serialize = "is", serialize = "equals")]
+    Is,
+    #[strum(serialize = "isnt", serialize = "not")]
+    Isnt,
+    #[strum(serialize = "before", serialize = "below", serialize = "under")]
+    Before,
+    #[strum(serialize = "after", serialize = "above", serialize = "over")]
+    After,
+    By,
+    None,
+    Any,
+    #[strum(serialize = "has", serialize = "contains")]
+    Has,
+    Hasnt,
+    #[strum(serialize = "startswith", serialize = "left")]
+    StartsWith,
+    #[strum(serialize = "endswith", serialize = "right")]
+    EndsWith,
+    Word,
+    NoWord,
+}
+
+impl Modifier {
+    /// True if this modifier compares the value as a date, for date attributes.
+    fn compares_dates(&self) -> bool {
+        matches!(
+            self,
+            Modifier::Is | Modifier::Isnt | Modifier::Before | Modifier::After | Modifier::By
+        )
+    }
+}
+
+/// A node in the syntax tree of a filter.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Expr {
+    /// Matches all tasks (an empty filter)
+    All,
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Xor(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    HasTag(Tag),
+    Attribute {
+        name: String,
+        modifier: Modifier,
+        value: String,
+    },
+    /// A bare word, matched against the description and annotations
+    Word(String),
+}
+
+impl Expr {
+    fn is_date_attribute(name: &str) -> bool {
+        matches!(
+            name,
+            "due" | "wait" | "scheduled" | "until" | "entry" | "modified" | "start" | "end"
+        )
+    }
+
+    fn eval(&self, task: &Task, now: Timestamp) -> bool {
+        match self {
+            Expr::All => true,
+            Expr::And(a, b) => a.eval(task, now) && b.eval(task, now),
+            Expr::Or(a, b) => a.eval(task, now) || b.eval(task, now),
+            Expr::Xor(a, b) => a.eval(task, now) != b.eval(task, now),
+            Expr::Not(a) => !a.eval(task, now),
+            Expr::HasTag(tag) => task.has_tag(tag),
+            Expr::Attribute {
+                name,
+                modifier,
+                value,
+            } => Self::eval_attribute(task, name, *modifier, value, now),
+            Expr::Word(word) => {
+                task.get_description().contains(word.as_str())
+                    || task
+                        .get_annotations()
+                        .any(|a| a.description.contains(word.as_str()))
+            }
+        }
+    }
+
+    fn eval_attribute(
+        task: &Task,
+        name: &str,
+        modifier: Modifier,
+        value: &str,
+        now: Timestamp,
+    ) -> bool {
+        let (status, uuid);
+        let actual = match name {
+            "status" => {
+                status = task.get_status();
+                Some(status.to_taskmap())
+            }
+            "uuid" => {
+                uuid = task.get_uuid().to_string();
+                Some(uuid.as_str())
+            }
+            _ => task.get_value(name),
+        }
+        .filter(|v| !v.is_empty());
+
+        if Self::is_date_attribute(name) && !value.is_empty() && modifier.compares_dates() {
+            // a value that is not a valid timestamp is treated as unset
+            let actual = actual
+                .and_then(|v| v.parse().ok())
+                .and_then(date::from_epoch);
+            // the parser has already validated the date, but "now" may have changed
+            let Some(value) = date::parse_date(value, now) else {
+                return false;
+            };
+            return match (modifier, actual) {
+                (Modifier::Is, Some(actual)) => date::same_day(actual, value),
+                (Modifier::Isnt, Some(actual)) => !date::same_day(actual, value),
+                (Modifier::Isnt, None) => true,
+                (Modifier::Before, Some(actual)) => actual < value,
+                (Modifier::After, Some(actual)) => actual > value,
+                (Modifier::By, Some(actual)) => actual <= value,
+                _ => false,
+            };
+        }
+
+        let is = |actual: Option<&str>| match actual {
+            None => value.is_empty(),
+            Some(actual) if name == "project" => {
+                actual == value
+                    || (actual.starts_with(value) && actual[value.len()..].starts_with('.'))
+            }
+            Some(actual) => actual == value,
+        };
+        let has_word = |actual: &str| actual.split_whitespace().any(|w| w == value);
+
+        match (modifier, actual) {
+            (Modifier::Is, actual) => is(actual),
+            (Modifier::Isnt, actual) => !is(actual),
+            (Modifier::None, actual) => actual.is_none(),
+            (Modifier::Any, actual) => actual.is_some(),
+            (Modifier::Hasnt, None) | (Modifier::NoWord, None) => true,
+            (_, None) => false,
+            (Modifier::Before, Some(actual)) => actual < value,
+            (Modifier::After, Some(actual)) => actual > value,
+            (Modifier::By, Some(actual)) => actual <= value,
+            (Modifier::Has, Some(actual)) => actual.contains(value),
+            (Modifier::Hasnt, Some(actual)) => !actual.contains(value),
+            (Modifier::StartsWith, Some(actual)) => actual.starts_with(value),
+            (Modifier::EndsWith, Some(actual)) => actual.ends_with(value),
+            (Modifier::Word, Some(actual)) => has_word(actual),
+            (Modifier::NoWord, Some(actual)) => !has_word(actual),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::depmap::DependencyMap;
+    use crate::storage::TaskMap;
+    use crate::TaskData;
+    use chrono::TimeZone;
+    use pretty_assertions::assert_eq;
+    use rstest::rstest;
+    use std::sync::Arc;
+    use uuid::Uuid;
+
+    const TEST_UUID: Uuid = Uuid::from_u128(1234);
+
+    /// 2024-02-14T13:30:00Z, a Wednesday
+    fn now() -> Timestamp {
+        Utc.with_ymd_and_hms(2024, 2, 14, 13, 30, 0).unwrap()
+    }
+
+    fn task(props: &[(&str, &str)]) -> Task {
+        let taskmap: TaskMap = props
+            .iter()
+            .map(|(p, v)| (p.to_string(), v.to_string()))
+            .collect();
+        Task::new(
+            TaskData::new(TEST_UUID, taskmap),
+            Arc::new(DependencyMap::new()),
+        )
+    }
+
+    fn matches(filter: &str, task: &Task) -> bool {
+        let filter: Filter = filter.parse().unwrap();
+        filter.expr.eval(task, now())
+    }
+
+    fn ts(y: i32, mo: u32, d: u32, h: u32) -> String {
+        Utc.with_ymd_and_hms(y, mo, d, h, 0, 0)
+            .unwrap()
+            .timestamp()
+            .to_string()
+    }
+
+    #[test]
+    fn test_default_matches_all() {
+        assert!(Filter::default().matches(&task(&[])));
+        assert_eq!(Filter::default(), "".parse().unwrap());
+    }
+
+    #[rstest]
+    #[case::status("status:pending", true)]
+    #[case::status_other("status:completed", false)]
+    #[case::tag("+work", true)]
+    #[case::tag_absent("-work", false)]
+    #[case::synthetic("+PENDING", true)]
+    #[case::synthetic_absent("+COMPLETED", false)]
+    #[case::and("+work and priority:H", true)]
+    #[case::implicit_and("+work priority:L", false)]
+    #[case::or("priority:L or +work", true)]
+    #[case::xor("+work xor priority:H", false)]
+    #[case::not("not +home", true)]
+    #[case::parens("(priority:L or +home) +work", false)]
+    #[case::word("milk", true)]
+    #[case::word_annotation("store", true)]
+    #[case::quoted_word(r#""buy milk""#, true)]
+    #[case::word_absent("eggs", false)]
+    #[case::uda("estimate:3", true)]
+    #[case::uda_missing("color:", true)]
+    #[case::uda_none("color.none:", true)]
+    #[case::uda_any("estimate.any:", true)]
+    #[case::has("description.has:mil", true)]
+    #[case::hasnt("description.hasnt:mil", false)]
+    #[case::startswith("description.startswith:buy", true)]
+    #[case::endswith("description.endswith:buy", false)]
+    #[case::word_modifier("description.word:milk", true)]
+    #[case::noword_modifier("description.noword:mil", true)]
+    #[case::isnt("priority.isnt:L", true)]
+    #[case::project("project:home", true)]
+    #[case::project_parent("project:ho", false)]
+    #[case::project_child("project:home.garden", true)]
+    #[case::project_grandchild("project:home.garden.roses", false)]
+    #[case::uuid("uuid:00000000-0000-0000-0000-0000000004d2", true)]
+    fn test_matches(#[case] filter: &'static str, #[case] expected: bool) {
+        let t = task(&[
+            ("status", "pending"),
+            ("description", "buy milk"),
+            ("priority", "H"),
+            ("tag_work", ""),
+            ("estimate", "3"),
+            ("project", "home.garden"),
+            ("annotation_1700000000", "at the store"),
+        ]);
+        assert_eq!(matches(filter, &t), expected, "filter {:?}", filter);
+    }
+
+    #[rstest]
+    #[case::due_today("due:today", true)]
+    #[case::due_tomorrow("due:tomorrow", false)]
+    #[case::due_date("due:2024-02-14", true)]
+    #[case::before_eod("due.before:eod", true)]
+    #[case::before_now("due.before:now", false)]
+    #[case::after_now("due.after:now", true)]
+    #[case::by_eom("due.by:eom", true)]
+    #[case::before_sod("due.before:sod", false)]
+    #[case::isnt("due.isnt:yesterday", true)]
+    #[case::empty("due:", false)]
+    #[case::wait_unset("wait:", true)]
+    #[case::wait_before("wait.before:eoy", false)]
+    #[case::wait_isnt("wait.isnt:today", true)]
+    #[case::entry_after("entry.after:som", false)]
+    #[case::entry_before("entry.before:2024-01-01T00:00:00Z", true)]
+    fn test_matches_dates(#[case] filter: &'static str, #[case] expected: bool) {
+        let t = task(&[
+            ("due", &ts(2024, 2, 14, 18)),
+            ("entry", &ts(2023, 12, 25, 8)),
+        ]);
+        assert_eq!(matches(filter, &t), expected, "filter {:?}", filter);
+    }
+
+    #[test]
+    fn test_matches_status_default() {
+        // a task without a status is pending
+        assert!(matches("status:pending", &task(&[])));
+    }
+
+    #[test]
+    fn test_matches_uses_current_time() {
+        let filter: Filter = "due.before:now".parse().unwrap();
+        assert!(filter.matches(&task(&[("due", "1")])));
+        assert!(!filter.matches(&task(&[("due", &ts(3000, 1, 1, 0))])));
+    }
+
+    #[test]
+    fn test_matches_date_out_of_range() {
+        let t = task(&[("due", "99999999999999999")]);
+        assert!(!matches("due.before:now", &t));
+        assert!(!matches("due.after:now", &t));
+        assert!(matches("due.isnt:today", &t));
+    }
+}
@@ -0,0 +1,388 @@
+use super::{date, Expr, Modifier};
+use crate::errors::{Error, Result};
+use crate::Tag;
+use chrono::Utc;
+use std::str::FromStr;
+
+/// A lexical token in a filter expression.
+#[derive(Debug, PartialEq, Eq)]
+enum Token {
+    LParen,
+    RParen,
+    /// A whitespace-delimited word.  If `quoted` is true, the word began with a quote character
+    /// and is never interpreted as an operator, tag, or attribute.
+    Word {
+        text: String,
+        quoted: bool,
+    },
+}
+
+/// Split a filter string into tokens.  Whitespace separates tokens except within single or double
+/// quotes, and parentheses are always tokens of their own when not quoted.
+fn tokenize(input: &str) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut chars = input.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c == '(' {
+            chars.next();
+            tokens.push(Token::LParen);
+        } else if c == ')' {
+            chars.next();
+            tokens.push(Token::RParen);
+        } else {
+            let quoted = c == '"' || c == '\'';
+            let mut text = String::new();
+            let mut in_quote: Option<char> = None;
+            while let Some(&c) = chars.peek() {
+                match in_quote {
+                    Some(q) if c == q => in_quote = None,
+                    Some(_) => text.push(c),
+                    None if c == '"' || c == '\'' => in_quote = Some(c),
+                    None if c.is_whitespace() || c == '(' || c == ')' => break,
+                    None => text.push(c),
+                }
+                chars.next();
+            }
+            if in_quote.is_some() {
+                return Err(Error::Usage(format!(
+                    "Unterminated quote in filter {:?}",
+                    input
+                )));
+            }
+            tokens.push(Token::Word { text, quoted });
+        }
+    }
+    Ok(tokens)
+}
+
+/// Parse a filter string into an expression.
+pub(super) fn parse(input: &str) -> Result<Expr> {
+    let tokens = tokenize(input)?;
+    if tokens.is_empty() {
+        return Ok(Expr::All);
+    }
+    let mut parser = Parser { tokens, pos: 0 };
+    let expr = parser.or_expr()?;
+    if let Some(tok) = parser.peek() {
+        return Err(Error::Usage(format!(
+            "Unexpected {} in filter",
+            describe(tok)
+        )));
+    }
+    Ok(expr)
+}
+
+fn describe(tok: &Token) -> String {
+    match tok {
+        Token::LParen => "'('".into(),
+        Token::RParen => "')'".into(),
+        Token::Word { text, .. } => format!("{:?}", text),
+    }
+}
+
+/// A recursive-descent parser.  Operator precedence, from loosest to tightest binding, is `or`,
+/// `xor`, `and` (which is also implied between adjacent terms), and `not`.
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    /// If the next token is the given (unquoted) operator keyword, consume it and return true.
+    fn eat_keyword(&mut self, keyword: &str) -> bool {
+        if self.is_keyword(keyword) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn is_keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Some(Token::Word { text, quoted: false }) if text == keyword)
+    }
+
+    fn or_expr(&mut self) -> Result<Expr> {
+        let mut expr = self.xor_expr()?;
+        while self.eat_keyword("or") {
+            expr = Expr::Or(Box::new(expr), Box::new(self.xor_expr()?));
+        }
+        Ok(expr)
+    }
+
+    fn xor_expr(&mut self) -> Result<Expr> {
+        let mut expr = self.and_expr()?;
+        while self.eat_keyword("xor") {
+            expr = Expr::Xor(Box::new(expr), Box::new(self.and_expr()?));
+        }
+        Ok(expr)
+    }
+
+    fn and_expr(&mut self) -> Result<Expr> {
+        let mut expr = self.unary_expr()?;
+        loop {
+            if self.eat_keyword("and") {
+                expr = Expr::And(Box::new(expr), Box::new(self.unary_expr()?));
+                continue;
+            }
+            // adjacent terms are implicitly joined with `and`
+            match self.peek() {
+                None | Some(Token::RParen) => break,
+                _ if self.is_keyword("or") || self.is_keyword("xor") => break,
+                _ => expr = Expr::And(Box::new(expr), Box::new(self.unary_expr()?)),
+            }
+        }
+        Ok(expr)
+    }
+
+    fn unary_expr(&mut self) -> Result<Expr> {
+        if self.eat_keyword("not") || self.eat_keyword("!") {
+            return Ok(Expr::Not(Box::new(self.unary_expr()?)));
+        }
+        self.primary_expr()
+    }
+
+    fn primary_expr(&mut self) -> Result<Expr> {
+        let Some(tok) = self.tokens.get(self.pos) else {
+            return Err(Error::Usage("Unexpected end of filter".into()));
+        };
+        self.pos += 1;
+        match tok {
+            Token::LParen => {
+                let expr = self.or_expr()?;
+                if self.peek() != Some(&Token::RParen) {
+                    return Err(Error::Usage("Unbalanced parentheses in filter".into()));
+                }
+                self.pos += 1;
+                Ok(expr)
+            }
+            Token::RParen => Err(Error::Usage("Unexpected ')' in filter".into())),
+            Token::Word { text, quoted: true } => Ok(Expr::Word(text.clone())),
+            Token::Word {
+                text,
+                quoted: false,
+            } => {
+                if ["and", "or", "xor"].contains(&text.as_str()) {
+                    return Err(Error::Usage(format!("Unexpected {:?} in filter", text)));
+                }
+                term(text)
+            }
+        }
+    }
+}
+
+/// Interpret a single unquoted word as a tag, attribute, or search term.
+fn term(text: &str) -> Result<Expr> {
+    if let Some(tag) = text.strip_prefix('+') {
+        return Ok(Expr::HasTag(parse_tag(tag)?));
+    }
+    if let Some(tag) = text.strip_prefix('-') {
+        if !tag.is_empty() {
+            return Ok(Expr::Not(Box::new(Expr::HasTag(parse_tag(tag)?))));
+        }
+    }
+
+    if let Some((name, value)) = text.split_once(':') {
+        if is_attribute_name(name) {
+            let (name, modifier) = match name.rsplit_once('.') {
+                Some((n, m)) => match Modifier::from_str(m) {
+                    Ok(modifier) => (n, modifier),
+                    Err(_) => (name, Modifier::Is),
+                },
+                None => (name, Modifier::Is),
+            };
+            if Expr::is_date_attribute(name)
+                && !value.is_empty()
+                && modifier.compares_dates()
+                && date::parse_date(value, Utc::now()).is_none()
+            {
+                return Err(Error::Usage(format!(
+                    "Invalid date {:?} for attribute {}",
+                    value, name
+                )));
+            }
+            return Ok(Expr::Attribute {
+                name: name.into(),
+                modifier,
+                value: value.into(),
+            });
+        }
+    }
+
+    Ok(Expr::Word(text.into()))
+}
+
+fn parse_tag(tag: &str) -> Result<Tag> {
+    Tag::from_str(tag).map_err(|e| Error::Usage(e.to_string()))
+}
+
+fn is_attribute_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || "_.-".contains(c))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use rstest::rstest;
+
+    fn word(text: &str) -> Token {
+        Token::Word {
+            text: text.into(),
+            quoted: false,
+        }
+    }
+
+    fn attr(name: &str, modifier: Modifier, value: &str) -> Expr {
+        Expr::Attribute {
+            name: name.into(),
+            modifier,
+            value: value.into(),
+        }
+    }
+
+    fn tag(name: &str) -> Expr {
+        Expr::HasTag(Tag::from_str(name).unwrap())
+    }
+
+    fn and(a: Expr, b: Expr) -> Expr {
+        Expr::And(Box::new(a), Box::new(b))
+    }
+
+    fn or(a: Expr, b: Expr) -> Expr {
+        Expr::Or(Box::new(a), Box::new(b))
+    }
+
+    fn not(a: Expr) -> Expr {
+        Expr::Not(Box::new(a))
+    }
+
+    #[test]
+    fn test_tokenize() {
+        assert_eq!(
+            tokenize("status:pending (+a or -b)").unwrap(),
+            vec![
+                word("status:pending"),
+                Token::LParen,
+                word("+a"),
+                word("or"),
+                word("-b"),
+                Token::RParen,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tokenize_quotes() {
+        assert_eq!(
+            tokenize(r#"description:"buy (some) milk" 'and'"#).unwrap(),
+            vec![
+                word("description:buy (some) milk"),
+                Token::Word {
+                    text: "and".into(),
+                    quoted: true
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tokenize_unterminated() {
+        assert!(tokenize(r#"description:"oops"#).is_err());
+    }
+
+    #[test]
+    fn test_parse_empty() {
+        assert_eq!(parse("  ").unwrap(), Expr::All);
+    }
+
+    #[test]
+    fn test_parse_implicit_and() {
+        assert_eq!(
+            parse("status:pending +work").unwrap(),
+            and(attr("status", Modifier::Is, "pending"), tag("work"))
+        );
+    }
+
+    #[test]
+    fn test_parse_precedence() {
+        // `and` binds more tightly than `or`
+        assert_eq!(
+            parse("+a or +b and +c").unwrap(),
+            or(tag("a"), and(tag("b"), tag("c")))
+        );
+    }
+
+    #[test]
+    fn test_parse_complex() {
+        assert_eq!(
+            parse("status:pending +work due.before:eom and (priority:H or +urgent)").unwrap(),
+            and(
+                and(
+                    and(attr("status", Modifier::Is, "pending"), tag("work")),
+                    attr("due", Modifier::Before, "eom")
+                ),
+                or(attr("priority", Modifier::Is, "H"), tag("urgent"))
+            )
+        );
+    }
+
+    #[test]
+    fn test_parse_not() {
+        assert_eq!(
+            parse("not +a -b ! +c").unwrap(),
+            and(and(not(tag("a")), not(tag("b"))), not(tag("c")))
+        );
+    }
+
+    #[rstest]
+    #[case::plain("due:today", "due", Modifier::Is, "today")]
+    #[case::empty("due:", "due", Modifier::Is, "")]
+    #[case::alias("due.under:eom", "due", Modifier::Before, "eom")]
+    #[case::namespaced_uda("devsync.issue-id:123", "devsync.issue-id", Modifier::Is, "123")]
+    #[case::namespaced_uda_mod(
+        "devsync.issue-id.startswith:12",
+        "devsync.issue-id",
+        Modifier::StartsWith,
+        "12"
+    )]
+    #[case::colon_in_value("url:https://example.com", "url", Modifier::Is, "https://example.com")]
+    fn test_parse_attribute(
+        #[case] input: &'static str,
+        #[case] name: &'static str,
+        #[case] modifier: Modifier,
+        #[case] value: &'static str,
+    ) {
+        assert_eq!(parse(input).unwrap(), attr(name, modifier, value));
+    }
+
+    #[rstest]
+    #[case::bare("milk")]
+    #[case::dash("-")]
+    #[case::not_an_attribute("12:30")]
+    fn test_parse_word(#[case] input: &'static str) {
+        assert_eq!(parse(input).unwrap(), Expr::Word(input.into()));
+    }
+
+    #[rstest]
+    #[case::unbalanced_open("(+a or +b")]
+    #[case::unbalanced_close("+a )")]
+    #[case::empty_parens("()")]
+    #[case::dangling_or("+a or")]
+    #[case::leading_and("and +a")]
+    #[case::bad_tag("+a!b")]
+    #[case::bad_synthetic("+NOSUCH")]
+    #[case::bad_date("due.before:someday")]
+    #[case::date_out_of_range("due.before:99999999999999999")]
+    fn test_parse_errors(#[case] input: &'static str) {
+        assert!(matches!(parse(input), Err(Error::Usage(_))));
+    }
+}
@@ -0,0 +1,564 @@
+use crate::errors::{Error, Result};
+use crate::operation::{Operation, Operations};
+use crate::storage::TaskMap;
+use crate::task::{export_task, import_task};
+use crate::TaskData;
+use chrono::Utc;
+use log::info;
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use uuid::Uuid;
+
+/// A hook, called by [`Replica::commit_operations`](crate::Replica::commit_operations) to
+/// inspect, rewrite, or reject changes to tasks.  Register hooks with
+/// [`Replica::add_hook`](crate::Replica::add_hook).
+///
+/// To reject a change, return [`Error::Usage`] with a message for the user.  No changes are
+/// committed in this case.  Any other error also prevents the changes from being committed.
+///
+/// Hooks that rewrite a task may use [`TaskData::update`] to do so.  The operations produced by
+/// such updates are ignored, as the replica determines the necessary operations itself.
+///
+/// Hooks are not called for changes applied during a sync, nor for
+/// [`Replica::commit_reversed_operations`](crate::Replica::commit_reversed_operations).
+///
+/// Hooks must be `Send`, so that a [`Replica`](crate::Replica) can be moved between threads.
+pub trait Hook: Send {
+    /// Called for each task created by the committed operations, with the task as it will be
+    /// committed.  Returns the task to commit in its place, which must have the same UUID.
+    fn on_add(&mut self, task: TaskData) -> Result<TaskData> {
+        Ok(task)
+    }
+
+    /// Called for each existing task modified by the committed operations, with the task before
+    /// and after the modification.  Returns the task to commit in place of `after`, which must
+    /// have the same UUID.
+    ///
+    /// Deleting a task (as opposed to setting its status to deleted) does not invoke this hook.
+    fn on_modify(&mut self, before: &TaskData, after: TaskData) -> Result<TaskData> {
+        let _ = before;
+        Ok(after)
+    }
+
+    /// Called after the operations have been committed, with each task that was added or
+    /// modified.  An error returned from this method is returned from `commit_operations`, but
+    /// the operations remain committed.
+    fn on_exit(&mut self, tasks: &[TaskData]) -> Result<()> {
+        let _ = tasks;
+        Ok(())
+    }
+}
+
+/// The hook event handled by an [`ExecutableHook`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HookEvent {
+    /// The hook is called when a task is added.
+    OnAdd,
+    /// The hook is called when a task is modified.
+    OnModify,
+    /// The hook is called after changes are committed.
+    OnExit,
+}
+
+/// A hook implemented by an external executable, compatible with [Taskwarrior
+/// hooks](https://taskwarrior.org/docs/hooks_guide/).
+///
+/// The executable is run with an `api:2` argument, and receives tasks in Taskwarrior's JSON
+/// format on stdin, one per line:
+///
+/// * An on-add hook receives the added task, and must print the task to commit on stdout.
+/// * An on-modify hook receives the original task and then the modified task, and must print
+///   the task to commit on stdout.
+/// * An on-exit hook receives each added or modified task, and its output other than feedback
+///   is ignored.
+///
+/// Lines of output not beginning with `{` are feedback.  If the executable exits with a nonzero
+/// status, the change is rejected with the feedback as the message, as an [`Error::Usage`].
+#[derive(Clone, Debug)]
+pub struct ExecutableHook {
+    event: HookEvent,
+    path: PathBuf,
+}
+
+impl ExecutableHook {
+    /// Create a hook running the given executable for the given event.
+    pub fn new(event: HookEvent, path: impl Into<PathBuf>) -> Self {
+        ExecutableHook {
+            event,
+            path: path.into(),
+        }
+    }
+
+    /// Find the hooks in the given directory, as Taskwarrior does: executable files with names
+    /// beginning with `on-add`, `on-modify`, or `on-exit`.  The hooks are returned sorted by
+    /// name, which is the order in which they should be added to the replica.
+    pub fn from_dir(dir: impl AsRef<Path>) -> Result<Vec<ExecutableHook>> {
+        let mut hooks = vec![];
+        for entry in std::fs::read_dir(dir)? {
+            let entry = entry?;
+            let name = entry.file_name();
+            let Some(name) = name.to_str() else {
+                continue;
+            };
+            let event = if name.starts_with("on-add") {
+                HookEvent::OnAdd
+            } else if name.starts_with("on-modify") {
+                HookEvent::OnModify
+            } else if name.starts_with("on-exit") {
+                HookEvent::OnExit
+            } else {
+                continue;
+            };
+            let metadata = entry.metadata()?;
+            if !metadata.is_file() || !is_executable(&metadata) {
+                continue;
+            }
+            hooks.push(ExecutableHook::new(event, entry.path()));
+        }
+        hooks.sort_by(|a, b| a.path.cmp(&b.path));
+        Ok(hooks)
+    }
+
+    /// Get the event this hook handles.
+    pub fn event(&self) -> HookEvent {
+        self.event
+    }
+
+    /// Get the path to the executable.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Run the executable with the given tasks as input, returning the tasks it outputs.
+    fn run(&self, input: &[&TaskData]) -> Result<Vec<TaskData>> {
+        let mut stdin = String::new();
+        for task in input {
+            stdin.push_str(&export_task(task, None).to_string());
+            stdin.push('\n');
+        }
+
+        let mut child = Command::new(&self.path)
+            .arg("api:2")
+            .arg(format!("version:{}", env!("CARGO_PKG_VERSION")))
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .map_err(|e| Error::Other(anyhow::anyhow!("Running hook {:?}: {}", self.path, e)))?;
+        // Write the input on another thread while reading the output, so that a hook which writes
+        // before it has read all of its input cannot fill the output pipe and block.
+        let writer = child.stdin.take().map(|mut child_stdin| {
+            std::thread::spawn(move || match child_stdin.write_all(stdin.as_bytes()) {
+                // A hook that does not read its input may exit before it is written.
+                Err(e) if e.kind() != ErrorKind::BrokenPipe => Err(e),
+                _ => Ok(()),
+            })
+        });
+        let output = child.wait_with_output()?;
+        if let Some(writer) = writer {
+            writer.join().map_err(|_| {
+                Error::Other(anyhow::anyhow!(
+                    "Writing input to hook {:?} panicked",
+                    self.path
+                ))
+            })??;
+        }
+
+        let mut tasks = vec![];
+        let mut feedback = vec![];
+        for line in String::from_utf8_lossy(&output.stdout).lines() {
+            if line.starts_with('{') {
+                let value: serde_json::Value = serde_json::from_str(line).map_err(|e| {
+                    Error::Usage(format!("Hook {:?} output invalid JSON: {}", self.path, e))
+                })?;
+                let (uuid, taskmap) = import_task(&value)?;
+                tasks.push(TaskData::new(uuid, taskmap));
+            } else if !line.trim().is_empty() {
+                feedback.push(line.to_string());
+            }
+        }
+
+        if !output.status.success() {
+            if feedback.is_empty() {
+                return Err(Error::Usage(format!(
+                    "Hook {:?} failed with {}",
+                    self.path, output.status
+                )));
+            }
+            return Err(Error::Usage(feedback.join("\n")));
+        }
+        for line in feedback {
+            info!("{}", line);
+        }
+        Ok(tasks)
+    }
+
+    /// Run the executable with the given tasks, expecting a single task in response.
+    fn run_one(&self, input: &[&TaskData]) -> Result<TaskData> {
+        let mut tasks = self.run(input)?;
+        if tasks.len() != 1 {
+            return Err(Error::Usage(format!(
+                "Hook {:?} output {} tasks; expected 1",
+                self.path,
+                tasks.len()
+            )));
+        }
+        Ok(tasks.remove(0))
+    }
+}
+
+#[cfg(unix)]
+fn is_executable(metadata: &std::fs::Metadata) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    metadata.permissions().mode() & 0o111 != 0
+}
+
+#[cfg(not(unix))]
+fn is_executable(_metadata: &std::fs::Metadata) -> bool {
+    true
+}
+
+impl Hook for ExecutableHook {
+    fn on_add(&mut self, task: TaskData) -> Result<TaskData> {
+        if self.event != HookEvent::OnAdd {
+            return Ok(task);
+        }
+        self.run_one(&[&task])
+    }
+
+    fn on_modify(&mut self, before: &TaskData, after: TaskData) -> Result<TaskData> {
+        if self.event != HookEvent::OnModify {
+            return Ok(after);
+        }
+        self.run_one(&[before, &after])
+    }
+
+    fn on_exit(&mut self, tasks: &[TaskData]) -> Result<()> {
+        if self.event != HookEvent::OnExit {
+            return Ok(());
+        }
+        self.run(&tasks.iter().collect::<Vec<_>>())?;
+        Ok(())
+    }
+}
+
+fn taskmap_of(task: &TaskData) -> TaskMap {
+    task.iter().map(|(k, v)| (k.clone(), v.clone())).collect()
+}
+
+/// Run the on-add and on-modify hooks for the tasks changed by `operations`, appending any
+/// operations required to make the changes the hooks request.  `get_task` gets the current
+/// state of a task.
+///
+/// Returns the added and modified tasks, for the on-exit hooks.
+pub(crate) fn run_hooks<F>(
+    hooks: &mut [Box<dyn Hook>],
+    operations: &mut Operations,
+    mut get_task: F,
+) -> Result<Vec<TaskData>>
+where
+    F: FnMut(Uuid) -> Result<Option<TaskMap>>,
+{
+    // Determine the state of each affected task before and after the operations, in the order
+    // the tasks first appear.
+    let mut uuids = vec![];
+    let mut states: HashMap<Uuid, (Option<TaskMap>, Option<TaskMap>)> = HashMap::new();
+    for op in operations.iter() {
+        let uuid = match op {
+            Operation::Create { uuid }
+            | Operation::Delete { uuid, .. }
+            | Operation::Update { uuid, .. } => *uuid,
+            Operation::UndoPoint => continue,
+        };
+        let (_, after) = match states.entry(uuid) {
+            Entry::Occupied(e) => e.into_mut(),
+            Entry::Vacant(e) => {
+                let before = get_task(uuid)?;
+                uuids.push(uuid);
+                e.insert((before.clone(), before))
+            }
+        };
+        match op {
+            Operation::Create { .. } => {
+                if after.is_none() {
+                    *after = Some(TaskMap::new());
+                }
+            }
+            Operation::Delete { .. } => *after = None,
+            Operation::Update {
+                property, value, ..
+            } => {
+                if let Some(after) = after {
+                    match value {
+                        Some(v) => after.insert(property.clone(), v.clone()),
+                        None => after.remove(property),
+                    };
+                }
+            }
+            Operation::UndoPoint => {}
+        }
+    }
+
+    let mut changed = vec![];
+    for uuid in uuids {
+        let (before, after) = states.remove(&uuid).unwrap();
+        let Some(after) = after else {
+            continue;
+        };
+        if before.as_ref() == Some(&after) {
+            continue;
+        }
+        let mut task = TaskData::new(uuid, after.clone());
+        let before = before.map(|b| TaskData::new(uuid, b));
+        for hook in hooks.iter_mut() {
+            task = match before {
+                None => hook.on_add(task)?,
+                Some(ref before) => hook.on_modify(before, task)?,
+            };
+            if task.get_uuid() != uuid {
+                return Err(Error::Usage(format!(
+                    "Hook changed the UUID of task {} to {}",
+                    uuid,
+                    task.get_uuid()
+                )));
+            }
+        }
+
+        // Add operations for any changes made by the hooks.
+        let rewritten = taskmap_of(&task);
+        let mut props: Vec<_> = after.keys().chain(rewritten.keys()).collect();
+        props.sort();
+        props.dedup();
+        for property in props {
+            let old_value = after.get(property);
+            let value = rewritten.get(property);
+            if old_value != value {
+                operations.push(Operation::Update {
+                    uuid,
+                    property: property.clone(),
+                    old_value: old_value.cloned(),
+                    value: value.cloned(),
+                    timestamp: Utc::now(),
+                });
+            }
+        }
+        changed.push(task);
+    }
+    Ok(changed)
+}
+
+/// Run the on-exit hooks, if any tasks changed.
+pub(crate) fn run_exit_hooks(hooks: &mut [Box<dyn Hook>], tasks: &[TaskData]) -> Result<()> {
+    if tasks.is_empty() {
+        return Ok(());
+    }
+    for hook in hooks.iter_mut() {
+        hook.on_exit(tasks)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use tempfile::TempDir;
+
+    /// A hook that sets "tagged" on added tasks and rejects modifications to "locked" tasks.
+    struct TestHook;
+
+    impl Hook for TestHook {
+        fn on_add(&mut self, mut task: TaskData) -> Result<TaskData> {
+            task.update("tagged", Some("yes".into()), &mut Operations::new());
+            Ok(task)
+        }
+
+        fn on_modify(&mut self, before: &TaskData, after: TaskData) -> Result<TaskData> {
+            if before.has("locked") {
+                return Err(Error::Usage("task is locked".into()));
+            }
+            Ok(after)
+        }
+    }
+
+    fn update(uuid: Uuid, property: &str, value: Option<&str>) -> Operation {
+        Operation::Update {
+            uuid,
+            property: property.into(),
+            old_value: None,
+            value: value.map(String::from),
+            timestamp: Utc::now(),
+        }
+    }
+
+    fn hooks() -> Vec<Box<dyn Hook>> {
+        vec![Box::new(TestHook)]
+    }
+
+    #[test]
+    fn run_hooks_on_add() -> Result<()> {
+        let uuid = Uuid::new_v4();
+        let mut ops = vec![Operation::Create { uuid }, update(uuid, "title", Some("x"))];
+        let changed = run_hooks(&mut hooks(), &mut ops, |_| Ok(None))?;
+        assert_eq!(changed.len(), 1);
+        assert_eq!(changed[0].get("tagged"), Some("yes"));
+        assert_eq!(ops.len(), 3);
+        let Operation::Update {
+            property, value, ..
+        } = &ops[2]
+        else {
+            panic!("expected Update, got {:?}", ops[2]);
+        };
+        assert_eq!(property, "tagged");
+        assert_eq!(value.as_deref(), Some("yes"));
+        Ok(())
+    }
+
+    #[test]
+    fn run_hooks_on_modify_rejected() {
+        let uuid = Uuid::new_v4();
+        let before: TaskMap = [("locked".to_string(), "1".to_string())].into();
+        let mut ops = vec![update(uuid, "title", Some("x"))];
+        let err = run_hooks(&mut hooks(), &mut ops, |_| Ok(Some(before.clone()))).unwrap_err();
+        assert!(matches!(err, Error::Usage(msg) if msg == "task is locked"));
+        assert_eq!(ops.len(), 1);
+    }
+
+    #[test]
+    fn run_hooks_unchanged_or_deleted() -> Result<()> {
+        let uuid1 = Uuid::new_v4();
+        let uuid2 = Uuid::new_v4();
+        let before: TaskMap = [
+            ("locked".to_string(), "1".to_string()),
+            ("title".to_string(), "x".to_string()),
+        ]
+        .into();
+        let mut ops = vec![
+            Operation::UndoPoint,
+            update(uuid1, "title", Some("x")),
+            Operation::Delete {
+                uuid: uuid2,
+                old_task: before.clone(),
+            },
+        ];
+        let changed = run_hooks(&mut hooks(), &mut ops, |_| Ok(Some(before.clone())))?;
+        assert!(changed.is_empty());
+        assert_eq!(ops.len(), 3);
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    fn write_hook(dir: &TempDir, name: &str, script: &str) -> PathBuf {
+        use std::os::unix::fs::PermissionsExt;
+        let path = dir.path().join(name);
+        std::fs::write(&path, format!("#!/bin/sh\n{}", script)).unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        path
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn from_dir() -> Result<()> {
+        let dir = TempDir::new()?;
+        write_hook(&dir, "on-modify.b", "");
+        write_hook(&dir, "on-add.a", "");
+        write_hook(&dir, "on-exit", "");
+        write_hook(&dir, "README", "");
+        std::fs::write(dir.path().join("on-add.disabled"), "")?;
+        let hooks: Vec<_> = ExecutableHook::from_dir(dir.path())?
+            .into_iter()
+            .map(|h| (h.event(), h.path().file_name().unwrap().to_owned()))
+            .collect();
+        assert_eq!(
+            hooks,
+            vec![
+                (HookEvent::OnAdd, "on-add.a".into()),
+                (HookEvent::OnExit, "on-exit".into()),
+                (HookEvent::OnModify, "on-modify.b".into()),
+            ]
+        );
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn executable_on_add_passthrough() -> Result<()> {
+        let dir = TempDir::new()?;
+        let path = write_hook(&dir, "on-add", "read task\necho \"$task\"\necho added\n");
+        let mut hook = ExecutableHook::new(HookEvent::OnAdd, path);
+        let mut task = TaskData::create(Uuid::new_v4(), &mut Operations::new());
+        task.update("description", Some("hello".into()), &mut Operations::new());
+        task.update("entry", Some("1700000000".into()), &mut Operations::new());
+        task.update("tag_next", Some("".into()), &mut Operations::new());
+        let result = hook.on_add(task.clone())?;
+        assert_eq!(taskmap_of(&result), taskmap_of(&task));
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn executable_on_modify_rewrites() -> Result<()> {
+        let dir = TempDir::new()?;
+        let path = write_hook(
+            &dir,
+            "on-modify",
+            "read before\nread after\necho \"$after\" | sed 's/\"description\":\"[^\"]*\"/\"description\":\"rewritten\"/'\n",
+        );
+        let mut hook = ExecutableHook::new(HookEvent::OnModify, path);
+        let uuid = Uuid::new_v4();
+        let before = TaskData::new(uuid, [("description".into(), "a".into())].into());
+        let after = TaskData::new(uuid, [("description".into(), "b".into())].into());
+        let result = hook.on_modify(&before, after)?;
+        assert_eq!(result.get("description"), Some("rewritten"));
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn executable_large_output_before_input() -> Result<()> {
+        let dir = TempDir::new()?;
+        // the output and input are each larger than a pipe buffer
+        let path = write_hook(
+            &dir,
+            "on-exit",
+            "head -c 200000 /dev/zero | tr '\\0' x\necho\ncat >/dev/null\n",
+        );
+        let mut hook = ExecutableHook::new(HookEvent::OnExit, path);
+        let tasks: Vec<_> = (0..200)
+            .map(|_| {
+                TaskData::new(
+                    Uuid::new_v4(),
+                    [("description".into(), "x".repeat(1000))].into(),
+                )
+            })
+            .collect();
+        hook.on_exit(&tasks)?;
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn executable_rejects() -> Result<()> {
+        let dir = TempDir::new()?;
+        let path = write_hook(&dir, "on-add", "echo 'No tasks on Mondays'\nexit 1\n");
+        let mut hook = ExecutableHook::new(HookEvent::OnAdd, path);
+        let task = TaskData::new(Uuid::new_v4(), TaskMap::new());
+        let err = hook.on_add(task).unwrap_err();
+        assert!(matches!(err, Error::Usage(msg) if msg == "No tasks on Mondays"));
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn executable_wrong_event_not_run() -> Result<()> {
+        let dir = TempDir::new()?;
+        let path = write_hook(&dir, "on-exit", "exit 1\n");
+        let mut hook = ExecutableHook::new(HookEvent::OnExit, path);
+        let task = TaskData::new(Uuid::new_v4(), TaskMap::new());
+        hook.on_add(task.clone())?;
+        assert!(hook.on_exit(&[task]).is_err());
+        Ok(())
+    }
+}
@@ -18,6 +18,9 @@ synchronize with one another.
 
 Replicas are accessed using the [`Replica`] type.
 
+A `Replica` can be moved between threads, but not shared between them.  To share access to a
+replica between threads, such as in a multi-threaded or async server, use [`SharedReplica`].
+
 # Task Storage
 
 Replicas access the task database via a [storage object](crate::storage::Storage).
@@ -31,6 +34,12 @@ An implementation is provided, but users of this crate can provide their own imp
 Replica synchronization takes place against a server.
 Create a server with [`ServerConfig`].
 
+Applications running in an async runtime such as Tokio can sync without blocking, using
+[`ServerConfig::into_async_server`] and [`Replica::sync_async`].
+
+To summarize what a sync did, use [`Replica::sync_with_report`].  To report the progress of a
+sync, or to cancel it, use [`Replica::sync_with_options`].
+
 The [`server`] module defines the interface a server must meet.
 Users can define their own server impelementations.
 
@@ -38,8 +47,11 @@ Users can define their own server impelementations.
 
 Support for some optional functionality is controlled by feature flags.
 
+ * `server-aws` - sync to Amazon Web Services S3, or an S3-compatible service
+ * `server-directory` - sync via a directory on a shared filesystem
  * `server-gcp` - sync to Google Cloud Platform
  * `server-sync` - sync to the taskchampion-sync-server
+ * `server-webdav` - sync to a WebDAV share, such as a Nextcloud folder
  * `sync` - enables all of the sync features above
  * `bundled` - activates bundling system libraries like sqlite
 
@@ -55,24 +67,48 @@ for more information about the design and usage of the tool.
 This crate supports Rust version 1.73.0 and higher.
 
  */
+mod changes;
 mod depmap;
 mod errors;
+mod filter;
+mod hooks;
+mod merge;
 mod operation;
 mod replica;
 pub mod server;
+mod shared_replica;
 pub mod storage;
+mod sync_options;
+mod sync_report;
 mod task;
 mod taskdb;
+mod undo;
+mod urgency;
 mod utils;
 mod workingset;
 
+pub use changes::{
+    ChangeKind, ChangeOrigin, HistoryEntry, SubscriptionId, SyncConflict, TaskChange,
+};
 pub use depmap::DependencyMap;
 pub use errors::Error;
+pub use filter::Filter;
+pub use hooks::{ExecutableHook, Hook, HookEvent};
+pub use merge::MergeStrategy;
 pub use operation::{Operation, Operations};
 pub use replica::Replica;
+#[cfg(feature = "server-aws")]
+pub use server::AwsCredentials;
 pub use server::{Server, ServerConfig};
+pub use shared_replica::SharedReplica;
 pub use storage::StorageConfig;
-pub use task::{utc_timestamp, Annotation, Status, Tag, Task, TaskData};
+pub use sync_options::{CancellationToken, SyncOptions, SyncProgress};
+pub use sync_report::SyncReport;
+pub use task::{
+    utc_timestamp, Annotation, Recurrence, RecurrencePeriod, Status, Tag, Task, TaskData,
+};
+pub use undo::{UndoStep, UndoTaskSummary};
+pub use urgency::Urgency;
 pub use workingset::WorkingSet;
 
 /// Re-exported type from the `uuid` crate, for ease of compatibility for consumers of this crate.
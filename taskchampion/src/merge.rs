@@ -0,0 +1,179 @@
+use std::collections::HashMap;
+
+/// The merge strategy configured for each property, where not the default.
+pub(crate) type MergeStrategies = HashMap<String, MergeStrategy>;
+
+/// How concurrent updates to a task property are combined during sync, as configured with
+/// [`Replica::set_merge_strategy`](crate::Replica::set_merge_strategy).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum MergeStrategy {
+    /// The later update takes precedence, and the earlier update is discarded.
+    #[default]
+    LastWriterWins,
+    /// Combine the updates with a three-way merge of the text, word by word, using the value
+    /// before the local update as the common ancestor.  If the updates change the same part of
+    /// the text, or either update removes the property, the later update takes precedence as for
+    /// [`MergeStrategy::LastWriterWins`].
+    ///
+    /// This is suited to long text properties, such as `description`.
+    TextMerge,
+}
+
+/// The largest product of token counts for which a merge is attempted, bounding the time and
+/// memory used to compare the texts.
+const MAX_MERGE_SIZE: usize = 1 << 22;
+
+/// Perform a three-way merge of `local` and `remote`, both derived from `ancestor`.  Returns None
+/// if the changes conflict.
+pub(crate) fn merge_text(ancestor: &str, local: &str, remote: &str) -> Option<String> {
+    let ancestor = tokenize(ancestor);
+    let local = tokenize(local);
+    let remote = tokenize(remote);
+    let local_matches = matches(&ancestor, &local)?;
+    let remote_matches = matches(&ancestor, &remote)?;
+
+    let mut merged = vec![];
+    let (mut i, mut l, mut r) = (0, 0, 0);
+    loop {
+        // copy tokens unchanged on both sides
+        while i < ancestor.len() && local_matches[i] == Some(l) && remote_matches[i] == Some(r) {
+            merged.push(ancestor[i]);
+            i += 1;
+            l += 1;
+            r += 1;
+        }
+
+        // find the end of the changed chunk: the next token unchanged on both sides
+        let mut k = i;
+        while k < ancestor.len() && (local_matches[k].is_none() || remote_matches[k].is_none()) {
+            k += 1;
+        }
+        let (l_end, r_end) = if k < ancestor.len() {
+            (local_matches[k].unwrap(), remote_matches[k].unwrap())
+        } else {
+            (local.len(), remote.len())
+        };
+
+        let (a, lc, rc) = (&ancestor[i..k], &local[l..l_end], &remote[r..r_end]);
+        if lc == a || lc == rc {
+            merged.extend_from_slice(rc);
+        } else if rc == a {
+            merged.extend_from_slice(lc);
+        } else {
+            return None;
+        }
+
+        if k == ancestor.len() {
+            return Some(merged.concat());
+        }
+        (i, l, r) = (k, l_end, r_end);
+    }
+}
+
+/// Split text into words and runs of whitespace, so that joining the tokens gives the original
+/// text.
+fn tokenize(text: &str) -> Vec<&str> {
+    let mut tokens = vec![];
+    let mut start = 0;
+    let mut prev_ws = None;
+    for (i, c) in text.char_indices() {
+        let ws = c.is_whitespace();
+        if prev_ws.is_some_and(|p| p != ws) {
+            tokens.push(&text[start..i]);
+            start = i;
+        }
+        prev_ws = Some(ws);
+    }
+    if start < text.len() {
+        tokens.push(&text[start..]);
+    }
+    tokens
+}
+
+/// Find a longest common subsequence of `base` and `other`, returning for each token in `base`
+/// the index of the matching token in `other`, if any.  Returns None if the texts are too large
+/// to compare.
+fn matches(base: &[&str], other: &[&str]) -> Option<Vec<Option<usize>>> {
+    let (n, m) = (base.len(), other.len());
+    if n.saturating_mul(m) > MAX_MERGE_SIZE {
+        return None;
+    }
+    // lengths[i][j] is the length of the LCS of base[i..] and other[j..]
+    let mut lengths = vec![vec![0usize; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lengths[i][j] = if base[i] == other[j] {
+                lengths[i + 1][j + 1] + 1
+            } else {
+                lengths[i + 1][j].max(lengths[i][j + 1])
+            };
+        }
+    }
+    let mut result = vec![None; n];
+    let (mut i, mut j) = (0, 0);
+    while i < n && j < m {
+        if base[i] == other[j] {
+            result[i] = Some(j);
+            i += 1;
+            j += 1;
+        } else if lengths[i + 1][j] >= lengths[i][j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    Some(result)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use rstest::rstest;
+
+    #[test]
+    fn tokenize_round_trip() {
+        let text = "  buy  milk\nand eggs ";
+        let tokens = tokenize(text);
+        assert_eq!(
+            tokens,
+            vec!["  ", "buy", "  ", "milk", "\n", "and", " ", "eggs", " "]
+        );
+        assert_eq!(tokens.concat(), text);
+        assert_eq!(tokenize(""), Vec::<&str>::new());
+    }
+
+    #[rstest]
+    #[case::unchanged("a b c", "a b c", "a b c", Some("a b c"))]
+    #[case::local_only("a b c", "a B c", "a b c", Some("a B c"))]
+    #[case::remote_only("a b c", "a b c", "a b C", Some("a b C"))]
+    #[case::both(
+        "buy milk",
+        "buy oat milk",
+        "buy milk today",
+        Some("buy oat milk today")
+    )]
+    #[case::same_change("a b c", "a x c", "a x c", Some("a x c"))]
+    #[case::insert_and_delete("a b c d", "a b c", "x a b c d", Some("x a b c"))]
+    #[case::from_empty("", "local", "", Some("local"))]
+    #[case::conflict("a b c", "a x c", "a y c", None)]
+    #[case::conflicting_inserts("a c", "a x c", "a y c", None)]
+    fn merge(
+        #[case] ancestor: &str,
+        #[case] local: &str,
+        #[case] remote: &str,
+        #[case] expected: Option<&str>,
+    ) {
+        assert_eq!(
+            merge_text(ancestor, local, remote),
+            expected.map(String::from)
+        );
+    }
+
+    #[test]
+    fn merge_too_large() {
+        let text = "word ".repeat(3000);
+        assert_eq!(merge_text(&text, &text, &text), None);
+    }
+}
@@ -1,17 +1,31 @@
+use crate::changes::{
+    local_changes, reversed_changes, HistoryEntry, SubscriptionId, SyncConflict, TaskChange,
+};
 use crate::depmap::DependencyMap;
 use crate::errors::Result;
+use crate::filter::Filter;
+use crate::hooks::{run_exit_hooks, run_hooks, Hook};
+use crate::merge::MergeStrategy;
 use crate::operation::{Operation, Operations};
-use crate::server::Server;
+use crate::server::{AsyncServer, Server};
 use crate::storage::{Storage, TaskMap};
-use crate::task::{Status, Task};
+use crate::sync_options::SyncOptions;
+use crate::sync_report::SyncReport;
+use crate::task::{
+    begin_vcalendar, end_vcalendar, export_task, export_vtodo, import_task, import_vtodos,
+    Recurrence, Status, Task, Timestamp,
+};
 use crate::taskdb::TaskDb;
+use crate::undo::UndoStep;
 use crate::workingset::WorkingSet;
 use crate::{Error, TaskData};
 use anyhow::Context;
 use chrono::{Duration, Utc};
-use log::trace;
+use log::{trace, warn};
+use std::collections::hash_map::Entry;
 use std::collections::HashMap;
-use std::rc::Rc;
+use std::io::{Read, Write};
+use std::sync::Arc;
 use uuid::Uuid;
 
 /// A replica represents an instance of a user's task data, providing an easy interface
@@ -30,6 +44,28 @@ use uuid::Uuid;
 /// specifically pending tasks.  These are indexed with small, easy-to-type integers.  Newly
 /// pending tasks are automatically added to the working set, and the working set can be
 /// "renumbered" when necessary.
+///
+/// ## Change Notification
+///
+/// Observers registered with [`Replica::subscribe`] are called with the changes made to tasks
+/// each time changes are committed locally or applied from a sync.
+///
+/// ## Hooks
+///
+/// Hooks registered with [`Replica::add_hook`] can inspect, rewrite, or reject changes made by
+/// [`Replica::commit_operations`], similar to Taskwarrior's hooks.
+///
+/// ## Undo and Redo
+///
+/// Local changes that have not yet been synchronized can be undone, one step at a time, where
+/// steps are separated by [`Operation::UndoPoint`]. [`Replica::undo_steps`] summarizes the
+/// available steps, suitable for display in an undo menu, and [`Replica::undo`] undoes the most
+/// recent step.  Undone steps can be re-applied with [`Replica::redo`], until other changes are
+/// committed.
+///
+/// Once local changes are synchronized, they can no longer be removed from the operation history.
+/// Instead, [`Replica::undo_synced`] reverts the most recent synchronized step by committing new
+/// compensating operations, which are then synchronized to other replicas like any other change.
 pub struct Replica {
     taskdb: TaskDb,
 
@@ -37,15 +73,33 @@ pub struct Replica {
     added_undo_point: bool,
 
     /// The dependency map for this replica, if it has been calculated.
-    depmap: Option<Rc<DependencyMap>>,
+    depmap: Option<Arc<DependencyMap>>,
+
+    /// Observers registered with `subscribe`, in the order they were registered.
+    observers: Vec<(SubscriptionId, Observer)>,
+
+    /// The ID to assign to the next observer.
+    next_subscription_id: u64,
+
+    /// Hooks registered with `add_hook`, in the order they were registered.
+    hooks: Vec<Box<dyn Hook>>,
+
+    /// Operations undone by `commit_reversed_operations`, most recent last, which can be redone.
+    redo_stack: Vec<Operations>,
 }
 
+type Observer = Box<dyn FnMut(&[TaskChange]) + Send>;
+
 impl Replica {
     pub fn new(storage: Box<dyn Storage>) -> Replica {
         Replica {
             taskdb: TaskDb::new(storage),
             added_undo_point: false,
             depmap: None,
+            observers: Vec::new(),
+            next_subscription_id: 0,
+            hooks: Vec::new(),
+            redo_stack: Vec::new(),
         }
     }
 
@@ -92,6 +146,15 @@ impl Replica {
         Ok(res)
     }
 
+    /// Get all tasks matching the given filter, represented as a map keyed by UUID.
+    ///
+    /// This evaluates the filter against every task in the replica.
+    pub fn query(&mut self, filter: &Filter) -> Result<HashMap<Uuid, Task>> {
+        let mut res = self.all_tasks()?;
+        res.retain(|_, t| filter.matches(t));
+        Ok(res)
+    }
+
     /// Get all task represented as a map of [`TaskData`] keyed by UUID
     pub fn all_task_data(&mut self) -> Result<HashMap<Uuid, TaskData>> {
         let mut res = HashMap::new();
@@ -126,7 +189,7 @@ impl Replica {
     ///
     /// Calculating this value requires a scan of the full working set and may not be performant.
     /// The [`TaskData`] API avoids generating this value.
-    pub fn dependency_map(&mut self, force: bool) -> Result<Rc<DependencyMap>> {
+    pub fn dependency_map(&mut self, force: bool) -> Result<Arc<DependencyMap>> {
         if force || self.depmap.is_none() {
             // note: we can't use self.get_task here, as that depends on a
             // DependencyMap
@@ -178,13 +241,22 @@ impl Replica {
                     }
                 }
             }
-            self.depmap = Some(Rc::new(dm));
+            self.depmap = Some(Arc::new(dm));
         }
 
         // at this point self.depmap is guaranteed to be Some(_)
         Ok(self.depmap.as_ref().unwrap().clone())
     }
 
+    /// Discard any state cached from storage, such as the dependency map, so that it is
+    /// re-calculated when next required.  This is necessary when the storage may have been
+    /// modified other than through this instance.  The redo stack is also cleared, as the
+    /// operations it holds may no longer apply to the modified storage.
+    pub(crate) fn reset_caches(&mut self) {
+        self.depmap = None;
+        self.redo_stack.clear();
+    }
+
     /// Get an existing task by its UUID
     pub fn get_task(&mut self, uuid: Uuid) -> Result<Option<Task>> {
         let depmap = self.dependency_map(false)?;
@@ -274,10 +346,28 @@ impl Replica {
     ///
     /// All local state on the replica will be updated accordingly, including the working set and
     /// and temporarily cached data.
-    pub fn commit_operations(&mut self, operations: Operations) -> Result<()> {
+    ///
+    /// If any hooks are registered, they are called for each added or modified task before the
+    /// operations are committed, and may add further operations or reject the change. See
+    /// [`Replica::add_hook`].
+    pub fn commit_operations(&mut self, mut operations: Operations) -> Result<()> {
         if operations.is_empty() {
             return Ok(());
         }
+        self.redo_stack.clear();
+        let hooked_tasks = if self.hooks.is_empty() {
+            vec![]
+        } else {
+            let taskdb = &mut self.taskdb;
+            run_hooks(&mut self.hooks, &mut operations, |uuid| {
+                taskdb.get_task(uuid)
+            })?
+        };
+        let changes = if self.observers.is_empty() {
+            vec![]
+        } else {
+            local_changes(&operations)
+        };
 
         // Add tasks to the working set when the status property is updated from anything other
         // than pending or recurring to one of those two statuses.
@@ -306,7 +396,8 @@ impl Replica {
         // will continue to use the old map.
         self.depmap = None;
 
-        Ok(())
+        self.notify(&changes);
+        run_exit_hooks(&mut self.hooks, &hooked_tasks)
     }
 
     /// Synchronize this replica against the given server.  The working set is rebuilt after
@@ -320,11 +411,146 @@ impl Replica {
     /// Set this to true on systems more constrained in CPU, memory, or bandwidth than a typical desktop
     /// system
     pub fn sync(&mut self, server: &mut Box<dyn Server>, avoid_snapshots: bool) -> Result<()> {
-        self.taskdb
-            .sync(server, avoid_snapshots)
-            .context("Failed to synchronize with server")?;
+        self.sync_with_report(server, avoid_snapshots)?;
+        Ok(())
+    }
+
+    /// Synchronize this replica against the given server, as for [`Replica::sync`], returning a
+    /// summary of what the sync did.
+    pub fn sync_with_report(
+        &mut self,
+        server: &mut Box<dyn Server>,
+        avoid_snapshots: bool,
+    ) -> Result<SyncReport> {
+        self.sync_with_options(server, SyncOptions::new().avoid_snapshots(avoid_snapshots))
+    }
+
+    /// Synchronize this replica against the given server, as for [`Replica::sync_with_report`],
+    /// with the given options.  These allow the caller to observe the progress of the sync, and
+    /// to cancel it.
+    ///
+    /// If the sync is cancelled, this returns [`Error::Cancelled`].  Versions from the server
+    /// that were applied before the cancellation are kept, but the replica is otherwise unchanged.
+    pub fn sync_with_options(
+        &mut self,
+        server: &mut Box<dyn Server>,
+        mut options: SyncOptions<'_>,
+    ) -> Result<SyncReport> {
+        let mut changes = vec![];
+        let res = self
+            .taskdb
+            .sync_with_options(server, &mut options, &mut changes);
+        self.finish_sync(res, changes)
+    }
+
+    /// Synchronize this replica against the given asynchronous server.  This is the same as
+    /// [`Replica::sync`], but does not block while waiting for the server, making it suitable
+    /// for use in an async application.  Storage access is still blocking.
+    ///
+    /// The returned future is `Send`, so it may be spawned on a multi-threaded executor.  Use
+    /// [`ServerConfig::into_async_server`](crate::ServerConfig::into_async_server) to create the
+    /// server.
+    pub async fn sync_async(
+        &mut self,
+        server: &mut Box<dyn AsyncServer>,
+        avoid_snapshots: bool,
+    ) -> Result<()> {
+        self.sync_async_with_options(server, SyncOptions::new().avoid_snapshots(avoid_snapshots))
+            .await?;
+        Ok(())
+    }
+
+    /// Synchronize this replica against the given asynchronous server, as for
+    /// [`Replica::sync_async`], with the given options, returning a summary of what the sync did.
+    /// See [`Replica::sync_with_options`].
+    pub async fn sync_async_with_options(
+        &mut self,
+        server: &mut Box<dyn AsyncServer>,
+        mut options: SyncOptions<'_>,
+    ) -> Result<SyncReport> {
+        let mut changes = vec![];
+        let res = self
+            .taskdb
+            .sync_async_with_options(server.as_mut(), &mut options, &mut changes)
+            .await;
+        self.finish_sync(res, changes)
+    }
+
+    /// Change the secret used to encrypt the data on the given server, which must have been
+    /// created with `old_secret`.
+    ///
+    /// This first synchronizes with the server, and then sends it a snapshot of the replica
+    /// encrypted with `new_secret`.  If the replica or the server changes in the interim, this
+    /// synchronizes and tries again.  Once this completes, `server` uses the new secret, and
+    /// should be re-created with `new_secret` in future.
+    ///
+    /// Other replicas must then be given the new secret.  Until they are, their syncs fail with
+    /// [`Error::WrongEncryptionSecret`].  A replica that did not synchronize with the old secret
+    /// before the change may need to be re-created, beginning from the new snapshot, as the
+    /// versions it is missing cannot be decrypted with the new secret.
+    ///
+    /// Servers that do not encrypt data return [`Error::Usage`].
+    pub fn rotate_encryption_secret(
+        &mut self,
+        server: &mut Box<dyn Server>,
+        old_secret: &[u8],
+        new_secret: &[u8],
+    ) -> Result<()> {
+        loop {
+            self.sync(server, false)?;
+            if self
+                .taskdb
+                .rotate_encryption_secret(server, old_secret, new_secret)?
+            {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Set the strategy used to combine concurrent updates to the given property, made on this
+    /// replica and another, when they are synchronized.  By default, the later update takes
+    /// precedence.
+    ///
+    /// The strategy applies when this replica resolves the conflict, which occurs when it
+    /// synchronizes after the other replica.  The strategy is held in this `Replica` instance,
+    /// and is not retained in storage.
+    pub fn set_merge_strategy(&mut self, property: impl Into<String>, strategy: MergeStrategy) {
+        self.taskdb.set_merge_strategy(property.into(), strategy);
+    }
+
+    /// Update this replica's state after a sync with the given result, which made the given
+    /// changes.  A failed sync may still have made changes, which must be reflected here.
+    fn finish_sync(
+        &mut self,
+        res: Result<SyncReport>,
+        changes: Vec<TaskChange>,
+    ) -> Result<SyncReport> {
+        match res {
+            Ok(report) => {
+                self.after_sync(changes)?;
+                Ok(report)
+            }
+            Err(err) => {
+                if !changes.is_empty() {
+                    if let Err(e) = self.after_sync(changes) {
+                        warn!("Could not update replica after failed sync: {}", e);
+                    }
+                }
+                Err(sync_error(err))
+            }
+        }
+    }
+
+    /// Update this replica's state after a sync made the given changes.
+    fn after_sync(&mut self, changes: Vec<TaskChange>) -> Result<()> {
+        if !changes.is_empty() {
+            // Redoing undone changes on top of remote changes could give surprising results.
+            self.redo_stack.clear();
+            self.depmap = None;
+        }
         self.rebuild_working_set(false)
             .context("Failed to rebuild working set after sync")?;
+        self.notify(&changes);
         Ok(())
     }
 
@@ -342,19 +568,221 @@ impl Replica {
     ///
     /// This method only supports reversing operations if they precisely match local operations
     /// that have not yet been synchronized, and will return `false` if this is not the case.
+    ///
+    /// The reversed operations can subsequently be re-applied with [`Replica::redo`].
     pub fn commit_reversed_operations(&mut self, operations: Operations) -> Result<bool> {
-        if !self.taskdb.commit_reversed_operations(operations)? {
+        let changes = if self.observers.is_empty() {
+            vec![]
+        } else {
+            reversed_changes(&operations)
+        };
+        if !self.taskdb.commit_reversed_operations(operations.clone())? {
             return Ok(false);
         }
+        self.redo_stack.push(operations);
 
         // Both the dependency map and the working set are potentially now invalid.
         self.depmap = None;
         self.rebuild_working_set(false)
             .context("Failed to rebuild working set after committing reversed operations")?;
 
+        self.notify(&changes);
+        Ok(true)
+    }
+
+    /// Undo the most recent step, as described by the first element of
+    /// [`Replica::undo_steps`].  Returns false if there is nothing to undo.
+    pub fn undo(&mut self) -> Result<bool> {
+        let operations = self.get_undo_operations()?;
+        if operations.is_empty() {
+            return Ok(false);
+        }
+        self.commit_reversed_operations(operations)
+    }
+
+    /// Summarize the steps that can be undone, most recent first.  Calling [`Replica::undo`]
+    /// `n` times undoes the first `n` of these steps.
+    pub fn undo_steps(&mut self) -> Result<Vec<UndoStep>> {
+        self.taskdb.undo_steps()
+    }
+
+    /// Revert the most recent local step that has already been synchronized, as described by the
+    /// first element of [`Replica::synced_undo_steps`], by committing new operations that reverse
+    /// its effects.  Returns false if there is no such step.
+    ///
+    /// Changes that have been superseded since the step was made, such as a property that has
+    /// since been updated again, are not reverted.  The compensating operations form a new undo
+    /// step, so they can be undone with [`Replica::undo`] until they are synchronized.
+    ///
+    /// Only the most recent synchronized steps are retained, and only by storage implementations
+    /// that support it (see [`StorageTxn::synced_operations`]).
+    ///
+    /// [`StorageTxn::synced_operations`]: crate::storage::StorageTxn::synced_operations
+    pub fn undo_synced(&mut self) -> Result<bool> {
+        let undo_ops = self.taskdb.get_synced_undo_operations()?;
+        if undo_ops.is_empty() {
+            return Ok(false);
+        }
+        let operations = self.taskdb.compensating_operations(&undo_ops)?;
+        // The first operation is always an undo point; omit it if there is nothing else.
+        if operations.len() > 1 {
+            self.commit_operations(operations)?;
+        }
+        self.taskdb.drop_synced_undo_operations(&undo_ops)?;
         Ok(true)
     }
 
+    /// Summarize the synchronized steps that can be reverted with [`Replica::undo_synced`], most
+    /// recent first.
+    pub fn synced_undo_steps(&mut self) -> Result<Vec<UndoStep>> {
+        self.taskdb.synced_undo_steps()
+    }
+
+    /// Re-apply the most recently undone step, as described by the first element of
+    /// [`Replica::redo_steps`].  Returns false if there is nothing to redo.
+    ///
+    /// Steps can be redone until other changes are committed, or remote changes are applied
+    /// in a sync.  The redo stack is held in this `Replica` instance, and is not retained in
+    /// storage.
+    pub fn redo(&mut self) -> Result<bool> {
+        let Some(operations) = self.redo_stack.pop() else {
+            return Ok(false);
+        };
+        // Give the re-applied updates a current timestamp, so that they take precedence over
+        // any changes made in the interim when synchronizing.
+        let now = Utc::now();
+        let redo_operations = operations
+            .iter()
+            .cloned()
+            .map(|mut op| {
+                if let Operation::Update { timestamp, .. } = &mut op {
+                    *timestamp = now;
+                }
+                op
+            })
+            .collect();
+        // commit_operations clears the redo stack, so preserve it.
+        let mut redo_stack = std::mem::take(&mut self.redo_stack);
+        let res = self.commit_operations(redo_operations);
+        if res.is_err() {
+            redo_stack.push(operations);
+        }
+        self.redo_stack = redo_stack;
+        res.map(|_| true)
+    }
+
+    /// Summarize the steps that can be redone, most recent first.
+    pub fn redo_steps(&mut self) -> Result<Vec<UndoStep>> {
+        let taskdb = &mut self.taskdb;
+        self.redo_stack
+            .iter()
+            .rev()
+            .map(|ops| UndoStep::summarize(ops, |uuid| taskdb.get_task(uuid)))
+            .collect()
+    }
+
+    /// Get the recorded history of the given task, oldest first, including both local changes and
+    /// changes applied during a sync.
+    ///
+    /// History is only recorded if enabled in the storage, such as with
+    /// [`SqliteStorage::enable_history`](crate::storage::SqliteStorage::enable_history), and
+    /// only for changes made after it is enabled.  Otherwise, this returns an empty list.
+    pub fn task_history(&mut self, uuid: Uuid) -> Result<Vec<HistoryEntry>> {
+        self.taskdb.task_history(uuid)
+    }
+
+    /// Get the updates that were overridden by conflicting updates to the same property during
+    /// sync, oldest first.
+    ///
+    /// Conflicts are recorded until removed with [`Replica::reapply_sync_conflict`] or
+    /// [`Replica::clear_sync_conflicts`], and only by storage implementations that support it
+    /// (see [`StorageTxn::conflicts`]).
+    ///
+    /// [`StorageTxn::conflicts`]: crate::storage::StorageTxn::conflicts
+    pub fn sync_conflicts(&mut self) -> Result<Vec<SyncConflict>> {
+        self.taskdb.sync_conflicts()
+    }
+
+    /// Re-apply the overridden update described by the given conflict, by committing a new
+    /// update setting the property to the losing value, and remove the conflict from those
+    /// recorded.  The new update has a current timestamp, so it takes precedence over the
+    /// previous winner when synchronized.
+    ///
+    /// Returns false, after removing the conflict, if the task no longer exists.
+    pub fn reapply_sync_conflict(&mut self, conflict: &SyncConflict) -> Result<bool> {
+        if !self.sync_conflicts()?.contains(conflict) {
+            return Err(Error::Usage(format!(
+                "No conflict recorded for property {} of task {}",
+                conflict.property, conflict.uuid
+            )));
+        }
+        if let Some(mut task) = self.get_task_data(conflict.uuid)? {
+            let mut ops = Operations::new();
+            ops.push(Operation::UndoPoint);
+            task.update(&conflict.property, conflict.losing_value.clone(), &mut ops);
+            self.commit_operations(ops)?;
+            self.taskdb.remove_sync_conflict(conflict)?;
+            Ok(true)
+        } else {
+            self.taskdb.remove_sync_conflict(conflict)?;
+            Ok(false)
+        }
+    }
+
+    /// Remove all conflicts recorded during sync, without re-applying them.
+    pub fn clear_sync_conflicts(&mut self) -> Result<()> {
+        self.taskdb.clear_sync_conflicts()
+    }
+
+    /// Register an observer to be called with the changes made to tasks in this replica.
+    ///
+    /// The observer is called once for each successful call to [`Replica::commit_operations`],
+    /// [`Replica::commit_reversed_operations`], or [`Replica::sync`] that changes any tasks, with
+    /// the changes in the order they were made.  Local changes are reported as described by the
+    /// committed operations.  Remote changes are those actually applied to this replica during a
+    /// sync, after resolving any conflicts with local changes.
+    ///
+    /// Observers are not called when the replica is changed by other `Replica` instances with
+    /// the same storage.
+    pub fn subscribe<F>(&mut self, observer: F) -> SubscriptionId
+    where
+        F: FnMut(&[TaskChange]) + Send + 'static,
+    {
+        let id = SubscriptionId(self.next_subscription_id);
+        self.next_subscription_id += 1;
+        self.observers.push((id, Box::new(observer)));
+        id
+    }
+
+    /// Remove an observer registered with [`Replica::subscribe`]. Returns false if no such
+    /// observer was registered.
+    pub fn unsubscribe(&mut self, id: SubscriptionId) -> bool {
+        let len = self.observers.len();
+        self.observers.retain(|(i, _)| *i != id);
+        self.observers.len() != len
+    }
+
+    /// Register a hook to be called when changes are committed with
+    /// [`Replica::commit_operations`].  Hooks are called in the order they were registered, each
+    /// receiving the task as rewritten by the previous hook.
+    ///
+    /// Hooks can reject a change by returning [`Error::Usage`], in which case
+    /// `commit_operations` returns that error and commits nothing.  See [`Hook`] for details, and
+    /// [`ExecutableHook`](crate::ExecutableHook) for hooks implemented as external executables.
+    pub fn add_hook<H: Hook + 'static>(&mut self, hook: H) {
+        self.hooks.push(Box::new(hook));
+    }
+
+    /// Call all observers with the given changes, if there are any.
+    fn notify(&mut self, changes: &[TaskChange]) {
+        if changes.is_empty() {
+            return;
+        }
+        for (_, observer) in &mut self.observers {
+            observer(changes);
+        }
+    }
+
     /// Rebuild this replica's working set, based on whether tasks are pending or not.  If
     /// `renumber` is true, then existing tasks may be moved to new working-set indices; in any
     /// case, on completion all pending and recurring tasks are in the working set and all tasks
@@ -399,6 +827,232 @@ impl Replica {
         self.commit_operations(ops)
     }
 
+    /// Generate instances of recurring tasks, up to and including the given time.
+    ///
+    /// For each recurrence template (see [`Task::recurrence`]), a pending instance is created
+    /// for each due time up to `until` (or the template's own `until`, if earlier) that has not
+    /// already been generated, and the template's `mask` is updated to reflect the state of
+    /// all of its instances.  Instances copy the template's properties, with `due` (and `wait`,
+    /// if set) shifted to the instance's due time.
+    ///
+    /// Instance UUIDs are derived from the template UUID and instance index, so replicas that
+    /// generate the same instance before synchronizing will create the same task rather than
+    /// duplicates.  Calling this function repeatedly is harmless.
+    pub fn generate_recurring_instances(
+        &mut self,
+        until: Timestamp,
+        ops: &mut Operations,
+    ) -> Result<()> {
+        let depmap = self.dependency_map(false)?;
+        let tasks = self.all_tasks()?;
+        let now = Utc::now();
+
+        // Determine the current state of every instance, keyed by template.
+        let mut instance_states: HashMap<Uuid, Vec<(usize, char)>> = HashMap::new();
+        for task in tasks.values() {
+            if let Some(Recurrence::Instance { parent, index }) = task.recurrence() {
+                instance_states
+                    .entry(parent)
+                    .or_default()
+                    .push((index, mask_char(task)));
+            }
+        }
+
+        for template in tasks.values() {
+            let Some(Recurrence::Template {
+                period,
+                due,
+                until: template_until,
+                mask: old_mask,
+            }) = template.recurrence()
+            else {
+                continue;
+            };
+            let template_uuid = template.get_uuid();
+            let limit = template_until.map_or(until, |tu| tu.min(until));
+            let wait_offset = template.get_wait().map(|wait| wait - due);
+
+            let template_data = template.clone().into_task_data();
+            let mut mask: Vec<char> = old_mask.chars().collect();
+            for index in 0.. {
+                let Some(instance_due) = period.nth(due, index) else {
+                    break;
+                };
+                if instance_due > limit {
+                    break;
+                }
+                // instances already recorded in the mask are never regenerated, even if they
+                // have since been expired.
+                if index < mask.len() {
+                    continue;
+                }
+                mask.push('-');
+                let uuid = Recurrence::instance_uuid(template_uuid, index);
+                if tasks.contains_key(&uuid) {
+                    continue;
+                }
+
+                let mut instance = TaskData::create(uuid, ops);
+                for (prop, value) in template_data.iter() {
+                    if !matches!(
+                        prop.as_str(),
+                        "status" | "mask" | "entry" | "modified" | "start" | "end" | "due" | "wait"
+                    ) {
+                        instance.update(prop, Some(value.clone()), ops);
+                    }
+                }
+                let mut instance = Task::new(instance, depmap.clone());
+                instance.set_status(Status::Pending, ops)?;
+                instance.set_due(Some(instance_due), ops)?;
+                if let Some(offset) = wait_offset {
+                    instance.set_wait(Some(instance_due + offset), ops)?;
+                }
+                instance.set_value("parent", Some(template_uuid.to_string()), ops)?;
+                instance.set_value("imask", Some(index.to_string()), ops)?;
+                instance.set_entry(Some(now), ops)?;
+            }
+
+            if let Some(states) = instance_states.get(&template_uuid) {
+                for &(index, state) in states {
+                    if let Some(c) = mask.get_mut(index) {
+                        *c = state;
+                    }
+                }
+            }
+            let mask: String = mask.into_iter().collect();
+            if mask != old_mask {
+                template.clone().set_value("mask", Some(mask), ops)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Export all tasks in the JSON format used by Taskwarrior's `task export`.
+    ///
+    /// The output is a JSON array with one task object per line.  Tags, annotations, and
+    /// dependencies are represented as the `tags`, `annotations`, and `depends` arrays, dates use
+    /// Taskwarrior's `YYYYMMDDTHHMMSSZ` format, and tasks in the working set carry their `id`.
+    pub fn export_tasks<W: Write>(&mut self, mut writer: W) -> Result<()> {
+        let working_set = self.working_set()?;
+        let mut tasks: Vec<_> = self.all_task_data()?.into_values().collect();
+        tasks.sort_by_key(|t| t.get_uuid());
+        writeln!(writer, "[")?;
+        for (i, task) in tasks.iter().enumerate() {
+            let id = working_set.by_uuid(task.get_uuid());
+            serde_json::to_writer(&mut writer, &export_task(task, id))?;
+            writeln!(writer, "{}", if i + 1 < tasks.len() { "," } else { "" })?;
+        }
+        writeln!(writer, "]")?;
+        Ok(())
+    }
+
+    /// Import tasks in the JSON format used by Taskwarrior's `task export`, adding the necessary
+    /// operations to `ops`.  The input may be a JSON array of tasks or a sequence of task
+    /// objects, such as one per line.
+    ///
+    /// Each imported task replaces any existing task with the same UUID: properties not present
+    /// in the imported task are removed.  The computed `id` and `urgency` properties are ignored.
+    /// Returns the UUIDs of the imported tasks, in the order they appear in the input.
+    ///
+    /// The input is fully parsed before any operations are added, so an error in the input does
+    /// not result in a partial import.
+    pub fn import_tasks<R: Read>(&mut self, reader: R, ops: &mut Operations) -> Result<Vec<Uuid>> {
+        let mut imported = vec![];
+        for value in serde_json::Deserializer::from_reader(reader).into_iter::<serde_json::Value>()
+        {
+            match value? {
+                serde_json::Value::Array(values) => {
+                    for value in values {
+                        imported.push(import_task(&value)?);
+                    }
+                }
+                value => imported.push(import_task(&value)?),
+            }
+        }
+        self.replace_tasks(imported, ops)
+    }
+
+    /// Export all tasks as an iCalendar (RFC 5545) object containing a `VTODO` component for each
+    /// task.
+    ///
+    /// The description, status, `start`, `due`, `entry`, and `modified` properties are mapped to
+    /// the `SUMMARY`, `STATUS`, `DTSTART`, `DUE`, `CREATED`, and `LAST-MODIFIED` properties, tags
+    /// to `CATEGORIES`, and dependencies to `RELATED-TO` with `RELTYPE=DEPENDS-ON`.  All other task
+    /// properties, including UDAs, are represented as `X-TASKCHAMPION-PROPERTY` properties, so
+    /// that [`Replica::import_icalendar`] can restore the tasks exactly.
+    pub fn export_icalendar<W: Write>(&mut self, mut writer: W) -> Result<()> {
+        let mut tasks: Vec<_> = self.all_task_data()?.into_values().collect();
+        tasks.sort_by_key(|t| t.get_uuid());
+        let now = Utc::now();
+        let mut out = String::new();
+        begin_vcalendar(&mut out);
+        for task in &tasks {
+            export_vtodo(&mut out, task, now);
+        }
+        end_vcalendar(&mut out);
+        writer.write_all(out.as_bytes())?;
+        Ok(())
+    }
+
+    /// Import the `VTODO` components in iCalendar (RFC 5545) data, adding the necessary operations
+    /// to `ops`.  Other components, such as `VEVENT`, are ignored.
+    ///
+    /// Tasks are identified by the component's `UID`.  A `UID` that is not a UUID is converted to
+    /// a version-5 UUID, so that importing the same component again updates the same task.  As
+    /// with [`Replica::import_tasks`], each imported task replaces any existing task with the same
+    /// UUID, and the UUIDs of the imported tasks are returned.
+    ///
+    /// Date-times with a time zone, or with no time zone, are interpreted as UTC.
+    pub fn import_icalendar<R: Read>(
+        &mut self,
+        mut reader: R,
+        ops: &mut Operations,
+    ) -> Result<Vec<Uuid>> {
+        let mut input = String::new();
+        reader.read_to_string(&mut input)?;
+        let imported = import_vtodos(&input)?;
+        self.replace_tasks(imported, ops)
+    }
+
+    /// Replace the given tasks, creating them if necessary, adding the necessary operations to
+    /// `ops`.  Returns the UUIDs of the tasks.
+    fn replace_tasks(
+        &mut self,
+        imported: Vec<(Uuid, TaskMap)>,
+        ops: &mut Operations,
+    ) -> Result<Vec<Uuid>> {
+        // Track tasks as they are updated, in case the input contains a task more than once.
+        let mut tasks: HashMap<Uuid, TaskData> = HashMap::new();
+        let mut uuids = Vec::with_capacity(imported.len());
+        for (uuid, mut taskmap) in imported {
+            let task = match tasks.entry(uuid) {
+                Entry::Occupied(e) => e.into_mut(),
+                Entry::Vacant(e) => e.insert(match self.get_task_data(uuid)? {
+                    Some(task) => task,
+                    None => TaskData::create(uuid, ops),
+                }),
+            };
+            let mut removed: Vec<String> = task
+                .properties()
+                .filter(|p| !taskmap.contains_key(*p))
+                .cloned()
+                .collect();
+            removed.sort();
+            for property in removed {
+                task.update(property, None, ops);
+            }
+            let mut props: Vec<_> = taskmap.drain().collect();
+            props.sort();
+            for (property, value) in props {
+                if task.get(&property) != Some(value.as_str()) {
+                    task.update(property, Some(value), ops);
+                }
+            }
+            uuids.push(uuid);
+        }
+        Ok(uuids)
+    }
+
     /// Add an UndoPoint, if one has not already been added by this Replica.  This occurs
     /// automatically when a change is made.  The `force` flag allows forcing a new UndoPoint
     /// even if one has already been created by this Replica, and may be useful when a Replica
@@ -438,13 +1092,39 @@ impl Replica {
     }
 }
 
+/// Get the character representing the state of a recurrence instance in its template's `mask`.
+fn mask_char(task: &Task) -> char {
+    match task.get_status() {
+        Status::Completed => '+',
+        Status::Deleted => 'X',
+        _ if task.is_waiting() => 'W',
+        _ => '-',
+    }
+}
+
+/// Add context to an error from a sync, except for a cancellation or a wrong encryption secret,
+/// which callers may wish to handle specially.
+fn sync_error(err: Error) -> Error {
+    match err {
+        Error::Cancelled | Error::WrongEncryptionSecret => err,
+        err => anyhow::Error::from(err)
+            .context("Failed to synchronize with server")
+            .into(),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::changes::{ChangeKind, ChangeOrigin};
+    use crate::hooks::Hook;
+    use crate::server::test::TestServer;
     use crate::task::Status;
+    use crate::MergeStrategy;
     use chrono::TimeZone;
     use pretty_assertions::assert_eq;
     use std::collections::HashSet;
+    use std::sync::Mutex;
     use uuid::Uuid;
 
     #[test]
@@ -628,6 +1308,45 @@ mod tests {
         assert_eq!(all_tasks.get(&uuid2).unwrap().get_uuid(), uuid2);
     }
 
+    #[test]
+    fn query() {
+        let mut rep = Replica::new_inmemory();
+
+        let (uuid1, uuid2, uuid3) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        let mut ops = Operations::new();
+        let mut t = rep.create_task(uuid1, &mut ops).unwrap();
+        t.set_status(Status::Pending, &mut ops).unwrap();
+        t.add_tag(&"work".try_into().unwrap(), &mut ops).unwrap();
+        let mut t = rep.create_task(uuid2, &mut ops).unwrap();
+        t.set_status(Status::Pending, &mut ops).unwrap();
+        t.add_dependency(uuid1, &mut ops).unwrap();
+        let mut t = rep.create_task(uuid3, &mut ops).unwrap();
+        t.set_status(Status::Completed, &mut ops).unwrap();
+        t.add_tag(&"work".try_into().unwrap(), &mut ops).unwrap();
+        rep.commit_operations(ops).unwrap();
+
+        let mut uuids = |filter: &str| {
+            let mut uuids = rep
+                .query(&filter.parse().unwrap())
+                .unwrap()
+                .into_keys()
+                .collect::<Vec<_>>();
+            uuids.sort();
+            uuids
+        };
+        let sorted = |mut v: Vec<Uuid>| {
+            v.sort();
+            v
+        };
+
+        assert_eq!(uuids(""), sorted(vec![uuid1, uuid2, uuid3]));
+        assert_eq!(uuids("+work"), sorted(vec![uuid1, uuid3]));
+        assert_eq!(uuids("status:pending +work"), vec![uuid1]);
+        // synthetic tags use the dependency map
+        assert_eq!(uuids("+BLOCKING"), vec![uuid1]);
+        assert_eq!(uuids("+BLOCKED or +COMPLETED"), sorted(vec![uuid2, uuid3]));
+    }
+
     #[test]
     fn commit_operations() -> Result<()> {
         // This mostly tests the working-set callback, as `TaskDB::commit_operations` has
@@ -902,40 +1621,320 @@ mod tests {
         }
     }
 
+    fn recurring_template(rep: &mut Replica, recur: &str) -> Uuid {
+        let mut ops = Operations::new();
+        let uuid = Uuid::new_v4();
+        let mut t = rep.create_task(uuid, &mut ops).unwrap();
+        t.set_description("water the plants".into(), &mut ops)
+            .unwrap();
+        t.set_status(Status::Recurring, &mut ops).unwrap();
+        t.set_value("recur", Some(recur.into()), &mut ops).unwrap();
+        t.set_due(
+            Some(Utc.with_ymd_and_hms(2024, 1, 1, 12, 0, 0).unwrap()),
+            &mut ops,
+        )
+        .unwrap();
+        t.set_wait(
+            Some(Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap()),
+            &mut ops,
+        )
+        .unwrap();
+        t.add_tag(&"garden".try_into().unwrap(), &mut ops).unwrap();
+        rep.commit_operations(ops).unwrap();
+        uuid
+    }
+
     #[test]
-    fn dependency_map() {
+    fn generate_recurring_instances() {
         let mut rep = Replica::new_inmemory();
+        let template = recurring_template(&mut rep, "weekly");
 
-        let mut tasks = vec![];
         let mut ops = Operations::new();
-        for _ in 0..4 {
-            let mut t = rep.create_task(Uuid::new_v4(), &mut ops).unwrap();
-            t.set_status(Status::Pending, &mut ops).unwrap();
-            tasks.push(t);
+        rep.generate_recurring_instances(
+            Utc.with_ymd_and_hms(2024, 1, 15, 12, 0, 0).unwrap(),
+            &mut ops,
+        )
+        .unwrap();
+        rep.commit_operations(ops).unwrap();
+
+        let t = rep.get_task(template).unwrap().unwrap();
+        assert_eq!(t.get_value("mask"), Some("---"));
+
+        let mut instances: Vec<_> = rep
+            .all_tasks()
+            .unwrap()
+            .into_values()
+            .filter(|t| t.get_status() == Status::Pending)
+            .collect();
+        instances.sort_by_key(|t| t.get_due());
+        assert_eq!(instances.len(), 3);
+        for (i, t) in instances.iter().enumerate() {
+            assert_eq!(
+                t.recurrence(),
+                Some(Recurrence::Instance {
+                    parent: template,
+                    index: i
+                })
+            );
+            assert_eq!(t.get_description(), "water the plants");
+            assert!(t.has_tag(&"garden".try_into().unwrap()));
+            let due = Utc
+                .with_ymd_and_hms(2024, 1, 1 + 7 * i as u32, 12, 0, 0)
+                .unwrap();
+            assert_eq!(t.get_due(), Some(due));
+            assert_eq!(t.get_wait(), Some(due - Duration::hours(12)));
+            assert_eq!(t.get_value("recur"), Some("weekly"));
+            assert_eq!(t.get_value("mask"), None);
         }
-        let uuids: Vec<_> = tasks.iter().map(|t| t.get_uuid()).collect();
 
-        // t[3] depends on t[2], and t[1]
-        let mut t = tasks.pop().unwrap();
-        t.add_dependency(uuids[2], &mut ops).unwrap();
-        t.add_dependency(uuids[1], &mut ops).unwrap();
+        // instances are in the working set
+        let ws = rep.working_set().unwrap();
+        for t in &instances {
+            assert!(ws.by_uuid(t.get_uuid()).is_some());
+        }
+    }
 
-        // t[2] depends on t[0]
-        let mut t = tasks.pop().unwrap();
-        t.add_dependency(uuids[0], &mut ops).unwrap();
+    #[test]
+    fn generate_recurring_instances_idempotent() {
+        let mut rep = Replica::new_inmemory();
+        let template = recurring_template(&mut rep, "daily");
+        let until = Utc.with_ymd_and_hms(2024, 1, 2, 12, 0, 0).unwrap();
 
-        // t[1] depends on t[0]
-        let mut t = tasks.pop().unwrap();
-        t.add_dependency(uuids[0], &mut ops).unwrap();
+        let mut ops = Operations::new();
+        rep.generate_recurring_instances(until, &mut ops).unwrap();
+        rep.commit_operations(ops).unwrap();
+        assert_eq!(rep.all_tasks().unwrap().len(), 3);
 
+        let mut ops = Operations::new();
+        rep.generate_recurring_instances(until, &mut ops).unwrap();
+        assert!(ops.is_empty());
+
+        // an expired instance is not regenerated
+        let first = Recurrence::instance_uuid(template, 0);
+        let mut ops = Operations::new();
+        rep.get_task_data(first).unwrap().unwrap().delete(&mut ops);
         rep.commit_operations(ops).unwrap();
+        let mut ops = Operations::new();
+        rep.generate_recurring_instances(until, &mut ops).unwrap();
+        assert!(ops.is_empty());
+        assert_eq!(rep.get_task(first).unwrap(), None);
+    }
 
-        // generate the dependency map, forcing an update based on the newly-added dependencies.
-        // This need not be forced since the `commit_operations` invalidated the cached value.
-        let dm = rep.dependency_map(false).unwrap();
+    #[test]
+    fn generate_recurring_instances_mask() {
+        let mut rep = Replica::new_inmemory();
+        let template = recurring_template(&mut rep, "daily");
 
-        assert_eq!(
-            dm.dependencies(uuids[3]).collect::<HashSet<_>>(),
+        let mut ops = Operations::new();
+        rep.generate_recurring_instances(
+            Utc.with_ymd_and_hms(2024, 1, 3, 12, 0, 0).unwrap(),
+            &mut ops,
+        )
+        .unwrap();
+        rep.commit_operations(ops).unwrap();
+
+        let mut ops = Operations::new();
+        let mut t = rep
+            .get_task(Recurrence::instance_uuid(template, 0))
+            .unwrap()
+            .unwrap();
+        t.done(&mut ops).unwrap();
+        let mut t = rep
+            .get_task(Recurrence::instance_uuid(template, 2))
+            .unwrap()
+            .unwrap();
+        t.set_status(Status::Deleted, &mut ops).unwrap();
+        rep.commit_operations(ops).unwrap();
+
+        let mut ops = Operations::new();
+        rep.generate_recurring_instances(
+            Utc.with_ymd_and_hms(2024, 1, 4, 12, 0, 0).unwrap(),
+            &mut ops,
+        )
+        .unwrap();
+        rep.commit_operations(ops).unwrap();
+
+        let t = rep.get_task(template).unwrap().unwrap();
+        assert_eq!(t.get_value("mask"), Some("+-X-"));
+    }
+
+    #[test]
+    fn generate_recurring_instances_until() {
+        let mut rep = Replica::new_inmemory();
+        let template = recurring_template(&mut rep, "monthly");
+
+        let mut ops = Operations::new();
+        let mut t = rep.get_task(template).unwrap().unwrap();
+        t.set_value(
+            "until",
+            Some(
+                Utc.with_ymd_and_hms(2024, 3, 1, 12, 0, 0)
+                    .unwrap()
+                    .timestamp()
+                    .to_string(),
+            ),
+            &mut ops,
+        )
+        .unwrap();
+        rep.commit_operations(ops).unwrap();
+
+        let mut ops = Operations::new();
+        rep.generate_recurring_instances(
+            Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap(),
+            &mut ops,
+        )
+        .unwrap();
+        rep.commit_operations(ops).unwrap();
+
+        let t = rep.get_task(template).unwrap().unwrap();
+        assert_eq!(t.get_value("mask"), Some("---"));
+        assert_eq!(rep.all_tasks().unwrap().len(), 4);
+    }
+
+    #[test]
+    fn export_import_tasks() {
+        let mut rep = Replica::new_inmemory();
+        let mut ops = Operations::new();
+        let mut t1 = rep.create_task(Uuid::new_v4(), &mut ops).unwrap();
+        t1.set_description("first".into(), &mut ops).unwrap();
+        t1.set_status(Status::Pending, &mut ops).unwrap();
+        t1.add_tag(&"home".try_into().unwrap(), &mut ops).unwrap();
+        let mut t2 = rep.create_task(Uuid::new_v4(), &mut ops).unwrap();
+        t2.set_description("second".into(), &mut ops).unwrap();
+        t2.set_status(Status::Completed, &mut ops).unwrap();
+        t2.add_dependency(t1.get_uuid(), &mut ops).unwrap();
+        rep.commit_operations(ops).unwrap();
+
+        let mut exported = vec![];
+        rep.export_tasks(&mut exported).unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&exported).unwrap();
+        assert_eq!(json.as_array().unwrap().len(), 2);
+
+        let mut rep2 = Replica::new_inmemory();
+        let mut ops = Operations::new();
+        let mut uuids = rep2.import_tasks(&exported[..], &mut ops).unwrap();
+        rep2.commit_operations(ops).unwrap();
+        uuids.sort();
+        let mut expected = vec![t1.get_uuid(), t2.get_uuid()];
+        expected.sort();
+        assert_eq!(uuids, expected);
+        assert_eq!(rep2.all_task_data().unwrap(), rep.all_task_data().unwrap());
+
+        // exporting again produces the same result, including working-set IDs
+        let mut exported2 = vec![];
+        rep2.export_tasks(&mut exported2).unwrap();
+        assert_eq!(
+            String::from_utf8(exported2).unwrap(),
+            String::from_utf8(exported).unwrap()
+        );
+    }
+
+    #[test]
+    fn import_tasks_replaces_existing() {
+        let mut rep = Replica::new_inmemory();
+        let uuid = Uuid::new_v4();
+        let mut ops = Operations::new();
+        let mut t = rep.create_task(uuid, &mut ops).unwrap();
+        t.set_description("old".into(), &mut ops).unwrap();
+        t.set_value("project", Some("work".into()), &mut ops)
+            .unwrap();
+        rep.commit_operations(ops).unwrap();
+
+        let input = format!(
+            r#"{{"uuid":"{uuid}","description":"new","tags":["a"],"urgency":2.0}}
+{{"uuid":"{uuid}","description":"newer","tags":["a"]}}"#
+        );
+        let mut ops = Operations::new();
+        let uuids = rep.import_tasks(input.as_bytes(), &mut ops).unwrap();
+        assert_eq!(uuids, vec![uuid, uuid]);
+        rep.commit_operations(ops).unwrap();
+
+        let t = rep.get_task_data(uuid).unwrap().unwrap();
+        assert_eq!(t.get("description"), Some("newer"));
+        assert!(t.has("tag_a"));
+        assert!(!t.has("project"));
+        assert!(!t.has("urgency"));
+    }
+
+    #[test]
+    fn export_import_icalendar() {
+        let mut rep = Replica::new_inmemory();
+        let mut ops = Operations::new();
+        let mut t1 = rep.create_task(Uuid::new_v4(), &mut ops).unwrap();
+        t1.set_description("first".into(), &mut ops).unwrap();
+        t1.set_status(Status::Pending, &mut ops).unwrap();
+        t1.add_tag(&"home".try_into().unwrap(), &mut ops).unwrap();
+        t1.set_value("devsync.github.issue-id", Some("123".into()), &mut ops)
+            .unwrap();
+        let mut t2 = rep.create_task(Uuid::new_v4(), &mut ops).unwrap();
+        t2.set_description("second".into(), &mut ops).unwrap();
+        t2.set_status(Status::Completed, &mut ops).unwrap();
+        t2.add_dependency(t1.get_uuid(), &mut ops).unwrap();
+        rep.commit_operations(ops).unwrap();
+
+        let mut exported = vec![];
+        rep.export_icalendar(&mut exported).unwrap();
+
+        let mut rep2 = Replica::new_inmemory();
+        let mut ops = Operations::new();
+        let mut uuids = rep2.import_icalendar(&exported[..], &mut ops).unwrap();
+        rep2.commit_operations(ops).unwrap();
+        uuids.sort();
+        let mut expected = vec![t1.get_uuid(), t2.get_uuid()];
+        expected.sort();
+        assert_eq!(uuids, expected);
+        assert_eq!(rep2.all_task_data().unwrap(), rep.all_task_data().unwrap());
+
+        // importing the same data again makes no changes
+        let mut ops = Operations::new();
+        rep2.import_icalendar(&exported[..], &mut ops).unwrap();
+        assert!(ops.is_empty());
+    }
+
+    #[test]
+    fn import_tasks_invalid() {
+        let mut rep = Replica::new_inmemory();
+        let mut ops = Operations::new();
+        let input = r#"[{"uuid":"a3ac8b5e-3f3b-4f4f-9c71-1ad2b2b7e0e4"},{"description":"x"}]"#;
+        assert!(rep.import_tasks(input.as_bytes(), &mut ops).is_err());
+        assert!(ops.is_empty());
+        assert!(rep.import_tasks("[{".as_bytes(), &mut ops).is_err());
+    }
+
+    #[test]
+    fn dependency_map() {
+        let mut rep = Replica::new_inmemory();
+
+        let mut tasks = vec![];
+        let mut ops = Operations::new();
+        for _ in 0..4 {
+            let mut t = rep.create_task(Uuid::new_v4(), &mut ops).unwrap();
+            t.set_status(Status::Pending, &mut ops).unwrap();
+            tasks.push(t);
+        }
+        let uuids: Vec<_> = tasks.iter().map(|t| t.get_uuid()).collect();
+
+        // t[3] depends on t[2], and t[1]
+        let mut t = tasks.pop().unwrap();
+        t.add_dependency(uuids[2], &mut ops).unwrap();
+        t.add_dependency(uuids[1], &mut ops).unwrap();
+
+        // t[2] depends on t[0]
+        let mut t = tasks.pop().unwrap();
+        t.add_dependency(uuids[0], &mut ops).unwrap();
+
+        // t[1] depends on t[0]
+        let mut t = tasks.pop().unwrap();
+        t.add_dependency(uuids[0], &mut ops).unwrap();
+
+        rep.commit_operations(ops).unwrap();
+
+        // generate the dependency map, forcing an update based on the newly-added dependencies.
+        // This need not be forced since the `commit_operations` invalidated the cached value.
+        let dm = rep.dependency_map(false).unwrap();
+
+        assert_eq!(
+            dm.dependencies(uuids[3]).collect::<HashSet<_>>(),
             HashSet::from([uuids[1], uuids[2]])
         );
         assert_eq!(
@@ -995,4 +1994,702 @@ mod tests {
             HashSet::from([])
         );
     }
+
+    /// A vector shared between a test and an observer or hook.
+    #[derive(Clone)]
+    struct Recorded<T>(Arc<Mutex<Vec<T>>>);
+
+    impl<T> Default for Recorded<T> {
+        fn default() -> Self {
+            Recorded(Arc::new(Mutex::new(Vec::new())))
+        }
+    }
+
+    impl<T> Recorded<T> {
+        fn extend(&self, items: impl IntoIterator<Item = T>) {
+            self.0.lock().unwrap().extend(items);
+        }
+
+        /// Take the recorded items, leaving the vector empty.
+        fn take(&self) -> Vec<T> {
+            std::mem::take(&mut *self.0.lock().unwrap())
+        }
+    }
+
+    /// Subscribe to changes on the replica, returning a shared vector of the changes reported.
+    fn record_changes(rep: &mut Replica) -> (SubscriptionId, Recorded<TaskChange>) {
+        let changes = Recorded::default();
+        let changes2 = changes.clone();
+        let id = rep.subscribe(move |c| changes2.extend(c.iter().cloned()));
+        (id, changes)
+    }
+
+    #[test]
+    fn subscribe_local_changes() {
+        let mut rep = Replica::new_inmemory();
+        let (id, changes) = record_changes(&mut rep);
+        let uuid = Uuid::new_v4();
+
+        let mut ops = Operations::new();
+        let mut t = TaskData::create(uuid, &mut ops);
+        t.update("description", Some("one".into()), &mut ops);
+        rep.commit_operations(ops).unwrap();
+        assert_eq!(
+            changes.take(),
+            vec![
+                TaskChange::created(uuid, ChangeOrigin::Local),
+                TaskChange::updated(
+                    uuid,
+                    "description".into(),
+                    None,
+                    Some("one".into()),
+                    ChangeOrigin::Local
+                ),
+            ]
+        );
+
+        // undoing those changes reports them in reverse
+        let undo_ops = rep.get_undo_operations().unwrap();
+        assert!(rep.commit_reversed_operations(undo_ops).unwrap());
+        assert_eq!(
+            changes.take(),
+            vec![
+                TaskChange::updated(
+                    uuid,
+                    "description".into(),
+                    Some("one".into()),
+                    None,
+                    ChangeOrigin::Local
+                ),
+                TaskChange::deleted(uuid, ChangeOrigin::Local),
+            ]
+        );
+
+        // no changes are reported after unsubscribing
+        assert!(rep.unsubscribe(id));
+        assert!(!rep.unsubscribe(id));
+        let mut ops = Operations::new();
+        TaskData::create(uuid, &mut ops);
+        rep.commit_operations(ops).unwrap();
+        assert_eq!(changes.take(), vec![]);
+    }
+
+    #[test]
+    fn subscribe_remote_changes() {
+        let test_server = TestServer::new();
+        let mut server = test_server.server();
+        let mut rep1 = Replica::new_inmemory();
+        let mut rep2 = Replica::new_inmemory();
+        let (_, changes1) = record_changes(&mut rep1);
+        let (_, changes2) = record_changes(&mut rep2);
+        let uuid = Uuid::new_v4();
+
+        let mut ops = Operations::new();
+        let mut t = TaskData::create(uuid, &mut ops);
+        t.update("description", Some("one".into()), &mut ops);
+        rep1.commit_operations(ops).unwrap();
+        rep1.sync(&mut server, false).unwrap();
+        rep2.sync(&mut server, false).unwrap();
+        changes1.take();
+        assert_eq!(
+            changes2.take(),
+            vec![
+                TaskChange::created(uuid, ChangeOrigin::Remote),
+                TaskChange::updated(
+                    uuid,
+                    "description".into(),
+                    None,
+                    Some("one".into()),
+                    ChangeOrigin::Remote
+                ),
+            ]
+        );
+
+        // conflicting changes: rep2's change is applied last and wins, so rep2 sees no remote
+        // change and rep1 sees the change from "three" to "two".
+        let mut ops = Operations::new();
+        rep1.get_task_data(uuid).unwrap().unwrap().update(
+            "description",
+            Some("three".into()),
+            &mut ops,
+        );
+        rep1.commit_operations(ops).unwrap();
+        rep1.sync(&mut server, false).unwrap();
+        let mut ops = Operations::new();
+        rep2.get_task_data(uuid).unwrap().unwrap().update(
+            "description",
+            Some("two".into()),
+            &mut ops,
+        );
+        rep2.commit_operations(ops).unwrap();
+        changes2.take();
+        rep2.sync(&mut server, false).unwrap();
+        assert_eq!(changes2.take(), vec![]);
+        changes1.take();
+        rep1.sync(&mut server, false).unwrap();
+        assert_eq!(
+            changes1.take(),
+            vec![TaskChange::updated(
+                uuid,
+                "description".into(),
+                Some("three".into()),
+                Some("two".into()),
+                ChangeOrigin::Remote
+            )]
+        );
+    }
+
+    /// A hook that sets a default priority on new tasks, rejects descriptions containing
+    /// "forbidden", and records the tasks passed to on_exit.
+    struct TestHook(Recorded<Uuid>);
+
+    impl Hook for TestHook {
+        fn on_add(&mut self, mut task: TaskData) -> Result<TaskData> {
+            if !task.has("priority") {
+                task.update("priority", Some("M".into()), &mut Operations::new());
+            }
+            Ok(task)
+        }
+
+        fn on_modify(&mut self, _before: &TaskData, after: TaskData) -> Result<TaskData> {
+            if after.get("description").unwrap_or("").contains("forbidden") {
+                return Err(Error::Usage("forbidden description".into()));
+            }
+            Ok(after)
+        }
+
+        fn on_exit(&mut self, tasks: &[TaskData]) -> Result<()> {
+            self.0.extend(tasks.iter().map(|t| t.get_uuid()));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn hooks() {
+        let mut rep = Replica::new_inmemory();
+        let exited = Recorded::default();
+        rep.add_hook(TestHook(exited.clone()));
+        let (_, changes) = record_changes(&mut rep);
+        let uuid = Uuid::new_v4();
+
+        let mut ops = Operations::new();
+        let mut t = TaskData::create(uuid, &mut ops);
+        t.update("description", Some("one".into()), &mut ops);
+        rep.commit_operations(ops).unwrap();
+        let t = rep.get_task_data(uuid).unwrap().unwrap();
+        assert_eq!(t.get("priority"), Some("M"));
+        assert_eq!(exited.take(), vec![uuid]);
+        // the change made by the hook is reported to observers
+        assert_eq!(
+            changes.take().last(),
+            Some(&TaskChange::updated(
+                uuid,
+                "priority".into(),
+                None,
+                Some("M".into()),
+                ChangeOrigin::Local
+            ))
+        );
+
+        // a rejected modification is not committed
+        let mut ops = Operations::new();
+        rep.get_task_data(uuid).unwrap().unwrap().update(
+            "description",
+            Some("forbidden".into()),
+            &mut ops,
+        );
+        let err = rep.commit_operations(ops).unwrap_err();
+        assert!(matches!(err, Error::Usage(msg) if msg == "forbidden description"));
+        let t = rep.get_task_data(uuid).unwrap().unwrap();
+        assert_eq!(t.get("description"), Some("one"));
+        assert_eq!(exited.take(), vec![]);
+        assert_eq!(changes.take(), vec![]);
+    }
+
+    #[test]
+    fn task_history() {
+        let test_server = TestServer::new();
+        let mut server = test_server.server();
+        let mut storage = crate::storage::InMemoryStorage::new();
+        storage.enable_history();
+        let mut rep1 = Replica::new(Box::new(storage));
+        let mut rep2 = Replica::new_inmemory();
+        let uuid = Uuid::new_v4();
+
+        let mut ops = Operations::new();
+        let mut t = TaskData::create(uuid, &mut ops);
+        t.update("description", Some("one".into()), &mut ops);
+        rep1.commit_operations(ops).unwrap();
+        rep1.sync(&mut server, false).unwrap();
+
+        // history survives the sync, and includes remote changes
+        rep2.sync(&mut server, false).unwrap();
+        let mut ops = Operations::new();
+        rep2.get_task_data(uuid).unwrap().unwrap().update(
+            "description",
+            Some("two".into()),
+            &mut ops,
+        );
+        rep2.commit_operations(ops).unwrap();
+        rep2.sync(&mut server, false).unwrap();
+        rep1.sync(&mut server, false).unwrap();
+
+        // and undone changes
+        let mut ops = Operations::new();
+        rep1.get_task_data(uuid).unwrap().unwrap().update(
+            "description",
+            Some("three".into()),
+            &mut ops,
+        );
+        rep1.commit_operations(ops).unwrap();
+        let undo_ops = rep1.get_undo_operations().unwrap();
+        assert!(rep1.commit_reversed_operations(undo_ops).unwrap());
+
+        let history: Vec<_> = rep1
+            .task_history(uuid)
+            .unwrap()
+            .into_iter()
+            .map(|e| (e.kind, e.origin))
+            .collect();
+        let updated = |old: Option<&str>, new: Option<&str>| ChangeKind::Updated {
+            property: "description".into(),
+            old_value: old.map(String::from),
+            value: new.map(String::from),
+        };
+        assert_eq!(
+            history,
+            vec![
+                (ChangeKind::Created, ChangeOrigin::Local),
+                (updated(None, Some("one")), ChangeOrigin::Local),
+                (updated(Some("one"), Some("two")), ChangeOrigin::Remote),
+                (updated(Some("two"), Some("three")), ChangeOrigin::Local),
+                (updated(Some("three"), Some("two")), ChangeOrigin::Local),
+            ]
+        );
+
+        // history is not recorded unless enabled
+        assert_eq!(rep2.task_history(uuid).unwrap(), vec![]);
+    }
+
+    #[test]
+    fn task_history_timestamps() {
+        let test_server = TestServer::new();
+        let mut server = test_server.server();
+        let mut storage = crate::storage::InMemoryStorage::new();
+        storage.enable_history();
+        let mut rep1 = Replica::new(Box::new(storage));
+        let mut rep2 = Replica::new_inmemory();
+        let uuid = Uuid::new_v4();
+        let local_time = Utc.with_ymd_and_hms(2020, 1, 1, 0, 0, 0).unwrap();
+        let remote_time = Utc.with_ymd_and_hms(2021, 1, 1, 0, 0, 0).unwrap();
+        let update = |old_value: Option<&str>, value: &str, timestamp| Operation::Update {
+            uuid,
+            property: "description".into(),
+            old_value: old_value.map(String::from),
+            value: Some(value.into()),
+            timestamp,
+        };
+
+        let ops = vec![Operation::Create { uuid }, update(None, "one", local_time)];
+        let before = Utc::now();
+        rep1.commit_operations(ops).unwrap();
+        rep1.sync(&mut server, false).unwrap();
+
+        rep2.sync(&mut server, false).unwrap();
+        rep2.commit_operations(vec![update(Some("one"), "two", remote_time)])
+            .unwrap();
+        rep2.sync(&mut server, false).unwrap();
+        rep1.sync(&mut server, false).unwrap();
+
+        // updates are recorded as of the time they were made, on whichever replica made them;
+        // the creation is recorded as of when it was applied
+        let history = rep1.task_history(uuid).unwrap();
+        assert_eq!(history.len(), 3);
+        assert!(history[0].timestamp >= before);
+        assert_eq!(history[1].timestamp, local_time);
+        assert_eq!(history[2].timestamp, remote_time);
+        assert_eq!(history[2].origin, ChangeOrigin::Remote);
+    }
+
+    #[test]
+    fn undo_redo() {
+        let mut rep = Replica::new_inmemory();
+        let uuid = Uuid::new_v4();
+        let description = |rep: &mut Replica| {
+            rep.get_task_data(uuid)
+                .unwrap()
+                .and_then(|t| t.get("description").map(String::from))
+        };
+
+        let mut ops = vec![Operation::UndoPoint];
+        let mut t = TaskData::create(uuid, &mut ops);
+        t.update("description", Some("one".into()), &mut ops);
+        rep.commit_operations(ops).unwrap();
+        let mut ops = vec![Operation::UndoPoint];
+        t.update("description", Some("two".into()), &mut ops);
+        t.update("priority", Some("H".into()), &mut ops);
+        rep.commit_operations(ops).unwrap();
+
+        let steps: Vec<_> = rep
+            .undo_steps()
+            .unwrap()
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        assert_eq!(
+            steps,
+            vec![
+                r#"modified "two" (description, priority)"#,
+                r#"created "one""#
+            ]
+        );
+        assert!(rep.redo_steps().unwrap().is_empty());
+        assert!(!rep.redo().unwrap());
+
+        // undo both steps, then redo them in order
+        assert!(rep.undo().unwrap());
+        assert_eq!(description(&mut rep), Some("one".into()));
+        assert!(rep.undo().unwrap());
+        assert_eq!(description(&mut rep), None);
+        assert!(!rep.undo().unwrap());
+        assert_eq!(rep.undo_steps().unwrap(), vec![]);
+        assert_eq!(rep.redo_steps().unwrap().len(), 2);
+
+        assert!(rep.redo().unwrap());
+        assert_eq!(description(&mut rep), Some("one".into()));
+        let steps = rep.redo_steps().unwrap();
+        assert_eq!(steps.len(), 1);
+        assert_eq!(
+            steps[0].to_string(),
+            r#"modified "two" (description, priority)"#
+        );
+        assert!(rep.redo().unwrap());
+        assert_eq!(description(&mut rep), Some("two".into()));
+        assert!(!rep.redo().unwrap());
+        assert_eq!(rep.undo_steps().unwrap().len(), 2);
+
+        // committing other changes discards the redo stack
+        assert!(rep.undo().unwrap());
+        let mut ops = vec![Operation::UndoPoint];
+        t.update("project", Some("home".into()), &mut ops);
+        rep.commit_operations(ops).unwrap();
+        assert!(rep.redo_steps().unwrap().is_empty());
+        assert!(!rep.redo().unwrap());
+    }
+
+    #[test]
+    fn sync_async() -> Result<()> {
+        fn assert_send<T: Send>(_: &T) {}
+        let tmp_dir = tempfile::TempDir::new()?;
+        let config = crate::ServerConfig::Local {
+            server_dir: tmp_dir.path().to_path_buf(),
+        };
+        let mut server = futures_executor::block_on(config.into_async_server())?;
+        let mut rep1 = Replica::new_inmemory();
+        let mut rep2 = Replica::new_inmemory();
+
+        let uuid = Uuid::new_v4();
+        let mut ops = Operations::new();
+        let mut t = TaskData::create(uuid, &mut ops);
+        t.update("status", Some("pending".into()), &mut ops);
+        t.update("description", Some("async".into()), &mut ops);
+        rep1.commit_operations(ops)?;
+
+        let fut = rep1.sync_async(&mut server, false);
+        assert_send(&fut);
+        futures_executor::block_on(fut)?;
+        futures_executor::block_on(rep2.sync_async(&mut server, false))?;
+
+        let task = rep2.get_task(uuid)?.unwrap();
+        assert_eq!(task.get_description(), "async");
+        // the working set is rebuilt after the sync
+        assert_eq!(rep2.working_set()?.by_uuid(uuid), Some(1));
+        Ok(())
+    }
+
+    #[test]
+    fn sync_with_options_cancelled() -> Result<()> {
+        let mut server = TestServer::new().server();
+        let mut rep = Replica::new_inmemory();
+        let mut ops = Operations::new();
+        TaskData::create(Uuid::new_v4(), &mut ops);
+        rep.commit_operations(ops)?;
+
+        let token = crate::CancellationToken::new();
+        token.cancel();
+        let res = rep.sync_with_options(
+            &mut server,
+            crate::SyncOptions::new().cancellation_token(token),
+        );
+        assert!(matches!(res, Err(Error::Cancelled)));
+        assert_eq!(rep.num_local_operations()?, 1);
+
+        let mut progress = vec![];
+        rep.sync_with_options(
+            &mut server,
+            crate::SyncOptions::new().on_progress(|p| progress.push(p)),
+        )?;
+        assert_eq!(progress.len(), 2); // operations transformed, and one batch uploaded
+        assert_eq!(rep.num_local_operations()?, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn sync_with_options_cancelled_after_applying() -> Result<()> {
+        let mut server = TestServer::new().server();
+        let mut rep1 = Replica::new_inmemory();
+        let mut rep2 = Replica::new_inmemory();
+        let (_, changes) = record_changes(&mut rep2);
+        let (uuid1, uuid2) = (Uuid::new_v4(), Uuid::new_v4());
+        for uuid in [uuid1, uuid2] {
+            let mut ops = Operations::new();
+            TaskData::create(uuid, &mut ops);
+            rep1.commit_operations(ops)?;
+            rep1.sync(&mut server, false)?;
+        }
+
+        // with no local operations, the first version is applied before the sync is cancelled,
+        // and observers are notified of it
+        let token = crate::CancellationToken::new();
+        let res = rep2.sync_with_options(
+            &mut server,
+            crate::SyncOptions::new()
+                .cancellation_token(token.clone())
+                .on_progress(|p| {
+                    if p == (crate::SyncProgress::VersionFetched { count: 2 }) {
+                        token.cancel();
+                    }
+                }),
+        );
+        assert!(matches!(res, Err(Error::Cancelled)));
+        assert!(rep2.get_task_data(uuid1)?.is_some());
+        assert!(rep2.get_task_data(uuid2)?.is_none());
+        assert_eq!(
+            changes.take(),
+            vec![TaskChange::created(uuid1, ChangeOrigin::Remote)]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn rotate_encryption_secret() -> Result<()> {
+        let test_server = TestServer::new();
+        let mut server = test_server.server();
+        let mut rep1 = Replica::new_inmemory();
+        let mut rep2 = Replica::new_inmemory();
+        rep2.sync(&mut server, false)?;
+
+        let uuid = Uuid::new_v4();
+        let mut ops = Operations::new();
+        TaskData::create(uuid, &mut ops);
+        rep1.commit_operations(ops)?;
+
+        // the local change is synchronized before the snapshot is sent
+        rep1.rotate_encryption_secret(&mut server, b"old", b"new")?;
+        assert_eq!(rep1.num_local_operations()?, 0);
+        assert!(test_server.snapshot().is_some());
+
+        rep2.sync(&mut server, false)?;
+        assert!(rep2.get_task(uuid)?.is_some());
+        Ok(())
+    }
+
+    #[test]
+    fn sync_with_report() -> Result<()> {
+        let mut server = TestServer::new().server();
+        let mut rep1 = Replica::new_inmemory();
+        let mut rep2 = Replica::new_inmemory();
+
+        let mut ops = Operations::new();
+        let mut t = TaskData::create(Uuid::new_v4(), &mut ops);
+        t.update("status", Some("pending".into()), &mut ops);
+        rep1.commit_operations(ops)?;
+
+        let report = rep1.sync_with_report(&mut server, false)?;
+        assert_eq!(report.operations_pushed, 2);
+        assert_eq!(report.tasks_created, 0);
+
+        let report = rep2.sync_with_report(&mut server, false)?;
+        assert_eq!(report.operations_pushed, 0);
+        assert_eq!(report.versions_applied, 1);
+        assert_eq!(report.tasks_created, 1);
+        Ok(())
+    }
+
+    #[test]
+    fn reapply_sync_conflict() -> Result<()> {
+        let mut server = TestServer::new().server();
+        let mut rep1 = Replica::new_inmemory();
+        let mut rep2 = Replica::new_inmemory();
+
+        let uuid = Uuid::new_v4();
+        let mut ops = Operations::new();
+        let mut t = TaskData::create(uuid, &mut ops);
+        t.update("description", Some("original".into()), &mut ops);
+        rep1.commit_operations(ops)?;
+        rep1.sync(&mut server, false)?;
+        rep2.sync(&mut server, false)?;
+
+        // rep2's change is overridden by rep1's later change
+        let mut ops = Operations::new();
+        let mut t = rep2.get_task_data(uuid)?.unwrap();
+        t.update("description", Some("from rep2".into()), &mut ops);
+        rep2.commit_operations(ops)?;
+        let mut ops = Operations::new();
+        let mut t = rep1.get_task_data(uuid)?.unwrap();
+        t.update("description", Some("from rep1".into()), &mut ops);
+        rep1.commit_operations(ops)?;
+        rep1.sync(&mut server, false)?;
+        rep2.sync(&mut server, false)?;
+
+        let conflicts = rep2.sync_conflicts()?;
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].losing_value, Some("from rep2".into()));
+        assert_eq!(conflicts[0].winning_value, Some("from rep1".into()));
+        assert_eq!(
+            rep2.get_task_data(uuid)?.unwrap().get("description"),
+            Some("from rep1")
+        );
+
+        // re-applying the conflict restores rep2's change, which then wins everywhere
+        assert!(rep2.reapply_sync_conflict(&conflicts[0])?);
+        assert_eq!(rep2.sync_conflicts()?, vec![]);
+        rep2.sync(&mut server, false)?;
+        rep1.sync(&mut server, false)?;
+        assert_eq!(
+            rep1.get_task_data(uuid)?.unwrap().get("description"),
+            Some("from rep2")
+        );
+
+        // the conflict can no longer be re-applied
+        assert!(matches!(
+            rep2.reapply_sync_conflict(&conflicts[0]),
+            Err(Error::Usage(_))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn clear_sync_conflicts() -> Result<()> {
+        let conflict = |property: &str| SyncConflict {
+            uuid: Uuid::new_v4(),
+            property: property.into(),
+            losing_value: None,
+            losing_timestamp: Utc::now(),
+            losing_origin: crate::ChangeOrigin::Local,
+            winning_value: Some("x".into()),
+            winning_timestamp: Utc::now(),
+        };
+        let (c1, c2) = (conflict("a"), conflict("b"));
+        let mut storage = crate::storage::InMemoryStorage::new();
+        {
+            let mut txn = storage.txn()?;
+            txn.add_conflict(c1.clone())?;
+            txn.add_conflict(c2.clone())?;
+            txn.commit()?;
+        }
+        let mut rep = Replica::new(Box::new(storage));
+        assert_eq!(rep.sync_conflicts()?, vec![c1.clone(), c2.clone()]);
+
+        // re-applying a conflict for a task that no longer exists just removes it
+        assert!(!rep.reapply_sync_conflict(&c1)?);
+        assert_eq!(rep.sync_conflicts()?, vec![c2]);
+
+        rep.clear_sync_conflicts()?;
+        assert_eq!(rep.sync_conflicts()?, vec![]);
+        Ok(())
+    }
+
+    #[test]
+    fn set_merge_strategy() -> Result<()> {
+        let mut server = TestServer::new().server();
+        let mut rep1 = Replica::new_inmemory();
+        let mut rep2 = Replica::new_inmemory();
+        rep2.set_merge_strategy("description", MergeStrategy::TextMerge);
+
+        let uuid = Uuid::new_v4();
+        let mut ops = Operations::new();
+        let mut t = TaskData::create(uuid, &mut ops);
+        t.update("description", Some("call mom".into()), &mut ops);
+        rep1.commit_operations(ops)?;
+        rep1.sync(&mut server, false)?;
+        rep2.sync(&mut server, false)?;
+
+        for (rep, description) in [
+            (&mut rep1, "call mom tonight"),
+            (&mut rep2, "please call mom"),
+        ] {
+            let mut ops = Operations::new();
+            let mut t = rep.get_task_data(uuid)?.unwrap();
+            t.update("description", Some(description.into()), &mut ops);
+            rep.commit_operations(ops)?;
+        }
+        rep1.sync(&mut server, false)?;
+        let report = rep2.sync_with_report(&mut server, false)?;
+        rep1.sync(&mut server, false)?;
+
+        assert_eq!(report.updates_merged, 1);
+        for rep in [&mut rep1, &mut rep2] {
+            assert_eq!(
+                rep.get_task_data(uuid)?.unwrap().get("description"),
+                Some("please call mom tonight")
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn undo_synced() {
+        let test_server = TestServer::new();
+        let mut server = test_server.server();
+        let mut rep1 = Replica::new_inmemory();
+        let mut rep2 = Replica::new_inmemory();
+        let uuid = Uuid::new_v4();
+
+        let mut ops = vec![Operation::UndoPoint];
+        let mut t = TaskData::create(uuid, &mut ops);
+        t.update("description", Some("one".into()), &mut ops);
+        rep1.commit_operations(ops).unwrap();
+        let mut ops = vec![Operation::UndoPoint];
+        t.update("description", Some("two".into()), &mut ops);
+        rep1.commit_operations(ops).unwrap();
+        rep1.sync(&mut server, false).unwrap();
+        rep2.sync(&mut server, false).unwrap();
+
+        // ordinary undo is no longer possible
+        assert!(!rep1.undo().unwrap());
+        let steps: Vec<_> = rep1
+            .synced_undo_steps()
+            .unwrap()
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        assert_eq!(
+            steps,
+            vec![r#"modified "two" (description)"#, r#"created "one""#]
+        );
+
+        // revert the modification, and propagate that to rep2
+        assert!(rep1.undo_synced().unwrap());
+        assert_eq!(rep1.synced_undo_steps().unwrap().len(), 1);
+        rep1.sync(&mut server, false).unwrap();
+        rep2.sync(&mut server, false).unwrap();
+        let t2 = rep2.get_task_data(uuid).unwrap().unwrap();
+        assert_eq!(t2.get("description"), Some("one"));
+
+        // the compensating operations are themselves synced and can be reverted
+        assert_eq!(rep1.synced_undo_steps().unwrap().len(), 2);
+        assert!(rep1.undo_synced().unwrap());
+        let t1 = rep1.get_task_data(uuid).unwrap().unwrap();
+        assert_eq!(t1.get("description"), Some("two"));
+        assert!(rep1.undo().unwrap());
+
+        // revert the creation
+        assert!(rep1.undo_synced().unwrap());
+        assert!(rep1.get_task_data(uuid).unwrap().is_none());
+        assert!(!rep1.undo_synced().unwrap());
+        rep1.sync(&mut server, false).unwrap();
+        rep2.sync(&mut server, false).unwrap();
+        assert!(rep2.get_task_data(uuid).unwrap().is_none());
+    }
 }
@@ -0,0 +1,154 @@
+use crate::errors::Result;
+use crate::server::{
+    AddVersionResult, AsyncServer, GetVersionResult, HistorySegment, Server, Snapshot,
+    SnapshotUrgency, VersionId,
+};
+use async_trait::async_trait;
+use std::ops::DerefMut;
+
+/// Present a blocking [`Server`] as an [`AsyncServer`].
+///
+/// Each future returned from this adapter performs the blocking operation when first polled, and
+/// is then ready. This is used to run the sync process, which is implemented asynchronously, with
+/// a blocking server. It is also used for servers which have no async implementation.
+pub(crate) struct AsyncAdapter<S>(pub(crate) S);
+
+#[async_trait]
+impl<S> AsyncServer for AsyncAdapter<S>
+where
+    S: DerefMut + Send,
+    S::Target: Server,
+{
+    async fn add_version(
+        &mut self,
+        parent_version_id: VersionId,
+        history_segment: HistorySegment,
+    ) -> Result<(AddVersionResult, SnapshotUrgency)> {
+        self.0.add_version(parent_version_id, history_segment)
+    }
+
+    async fn get_child_version(
+        &mut self,
+        parent_version_id: VersionId,
+    ) -> Result<GetVersionResult> {
+        self.0.get_child_version(parent_version_id)
+    }
+
+    async fn add_snapshot(&mut self, version_id: VersionId, snapshot: Snapshot) -> Result<()> {
+        self.0.add_snapshot(version_id, snapshot)
+    }
+
+    async fn get_snapshot(&mut self) -> Result<Option<(VersionId, Snapshot)>> {
+        self.0.get_snapshot()
+    }
+
+    async fn rotate_encryption_secret(
+        &mut self,
+        old_secret: &[u8],
+        new_secret: &[u8],
+        parent_version_id: VersionId,
+        history_segment: HistorySegment,
+        snapshot: Snapshot,
+    ) -> Result<AddVersionResult> {
+        self.0.rotate_encryption_secret(
+            old_secret,
+            new_secret,
+            parent_version_id,
+            history_segment,
+            snapshot,
+        )
+    }
+}
+
+/// Present an [`AsyncServer`] as a blocking [`Server`], by running each operation to completion.
+///
+/// Servers based on a Tokio client library must be given a runtime in which to run, with
+/// [`BlockingServer::with_runtime`]. Others are run on the current thread.
+#[cfg(feature = "cloud")]
+pub(in crate::server) struct BlockingServer<S> {
+    server: S,
+    executor: Executor,
+}
+
+#[cfg(feature = "cloud")]
+impl<S: AsyncServer> BlockingServer<S> {
+    #[allow(dead_code)] // unused if only Tokio-based servers are enabled
+    pub(in crate::server) fn new(server: S) -> Self {
+        Self {
+            server,
+            executor: Executor {
+                #[cfg(any(feature = "server-gcp", feature = "server-aws"))]
+                rt: None,
+            },
+        }
+    }
+
+    #[cfg(any(feature = "server-gcp", feature = "server-aws"))]
+    pub(in crate::server) fn with_runtime(server: S, rt: tokio::runtime::Runtime) -> Self {
+        Self {
+            server,
+            executor: Executor { rt: Some(rt) },
+        }
+    }
+}
+
+#[cfg(feature = "cloud")]
+struct Executor {
+    #[cfg(any(feature = "server-gcp", feature = "server-aws"))]
+    rt: Option<tokio::runtime::Runtime>,
+}
+
+#[cfg(feature = "cloud")]
+impl Executor {
+    /// Run the given future to completion, in the runtime if there is one.
+    fn block_on<F: std::future::Future>(&self, fut: F) -> F::Output {
+        #[cfg(any(feature = "server-gcp", feature = "server-aws"))]
+        if let Some(rt) = &self.rt {
+            return rt.block_on(fut);
+        }
+        futures_executor::block_on(fut)
+    }
+}
+
+#[cfg(feature = "cloud")]
+impl<S: AsyncServer> Server for BlockingServer<S> {
+    fn add_version(
+        &mut self,
+        parent_version_id: VersionId,
+        history_segment: HistorySegment,
+    ) -> Result<(AddVersionResult, SnapshotUrgency)> {
+        self.executor
+            .block_on(self.server.add_version(parent_version_id, history_segment))
+    }
+
+    fn get_child_version(&mut self, parent_version_id: VersionId) -> Result<GetVersionResult> {
+        self.executor
+            .block_on(self.server.get_child_version(parent_version_id))
+    }
+
+    fn add_snapshot(&mut self, version_id: VersionId, snapshot: Snapshot) -> Result<()> {
+        self.executor
+            .block_on(self.server.add_snapshot(version_id, snapshot))
+    }
+
+    fn get_snapshot(&mut self) -> Result<Option<(VersionId, Snapshot)>> {
+        self.executor.block_on(self.server.get_snapshot())
+    }
+
+    fn rotate_encryption_secret(
+        &mut self,
+        old_secret: &[u8],
+        new_secret: &[u8],
+        parent_version_id: VersionId,
+        history_segment: HistorySegment,
+        snapshot: Snapshot,
+    ) -> Result<AddVersionResult> {
+        self.executor.block_on(self.server.rotate_encryption_secret(
+            old_secret,
+            new_secret,
+            parent_version_id,
+            history_segment,
+            snapshot,
+        ))
+    }
+}
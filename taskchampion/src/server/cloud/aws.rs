@@ -0,0 +1,435 @@
+use super::service::{ObjectInfo, Service};
+use crate::errors::{Error, Result};
+use crate::server::AwsCredentials;
+use async_trait::async_trait;
+use aws_config::{BehaviorVersion, Region};
+use aws_credential_types::Credentials;
+use aws_sdk_s3::error::ProvideErrorMetadata;
+use aws_sdk_s3::primitives::ByteStream;
+use aws_sdk_s3::Client;
+
+/// A [`Service`] implementation based on AWS S3, or any service implementing the S3 API.
+///
+/// The client library is based on Tokio, so this service must be used within a Tokio runtime.
+pub(in crate::server) struct AwsService {
+    client: Client,
+    bucket: String,
+}
+
+/// Determine whether the given result contains an error with the given S3 error code.
+fn is_s3_error<T, E: ProvideErrorMetadata>(
+    query: &str,
+    res: &std::result::Result<T, aws_sdk_s3::error::SdkError<E>>,
+) -> bool {
+    match res {
+        Err(err) => err.code() == Some(query),
+        _ => false,
+    }
+}
+
+/// Determine whether the given result contains an HTTP error with the given status.
+fn is_http_error<T, E>(
+    query: u16,
+    res: &std::result::Result<T, aws_sdk_s3::error::SdkError<E>>,
+) -> bool {
+    match res {
+        Err(err) => err.raw_response().map(|r| r.status().as_u16()) == Some(query),
+        _ => false,
+    }
+}
+
+impl AwsService {
+    pub(in crate::server) async fn new(
+        region: String,
+        bucket: String,
+        endpoint: Option<String>,
+        creds: AwsCredentials,
+    ) -> Result<Self> {
+        let mut loader =
+            aws_config::defaults(BehaviorVersion::latest()).region(Region::new(region));
+        match creds {
+            AwsCredentials::AccessKey {
+                access_key_id,
+                secret_access_key,
+            } => {
+                loader = loader.credentials_provider(Credentials::new(
+                    access_key_id,
+                    secret_access_key,
+                    None,
+                    None,
+                    "taskchampion",
+                ));
+            }
+            AwsCredentials::Profile { profile_name } => {
+                loader = loader.profile_name(profile_name);
+            }
+            AwsCredentials::Default => {}
+        }
+        let sdk_config = loader.load().await;
+
+        let mut config = aws_sdk_s3::config::Builder::from(&sdk_config);
+        if let Some(endpoint) = endpoint {
+            // S3-compatible services generally do not support virtual-hosted-style bucket
+            // addressing, so use path-style addressing with a custom endpoint.
+            config = config.endpoint_url(endpoint).force_path_style(true);
+        }
+
+        Ok(Self {
+            client: Client::from_conf(config.build()),
+            bucket,
+        })
+    }
+
+    /// Get the object's content and ETag, or None if it does not exist.
+    async fn get_with_etag(&mut self, name: String) -> Result<Option<(Vec<u8>, Option<String>)>> {
+        let get_res = self
+            .client
+            .get_object()
+            .bucket(self.bucket.clone())
+            .key(name)
+            .send()
+            .await;
+        if is_s3_error("NoSuchKey", &get_res) {
+            return Ok(None);
+        }
+        let output = get_res?;
+        let etag = output.e_tag.clone();
+        let data = output.body.collect().await.map_err(anyhow::Error::from)?;
+        Ok(Some((data.into_bytes().to_vec(), etag)))
+    }
+}
+
+#[async_trait]
+impl Service for AwsService {
+    async fn put(&mut self, name: &[u8], value: &[u8]) -> Result<()> {
+        let name = String::from_utf8(name.to_vec()).expect("non-UTF8 object name");
+        self.client
+            .put_object()
+            .bucket(self.bucket.clone())
+            .key(name)
+            .body(ByteStream::from(value.to_vec()))
+            .send()
+            .await?;
+        Ok(())
+    }
+
+    async fn get(&mut self, name: &[u8]) -> Result<Option<Vec<u8>>> {
+        let name = String::from_utf8(name.to_vec()).expect("non-UTF8 object name");
+        Ok(self.get_with_etag(name).await?.map(|(data, _)| data))
+    }
+
+    async fn del(&mut self, name: &[u8]) -> Result<()> {
+        // S3 does not report an error when deleting an object that does not exist.
+        let name = String::from_utf8(name.to_vec()).expect("non-UTF8 object name");
+        self.client
+            .delete_object()
+            .bucket(self.bucket.clone())
+            .key(name)
+            .send()
+            .await?;
+        Ok(())
+    }
+
+    async fn list(&mut self, prefix: &[u8]) -> Result<Vec<ObjectInfo>> {
+        let prefix = String::from_utf8(prefix.to_vec()).expect("non-UTF8 object prefix");
+        let mut objects = vec![];
+        let mut continuation_token = None;
+        // Fetch pages of results until there is no next page.
+        loop {
+            let request = self
+                .client
+                .list_objects_v2()
+                .bucket(self.bucket.clone())
+                .prefix(prefix.clone())
+                .set_continuation_token(continuation_token);
+            #[cfg(test)] // For testing, use a small page size.
+            let request = request.max_keys(6);
+            let response = request.send().await?;
+            for obj in response.contents() {
+                // S3 does not record creation time, but objects are never modified after
+                // creation, so the modification time is equivalent.  Default to 0 if the time
+                // is missing or before 1970.
+                let creation = obj.last_modified.map(|t| t.secs()).unwrap_or(0);
+                let creation: u64 = creation.try_into().unwrap_or(0);
+                let name = obj.key.clone().unwrap_or_default();
+                objects.push(ObjectInfo {
+                    name: name.into_bytes(),
+                    creation,
+                });
+            }
+            continuation_token = response.next_continuation_token;
+            if continuation_token.is_none() {
+                return Ok(objects);
+            }
+        }
+    }
+
+    async fn compare_and_swap(
+        &mut self,
+        name: &[u8],
+        existing_value: Option<Vec<u8>>,
+        new_value: Vec<u8>,
+    ) -> Result<bool> {
+        let name = String::from_utf8(name.to_vec()).expect("non-UTF8 object name");
+        let put = self
+            .client
+            .put_object()
+            .bucket(self.bucket.clone())
+            .key(name.clone())
+            .body(ByteStream::from(new_value));
+
+        // Condition the write on the object being unchanged since it was read: either that it
+        // still does not exist, or that it still has the same ETag.
+        let put = match (self.get_with_etag(name).await?, existing_value) {
+            (None, None) => put.if_none_match("*"),
+            (Some((data, etag)), Some(existing_value)) if data == existing_value => {
+                let Some(etag) = etag else {
+                    return Err(Error::Server(String::from(
+                        "S3 service did not provide an ETag",
+                    )));
+                };
+                put.if_match(etag)
+            }
+            _ => return Ok(false),
+        };
+
+        let put_res = put.send().await;
+        // A 412 indicates the precondition was not satisfied, and a 409 that a concurrent
+        // conditional write to the same object is in progress.
+        if is_http_error(412, &put_res) || is_http_error(409, &put_res) {
+            Ok(false)
+        } else {
+            put_res?;
+            Ok(true)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::OnceLock;
+    use tokio::runtime::Runtime;
+    use uuid::Uuid;
+
+    /// Run the given future in a runtime shared by all tests, as the client requires.
+    fn block_on<F: std::future::Future>(fut: F) -> F::Output {
+        static RT: OnceLock<Runtime> = OnceLock::new();
+        RT.get_or_init(|| Runtime::new().unwrap()).block_on(fut)
+    }
+
+    /// Make a service if `AWS_TEST_BUCKET` and the related environment variables are set, as
+    /// well as a function to put a unique prefix on an object name, so that tests do not
+    /// interfere with one another.
+    ///
+    /// The bucket is accessed in `AWS_TEST_REGION` with the access key in
+    /// `AWS_TEST_ACCESS_KEY_ID` and `AWS_TEST_SECRET_ACCESS_KEY`.  To test against an
+    /// S3-compatible service such as MinIO, set `AWS_TEST_ENDPOINT` to its URL.
+    ///
+    /// Set up this bucket with a lifecyle policy to delete objects with age > 1 day. While passing
+    /// tests should correctly clean up after themselves, failing tests may leave objects in the
+    /// bucket.
+    ///
+    /// When the environment variables are not set, this returns None and the test does not run.
+    /// Note that the Rust test runner will still show "ok" for the test, as there is no way to
+    /// indicate anything else.
+    fn make_service() -> Option<(AwsService, impl Fn(&str) -> Vec<u8>)> {
+        let Ok(bucket) = std::env::var("AWS_TEST_BUCKET") else {
+            return None;
+        };
+        let Ok(region) = std::env::var("AWS_TEST_REGION") else {
+            return None;
+        };
+        let Ok(access_key_id) = std::env::var("AWS_TEST_ACCESS_KEY_ID") else {
+            return None;
+        };
+        let Ok(secret_access_key) = std::env::var("AWS_TEST_SECRET_ACCESS_KEY") else {
+            return None;
+        };
+        let endpoint = std::env::var("AWS_TEST_ENDPOINT").ok();
+
+        let prefix = Uuid::new_v4();
+        Some((
+            block_on(AwsService::new(
+                region,
+                bucket,
+                endpoint,
+                AwsCredentials::AccessKey {
+                    access_key_id,
+                    secret_access_key,
+                },
+            ))
+            .unwrap(),
+            move |n: &_| format!("{}-{}", prefix.as_simple(), n).into_bytes(),
+        ))
+    }
+
+    #[test]
+    fn put_and_get() {
+        let Some((mut svc, pfx)) = make_service() else {
+            return;
+        };
+        block_on(svc.put(&pfx("testy"), b"foo")).unwrap();
+        let got = block_on(svc.get(&pfx("testy"))).unwrap();
+        assert_eq!(got, Some(b"foo".to_vec()));
+
+        // Clean up.
+        block_on(svc.del(&pfx("testy"))).unwrap();
+    }
+
+    #[test]
+    fn get_missing() {
+        let Some((mut svc, pfx)) = make_service() else {
+            return;
+        };
+        let got = block_on(svc.get(&pfx("testy"))).unwrap();
+        assert_eq!(got, None);
+    }
+
+    #[test]
+    fn del() {
+        let Some((mut svc, pfx)) = make_service() else {
+            return;
+        };
+        block_on(svc.put(&pfx("testy"), b"data")).unwrap();
+        block_on(svc.del(&pfx("testy"))).unwrap();
+        let got = block_on(svc.get(&pfx("testy"))).unwrap();
+        assert_eq!(got, None);
+    }
+
+    #[test]
+    fn del_missing() {
+        // Deleting an object that does not exist is not an error.
+        let Some((mut svc, pfx)) = make_service() else {
+            return;
+        };
+
+        assert!(block_on(svc.del(&pfx("testy"))).is_ok());
+    }
+
+    #[test]
+    fn list() {
+        let Some((mut svc, pfx)) = make_service() else {
+            return;
+        };
+        let mut names: Vec<_> = (0..20).map(|i| pfx(&format!("pp-{i:02}"))).collect();
+        names.sort();
+        // Create 20 objects that will be listed.
+        for n in &names {
+            block_on(svc.put(n, b"data")).unwrap();
+        }
+        // And another object that should not be included in the list.
+        block_on(svc.put(&pfx("xxx"), b"data")).unwrap();
+
+        let got_objects: Vec<_> = block_on(svc.list(&pfx("pp-"))).unwrap();
+        let mut got_names: Vec<_> = got_objects.into_iter().map(|oi| oi.name).collect();
+        got_names.sort();
+        assert_eq!(got_names, names);
+
+        // Clean up.
+        for n in got_names {
+            block_on(svc.del(&n)).unwrap();
+        }
+        block_on(svc.del(&pfx("xxx"))).unwrap();
+    }
+
+    #[test]
+    fn compare_and_swap_create() {
+        let Some((mut svc, pfx)) = make_service() else {
+            return;
+        };
+
+        assert!(block_on(svc.compare_and_swap(&pfx("testy"), None, b"bar".to_vec())).unwrap());
+        let got = block_on(svc.get(&pfx("testy"))).unwrap();
+        assert_eq!(got, Some(b"bar".to_vec()));
+
+        // Clean up.
+        block_on(svc.del(&pfx("testy"))).unwrap();
+    }
+
+    #[test]
+    fn compare_and_swap_matches() {
+        let Some((mut svc, pfx)) = make_service() else {
+            return;
+        };
+
+        block_on(svc.put(&pfx("testy"), b"foo1")).unwrap();
+        block_on(svc.put(&pfx("testy"), b"foo2")).unwrap();
+        assert!(block_on(svc.compare_and_swap(
+            &pfx("testy"),
+            Some(b"foo2".to_vec()),
+            b"bar".to_vec()
+        ))
+        .unwrap());
+        let got = block_on(svc.get(&pfx("testy"))).unwrap();
+        assert_eq!(got, Some(b"bar".to_vec()));
+
+        // Clean up.
+        block_on(svc.del(&pfx("testy"))).unwrap();
+    }
+
+    #[test]
+    fn compare_and_swap_expected_no_file() {
+        let Some((mut svc, pfx)) = make_service() else {
+            return;
+        };
+
+        block_on(svc.put(&pfx("testy"), b"foo1")).unwrap();
+        assert!(!block_on(svc.compare_and_swap(&pfx("testy"), None, b"bar".to_vec())).unwrap());
+        let got = block_on(svc.get(&pfx("testy"))).unwrap();
+        assert_eq!(got, Some(b"foo1".to_vec()));
+
+        // Clean up.
+        block_on(svc.del(&pfx("testy"))).unwrap();
+    }
+
+    #[test]
+    fn compare_and_swap_old_value() {
+        let Some((mut svc, pfx)) = make_service() else {
+            return;
+        };
+
+        block_on(svc.put(&pfx("testy"), b"foo1")).unwrap();
+        block_on(svc.put(&pfx("testy"), b"foo2")).unwrap();
+        assert!(!block_on(svc.compare_and_swap(
+            &pfx("testy"),
+            Some(b"foo1".to_vec()),
+            b"bar".to_vec()
+        ))
+        .unwrap());
+        let got = block_on(svc.get(&pfx("testy"))).unwrap();
+        assert_eq!(got, Some(b"foo2".to_vec()));
+
+        // Clean up.
+        block_on(svc.del(&pfx("testy"))).unwrap();
+    }
+
+    #[test]
+    fn compare_and_swap_changes() {
+        // Changes since the object was read cause compare_and_swap to fail.
+        let Some((mut svc, pfx)) = make_service() else {
+            return;
+        };
+
+        block_on(svc.put(&pfx("testy"), b"foo1")).unwrap();
+        let (_, etag) = block_on(svc.get_with_etag(String::from_utf8(pfx("testy")).unwrap()))
+            .unwrap()
+            .unwrap();
+        block_on(svc.put(&pfx("testy"), b"foo2")).unwrap();
+        let put_res = block_on(
+            svc.client
+                .put_object()
+                .bucket(svc.bucket.clone())
+                .key(String::from_utf8(pfx("testy")).unwrap())
+                .body(ByteStream::from(b"bar".to_vec()))
+                .if_match(etag.unwrap())
+                .send(),
+        );
+        assert!(is_http_error(412, &put_res));
+        let got = block_on(svc.get(&pfx("testy"))).unwrap();
+        assert_eq!(got, Some(b"foo2".to_vec()));
+
+        // Clean up.
+        block_on(svc.del(&pfx("testy"))).unwrap();
+    }
+}
@@ -0,0 +1,59 @@
+use crate::errors::{Error, Result};
+
+/// Run a function that performs blocking I/O, for a [`Service`](super::service::Service) based
+/// on a blocking API.
+///
+/// Within a Tokio runtime, the function runs on the runtime's blocking thread pool, so that it
+/// does not stall other tasks on the runtime. Otherwise, as when the service is used through a
+/// blocking [`Server`](crate::server::Server), it runs immediately on the current thread.
+///
+/// As the function may be moved to another thread, it cannot borrow from the caller.  Services
+/// typically clone themselves and the arguments, and move the clones into the function.
+pub(in crate::server) async fn run_blocking<F, T>(f: F) -> Result<T>
+where
+    F: FnOnce() -> Result<T> + Send + 'static,
+    T: Send + 'static,
+{
+    match tokio::runtime::Handle::try_current() {
+        Ok(handle) => match handle.spawn_blocking(f).await {
+            Ok(res) => res,
+            Err(e) if e.is_panic() => std::panic::resume_unwind(e.into_panic()),
+            Err(e) => Err(Error::Other(e.into())),
+        },
+        Err(_) => f(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn without_runtime() {
+        let thread = std::thread::current().id();
+        let res = futures_executor::block_on(run_blocking(move || {
+            Ok(std::thread::current().id() == thread)
+        }));
+        assert_eq!(res.unwrap(), true);
+    }
+
+    #[test]
+    fn within_runtime() {
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let thread = std::thread::current().id();
+        let res = rt.block_on(run_blocking(move || {
+            Ok(std::thread::current().id() == thread)
+        }));
+        assert_eq!(res.unwrap(), false);
+    }
+
+    #[test]
+    fn error() {
+        let res: Result<()> =
+            futures_executor::block_on(run_blocking(|| Err(Error::Server("oops".into()))));
+        assert!(matches!(res, Err(Error::Server(msg)) if msg == "oops"));
+    }
+}
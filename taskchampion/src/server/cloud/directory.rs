@@ -0,0 +1,362 @@
+use super::blocking::run_blocking;
+use super::service::{ObjectInfo, Service};
+use crate::errors::{Error, Result};
+use async_trait::async_trait;
+use std::fs::{self, File, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use uuid::Uuid;
+
+/// How long to wait for a lock held by another process before giving up.  This is longer than
+/// [`STALE_LOCK_AGE`], so that a lock left behind by a crashed process is removed rather than
+/// causing a timeout.
+const LOCK_TIMEOUT: Duration = Duration::from_secs(90);
+
+/// A lock file older than this is assumed to have been left behind by a process that crashed
+/// while holding it, and is removed.
+const STALE_LOCK_AGE: Duration = Duration::from_secs(60);
+
+/// A [`Service`] implementation storing each object as a file in a directory.
+///
+/// This allows syncing through any shared filesystem, such as an NFS mount or a folder
+/// synchronized by a tool like Syncthing or Dropbox.  Files are written to a temporary file and
+/// renamed into place, so readers never see a partially-written object.  Compare-and-swap is
+/// implemented with a lock file, created exclusively.  This is reliable on local filesystems
+/// and NFS, but folder-synchronization tools do not propagate locks between machines, so with
+/// those tools concurrent syncs from different machines may conflict; the tool's conflict
+/// handling then applies.
+///
+/// Temporary and lock files begin with `.`, which never occurs in object names.
+///
+/// Within a Tokio runtime, file operations, including waiting for a lock, are performed on the
+/// runtime's blocking thread pool.
+#[derive(Clone)]
+pub(in crate::server) struct DirectoryService {
+    path: PathBuf,
+    lock_timeout: Duration,
+    stale_lock_age: Duration,
+}
+
+/// A held lock, released when dropped.
+struct Lock(PathBuf);
+
+impl Drop for Lock {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+impl DirectoryService {
+    pub(in crate::server) fn new(path: PathBuf) -> Result<Self> {
+        fs::create_dir_all(&path)?;
+        Ok(Self {
+            path,
+            lock_timeout: LOCK_TIMEOUT,
+            stale_lock_age: STALE_LOCK_AGE,
+        })
+    }
+
+    fn object_path(&self, name: &[u8]) -> PathBuf {
+        let name = std::str::from_utf8(name).expect("non-UTF8 object name");
+        self.path.join(name)
+    }
+
+    /// Acquire the lock for the named object, waiting for any other holder to release it.
+    fn lock(&self, name: &[u8]) -> Result<Lock> {
+        let name = std::str::from_utf8(name).expect("non-UTF8 object name");
+        let path = self.path.join(format!(".{name}.lock"));
+        let start = SystemTime::now();
+        loop {
+            match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(_) => return Ok(Lock(path)),
+                Err(e) if e.kind() == ErrorKind::AlreadyExists => {}
+                Err(e) => return Err(e.into()),
+            }
+
+            // Remove the lock if it is stale.  If it was released in the interim, try again
+            // immediately.
+            match fs::metadata(&path).and_then(|m| m.modified()) {
+                Ok(modified) => {
+                    if modified.elapsed().unwrap_or_default() > self.stale_lock_age {
+                        log::warn!("Removing stale lock file {:?}", path);
+                        let _ = fs::remove_file(&path);
+                        continue;
+                    }
+                }
+                Err(e) if e.kind() == ErrorKind::NotFound => continue,
+                Err(e) => return Err(e.into()),
+            }
+
+            if start.elapsed().unwrap_or_default() > self.lock_timeout {
+                return Err(Error::Server(format!(
+                    "Timed out waiting for lock file {:?}",
+                    path
+                )));
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    fn put_blocking(&self, name: &[u8], value: &[u8]) -> Result<()> {
+        let path = self.object_path(name);
+        let tmp_path = self.path.join(format!(
+            ".{}.tmp-{}",
+            std::str::from_utf8(name).expect("non-UTF8 object name"),
+            Uuid::new_v4().as_simple()
+        ));
+        let res = (|| {
+            let mut file = File::create(&tmp_path)?;
+            file.write_all(value)?;
+            file.sync_all()?;
+            fs::rename(&tmp_path, &path)
+        })();
+        if res.is_err() {
+            let _ = fs::remove_file(&tmp_path);
+        }
+        Ok(res?)
+    }
+
+    fn get_blocking(&self, name: &[u8]) -> Result<Option<Vec<u8>>> {
+        match fs::read(self.object_path(name)) {
+            Ok(data) => Ok(Some(data)),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn del_blocking(&self, name: &[u8]) -> Result<()> {
+        match fs::remove_file(self.object_path(name)) {
+            Err(e) if e.kind() != ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    fn list_blocking(&self, prefix: &[u8]) -> Result<Vec<ObjectInfo>> {
+        let mut objects = vec![];
+        for entry in fs::read_dir(&self.path)? {
+            let entry = entry?;
+            // non-UTF-8 file names are not objects
+            let Ok(name) = entry.file_name().into_string() else {
+                continue;
+            };
+            let name = name.into_bytes();
+            if name.starts_with(b".") || !name.starts_with(prefix) {
+                continue;
+            }
+            let creation = match entry.metadata().and_then(|m| m.modified()) {
+                Ok(modified) => modified
+                    .duration_since(UNIX_EPOCH)
+                    .map(|d| d.as_secs())
+                    .unwrap_or(0),
+                // the object was deleted after it was listed
+                Err(e) if e.kind() == ErrorKind::NotFound => continue,
+                Err(e) => return Err(e.into()),
+            };
+            objects.push(ObjectInfo { name, creation });
+        }
+        Ok(objects)
+    }
+
+    fn compare_and_swap_blocking(
+        &self,
+        name: &[u8],
+        existing_value: Option<Vec<u8>>,
+        new_value: Vec<u8>,
+    ) -> Result<bool> {
+        let _lock = self.lock(name)?;
+        if self.get_blocking(name)? != existing_value {
+            return Ok(false);
+        }
+        self.put_blocking(name, &new_value)?;
+        Ok(true)
+    }
+}
+
+#[async_trait]
+impl Service for DirectoryService {
+    async fn put(&mut self, name: &[u8], value: &[u8]) -> Result<()> {
+        let (svc, name, value) = (self.clone(), name.to_vec(), value.to_vec());
+        run_blocking(move || svc.put_blocking(&name, &value)).await
+    }
+
+    async fn get(&mut self, name: &[u8]) -> Result<Option<Vec<u8>>> {
+        let (svc, name) = (self.clone(), name.to_vec());
+        run_blocking(move || svc.get_blocking(&name)).await
+    }
+
+    async fn del(&mut self, name: &[u8]) -> Result<()> {
+        let (svc, name) = (self.clone(), name.to_vec());
+        run_blocking(move || svc.del_blocking(&name)).await
+    }
+
+    async fn list(&mut self, prefix: &[u8]) -> Result<Vec<ObjectInfo>> {
+        let (svc, prefix) = (self.clone(), prefix.to_vec());
+        run_blocking(move || svc.list_blocking(&prefix)).await
+    }
+
+    async fn compare_and_swap(
+        &mut self,
+        name: &[u8],
+        existing_value: Option<Vec<u8>>,
+        new_value: Vec<u8>,
+    ) -> Result<bool> {
+        let (svc, name) = (self.clone(), name.to_vec());
+        run_blocking(move || svc.compare_and_swap_blocking(&name, existing_value, new_value)).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_executor::block_on;
+    use pretty_assertions::assert_eq;
+    use tempfile::TempDir;
+
+    fn make_service() -> (TempDir, DirectoryService) {
+        let tmp_dir = TempDir::new().unwrap();
+        let svc = DirectoryService::new(tmp_dir.path().join("sync")).unwrap();
+        (tmp_dir, svc)
+    }
+
+    #[test]
+    fn put_and_get() {
+        let (_tmp, mut svc) = make_service();
+        block_on(svc.put(b"testy", b"foo")).unwrap();
+        assert_eq!(block_on(svc.get(b"testy")).unwrap(), Some(b"foo".to_vec()));
+        block_on(svc.put(b"testy", b"bar")).unwrap();
+        assert_eq!(block_on(svc.get(b"testy")).unwrap(), Some(b"bar".to_vec()));
+    }
+
+    #[test]
+    fn get_missing() {
+        let (_tmp, mut svc) = make_service();
+        assert_eq!(block_on(svc.get(b"testy")).unwrap(), None);
+    }
+
+    #[test]
+    fn del() {
+        let (_tmp, mut svc) = make_service();
+        block_on(svc.put(b"testy", b"data")).unwrap();
+        block_on(svc.del(b"testy")).unwrap();
+        assert_eq!(block_on(svc.get(b"testy")).unwrap(), None);
+        // deleting an object that does not exist is not an error
+        block_on(svc.del(b"testy")).unwrap();
+    }
+
+    #[test]
+    fn list() {
+        let (_tmp, mut svc) = make_service();
+        let mut names: Vec<_> = (0..5).map(|i| format!("pp-{i:02}").into_bytes()).collect();
+        names.sort();
+        for n in &names {
+            block_on(svc.put(n, b"data")).unwrap();
+        }
+        block_on(svc.put(b"xxx", b"data")).unwrap();
+        // a lock file, which is not an object
+        let _lock = svc.lock(b"pp-00").unwrap();
+
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        let got_objects: Vec<_> = block_on(svc.list(b"pp-")).unwrap();
+        assert!(got_objects
+            .iter()
+            .all(|oi| oi.creation <= now && oi.creation + 60 > now));
+        let mut got_names: Vec<_> = got_objects.into_iter().map(|oi| oi.name).collect();
+        got_names.sort();
+        assert_eq!(got_names, names);
+        assert_eq!(block_on(svc.list(b"")).unwrap().len(), 6);
+    }
+
+    #[test]
+    fn compare_and_swap_create() {
+        let (_tmp, mut svc) = make_service();
+        assert!(block_on(svc.compare_and_swap(b"testy", None, b"bar".to_vec())).unwrap());
+        assert_eq!(block_on(svc.get(b"testy")).unwrap(), Some(b"bar".to_vec()));
+    }
+
+    #[test]
+    fn compare_and_swap_matches() {
+        let (_tmp, mut svc) = make_service();
+        block_on(svc.put(b"testy", b"foo")).unwrap();
+        assert!(
+            block_on(svc.compare_and_swap(b"testy", Some(b"foo".to_vec()), b"bar".to_vec()))
+                .unwrap()
+        );
+        assert_eq!(block_on(svc.get(b"testy")).unwrap(), Some(b"bar".to_vec()));
+    }
+
+    #[test]
+    fn compare_and_swap_expected_no_file() {
+        let (_tmp, mut svc) = make_service();
+        block_on(svc.put(b"testy", b"foo")).unwrap();
+        assert!(!block_on(svc.compare_and_swap(b"testy", None, b"bar".to_vec())).unwrap());
+        assert_eq!(block_on(svc.get(b"testy")).unwrap(), Some(b"foo".to_vec()));
+    }
+
+    #[test]
+    fn compare_and_swap_mismatch() {
+        let (_tmp, mut svc) = make_service();
+        block_on(svc.put(b"testy", b"foo2")).unwrap();
+        assert!(
+            !block_on(svc.compare_and_swap(b"testy", Some(b"foo1".to_vec()), b"bar".to_vec()))
+                .unwrap()
+        );
+        assert_eq!(block_on(svc.get(b"testy")).unwrap(), Some(b"foo2".to_vec()));
+    }
+
+    #[test]
+    fn compare_and_swap_locked() {
+        let (_tmp, mut svc) = make_service();
+        svc.lock_timeout = Duration::from_millis(50);
+        let lock = svc.lock(b"testy").unwrap();
+        assert!(block_on(svc.compare_and_swap(b"testy", None, b"bar".to_vec())).is_err());
+        drop(lock);
+        assert!(block_on(svc.compare_and_swap(b"testy", None, b"bar".to_vec())).unwrap());
+    }
+
+    #[test]
+    fn compare_and_swap_locked_in_runtime() {
+        let (_tmp, mut svc) = make_service();
+        svc.lock_timeout = Duration::from_secs(1);
+        let lock = svc.lock(b"testy").unwrap();
+        // waiting for the lock must not prevent the runtime's only thread from releasing it
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        rt.block_on(async {
+            let release = tokio::spawn(async move { drop(lock) });
+            assert!(svc
+                .compare_and_swap(b"testy", None, b"bar".to_vec())
+                .await
+                .unwrap());
+            release.await.unwrap();
+        });
+    }
+
+    #[test]
+    fn compare_and_swap_stale_lock() {
+        let (_tmp, mut svc) = make_service();
+        svc.stale_lock_age = Duration::from_millis(10);
+        // leak a lock, as a crashed process would
+        std::mem::forget(svc.lock(b"testy").unwrap());
+        std::thread::sleep(Duration::from_millis(20));
+        assert!(block_on(svc.compare_and_swap(b"testy", None, b"bar".to_vec())).unwrap());
+        assert!(!svc.path.join(".testy.lock").exists());
+    }
+
+    #[test]
+    fn compare_and_swap_stale_lock_within_timeout() {
+        let (_tmp, mut svc) = make_service();
+        // scale down the default durations, keeping their relationship
+        svc.lock_timeout = LOCK_TIMEOUT / 300;
+        svc.stale_lock_age = STALE_LOCK_AGE / 300;
+        // leave a lock file, as a crashed process would, and let it age somewhat
+        File::create(svc.path.join(".testy.lock")).unwrap();
+        std::thread::sleep(svc.stale_lock_age / 4);
+        assert!(block_on(svc.compare_and_swap(b"testy", None, b"bar".to_vec())).unwrap());
+        assert!(!svc.path.join(".testy.lock").exists());
+    }
+}
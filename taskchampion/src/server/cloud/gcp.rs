@@ -1,16 +1,17 @@
 use super::service::{ObjectInfo, Service};
 use crate::errors::Result;
+use async_trait::async_trait;
 use google_cloud_storage::client::google_cloud_auth::credentials::CredentialsFile;
 use google_cloud_storage::client::{Client, ClientConfig};
 use google_cloud_storage::http::error::ErrorResponse;
 use google_cloud_storage::http::Error as GcsError;
 use google_cloud_storage::http::{self, objects};
-use tokio::runtime::Runtime;
 
 /// A [`Service`] implementation based on the Google Cloud Storage service.
+///
+/// The client library is based on Tokio, so this service must be used within a Tokio runtime.
 pub(in crate::server) struct GcpService {
     client: Client,
-    rt: Runtime,
     bucket: String,
 }
 
@@ -26,49 +27,57 @@ fn is_http_error<T>(query: u16, res: &std::result::Result<T, http::Error>) -> bo
 }
 
 impl GcpService {
-    pub(in crate::server) fn new(bucket: String, credential_path: Option<String>) -> Result<Self> {
-        let rt = Runtime::new()?;
-
+    pub(in crate::server) async fn new(
+        bucket: String,
+        credential_path: Option<String>,
+    ) -> Result<Self> {
         let config: ClientConfig = if let Some(credentials) = credential_path {
-            let credentials = rt.block_on(CredentialsFile::new_from_file(credentials))?;
-            rt.block_on(ClientConfig::default().with_credentials(credentials))?
+            let credentials = CredentialsFile::new_from_file(credentials).await?;
+            ClientConfig::default()
+                .with_credentials(credentials)
+                .await?
         } else {
-            rt.block_on(ClientConfig::default().with_auth())?
+            ClientConfig::default().with_auth().await?
         };
 
         Ok(Self {
             client: Client::new(config),
-            rt,
             bucket,
         })
     }
 }
 
+#[async_trait]
 impl Service for GcpService {
-    fn put(&mut self, name: &[u8], value: &[u8]) -> Result<()> {
+    async fn put(&mut self, name: &[u8], value: &[u8]) -> Result<()> {
         let name = String::from_utf8(name.to_vec()).expect("non-UTF8 object name");
         let upload_type = objects::upload::UploadType::Simple(objects::upload::Media::new(name));
-        self.rt.block_on(self.client.upload_object(
-            &objects::upload::UploadObjectRequest {
-                bucket: self.bucket.clone(),
-                ..Default::default()
-            },
-            value.to_vec(),
-            &upload_type,
-        ))?;
+        self.client
+            .upload_object(
+                &objects::upload::UploadObjectRequest {
+                    bucket: self.bucket.clone(),
+                    ..Default::default()
+                },
+                value.to_vec(),
+                &upload_type,
+            )
+            .await?;
         Ok(())
     }
 
-    fn get(&mut self, name: &[u8]) -> Result<Option<Vec<u8>>> {
+    async fn get(&mut self, name: &[u8]) -> Result<Option<Vec<u8>>> {
         let name = String::from_utf8(name.to_vec()).expect("non-UTF8 object name");
-        let download_res = self.rt.block_on(self.client.download_object(
-            &objects::get::GetObjectRequest {
-                bucket: self.bucket.clone(),
-                object: name,
-                ..Default::default()
-            },
-            &objects::download::Range::default(),
-        ));
+        let download_res = self
+            .client
+            .download_object(
+                &objects::get::GetObjectRequest {
+                    bucket: self.bucket.clone(),
+                    object: name,
+                    ..Default::default()
+                },
+                &objects::download::Range::default(),
+            )
+            .await;
         if is_http_error(404, &download_res) {
             Ok(None)
         } else {
@@ -76,32 +85,57 @@ impl Service for GcpService {
         }
     }
 
-    fn del(&mut self, name: &[u8]) -> Result<()> {
+    async fn del(&mut self, name: &[u8]) -> Result<()> {
         let name = String::from_utf8(name.to_vec()).expect("non-UTF8 object name");
-        let del_res = self.rt.block_on(self.client.delete_object(
-            &objects::delete::DeleteObjectRequest {
+        let del_res = self
+            .client
+            .delete_object(&objects::delete::DeleteObjectRequest {
                 bucket: self.bucket.clone(),
                 object: name,
                 ..Default::default()
-            },
-        ));
+            })
+            .await;
         if !is_http_error(404, &del_res) {
             del_res?;
         }
         Ok(())
     }
 
-    fn list<'a>(&'a mut self, prefix: &[u8]) -> Box<dyn Iterator<Item = Result<ObjectInfo>> + 'a> {
+    async fn list(&mut self, prefix: &[u8]) -> Result<Vec<ObjectInfo>> {
         let prefix = String::from_utf8(prefix.to_vec()).expect("non-UTF8 object prefix");
-        Box::new(ObjectIterator {
-            service: self,
-            prefix,
-            last_response: None,
-            next_index: 0,
-        })
+        let mut objects = vec![];
+        let mut page_token = None;
+        // Fetch pages of results until there is no next page.
+        loop {
+            let response = self
+                .client
+                .list_objects(&objects::list::ListObjectsRequest {
+                    bucket: self.bucket.clone(),
+                    prefix: Some(prefix.clone()),
+                    page_token,
+                    #[cfg(test)] // For testing, use a small page size.
+                    max_results: Some(6),
+                    ..Default::default()
+                })
+                .await?;
+            for obj in response.items.unwrap_or_default() {
+                // It's unclear when `time_created` would be None, so default to 0 in that case
+                // or when the timestamp is not a valid u64 (before 1970).
+                let creation = obj.time_created.map(|t| t.unix_timestamp()).unwrap_or(0);
+                let creation: u64 = creation.try_into().unwrap_or(0);
+                objects.push(ObjectInfo {
+                    name: obj.name.into_bytes(),
+                    creation,
+                });
+            }
+            page_token = response.next_page_token;
+            if page_token.is_none() {
+                return Ok(objects);
+            }
+        }
     }
 
-    fn compare_and_swap(
+    async fn compare_and_swap(
         &mut self,
         name: &[u8],
         existing_value: Option<Vec<u8>>,
@@ -109,12 +143,13 @@ impl Service for GcpService {
     ) -> Result<bool> {
         let name = String::from_utf8(name.to_vec()).expect("non-UTF8 object name");
         let get_res = self
-            .rt
-            .block_on(self.client.get_object(&objects::get::GetObjectRequest {
+            .client
+            .get_object(&objects::get::GetObjectRequest {
                 bucket: self.bucket.clone(),
                 object: name.clone(),
                 ..Default::default()
-            }));
+            })
+            .await;
         // Determine the object's generation. See https://cloud.google.com/storage/docs/metadata#generation-number
         let generation = if is_http_error(404, &get_res) {
             // If a value was expected, that expectation has not been met.
@@ -129,16 +164,19 @@ impl Service for GcpService {
 
         // If the file existed, then verify its contents.
         if generation > 0 {
-            let data = self.rt.block_on(self.client.download_object(
-                &objects::get::GetObjectRequest {
-                    bucket: self.bucket.clone(),
-                    object: name.clone(),
-                    // Fetch the same generation.
-                    generation: Some(generation),
-                    ..Default::default()
-                },
-                &objects::download::Range::default(),
-            ))?;
+            let data = self
+                .client
+                .download_object(
+                    &objects::get::GetObjectRequest {
+                        bucket: self.bucket.clone(),
+                        object: name.clone(),
+                        // Fetch the same generation.
+                        generation: Some(generation),
+                        ..Default::default()
+                    },
+                    &objects::download::Range::default(),
+                )
+                .await?;
             if Some(data) != existing_value {
                 return Ok(false);
             }
@@ -146,15 +184,18 @@ impl Service for GcpService {
 
         // Finally, put the new value with a condition that the generation hasn't changed.
         let upload_type = objects::upload::UploadType::Simple(objects::upload::Media::new(name));
-        let upload_res = self.rt.block_on(self.client.upload_object(
-            &objects::upload::UploadObjectRequest {
-                bucket: self.bucket.clone(),
-                if_generation_match: Some(generation),
-                ..Default::default()
-            },
-            new_value.to_vec(),
-            &upload_type,
-        ));
+        let upload_res = self
+            .client
+            .upload_object(
+                &objects::upload::UploadObjectRequest {
+                    bucket: self.bucket.clone(),
+                    if_generation_match: Some(generation),
+                    ..Default::default()
+                },
+                new_value.to_vec(),
+                &upload_type,
+            )
+            .await;
         if is_http_error(412, &upload_res) {
             // A 412 indicates the precondition was not satisfied: the given generation
             // is no longer the latest.
@@ -166,78 +207,19 @@ impl Service for GcpService {
     }
 }
 
-/// An Iterator returning names of objects from `list_objects`.
-///
-/// This handles response pagination by fetching one page at a time.
-struct ObjectIterator<'a> {
-    service: &'a mut GcpService,
-    prefix: String,
-    last_response: Option<objects::list::ListObjectsResponse>,
-    next_index: usize,
-}
-
-impl<'a> ObjectIterator<'a> {
-    fn fetch_batch(&mut self) -> Result<()> {
-        let mut page_token = None;
-        if let Some(ref resp) = self.last_response {
-            page_token.clone_from(&resp.next_page_token);
-        }
-        self.last_response = Some(self.service.rt.block_on(self.service.client.list_objects(
-            &objects::list::ListObjectsRequest {
-                bucket: self.service.bucket.clone(),
-                prefix: Some(self.prefix.clone()),
-                page_token,
-                #[cfg(test)] // For testing, use a small page size.
-                max_results: Some(6),
-                ..Default::default()
-            },
-        ))?);
-        self.next_index = 0;
-        Ok(())
-    }
-}
-
-impl<'a> Iterator for ObjectIterator<'a> {
-    type Item = Result<ObjectInfo>;
-    fn next(&mut self) -> Option<Self::Item> {
-        // If the iterator is just starting, fetch the first response.
-        if self.last_response.is_none() {
-            if let Err(e) = self.fetch_batch() {
-                return Some(Err(e));
-            }
-        }
-        if let Some(ref result) = self.last_response {
-            if let Some(ref items) = result.items {
-                if self.next_index < items.len() {
-                    // Return a result from the existing response.
-                    let obj = &items[self.next_index];
-                    self.next_index += 1;
-                    // It's unclear when `time_created` would be None, so default to 0 in that case
-                    // or when the timestamp is not a valid u64 (before 1970).
-                    let creation = obj.time_created.map(|t| t.unix_timestamp()).unwrap_or(0);
-                    let creation: u64 = creation.try_into().unwrap_or(0);
-                    return Some(Ok(ObjectInfo {
-                        name: obj.name.as_bytes().to_vec(),
-                        creation,
-                    }));
-                } else if result.next_page_token.is_some() {
-                    // Fetch the next page and try again.
-                    if let Err(e) = self.fetch_batch() {
-                        return Some(Err(e));
-                    }
-                    return self.next();
-                }
-            }
-        }
-        None
-    }
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::sync::OnceLock;
+    use tokio::runtime::Runtime;
     use uuid::Uuid;
 
+    /// Run the given future in a runtime shared by all tests, as the client requires.
+    fn block_on<F: std::future::Future>(fut: F) -> F::Output {
+        static RT: OnceLock<Runtime> = OnceLock::new();
+        RT.get_or_init(|| Runtime::new().unwrap()).block_on(fut)
+    }
+
     /// Make a service if `GCP_TEST_BUCKET` is set, as well as a function to put a unique prefix on
     /// an object name, so that tests do not interfere with one another.
     ///
@@ -259,7 +241,7 @@ mod tests {
 
         let prefix = Uuid::new_v4();
         Some((
-            GcpService::new(bucket, Some(credential_path)).unwrap(),
+            block_on(GcpService::new(bucket, Some(credential_path))).unwrap(),
             move |n: &_| format!("{}-{}", prefix.as_simple(), n).into_bytes(),
         ))
     }
@@ -269,12 +251,12 @@ mod tests {
         let Some((mut svc, pfx)) = make_service() else {
             return;
         };
-        svc.put(&pfx("testy"), b"foo").unwrap();
-        let got = svc.get(&pfx("testy")).unwrap();
+        block_on(svc.put(&pfx("testy"), b"foo")).unwrap();
+        let got = block_on(svc.get(&pfx("testy"))).unwrap();
         assert_eq!(got, Some(b"foo".to_vec()));
 
         // Clean up.
-        svc.del(&pfx("testy")).unwrap();
+        block_on(svc.del(&pfx("testy"))).unwrap();
     }
 
     #[test]
@@ -282,7 +264,7 @@ mod tests {
         let Some((mut svc, pfx)) = make_service() else {
             return;
         };
-        let got = svc.get(&pfx("testy")).unwrap();
+        let got = block_on(svc.get(&pfx("testy"))).unwrap();
         assert_eq!(got, None);
     }
 
@@ -291,9 +273,9 @@ mod tests {
         let Some((mut svc, pfx)) = make_service() else {
             return;
         };
-        svc.put(&pfx("testy"), b"data").unwrap();
-        svc.del(&pfx("testy")).unwrap();
-        let got = svc.get(&pfx("testy")).unwrap();
+        block_on(svc.put(&pfx("testy"), b"data")).unwrap();
+        block_on(svc.del(&pfx("testy"))).unwrap();
+        let got = block_on(svc.get(&pfx("testy"))).unwrap();
         assert_eq!(got, None);
     }
 
@@ -304,7 +286,7 @@ mod tests {
             return;
         };
 
-        assert!(svc.del(&pfx("testy")).is_ok());
+        assert!(block_on(svc.del(&pfx("testy"))).is_ok());
     }
 
     #[test]
@@ -316,21 +298,21 @@ mod tests {
         names.sort();
         // Create 20 objects that will be listed.
         for n in &names {
-            svc.put(n, b"data").unwrap();
+            block_on(svc.put(n, b"data")).unwrap();
         }
         // And another object that should not be included in the list.
-        svc.put(&pfx("xxx"), b"data").unwrap();
+        block_on(svc.put(&pfx("xxx"), b"data")).unwrap();
 
-        let got_objects: Vec<_> = svc.list(&pfx("pp-")).collect::<Result<_>>().unwrap();
+        let got_objects: Vec<_> = block_on(svc.list(&pfx("pp-"))).unwrap();
         let mut got_names: Vec<_> = got_objects.into_iter().map(|oi| oi.name).collect();
         got_names.sort();
         assert_eq!(got_names, names);
 
         // Clean up.
         for n in got_names {
-            svc.del(&n).unwrap();
+            block_on(svc.del(&n)).unwrap();
         }
-        svc.del(&pfx("xxx")).unwrap();
+        block_on(svc.del(&pfx("xxx"))).unwrap();
     }
 
     #[test]
@@ -339,14 +321,12 @@ mod tests {
             return;
         };
 
-        assert!(svc
-            .compare_and_swap(&pfx("testy"), None, b"bar".to_vec())
-            .unwrap());
-        let got = svc.get(&pfx("testy")).unwrap();
+        assert!(block_on(svc.compare_and_swap(&pfx("testy"), None, b"bar".to_vec())).unwrap());
+        let got = block_on(svc.get(&pfx("testy"))).unwrap();
         assert_eq!(got, Some(b"bar".to_vec()));
 
         // Clean up.
-        svc.del(&pfx("testy")).unwrap();
+        block_on(svc.del(&pfx("testy"))).unwrap();
     }
 
     #[test]
@@ -356,16 +336,19 @@ mod tests {
         };
 
         // Create the existing file, with two generations.
-        svc.put(&pfx("testy"), b"foo1").unwrap();
-        svc.put(&pfx("testy"), b"foo2").unwrap();
-        assert!(svc
-            .compare_and_swap(&pfx("testy"), Some(b"foo2".to_vec()), b"bar".to_vec())
-            .unwrap());
-        let got = svc.get(&pfx("testy")).unwrap();
+        block_on(svc.put(&pfx("testy"), b"foo1")).unwrap();
+        block_on(svc.put(&pfx("testy"), b"foo2")).unwrap();
+        assert!(block_on(svc.compare_and_swap(
+            &pfx("testy"),
+            Some(b"foo2".to_vec()),
+            b"bar".to_vec()
+        ))
+        .unwrap());
+        let got = block_on(svc.get(&pfx("testy"))).unwrap();
         assert_eq!(got, Some(b"bar".to_vec()));
 
         // Clean up.
-        svc.del(&pfx("testy")).unwrap();
+        block_on(svc.del(&pfx("testy"))).unwrap();
     }
 
     #[test]
@@ -374,15 +357,13 @@ mod tests {
             return;
         };
 
-        svc.put(&pfx("testy"), b"foo1").unwrap();
-        assert!(!svc
-            .compare_and_swap(&pfx("testy"), None, b"bar".to_vec())
-            .unwrap());
-        let got = svc.get(&pfx("testy")).unwrap();
+        block_on(svc.put(&pfx("testy"), b"foo1")).unwrap();
+        assert!(!block_on(svc.compare_and_swap(&pfx("testy"), None, b"bar".to_vec())).unwrap());
+        let got = block_on(svc.get(&pfx("testy"))).unwrap();
         assert_eq!(got, Some(b"foo1".to_vec()));
 
         // Clean up.
-        svc.del(&pfx("testy")).unwrap();
+        block_on(svc.del(&pfx("testy"))).unwrap();
     }
 
     #[test]
@@ -392,15 +373,18 @@ mod tests {
         };
 
         // Create the existing file, with two generations.
-        svc.put(&pfx("testy"), b"foo1").unwrap();
-        svc.put(&pfx("testy"), b"foo2").unwrap();
-        assert!(!svc
-            .compare_and_swap(&pfx("testy"), Some(b"foo1".to_vec()), b"bar".to_vec())
-            .unwrap());
-        let got = svc.get(&pfx("testy")).unwrap();
+        block_on(svc.put(&pfx("testy"), b"foo1")).unwrap();
+        block_on(svc.put(&pfx("testy"), b"foo2")).unwrap();
+        assert!(!block_on(svc.compare_and_swap(
+            &pfx("testy"),
+            Some(b"foo1".to_vec()),
+            b"bar".to_vec()
+        ))
+        .unwrap());
+        let got = block_on(svc.get(&pfx("testy"))).unwrap();
         assert_eq!(got, Some(b"foo2".to_vec()));
 
         // Clean up.
-        svc.del(&pfx("testy")).unwrap();
+        block_on(svc.del(&pfx("testy"))).unwrap();
     }
 }
@@ -10,7 +10,22 @@
 mod server;
 mod service;
 
+#[cfg(any(feature = "server-directory", feature = "server-webdav"))]
+mod blocking;
+
+#[cfg(any(feature = "server-directory", feature = "server-webdav"))]
+pub(in crate::server) use blocking::run_blocking;
+
 pub(in crate::server) use server::CloudServer;
 
+#[cfg(feature = "server-aws")]
+pub(in crate::server) mod aws;
+
+#[cfg(feature = "server-directory")]
+pub(in crate::server) mod directory;
+
 #[cfg(feature = "server-gcp")]
 pub(in crate::server) mod gcp;
+
+#[cfg(feature = "server-webdav")]
+pub(in crate::server) mod webdav;
@@ -1,17 +1,18 @@
 use super::service::{ObjectInfo, Service};
 use crate::errors::{Error, Result};
-use crate::server::encryption::{Cryptor, Sealed, Unsealed};
+use crate::server::encryption::{Cryptor, Sealed, Unsealed, SALT_LEN};
 use crate::server::{
-    AddVersionResult, GetVersionResult, HistorySegment, Server, Snapshot, SnapshotUrgency,
+    AddVersionResult, AsyncServer, GetVersionResult, HistorySegment, Snapshot, SnapshotUrgency,
     VersionId,
 };
+use async_trait::async_trait;
 use ring::rand;
 use std::collections::{HashMap, HashSet};
 #[cfg(not(test))]
 use std::time::{SystemTime, UNIX_EPOCH};
 use uuid::Uuid;
 
-/// Implement the Server trait for a cloud service implemented by [`Service`].
+/// Implement the AsyncServer trait for a cloud service implemented by [`Service`].
 ///
 /// This type implements a TaskChampion server over a basic object-storage service. It encapsulates
 /// all of the logic to ensure a linear sequence of versions, encrypt and decrypt data, and clean
@@ -24,6 +25,17 @@ use uuid::Uuid;
 /// encrypted, by the nature of key/value stores. Since the content of the "latest" object can
 /// usually be inferred from object names, it, too, is not encrypted.
 ///
+/// The encryption secret is changed by adding a new version and a snapshot at that version, both
+/// sealed with a key derived from the new secret and a new salt, and then replacing "salt".
+/// Replicas using the old key fail to decrypt the new version, and so cannot add versions after
+/// it. Snapshots sealed with the old key are deleted, so that new replicas begin from the new
+/// snapshot. Versions sealed with the old key remain until they are cleaned up.
+///
+/// Before the new version is committed, the old and new salts are stored, concatenated, in an
+/// object with name `salt-VERSION`. If the change is interrupted after the version is committed
+/// but before "salt" is replaced, the next server to start completes it from this object. The
+/// object is deleted once the change is complete.
+///
 /// ## Object Organization
 ///
 /// UUIDs emebedded in names and values appear in their "simple" form: lower-case hexadecimal with
@@ -59,6 +71,9 @@ use uuid::Uuid;
 pub(in crate::server) struct CloudServer<SVC: Service> {
     service: SVC,
 
+    /// The salt from which the key was derived, as stored in "salt".
+    salt: Vec<u8>,
+
     /// The Cryptor supporting encryption and decryption of objects in this server.
     cryptor: Cryptor,
 
@@ -69,9 +84,15 @@ pub(in crate::server) struct CloudServer<SVC: Service> {
     /// a concurrent change in the service.
     #[cfg(test)]
     add_version_intercept: Option<fn(service: &mut SVC)>,
+
+    /// For testing, a function that is called in `rotate_encryption_secret` after the new version
+    /// is committed, to simulate a concurrent change in the service.
+    #[cfg(test)]
+    rotate_intercept: Option<fn(service: &mut SVC)>,
 }
 
 const LATEST: &[u8] = b"latest";
+const SALT: &[u8] = b"salt";
 const DEFAULT_CLEANUP_PROBABILITY: u8 = 13; // about 5%
 
 #[cfg(not(test))]
@@ -82,29 +103,89 @@ fn version_to_bytes(v: VersionId) -> Vec<u8> {
 }
 
 impl<SVC: Service> CloudServer<SVC> {
-    pub(in crate::server) fn new(mut service: SVC, encryption_secret: Vec<u8>) -> Result<Self> {
-        let salt = Self::get_salt(&mut service)?;
-        let cryptor = Cryptor::new(salt, &encryption_secret.into())?;
+    pub(in crate::server) async fn new(
+        mut service: SVC,
+        encryption_secret: Vec<u8>,
+    ) -> Result<Self> {
+        let salt = Self::get_salt(&mut service).await?;
+        let salt = Self::recover_salt(&mut service, salt).await?;
+        let cryptor = Cryptor::new(&salt, &encryption_secret.into())?;
         Ok(Self {
             service,
+            salt,
             cryptor,
             cleanup_probability: DEFAULT_CLEANUP_PROBABILITY,
             #[cfg(test)]
             add_version_intercept: None,
+            #[cfg(test)]
+            rotate_intercept: None,
         })
     }
 
     /// Get the salt value stored in the service, creating a new random one if necessary.
-    fn get_salt(service: &mut SVC) -> Result<Vec<u8>> {
-        const SALT_NAME: &[u8] = b"salt";
+    async fn get_salt(service: &mut SVC) -> Result<Vec<u8>> {
         loop {
-            if let Some(salt) = service.get(SALT_NAME)? {
+            if let Some(salt) = service.get(SALT).await? {
                 return Ok(salt);
             }
-            service.compare_and_swap(SALT_NAME, None, Cryptor::gen_salt()?)?;
+            service
+                .compare_and_swap(SALT, None, Cryptor::gen_salt()?)
+                .await?;
+        }
+    }
+
+    /// Complete any change of encryption secret that was interrupted after its new version was
+    /// committed, replacing "salt" with the new salt recorded for that version. Returns the
+    /// resulting salt.
+    async fn recover_salt(service: &mut SVC, mut salt: Vec<u8>) -> Result<Vec<u8>> {
+        let latest = Self::read_latest(service).await?;
+        loop {
+            let mut new_salt = None;
+            for ObjectInfo { name, .. } in service.list(b"salt-").await? {
+                let Some(version_id) = Self::parse_rotation_name(&name) else {
+                    continue;
+                };
+                let Some(salts) = service.get(&name).await? else {
+                    continue;
+                };
+                if salts.len() < SALT_LEN || salts[..salts.len() - SALT_LEN] != salt[..] {
+                    continue;
+                }
+                // Only the latest version can gain children, so a version is committed if it is
+                // latest or has children.
+                let committed = latest == Some(version_id)
+                    || !service
+                        .list(format!("v-{}-", version_id.as_simple()).as_bytes())
+                        .await?
+                        .is_empty();
+                if committed {
+                    new_salt = Some(salts[salts.len() - SALT_LEN..].to_vec());
+                    break;
+                }
+            }
+            let Some(new_salt) = new_salt else {
+                return Ok(salt);
+            };
+            service.compare_and_swap(SALT, Some(salt), new_salt).await?;
+            salt = Self::get_salt(service).await?;
         }
     }
 
+    /// Generate an object name for the salts recorded for a change of encryption secret at the
+    /// given version.
+    fn rotation_name(version_id: &VersionId) -> Vec<u8> {
+        format!("salt-{}", version_id.as_simple()).into_bytes()
+    }
+
+    /// Parse a name as generated by `rotation_name`, returning None if the name does not have a
+    /// valid format.
+    fn parse_rotation_name(name: &[u8]) -> Option<VersionId> {
+        if name.len() != 5 + 32 || !name.starts_with(b"salt-") {
+            return None;
+        }
+        VersionId::try_parse_ascii(&name[5..]).ok()
+    }
+
     /// Generate an object name for the given parent and child versions.
     fn version_name(parent_version_id: &VersionId, child_version_id: &VersionId) -> Vec<u8> {
         format!(
@@ -159,8 +240,13 @@ impl<SVC: Service> CloudServer<SVC> {
 
     /// Get the version from "latest", or None if the object does not exist. This always fetches a fresh
     /// value from storage.
-    fn get_latest(&mut self) -> Result<Option<VersionId>> {
-        let Some(latest) = self.service.get(LATEST)? else {
+    async fn get_latest(&mut self) -> Result<Option<VersionId>> {
+        Self::read_latest(&mut self.service).await
+    }
+
+    /// Get the version from "latest" in the given service, as for `get_latest`.
+    async fn read_latest(service: &mut SVC) -> Result<Option<VersionId>> {
+        let Some(latest) = service.get(LATEST).await? else {
             return Ok(None);
         };
         let latest = VersionId::try_parse_ascii(&latest)
@@ -170,20 +256,17 @@ impl<SVC: Service> CloudServer<SVC> {
 
     /// Get the possible child versions of the given parent version, based only on the object
     /// names.
-    fn get_child_versions(&mut self, parent_version_id: &VersionId) -> Result<Vec<VersionId>> {
-        self.service
+    async fn get_child_versions(
+        &mut self,
+        parent_version_id: &VersionId,
+    ) -> Result<Vec<VersionId>> {
+        Ok(self
+            .service
             .list(format!("v-{}-", parent_version_id.as_simple()).as_bytes())
-            .filter_map(|res| match res {
-                Ok(ObjectInfo { name, .. }) => {
-                    if let Some((_, c)) = Self::parse_version_name(&name) {
-                        Some(Ok(c))
-                    } else {
-                        None
-                    }
-                }
-                Err(e) => Some(Err(e)),
-            })
-            .collect::<Result<Vec<_>>>()
+            .await?
+            .into_iter()
+            .filter_map(|ObjectInfo { name, .. }| Self::parse_version_name(&name).map(|(_, c)| c))
+            .collect())
     }
 
     /// Determine the snapshot urgency. This is done probabalistically:
@@ -201,32 +284,27 @@ impl<SVC: Service> CloudServer<SVC> {
     }
 
     /// Maybe call `cleanup` depending on `cleanup_probability`.
-    fn maybe_cleanup(&mut self) -> Result<()> {
+    async fn maybe_cleanup(&mut self) -> Result<()> {
         if self.randint()? < self.cleanup_probability {
             self.cleanup_probability = DEFAULT_CLEANUP_PROBABILITY;
-            self.cleanup()
+            self.cleanup().await
         } else {
             Ok(())
         }
     }
 
     /// Perform cleanup, deleting unnecessary data.
-    fn cleanup(&mut self) -> Result<()> {
+    async fn cleanup(&mut self) -> Result<()> {
         // Construct a vector containing all (child, parent, creation) tuples
         let mut versions = self
             .service
             .list(b"v-")
-            .filter_map(|res| match res {
-                Ok(ObjectInfo { name, creation }) => {
-                    if let Some((p, c)) = Self::parse_version_name(&name) {
-                        Some(Ok((c, p, creation)))
-                    } else {
-                        None
-                    }
-                }
-                Err(e) => Some(Err(e)),
+            .await?
+            .into_iter()
+            .filter_map(|ObjectInfo { name, creation }| {
+                Self::parse_version_name(&name).map(|(p, c)| (c, p, creation))
             })
-            .collect::<Result<Vec<_>>>()?;
+            .collect::<Vec<_>>();
         versions.sort();
 
         // Function to find the parent of a given child version in `versions`, taking
@@ -240,7 +318,7 @@ impl<SVC: Service> CloudServer<SVC> {
         // at "latest".
         let mut rev_chain = HashMap::new();
         let mut iterations = versions.len() + 1; // For cycle detection.
-        let latest = self.get_latest()?;
+        let latest = self.get_latest().await?;
         if let Some(mut c) = latest {
             while let Some(p) = parent_of(c) {
                 rev_chain.insert(c, p);
@@ -282,7 +360,7 @@ impl<SVC: Service> CloudServer<SVC> {
         // so any pair with parent equal to latest is allowed to stay.
         for (c, p, _) in versions {
             if rev_chain.get(&c) != Some(&p) && Some(p) != latest {
-                self.service.del(&Self::version_name(&p, &c))?;
+                self.service.del(&Self::version_name(&p, &c)).await?;
             }
         }
 
@@ -290,11 +368,10 @@ impl<SVC: Service> CloudServer<SVC> {
         let snapshots = self
             .service
             .list(b"s-")
-            .filter_map(|res| match res {
-                Ok(ObjectInfo { name, .. }) => Self::parse_snapshot_name(&name).map(Ok),
-                Err(e) => Some(Err(e)),
-            })
-            .collect::<Result<HashSet<_>>>()?;
+            .await?
+            .into_iter()
+            .filter_map(|ObjectInfo { name, .. }| Self::parse_snapshot_name(&name))
+            .collect::<HashSet<_>>();
 
         // Find the latest snapshot by iterating back from "latest". Note that this iteration is
         // guaranteed not to be cyclical, as that was checked above.
@@ -320,7 +397,7 @@ impl<SVC: Service> CloudServer<SVC> {
         };
         for version in snapshots {
             if version != latest_snapshot {
-                self.service.del(&Self::snapshot_name(&version))?;
+                self.service.del(&Self::snapshot_name(&version)).await?;
             }
         }
 
@@ -329,7 +406,9 @@ impl<SVC: Service> CloudServer<SVC> {
         let mut version = latest_snapshot;
         while let Some(parent) = rev_chain.get(&version) {
             if old_versions.contains(&version) {
-                self.service.del(&Self::version_name(parent, &version))?;
+                self.service
+                    .del(&Self::version_name(parent, &version))
+                    .await?;
             }
             version = *parent;
         }
@@ -338,13 +417,14 @@ impl<SVC: Service> CloudServer<SVC> {
     }
 }
 
-impl<SVC: Service> Server for CloudServer<SVC> {
-    fn add_version(
+#[async_trait]
+impl<SVC: Service> AsyncServer for CloudServer<SVC> {
+    async fn add_version(
         &mut self,
         parent_version_id: VersionId,
         history_segment: HistorySegment,
     ) -> Result<(AddVersionResult, SnapshotUrgency)> {
-        let latest = self.get_latest()?;
+        let latest = self.get_latest().await?;
         if let Some(l) = latest {
             if l != parent_version_id {
                 return Ok((
@@ -361,7 +441,7 @@ impl<SVC: Service> Server for CloudServer<SVC> {
             version_id,
             payload: history_segment,
         })?;
-        self.service.put(&new_name, sealed.as_ref())?;
+        self.service.put(&new_name, sealed.as_ref()).await?;
 
         #[cfg(test)]
         if let Some(f) = self.add_version_intercept {
@@ -373,11 +453,12 @@ impl<SVC: Service> Server for CloudServer<SVC> {
         let new_value = version_to_bytes(version_id);
         if !self
             .service
-            .compare_and_swap(LATEST, old_value, new_value)?
+            .compare_and_swap(LATEST, old_value, new_value)
+            .await?
         {
             // Delete the version data, since it was not latest.
-            self.service.del(&new_name)?;
-            let latest = self.get_latest()?;
+            self.service.del(&new_name).await?;
+            let latest = self.get_latest().await?;
             let latest = latest.unwrap_or(Uuid::nil());
             return Ok((
                 AddVersionResult::ExpectedParentVersion(latest),
@@ -386,23 +467,26 @@ impl<SVC: Service> Server for CloudServer<SVC> {
         }
 
         // Attempt a cleanup, but ignore errors.
-        let _ = self.maybe_cleanup();
+        let _ = self.maybe_cleanup().await;
 
         Ok((AddVersionResult::Ok(version_id), self.snapshot_urgency()?))
     }
 
-    fn get_child_version(&mut self, parent_version_id: VersionId) -> Result<GetVersionResult> {
+    async fn get_child_version(
+        &mut self,
+        parent_version_id: VersionId,
+    ) -> Result<GetVersionResult> {
         // The `get_child_versions` function may return several possible children, only one of
         // those will lead to `latest`, and importantly the others will not have their own
         // children. So we can detect the "true" child as the one that is equal to "latest" or has
         // children. Note that even if `get_child_versions` returns a single version, that version
         // may not be valid and the appropriate result may be NoSuchVersion.
-        let version_id = match &(self.get_child_versions(&parent_version_id)?)[..] {
+        let version_id = match &(self.get_child_versions(&parent_version_id).await?)[..] {
             [] => return Ok(GetVersionResult::NoSuchVersion),
             children => {
                 // There are some extra version objects, so a cleanup is warranted.
                 self.cleanup_probability = 255;
-                let latest = self.get_latest()?;
+                let latest = self.get_latest().await?;
                 let mut true_child = None;
                 for child in children {
                     if Some(*child) == latest {
@@ -412,7 +496,7 @@ impl<SVC: Service> Server for CloudServer<SVC> {
                 }
                 if true_child.is_none() {
                     for child in children {
-                        if !self.get_child_versions(child)?.is_empty() {
+                        if !self.get_child_versions(child).await?.is_empty() {
                             true_child = Some(*child)
                         }
                     }
@@ -426,7 +510,8 @@ impl<SVC: Service> Server for CloudServer<SVC> {
 
         let Some(sealed) = self
             .service
-            .get(&Self::version_name(&parent_version_id, &version_id))?
+            .get(&Self::version_name(&parent_version_id, &version_id))
+            .await?
         else {
             // This really shouldn't happen, since the chain was derived from object names, but
             // perhaps the object was deleted.
@@ -443,26 +528,26 @@ impl<SVC: Service> Server for CloudServer<SVC> {
         })
     }
 
-    fn add_snapshot(&mut self, version_id: VersionId, snapshot: Snapshot) -> Result<()> {
+    async fn add_snapshot(&mut self, version_id: VersionId, snapshot: Snapshot) -> Result<()> {
         let name = Self::snapshot_name(&version_id);
         let sealed = self.cryptor.seal(Unsealed {
             version_id,
             payload: snapshot,
         })?;
-        self.service.put(&name, sealed.as_ref())?;
+        self.service.put(&name, sealed.as_ref()).await?;
         Ok(())
     }
 
-    fn get_snapshot(&mut self) -> Result<Option<(VersionId, Snapshot)>> {
+    async fn get_snapshot(&mut self) -> Result<Option<(VersionId, Snapshot)>> {
         // Pick the first snapshot we find.
-        let Some(name) = self.service.list(b"s-").next() else {
+        let Some(ObjectInfo { name, .. }) = self.service.list(b"s-").await?.into_iter().next()
+        else {
             return Ok(None);
         };
-        let ObjectInfo { name, .. } = name?;
         let Some(version_id) = Self::parse_snapshot_name(&name) else {
             return Ok(None);
         };
-        let Some(payload) = self.service.get(&name)? else {
+        let Some(payload) = self.service.get(&name).await? else {
             return Ok(None);
         };
         let unsealed = self.cryptor.unseal(Sealed {
@@ -471,12 +556,122 @@ impl<SVC: Service> Server for CloudServer<SVC> {
         })?;
         Ok(Some((version_id, unsealed.payload)))
     }
+
+    async fn rotate_encryption_secret(
+        &mut self,
+        old_secret: &[u8],
+        new_secret: &[u8],
+        parent_version_id: VersionId,
+        history_segment: HistorySegment,
+        snapshot: Snapshot,
+    ) -> Result<AddVersionResult> {
+        let old_cryptor = Cryptor::new(&self.salt, &old_secret.to_vec().into())?;
+        if !self.cryptor.same_key(&old_cryptor)? {
+            return Err(Error::Usage(
+                "The old encryption secret is not the secret used by this server".into(),
+            ));
+        }
+
+        let latest = self.get_latest().await?;
+        if let Some(l) = latest {
+            if l != parent_version_id {
+                return Ok(AddVersionResult::ExpectedParentVersion(l));
+            }
+        }
+
+        // Record the old and new salts, so that the change can be completed if it is interrupted
+        // once the new version is committed.
+        let salt = Cryptor::gen_salt()?;
+        let cryptor = Cryptor::new(&salt, &new_secret.to_vec().into())?;
+        let version_id = VersionId::new_v4();
+        let rotation_name = Self::rotation_name(&version_id);
+        self.service
+            .put(&rotation_name, &[&self.salt[..], &salt[..]].concat())
+            .await?;
+
+        // Upload the new version, sealed with the new key, and try to make it latest, exactly as
+        // in `add_version`.
+        let version_name = Self::version_name(&parent_version_id, &version_id);
+        let sealed = cryptor.seal(Unsealed {
+            version_id,
+            payload: history_segment,
+        })?;
+        self.service.put(&version_name, sealed.as_ref()).await?;
+        let old_value = latest.map(version_to_bytes);
+        let new_value = version_to_bytes(version_id);
+        if !self
+            .service
+            .compare_and_swap(LATEST, old_value.clone(), new_value.clone())
+            .await?
+        {
+            self.service.del(&version_name).await?;
+            self.service.del(&rotation_name).await?;
+            let latest = self.get_latest().await?;
+            let latest = latest.unwrap_or(Uuid::nil());
+            return Ok(AddVersionResult::ExpectedParentVersion(latest));
+        }
+
+        #[cfg(test)]
+        if let Some(f) = self.rotate_intercept {
+            f(&mut self.service);
+        }
+
+        // The new version is committed, so switch to the new key.
+        if !self
+            .service
+            .compare_and_swap(SALT, Some(self.salt.clone()), salt.clone())
+            .await?
+        {
+            // Restore the previous latest version, so that the data remains readable with the
+            // old key. Replicas using the old key cannot have added versions in the interim, as
+            // they cannot decrypt the new version.
+            let restored = match old_value {
+                Some(old_value) => {
+                    self.service
+                        .compare_and_swap(LATEST, Some(new_value), old_value)
+                        .await?
+                }
+                None => {
+                    self.service.del(LATEST).await?;
+                    true
+                }
+            };
+            if restored {
+                self.service.del(&version_name).await?;
+                self.service.del(&rotation_name).await?;
+            }
+            return Err(Error::Server(
+                "'salt' object was changed concurrently; the encryption secret was not changed"
+                    .into(),
+            ));
+        }
+        self.salt = salt;
+        self.cryptor = cryptor;
+
+        // Replace all existing snapshots, which can only be decrypted with the old key.
+        self.add_snapshot(version_id, snapshot).await?;
+        let snapshot_name = Self::snapshot_name(&version_id);
+        for ObjectInfo { name, .. } in self.service.list(b"s-").await? {
+            if name != snapshot_name {
+                self.service.del(&name).await?;
+            }
+        }
+
+        // The change is complete, so the recorded salts are no longer required, nor are those
+        // of any earlier changes that were abandoned.
+        for ObjectInfo { name, .. } in self.service.list(b"salt-").await? {
+            self.service.del(&name).await?;
+        }
+
+        Ok(AddVersionResult::Ok(version_id))
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::server::NIL_VERSION_ID;
+    use futures_executor::block_on;
 
     /// A simple in-memory service for testing. All insertions via Service methods occur at time
     /// `INSERTION_TIME`. All versions older that 1000 are considered "old".
@@ -494,23 +689,24 @@ mod tests {
         }
     }
 
+    #[async_trait]
     impl Service for MockService {
-        fn put(&mut self, name: &[u8], value: &[u8]) -> Result<()> {
+        async fn put(&mut self, name: &[u8], value: &[u8]) -> Result<()> {
             self.0
                 .insert(name.to_vec(), (INSERTION_TIME, value.to_vec()));
             Ok(())
         }
 
-        fn get(&mut self, name: &[u8]) -> Result<Option<Vec<u8>>> {
+        async fn get(&mut self, name: &[u8]) -> Result<Option<Vec<u8>>> {
             Ok(self.0.get(name).map(|(_, data)| data.clone()))
         }
 
-        fn del(&mut self, name: &[u8]) -> Result<()> {
+        async fn del(&mut self, name: &[u8]) -> Result<()> {
             self.0.remove(name);
             Ok(())
         }
 
-        fn compare_and_swap(
+        async fn compare_and_swap(
             &mut self,
             name: &[u8],
             existing_value: Option<Vec<u8>>,
@@ -523,22 +719,16 @@ mod tests {
             Ok(false)
         }
 
-        fn list<'a>(
-            &'a mut self,
-            prefix: &[u8],
-        ) -> Box<dyn Iterator<Item = Result<ObjectInfo>> + 'a> {
-            let prefix = prefix.to_vec();
-            Box::new(
-                self.0
-                    .iter()
-                    .filter(move |(k, _)| k.starts_with(&prefix))
-                    .map(|(k, (t, _))| {
-                        Ok(ObjectInfo {
-                            name: k.to_vec(),
-                            creation: *t,
-                        })
-                    }),
-            )
+        async fn list(&mut self, prefix: &[u8]) -> Result<Vec<ObjectInfo>> {
+            Ok(self
+                .0
+                .iter()
+                .filter(|(k, _)| k.starts_with(prefix))
+                .map(|(k, (t, _))| ObjectInfo {
+                    name: k.to_vec(),
+                    creation: *t,
+                })
+                .collect())
         }
     }
 
@@ -597,10 +787,12 @@ mod tests {
         /// to compare to with `assert_eq!`
         fn empty_clone(&self) -> Self {
             Self {
+                salt: self.salt.clone(),
                 cryptor: self.cryptor.clone(),
                 cleanup_probability: 0,
                 service: MockService::new(),
                 add_version_intercept: None,
+                rotate_intercept: None,
             }
         }
 
@@ -640,10 +832,12 @@ mod tests {
     impl Clone for CloudServer<MockService> {
         fn clone(&self) -> Self {
             Self {
+                salt: self.salt.clone(),
                 cryptor: self.cryptor.clone(),
                 cleanup_probability: self.cleanup_probability,
                 service: self.service.clone(),
                 add_version_intercept: None,
+                rotate_intercept: None,
             }
         }
     }
@@ -651,7 +845,7 @@ mod tests {
     const SECRET: &[u8] = b"testing";
 
     fn make_server() -> CloudServer<MockService> {
-        let mut server = CloudServer::new(MockService::new(), SECRET.into()).unwrap();
+        let mut server = block_on(CloudServer::new(MockService::new(), SECRET.into())).unwrap();
         // Prevent cleanup during tests.
         server.cleanup_probability = 0;
         server
@@ -768,7 +962,7 @@ mod tests {
     fn get_salt_existing() {
         let mut service = MockService::new();
         assert_eq!(
-            CloudServer::<MockService>::get_salt(&mut service).unwrap(),
+            block_on(CloudServer::<MockService>::get_salt(&mut service)).unwrap(),
             b"abcdefghabcdefgh".to_vec()
         );
     }
@@ -776,16 +970,16 @@ mod tests {
     #[test]
     fn get_salt_create() {
         let mut service = MockService::new();
-        service.del(b"salt").unwrap();
-        let got_salt = CloudServer::<MockService>::get_salt(&mut service).unwrap();
-        let salt_obj = service.get(b"salt").unwrap().unwrap();
+        block_on(service.del(b"salt")).unwrap();
+        let got_salt = block_on(CloudServer::<MockService>::get_salt(&mut service)).unwrap();
+        let salt_obj = block_on(service.get(b"salt")).unwrap().unwrap();
         assert_eq!(got_salt, salt_obj);
     }
 
     #[test]
     fn get_latest_empty() {
         let mut server = make_server();
-        assert_eq!(server.get_latest().unwrap(), None);
+        assert_eq!(block_on(server.get_latest()).unwrap(), None);
     }
 
     #[test]
@@ -793,7 +987,7 @@ mod tests {
         let mut server = make_server();
         let latest = Uuid::new_v4();
         server.mock_set_latest(latest);
-        assert_eq!(server.get_latest().unwrap(), Some(latest));
+        assert_eq!(block_on(server.get_latest()).unwrap(), Some(latest));
     }
 
     #[test]
@@ -803,13 +997,16 @@ mod tests {
             .service
             .0
             .insert(LATEST.to_vec(), (999, b"not-a-uuid".to_vec()));
-        assert!(server.get_latest().is_err());
+        assert!(block_on(server.get_latest()).is_err());
     }
 
     #[test]
     fn get_child_versions_empty() {
         let mut server = make_server();
-        assert_eq!(server.get_child_versions(&Uuid::new_v4()).unwrap(), vec![]);
+        assert_eq!(
+            block_on(server.get_child_versions(&Uuid::new_v4())).unwrap(),
+            vec![]
+        );
     }
 
     #[test]
@@ -817,8 +1014,8 @@ mod tests {
         let mut server = make_server();
         let (v1, v2) = (Uuid::new_v4(), Uuid::new_v4());
         server.mock_add_version(v2, v1, 1000, b"first");
-        assert_eq!(server.get_child_versions(&v1).unwrap(), vec![]);
-        assert_eq!(server.get_child_versions(&v2).unwrap(), vec![v1]);
+        assert_eq!(block_on(server.get_child_versions(&v1)).unwrap(), vec![]);
+        assert_eq!(block_on(server.get_child_versions(&v2)).unwrap(), vec![v1]);
     }
 
     #[test]
@@ -827,9 +1024,9 @@ mod tests {
         let (v1, v2, v3) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
         server.mock_add_version(v3, v1, 1000, b"first");
         server.mock_add_version(v3, v2, 1000, b"second");
-        assert_eq!(server.get_child_versions(&v1).unwrap(), vec![]);
-        assert_eq!(server.get_child_versions(&v2).unwrap(), vec![]);
-        let versions = server.get_child_versions(&v3).unwrap();
+        assert_eq!(block_on(server.get_child_versions(&v1)).unwrap(), vec![]);
+        assert_eq!(block_on(server.get_child_versions(&v2)).unwrap(), vec![]);
+        let versions = block_on(server.get_child_versions(&v3)).unwrap();
         assert!(versions == vec![v1, v2] || versions == vec![v2, v1]);
     }
 
@@ -837,7 +1034,7 @@ mod tests {
     fn add_version_empty() {
         let mut server = make_server();
         let parent = Uuid::new_v4();
-        let (res, _) = server.add_version(parent, b"history".to_vec()).unwrap();
+        let (res, _) = block_on(server.add_version(parent, b"history".to_vec())).unwrap();
         assert!(matches!(res, AddVersionResult::Ok(_)));
     }
 
@@ -848,7 +1045,7 @@ mod tests {
         server.mock_add_version(v1, v2, 1000, b"first");
         server.mock_set_latest(v2);
 
-        let (res, _) = server.add_version(v2, b"history".to_vec()).unwrap();
+        let (res, _) = block_on(server.add_version(v2, b"history".to_vec())).unwrap();
         let AddVersionResult::Ok(new_version) = res else {
             panic!("expected OK");
         };
@@ -871,7 +1068,7 @@ mod tests {
 
         let expected = server.clone();
 
-        let (res, _) = server.add_version(v1, b"history".to_vec()).unwrap();
+        let (res, _) = block_on(server.add_version(v1, b"history".to_vec())).unwrap();
         assert_eq!(res, AddVersionResult::ExpectedParentVersion(v2));
         assert_eq!(server.unencrypted(), expected.unencrypted());
     }
@@ -889,7 +1086,9 @@ mod tests {
         server.mock_add_version(v2, V3, 1000, b"second");
         server.mock_set_latest(v2);
         server.add_version_intercept = Some(|service| {
-            service.put(LATEST, &version_to_bytes(V3)).unwrap();
+            service
+                .0
+                .insert(LATEST.to_vec(), (INSERTION_TIME, version_to_bytes(V3)));
         });
 
         let mut expected = server.empty_clone();
@@ -898,7 +1097,7 @@ mod tests {
         expected.mock_set_latest(V3); // updated by the intercept
 
         assert_ne!(server.unencrypted(), expected.unencrypted());
-        let (res, _) = server.add_version(v2, b"history".to_vec()).unwrap();
+        let (res, _) = block_on(server.add_version(v2, b"history".to_vec())).unwrap();
         assert_eq!(res, AddVersionResult::ExpectedParentVersion(V3));
         assert_eq!(server.unencrypted(), expected.unencrypted());
     }
@@ -912,9 +1111,7 @@ mod tests {
 
         let expected = server.clone();
 
-        let (res, _) = server
-            .add_version(Uuid::new_v4(), b"history".to_vec())
-            .unwrap();
+        let (res, _) = block_on(server.add_version(Uuid::new_v4(), b"history".to_vec())).unwrap();
         assert_eq!(res, AddVersionResult::ExpectedParentVersion(v2));
         assert_eq!(server.unencrypted(), expected.unencrypted());
     }
@@ -923,7 +1120,7 @@ mod tests {
     fn get_child_version_empty() {
         let mut server = make_server();
         assert_eq!(
-            server.get_child_version(Uuid::new_v4()).unwrap(),
+            block_on(server.get_child_version(Uuid::new_v4())).unwrap(),
             GetVersionResult::NoSuchVersion
         );
     }
@@ -935,11 +1132,11 @@ mod tests {
         server.mock_add_version(v2, v1, 1000, b"first");
         server.mock_set_latest(v1);
         assert_eq!(
-            server.get_child_version(v1).unwrap(),
+            block_on(server.get_child_version(v1)).unwrap(),
             GetVersionResult::NoSuchVersion
         );
         assert_eq!(
-            server.get_child_version(v2).unwrap(),
+            block_on(server.get_child_version(v2)).unwrap(),
             GetVersionResult::Version {
                 version_id: v1,
                 parent_version_id: v2,
@@ -958,7 +1155,7 @@ mod tests {
         server.mock_add_version(v2, v3, 1000, b"third");
         server.mock_set_latest(v2);
         assert_eq!(
-            server.get_child_version(v1).unwrap(),
+            block_on(server.get_child_version(v1)).unwrap(),
             GetVersionResult::Version {
                 version_id: v2,
                 parent_version_id: v1,
@@ -966,7 +1163,7 @@ mod tests {
             }
         );
         assert_eq!(
-            server.get_child_version(v2).unwrap(),
+            block_on(server.get_child_version(v2)).unwrap(),
             GetVersionResult::NoSuchVersion
         );
     }
@@ -983,7 +1180,7 @@ mod tests {
         server.mock_add_version(v2, vz, 1000, b"false start z");
         server.mock_set_latest(v3);
         assert_eq!(
-            server.get_child_version(v1).unwrap(),
+            block_on(server.get_child_version(v1)).unwrap(),
             GetVersionResult::Version {
                 version_id: v2,
                 parent_version_id: v1,
@@ -991,7 +1188,7 @@ mod tests {
             }
         );
         assert_eq!(
-            server.get_child_version(v2).unwrap(),
+            block_on(server.get_child_version(v2)).unwrap(),
             GetVersionResult::Version {
                 version_id: v3,
                 parent_version_id: v2,
@@ -999,7 +1196,7 @@ mod tests {
             }
         );
         assert_eq!(
-            server.get_child_version(v3).unwrap(),
+            block_on(server.get_child_version(v3)).unwrap(),
             GetVersionResult::NoSuchVersion
         );
     }
@@ -1007,7 +1204,7 @@ mod tests {
     #[test]
     fn cleanup_empty() {
         let mut server = make_server();
-        server.cleanup().unwrap();
+        block_on(server.cleanup()).unwrap();
     }
 
     #[test]
@@ -1024,7 +1221,7 @@ mod tests {
 
         let expected = server.clone();
 
-        server.cleanup().unwrap();
+        block_on(server.cleanup()).unwrap();
         assert_eq!(server.unencrypted(), expected.unencrypted());
     }
 
@@ -1040,7 +1237,7 @@ mod tests {
 
         let expected = server.clone();
 
-        assert!(server.cleanup().is_err());
+        assert!(block_on(server.cleanup()).is_err());
         assert_eq!(server.unencrypted(), expected.unencrypted());
     }
 
@@ -1062,7 +1259,7 @@ mod tests {
         expected.mock_set_latest(v3);
 
         assert_ne!(server.unencrypted(), expected.unencrypted());
-        server.cleanup().unwrap();
+        block_on(server.cleanup()).unwrap();
         assert_eq!(server.unencrypted(), expected.unencrypted());
     }
 
@@ -1086,7 +1283,7 @@ mod tests {
         expected.mock_set_latest(v3);
 
         assert_ne!(server.unencrypted(), expected.unencrypted());
-        server.cleanup().unwrap();
+        block_on(server.cleanup()).unwrap();
         assert_eq!(server.unencrypted(), expected.unencrypted());
     }
 
@@ -1101,7 +1298,7 @@ mod tests {
 
         let expected = server.clone();
 
-        server.cleanup().unwrap();
+        block_on(server.cleanup()).unwrap();
         assert_eq!(server.unencrypted(), expected.unencrypted());
     }
 
@@ -1128,7 +1325,7 @@ mod tests {
         expected.mock_set_latest(v6);
 
         assert_ne!(server.unencrypted(), expected.unencrypted());
-        server.cleanup().unwrap();
+        block_on(server.cleanup()).unwrap();
         assert_eq!(server.unencrypted(), expected.unencrypted());
     }
 
@@ -1154,7 +1351,7 @@ mod tests {
         expected.mock_set_latest(v6);
 
         assert_ne!(server.unencrypted(), expected.unencrypted());
-        server.cleanup().unwrap();
+        block_on(server.cleanup()).unwrap();
         assert_eq!(server.unencrypted(), expected.unencrypted());
     }
 
@@ -1173,7 +1370,7 @@ mod tests {
 
         let expected = server.clone();
 
-        server.cleanup().unwrap();
+        block_on(server.cleanup()).unwrap();
         assert_eq!(server.unencrypted(), expected.unencrypted());
     }
 
@@ -1186,14 +1383,14 @@ mod tests {
         expected.mock_add_snapshot(v, INSERTION_TIME, b"SNAP");
 
         assert_ne!(server.unencrypted(), expected.unencrypted());
-        server.add_snapshot(v, b"SNAP".to_vec()).unwrap();
+        block_on(server.add_snapshot(v, b"SNAP".to_vec())).unwrap();
         assert_eq!(server.unencrypted(), expected.unencrypted());
     }
 
     #[test]
     fn get_snapshot_missing() {
         let mut server = make_server();
-        assert_eq!(server.get_snapshot().unwrap(), None);
+        assert_eq!(block_on(server.get_snapshot()).unwrap(), None);
     }
 
     #[test]
@@ -1201,6 +1398,195 @@ mod tests {
         let mut server = make_server();
         let v = Uuid::new_v4();
         server.mock_add_snapshot(v, 1000, b"SNAP");
-        assert_eq!(server.get_snapshot().unwrap(), Some((v, b"SNAP".to_vec())));
+        assert_eq!(
+            block_on(server.get_snapshot()).unwrap(),
+            Some((v, b"SNAP".to_vec()))
+        );
+    }
+
+    #[test]
+    fn rotate_encryption_secret() {
+        let mut server = make_server();
+        let (v1, v2) = (Uuid::new_v4(), Uuid::new_v4());
+        server.mock_add_version(NIL_VERSION_ID, v1, 1000, b"first");
+        server.mock_add_version(v1, v2, 1000, b"second");
+        server.mock_add_snapshot(v1, 1000, b"SNAP");
+        server.mock_set_latest(v2);
+        let mut old_server = server.clone();
+
+        let res = block_on(server.rotate_encryption_secret(
+            SECRET,
+            b"new-secret",
+            v2,
+            b"rotated".to_vec(),
+            b"NEWSNAP".to_vec(),
+        ))
+        .unwrap();
+        let AddVersionResult::Ok(v3) = res else {
+            panic!("rotation was rejected");
+        };
+        assert_eq!(block_on(server.get_latest()).unwrap(), Some(v3));
+        assert_ne!(server.salt, b"abcdefghabcdefgh".to_vec());
+        assert_eq!(
+            block_on(server.service.get(SALT)).unwrap(),
+            Some(server.salt.clone())
+        );
+        assert!(block_on(server.service.list(b"salt-")).unwrap().is_empty());
+
+        // A server using the new secret begins from the new snapshot.
+        let mut new_server = block_on(CloudServer::new(
+            server.service.clone(),
+            b"new-secret".to_vec(),
+        ))
+        .unwrap();
+        assert_eq!(
+            block_on(new_server.get_snapshot()).unwrap(),
+            Some((v3, b"NEWSNAP".to_vec()))
+        );
+        assert_eq!(
+            block_on(new_server.get_child_version(v2)).unwrap(),
+            GetVersionResult::Version {
+                version_id: v3,
+                parent_version_id: v2,
+                history_segment: b"rotated".to_vec(),
+            }
+        );
+
+        // Servers using the old secret cannot read the new data.
+        let mut restarted_server =
+            block_on(CloudServer::new(server.service.clone(), SECRET.to_vec())).unwrap();
+        assert!(matches!(
+            block_on(restarted_server.get_snapshot()),
+            Err(Error::WrongEncryptionSecret)
+        ));
+        old_server.service = server.service.clone();
+        assert!(matches!(
+            block_on(old_server.get_child_version(v2)),
+            Err(Error::WrongEncryptionSecret)
+        ));
+    }
+
+    #[test]
+    fn rotate_encryption_secret_salt_changed() {
+        let mut server = make_server();
+        let (v1, v2) = (Uuid::new_v4(), Uuid::new_v4());
+        server.mock_add_version(NIL_VERSION_ID, v1, 1000, b"first");
+        server.mock_add_version(v1, v2, 1000, b"second");
+        server.mock_set_latest(v2);
+        server.rotate_intercept = Some(|service| {
+            service
+                .0
+                .insert(SALT.to_vec(), (INSERTION_TIME, b"changed".to_vec()));
+        });
+
+        let res = block_on(server.rotate_encryption_secret(
+            SECRET,
+            b"new-secret",
+            v2,
+            b"rotated".to_vec(),
+            b"NEWSNAP".to_vec(),
+        ));
+        assert!(matches!(res, Err(Error::Server(_))));
+
+        // The new version was abandoned, and the server still uses the old key.
+        assert_eq!(block_on(server.get_latest()).unwrap(), Some(v2));
+        assert!(block_on(server.service.list(b"salt-")).unwrap().is_empty());
+        assert_eq!(
+            block_on(server.get_child_version(v2)).unwrap(),
+            GetVersionResult::NoSuchVersion
+        );
+        assert_eq!(
+            block_on(server.get_child_version(v1)).unwrap(),
+            GetVersionResult::Version {
+                version_id: v2,
+                parent_version_id: v1,
+                history_segment: b"second".to_vec(),
+            }
+        );
+        let (res, _) = block_on(server.add_version(v2, b"third".to_vec())).unwrap();
+        assert!(matches!(res, AddVersionResult::Ok(_)));
+    }
+
+    #[test]
+    fn recover_interrupted_rotation() {
+        let mut server = make_server();
+        let (v1, v2, v3) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        server.mock_add_version(NIL_VERSION_ID, v1, 1000, b"first");
+
+        // Simulate a change of secret that was interrupted after committing v2, and an
+        // abandoned change at v3 which was never committed.
+        let old_salt = b"abcdefghabcdefgh".to_vec();
+        let new_salt = b"ABCDEFGHABCDEFGH".to_vec();
+        let mut rotated = server.clone();
+        rotated.cryptor = Cryptor::new(&new_salt, &b"new-secret".to_vec().into()).unwrap();
+        rotated.mock_add_version(v1, v2, 1000, b"rotated");
+        rotated.mock_set_latest(v2);
+        let mut service = rotated.service;
+        let salts = [&old_salt[..], &new_salt[..]].concat();
+        block_on(service.put(&CloudServer::<MockService>::rotation_name(&v2), &salts)).unwrap();
+        let salts = [&new_salt[..], b"0123456701234567"].concat();
+        block_on(service.put(&CloudServer::<MockService>::rotation_name(&v3), &salts)).unwrap();
+
+        let mut server = block_on(CloudServer::new(service, b"new-secret".to_vec())).unwrap();
+        assert_eq!(server.salt, new_salt);
+        assert_eq!(block_on(server.service.get(SALT)).unwrap(), Some(new_salt));
+        assert_eq!(
+            block_on(server.get_child_version(v1)).unwrap(),
+            GetVersionResult::Version {
+                version_id: v2,
+                parent_version_id: v1,
+                history_segment: b"rotated".to_vec(),
+            }
+        );
+    }
+
+    #[test]
+    fn rotation_name_round_trip() {
+        let v = Uuid::new_v4();
+        assert_eq!(
+            CloudServer::<MockService>::parse_rotation_name(
+                &CloudServer::<MockService>::rotation_name(&v)
+            ),
+            Some(v)
+        );
+        assert_eq!(
+            CloudServer::<MockService>::parse_rotation_name(b"salt"),
+            None
+        );
+    }
+
+    #[test]
+    fn rotate_encryption_secret_not_latest() {
+        let mut server = make_server();
+        let (v1, v2) = (Uuid::new_v4(), Uuid::new_v4());
+        server.mock_add_version(NIL_VERSION_ID, v1, 1000, b"first");
+        server.mock_add_version(v1, v2, 1000, b"second");
+        server.mock_set_latest(v2);
+        let expected = server.unencrypted();
+
+        let res = block_on(server.rotate_encryption_secret(
+            SECRET,
+            b"new-secret",
+            v1,
+            b"rotated".to_vec(),
+            b"NEWSNAP".to_vec(),
+        ))
+        .unwrap();
+        assert_eq!(res, AddVersionResult::ExpectedParentVersion(v2));
+        assert_eq!(server.unencrypted(), expected);
+    }
+
+    #[test]
+    fn rotate_encryption_secret_wrong_old_secret() {
+        let mut server = make_server();
+        let res = block_on(server.rotate_encryption_secret(
+            b"not-the-secret",
+            b"new-secret",
+            NIL_VERSION_ID,
+            b"rotated".to_vec(),
+            b"NEWSNAP".to_vec(),
+        ));
+        assert!(matches!(res, Err(Error::Usage(_))));
+        assert_eq!(block_on(server.get_latest()).unwrap(), None);
     }
 }
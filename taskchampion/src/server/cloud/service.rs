@@ -1,6 +1,8 @@
 use crate::errors::Result;
+use async_trait::async_trait;
 
 /// Information about an object as returned from `Service::list`
+#[derive(Debug, PartialEq, Eq)]
 pub(in crate::server) struct ObjectInfo {
     /// Name of the object.
     pub(in crate::server) name: Vec<u8>,
@@ -14,22 +16,26 @@ pub(in crate::server) struct ObjectInfo {
 /// similar to a HashMap, with the addition of a compare-and-swap operation. Object names
 /// are always simple strings from the character set `[a-zA-Z0-9-]`, no more than 100 characters
 /// in length.
-pub(in crate::server) trait Service {
+///
+/// The methods are asynchronous, so that services based on async client libraries can be used
+/// without a dedicated runtime. Services based on blocking client libraries simply block.
+#[async_trait]
+pub(in crate::server) trait Service: Send {
     /// Put an object into cloud storage. If the object exists, it is overwritten.
-    fn put(&mut self, name: &[u8], value: &[u8]) -> Result<()>;
+    async fn put(&mut self, name: &[u8], value: &[u8]) -> Result<()>;
 
     /// Get an object from cloud storage, or None if the object does not exist.
-    fn get(&mut self, name: &[u8]) -> Result<Option<Vec<u8>>>;
+    async fn get(&mut self, name: &[u8]) -> Result<Option<Vec<u8>>>;
 
     /// Delete an object. Does nothing if the object does not exist.
-    fn del(&mut self, name: &[u8]) -> Result<()>;
+    async fn del(&mut self, name: &[u8]) -> Result<()>;
 
     /// Enumerate objects with the given prefix.
-    fn list<'a>(&'a mut self, prefix: &[u8]) -> Box<dyn Iterator<Item = Result<ObjectInfo>> + 'a>;
+    async fn list(&mut self, prefix: &[u8]) -> Result<Vec<ObjectInfo>>;
 
     /// Compare the existing object's value with `existing_value`, and replace with `new_value`
     /// only if the values match. Returns true if the replacement occurred.
-    fn compare_and_swap(
+    async fn compare_and_swap(
         &mut self,
         name: &[u8],
         existing_value: Option<Vec<u8>>,
@@ -1,17 +1,29 @@
-use super::types::Server;
+use super::types::{AsyncServer, Server};
 use crate::errors::Result;
+#[cfg(feature = "server-aws")]
+use crate::server::cloud::aws::AwsService;
+#[cfg(feature = "server-directory")]
+use crate::server::cloud::directory::DirectoryService;
 #[cfg(feature = "server-gcp")]
 use crate::server::cloud::gcp::GcpService;
+#[cfg(any(feature = "server-directory", feature = "server-webdav"))]
+use crate::server::cloud::run_blocking;
+#[cfg(feature = "server-webdav")]
+use crate::server::cloud::webdav::WebDavService;
 #[cfg(feature = "cloud")]
 use crate::server::cloud::CloudServer;
 use crate::server::local::LocalServer;
 #[cfg(feature = "server-sync")]
 use crate::server::sync::SyncServer;
+use crate::server::AsyncAdapter;
+#[cfg(feature = "cloud")]
+use crate::server::BlockingServer;
 use std::path::PathBuf;
 #[cfg(feature = "server-sync")]
 use uuid::Uuid;
 
 /// The configuration for a replica's access to a sync server.
+#[derive(Clone)]
 pub enum ServerConfig {
     /// A local task database, for situations with a single replica.
     Local {
@@ -65,6 +77,83 @@ pub enum ServerConfig {
         /// be any suitably un-guessable string of bytes.
         encryption_secret: Vec<u8>,
     },
+    /// An Amazon Web Services storage bucket, or a bucket in a service implementing the S3 API.
+    #[cfg(feature = "server-aws")]
+    Aws {
+        /// Region in which the bucket is located.
+        region: String,
+        /// Bucket in which to store the task data. This bucket must not be used for any other
+        /// purpose.
+        ///
+        /// The service must support conditional writes (`If-Match` and `If-None-Match`).
+        bucket: String,
+        /// Endpoint URL of an S3-compatible service, such as a MinIO instance.  If `None`, the
+        /// AWS endpoint for the region is used.
+        ///
+        /// When set, buckets are addressed with path-style URLs.
+        endpoint: Option<String>,
+        /// Credential configuration for access to the bucket.
+        ///
+        /// The credentials must permit `s3:GetObject`, `s3:PutObject`, `s3:DeleteObject`, and
+        /// `s3:ListBucket` on the bucket.
+        credentials: AwsCredentials,
+        /// Private encryption secret used to encrypt all data sent to the server.  This can
+        /// be any suitably un-guessable string of bytes.
+        encryption_secret: Vec<u8>,
+    },
+    /// A WebDAV collection, such as a folder in Nextcloud.
+    #[cfg(feature = "server-webdav")]
+    WebDav {
+        /// URL of the collection in which to store the task data, such as
+        /// `https://cloud.example.com/remote.php/dav/files/alice/taskchampion/`.  The collection
+        /// is created if it does not exist, but its parent must exist.  It must not be used for
+        /// any other purpose.  The URL must not contain credentials; give them as `username`
+        /// and `password` instead.
+        url: String,
+        /// Username for HTTP basic authentication, if required.
+        username: Option<String>,
+        /// Password for HTTP basic authentication, if required.  For Nextcloud, this should be
+        /// an app password.
+        password: Option<String>,
+        /// Private encryption secret used to encrypt all data sent to the server.  This can
+        /// be any suitably un-guessable string of bytes.
+        encryption_secret: Vec<u8>,
+    },
+    /// A directory on a filesystem shared between replicas, such as an NFS mount or a folder
+    /// synchronized with a tool like Syncthing or Dropbox.
+    ///
+    /// Data is stored encrypted, in the same format as for cloud storage services.  Concurrent
+    /// syncs are coordinated with lock files, which folder-synchronization tools do not
+    /// propagate between machines, so with such tools it is best to avoid syncing from multiple
+    /// machines at the same moment.
+    #[cfg(feature = "server-directory")]
+    Directory {
+        /// Directory in which to store the task data.  It is created if it does not exist, and
+        /// must not be used for any other purpose.
+        path: PathBuf,
+        /// Private encryption secret used to encrypt all data stored in the directory.  This can
+        /// be any suitably un-guessable string of bytes.
+        encryption_secret: Vec<u8>,
+    },
+}
+
+/// Credentials for access to an AWS S3 bucket, used in [`ServerConfig::Aws`].
+#[cfg(feature = "server-aws")]
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub enum AwsCredentials {
+    /// A pair of access key ID and secret access key.
+    AccessKey {
+        access_key_id: String,
+        secret_access_key: String,
+    },
+    /// A named profile from the AWS configuration files (`~/.aws/config` and
+    /// `~/.aws/credentials`).
+    Profile { profile_name: String },
+    /// The default credential chain, drawing from environment variables, the AWS configuration
+    /// files, and instance metadata, among others.  See the [AWS
+    /// documentation](https://docs.aws.amazon.com/sdkref/latest/guide/standardized-credentials.html).
+    Default,
 }
 
 impl ServerConfig {
@@ -79,14 +168,101 @@ impl ServerConfig {
                 encryption_secret,
             } => Box::new(SyncServer::new(url, client_id, encryption_secret)?),
             #[cfg(feature = "server-gcp")]
+            config @ ServerConfig::Gcp { .. } => config.into_runtime_server()?,
+            #[cfg(feature = "server-aws")]
+            config @ ServerConfig::Aws { .. } => config.into_runtime_server()?,
+            #[cfg(feature = "server-webdav")]
+            config @ ServerConfig::WebDav { .. } => Box::new(BlockingServer::new(
+                futures_executor::block_on(config.into_async_server())?,
+            )),
+            #[cfg(feature = "server-directory")]
+            config @ ServerConfig::Directory { .. } => Box::new(BlockingServer::new(
+                futures_executor::block_on(config.into_async_server())?,
+            )),
+        })
+    }
+
+    /// Get an asynchronous server based on this configuration, for use with
+    /// [`Replica::sync_async`](crate::Replica::sync_async).
+    ///
+    /// The GCP and AWS servers are based on Tokio, and must be used within a Tokio runtime. The
+    /// WebDAV and directory servers perform blocking I/O, which runs on the runtime's blocking
+    /// thread pool when used within a Tokio runtime. The other servers perform blocking I/O
+    /// directly, so their futures block until the I/O completes.
+    pub async fn into_async_server(self) -> Result<Box<dyn AsyncServer>> {
+        Ok(match self {
+            ServerConfig::Local { server_dir } => {
+                Box::new(AsyncAdapter(Box::new(LocalServer::new(server_dir)?)))
+            }
+            #[cfg(feature = "server-sync")]
+            ServerConfig::Remote {
+                url,
+                client_id,
+                encryption_secret,
+            } => Box::new(AsyncAdapter(Box::new(SyncServer::new(
+                url,
+                client_id,
+                encryption_secret,
+            )?))),
+            #[cfg(feature = "server-gcp")]
             ServerConfig::Gcp {
                 bucket,
                 credential_path,
                 encryption_secret,
-            } => Box::new(CloudServer::new(
-                GcpService::new(bucket, credential_path)?,
+            } => Box::new(
+                CloudServer::new(
+                    GcpService::new(bucket, credential_path).await?,
+                    encryption_secret,
+                )
+                .await?,
+            ),
+            #[cfg(feature = "server-aws")]
+            ServerConfig::Aws {
+                region,
+                bucket,
+                endpoint,
+                credentials,
+                encryption_secret,
+            } => Box::new(
+                CloudServer::new(
+                    AwsService::new(region, bucket, endpoint, credentials).await?,
+                    encryption_secret,
+                )
+                .await?,
+            ),
+            #[cfg(feature = "server-webdav")]
+            ServerConfig::WebDav {
+                url,
+                username,
+                password,
+                encryption_secret,
+            } => Box::new(
+                CloudServer::new(
+                    run_blocking(move || WebDavService::new(url, username, password)).await?,
+                    encryption_secret,
+                )
+                .await?,
+            ),
+            #[cfg(feature = "server-directory")]
+            ServerConfig::Directory {
+                path,
                 encryption_secret,
-            )?),
+            } => Box::new(
+                CloudServer::new(
+                    run_blocking(move || DirectoryService::new(path)).await?,
+                    encryption_secret,
+                )
+                .await?,
+            ),
         })
     }
+
+    /// Get a blocking server for a server based on a Tokio client library, which runs in its own
+    /// runtime.
+    #[cfg(any(feature = "server-gcp", feature = "server-aws"))]
+    fn into_runtime_server(self) -> Result<Box<dyn Server>> {
+        let rt = tokio::runtime::Runtime::new()?;
+        let server = rt.block_on(self.into_async_server())?;
+        Ok(Box::new(BlockingServer::with_runtime(server, rt)))
+    }
 }
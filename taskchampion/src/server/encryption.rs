@@ -9,6 +9,9 @@ const ENVELOPE_VERSION: u8 = 1;
 const AAD_LEN: usize = 17;
 const TASK_APP_ID: u8 = 1;
 
+/// The length of salts generated by [`Cryptor::gen_salt`].
+pub(super) const SALT_LEN: usize = 16;
+
 /// An Cryptor stores a secret and allows sealing and unsealing.  It derives a key from the secret,
 /// which takes a nontrivial amount of time, so it should be created once and re-used for the given
 /// context.
@@ -29,7 +32,7 @@ impl Cryptor {
     /// Generate a suitable random salt.
     pub(super) fn gen_salt() -> Result<Vec<u8>> {
         let rng = rand::SystemRandom::new();
-        let mut salt = [0u8; 16];
+        let mut salt = [0u8; SALT_LEN];
         rng.fill(&mut salt)
             .map_err(|_| anyhow::anyhow!("error generating random salt"))?;
         Ok(salt.to_vec())
@@ -91,6 +94,13 @@ impl Cryptor {
         } = payload;
 
         let env = Envelope::from_bytes(&payload)?;
+        // A payload too short to hold the authentication tag is malformed, rather than
+        // encrypted with a different secret.
+        if env.payload.len() < aead::CHACHA20_POLY1305.tag_len() {
+            return Err(Error::Server(String::from(
+                "encrypted payload is too small",
+            )));
+        }
 
         let mut nonce = [0u8; aead::NONCE_LEN];
         nonce.copy_from_slice(env.nonce);
@@ -101,7 +111,7 @@ impl Cryptor {
         let plaintext = self
             .key
             .open_in_place(nonce, aad, payload.as_mut())
-            .map_err(|_| anyhow::anyhow!("error while unsealing encrypted value"))?;
+            .map_err(|_| Error::WrongEncryptionSecret)?;
 
         Ok(Unsealed {
             version_id,
@@ -109,6 +119,15 @@ impl Cryptor {
         })
     }
 
+    /// Determine whether this Cryptor uses the same key as `other`.
+    pub(super) fn same_key(&self, other: &Cryptor) -> Result<bool> {
+        let sealed = self.seal(Unsealed {
+            version_id: Uuid::nil(),
+            payload: vec![],
+        })?;
+        Ok(other.unseal(sealed).is_ok())
+    }
+
     fn make_aad(&self, version_id: Uuid) -> aead::Aad<[u8; AAD_LEN]> {
         let mut aad = [0u8; AAD_LEN];
         aad[0] = TASK_APP_ID;
@@ -282,7 +301,33 @@ mod test {
 
         let secret = Secret(b"DIFFERENT_SECRET".to_vec());
         let cryptor = Cryptor::new(&salt, &secret).unwrap();
-        assert!(cryptor.unseal(sealed).is_err());
+        assert!(matches!(
+            cryptor.unseal(sealed),
+            Err(Error::WrongEncryptionSecret)
+        ));
+    }
+
+    #[test]
+    fn unseal_truncated() {
+        let cryptor = Cryptor::new(make_salt(), &Secret(b"SEKRIT".to_vec())).unwrap();
+        let mut sealed = cryptor
+            .seal(Unsealed {
+                version_id: Uuid::new_v4(),
+                payload: b"HISTORY REPEATS ITSELF".to_vec(),
+            })
+            .unwrap();
+        sealed.payload.truncate(1 + aead::NONCE_LEN + 5);
+        assert!(matches!(cryptor.unseal(sealed), Err(Error::Server(_))));
+    }
+
+    #[test]
+    fn same_key() {
+        let salt = make_salt();
+        let cryptor = Cryptor::new(&salt, &Secret(b"SEKRIT".to_vec())).unwrap();
+        let same = Cryptor::new(&salt, &Secret(b"SEKRIT".to_vec())).unwrap();
+        let different = Cryptor::new(&salt, &Secret(b"DIFFERENT_SECRET".to_vec())).unwrap();
+        assert!(cryptor.same_key(&same).unwrap());
+        assert!(!cryptor.same_key(&different).unwrap());
     }
 
     #[test]
@@ -396,7 +441,7 @@ mod test {
             };
 
             let cryptor = Cryptor::new(salt, &Secret(encryption_secret)).unwrap();
-            assert!(cryptor.unseal(sealed).is_err());
+            assert!(matches!(cryptor.unseal(sealed), Err(Error::Server(_))));
         }
 
         #[test]
@@ -23,7 +23,7 @@ pub struct LocalServer {
 }
 
 impl LocalServer {
-    fn txn(&mut self) -> Result<rusqlite::Transaction> {
+    fn txn(&mut self) -> Result<rusqlite::Transaction<'_>> {
         let txn = self.con.transaction()?;
         Ok(txn)
     }
@@ -179,6 +179,15 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn test_rotate_encryption_secret_unsupported() -> Result<()> {
+        let tmp_dir = TempDir::new()?;
+        let mut server = LocalServer::new(tmp_dir.path())?;
+        let res = server.rotate_encryption_secret(b"old", b"new", NIL_VERSION_ID, vec![], vec![]);
+        assert!(matches!(res, Err(crate::Error::Usage(_))));
+        Ok(())
+    }
+
     #[test]
     fn test_add_zero_base() -> Result<()> {
         let tmp_dir = TempDir::new()?;
@@ -1,8 +1,8 @@
 /*!
 This module defines the client interface to TaskChampion sync servers.
 
-It defines a [trait](crate::server::Server) for servers, and implements both local and remote
-servers.
+It defines traits for servers, both [blocking](crate::server::Server) and
+[asynchronous](crate::server::AsyncServer), and implements both local and remote servers.
 
 Typical uses of this crate do not interact directly with this module; [`ServerConfig`] is
 sufficient. However, users who wish to implement their own server interfaces can implement the
@@ -12,6 +12,7 @@ traits defined here and pass the result to [`Replica`](crate::Replica).
 #[cfg(test)]
 pub(crate) mod test;
 
+mod adapters;
 mod config;
 mod local;
 mod op;
@@ -26,7 +27,12 @@ mod sync;
 #[cfg(feature = "cloud")]
 mod cloud;
 
+#[cfg(feature = "server-aws")]
+pub use config::AwsCredentials;
 pub use config::ServerConfig;
 pub use types::*;
 
+pub(crate) use adapters::AsyncAdapter;
+#[cfg(feature = "cloud")]
+pub(in crate::server) use adapters::BlockingServer;
 pub(crate) use op::SyncOp;
@@ -199,4 +199,38 @@ impl Server for SyncServer {
             Err(err) => Err(err.into()),
         }
     }
+
+    fn rotate_encryption_secret(
+        &mut self,
+        old_secret: &[u8],
+        new_secret: &[u8],
+        parent_version_id: VersionId,
+        history_segment: HistorySegment,
+        snapshot: Snapshot,
+    ) -> Result<AddVersionResult> {
+        let old_cryptor = Cryptor::new(self.client_id, &Secret(old_secret.to_vec()))?;
+        if !self.cryptor.same_key(&old_cryptor)? {
+            return Err(Error::Usage(
+                "The old encryption secret is not the secret used by this server".into(),
+            ));
+        }
+
+        // The salt is the client_id, so only the secret changes. The sync server stores opaque
+        // data, so the new version and snapshot are sent just as any other, but sealed with the
+        // new key.
+        let new_cryptor = Cryptor::new(self.client_id, &Secret(new_secret.to_vec()))?;
+        let old_cryptor = std::mem::replace(&mut self.cryptor, new_cryptor);
+        let result = match self.add_version(parent_version_id, history_segment) {
+            Ok((result, _)) => result,
+            Err(err) => {
+                self.cryptor = old_cryptor;
+                return Err(err);
+            }
+        };
+        match result {
+            AddVersionResult::Ok(version_id) => self.add_snapshot(version_id, snapshot)?,
+            AddVersionResult::ExpectedParentVersion(_) => self.cryptor = old_cryptor,
+        }
+        Ok(result)
+    }
 }
@@ -133,4 +133,21 @@ impl Server for TestServer {
         let inner = self.0.lock().unwrap();
         Ok(inner.snapshot.clone())
     }
+
+    /// Add a version and snapshot.  The test implementation does not perform any encryption, so
+    /// the secrets are ignored.
+    fn rotate_encryption_secret(
+        &mut self,
+        _old_secret: &[u8],
+        _new_secret: &[u8],
+        parent_version_id: VersionId,
+        history_segment: HistorySegment,
+        snapshot: Snapshot,
+    ) -> Result<AddVersionResult> {
+        let (res, _) = self.add_version(parent_version_id, history_segment)?;
+        if let AddVersionResult::Ok(version_id) = res {
+            self.add_snapshot(version_id, snapshot)?;
+        }
+        Ok(res)
+    }
 }
@@ -1,4 +1,5 @@
-use crate::errors::Result;
+use crate::errors::{Error, Result};
+use async_trait::async_trait;
 use uuid::Uuid;
 
 /// Versions are referred to with UUIDs.
@@ -50,7 +51,9 @@ pub enum GetVersionResult {
 }
 
 /// A value implementing this trait can act as a server against which a replica can sync.
-pub trait Server {
+///
+/// Servers must be `Send`, so that they can be moved between threads along with a replica.
+pub trait Server: Send {
     /// Add a new version.
     ///
     /// This must ensure that the new version is the only version with the given
@@ -70,4 +73,114 @@ pub trait Server {
     fn add_snapshot(&mut self, version_id: VersionId, snapshot: Snapshot) -> Result<()>;
 
     fn get_snapshot(&mut self) -> Result<Option<(VersionId, Snapshot)>>;
+
+    /// Change the secret used to encrypt data on this server from `old_secret`, which must be the
+    /// secret the server was created with, to `new_secret`.
+    ///
+    /// This adds a new version containing `history_segment`, as for [`Server::add_version`], and a
+    /// snapshot at that version, both encrypted with the new secret. The server then uses the new
+    /// secret for all further operations. Replicas still using the old secret fail with
+    /// [`Error::WrongEncryptionSecret`] when they fetch the new version.
+    ///
+    /// Servers which do not encrypt data need not implement this method. The default
+    /// implementation returns an error.
+    fn rotate_encryption_secret(
+        &mut self,
+        _old_secret: &[u8],
+        _new_secret: &[u8],
+        _parent_version_id: VersionId,
+        _history_segment: HistorySegment,
+        _snapshot: Snapshot,
+    ) -> Result<AddVersionResult> {
+        Err(rotation_unsupported())
+    }
+}
+
+/// An asynchronous variant of [`Server`], against which a replica can sync with
+/// [`Replica::sync_async`](crate::Replica::sync_async).
+///
+/// The methods have the same meaning as those of [`Server`], but return futures that must be
+/// `Send`, so that a sync can be spawned on a multi-threaded executor.  Implement this trait using
+/// the [`async-trait`](https://docs.rs/async-trait) crate.
+#[async_trait]
+pub trait AsyncServer: Send {
+    /// Add a new version.  See [`Server::add_version`].
+    async fn add_version(
+        &mut self,
+        parent_version_id: VersionId,
+        history_segment: HistorySegment,
+    ) -> Result<(AddVersionResult, SnapshotUrgency)>;
+
+    /// Get the version with the given parent VersionId
+    async fn get_child_version(&mut self, parent_version_id: VersionId)
+        -> Result<GetVersionResult>;
+
+    /// Add a snapshot on the server
+    async fn add_snapshot(&mut self, version_id: VersionId, snapshot: Snapshot) -> Result<()>;
+
+    async fn get_snapshot(&mut self) -> Result<Option<(VersionId, Snapshot)>>;
+
+    /// Change the encryption secret. See [`Server::rotate_encryption_secret`].
+    async fn rotate_encryption_secret(
+        &mut self,
+        _old_secret: &[u8],
+        _new_secret: &[u8],
+        _parent_version_id: VersionId,
+        _history_segment: HistorySegment,
+        _snapshot: Snapshot,
+    ) -> Result<AddVersionResult> {
+        Err(rotation_unsupported())
+    }
+}
+
+#[async_trait]
+impl<S: AsyncServer + ?Sized> AsyncServer for Box<S> {
+    async fn add_version(
+        &mut self,
+        parent_version_id: VersionId,
+        history_segment: HistorySegment,
+    ) -> Result<(AddVersionResult, SnapshotUrgency)> {
+        (**self)
+            .add_version(parent_version_id, history_segment)
+            .await
+    }
+
+    async fn get_child_version(
+        &mut self,
+        parent_version_id: VersionId,
+    ) -> Result<GetVersionResult> {
+        (**self).get_child_version(parent_version_id).await
+    }
+
+    async fn add_snapshot(&mut self, version_id: VersionId, snapshot: Snapshot) -> Result<()> {
+        (**self).add_snapshot(version_id, snapshot).await
+    }
+
+    async fn get_snapshot(&mut self) -> Result<Option<(VersionId, Snapshot)>> {
+        (**self).get_snapshot().await
+    }
+
+    async fn rotate_encryption_secret(
+        &mut self,
+        old_secret: &[u8],
+        new_secret: &[u8],
+        parent_version_id: VersionId,
+        history_segment: HistorySegment,
+        snapshot: Snapshot,
+    ) -> Result<AddVersionResult> {
+        (**self)
+            .rotate_encryption_secret(
+                old_secret,
+                new_secret,
+                parent_version_id,
+                history_segment,
+                snapshot,
+            )
+            .await
+    }
+}
+
+/// The error returned from servers that do not support [`Server::rotate_encryption_secret`].
+fn rotation_unsupported() -> Error {
+    Error::Usage("This server does not support changing the encryption secret".into())
 }
@@ -0,0 +1,252 @@
+use crate::errors::Result;
+use crate::storage::{SqliteStorage, Storage};
+use crate::Replica;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+type MakeStorage = dyn Fn() -> Result<Box<dyn Storage>> + Send + Sync;
+type Init = dyn Fn(&mut Replica) + Send + Sync;
+
+/// A handle to a replica that can be shared between threads, such as in a multi-threaded or
+/// async server.
+///
+/// A [`Replica`] is `Send`, so it can be moved to another thread or held across an `.await`, but
+/// it is not `Sync`.  A `SharedReplica` is cheaply cloneable and is both `Send` and `Sync`.  It
+/// maintains a pool of `Replica` instances, each with its own storage connection, and lends one
+/// to each caller of [`SharedReplica::with`].  Concurrent callers thus use separate connections,
+/// relying on the storage's transactions for consistency, as the SQLite storage does.
+///
+/// State held in a `Replica` instance, rather than in its storage, is not shared between the
+/// instances in the pool.  This includes observers registered with [`Replica::subscribe`], hooks
+/// registered with [`Replica::add_hook`], and the redo stack.  Use [`SharedReplica::with_init`]
+/// to configure each instance as it is created.
+///
+/// Cached state, such as the dependency map, is discarded each time an instance is lent, so that
+/// it reflects changes made through other instances.  For the same reason, the redo stack is
+/// cleared, so an undo cannot be redone once the instance has been released.
+///
+/// In-memory storage cannot be shared in this fashion; wrap such a `Replica` in
+/// `Arc<Mutex<Replica>>` instead.
+#[derive(Clone)]
+pub struct SharedReplica {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    make_storage: Box<MakeStorage>,
+    init: Option<Box<Init>>,
+    /// Replicas not currently lent to a caller.
+    idle: Mutex<Vec<Replica>>,
+}
+
+impl SharedReplica {
+    /// Create a new `SharedReplica`, using the given function to open a new connection to the
+    /// storage each time another `Replica` instance is required.
+    pub fn new<F>(make_storage: F) -> Self
+    where
+        F: Fn() -> Result<Box<dyn Storage>> + Send + Sync + 'static,
+    {
+        SharedReplica {
+            inner: Arc::new(Inner {
+                make_storage: Box::new(make_storage),
+                init: None,
+                idle: Mutex::new(Vec::new()),
+            }),
+        }
+    }
+
+    /// Create a new `SharedReplica` using on-disk storage in the given directory, as for
+    /// [`StorageConfig::OnDisk`](crate::StorageConfig::OnDisk).  The database is opened
+    /// immediately, so any error in doing so is returned from this function.
+    pub fn on_disk(taskdb_dir: impl Into<PathBuf>, create_if_missing: bool) -> Result<Self> {
+        let taskdb_dir = taskdb_dir.into();
+        let storage = SqliteStorage::new(&taskdb_dir, create_if_missing)?;
+        let shared = SharedReplica::new(move || {
+            Ok(Box::new(SqliteStorage::new(&taskdb_dir, false)?) as Box<dyn Storage>)
+        });
+        shared.release(Replica::new(Box::new(storage)));
+        Ok(shared)
+    }
+
+    /// Set a function to call to configure each `Replica` instance when it is created, such as
+    /// to add hooks or observers.  This must be called before the `SharedReplica` is cloned or
+    /// used.
+    ///
+    /// # Panics
+    ///
+    /// Panics if this `SharedReplica` has been cloned.
+    pub fn with_init<F>(mut self, init: F) -> Self
+    where
+        F: Fn(&mut Replica) + Send + Sync + 'static,
+    {
+        let inner = Arc::get_mut(&mut self.inner)
+            .expect("SharedReplica::with_init must be called before cloning");
+        for rep in inner.idle.get_mut().unwrap().iter_mut() {
+            init(rep);
+        }
+        inner.init = Some(Box::new(init));
+        self
+    }
+
+    /// Call `f` with a `Replica` instance not in use by any other caller, returning its
+    /// result.  A new instance is created if none is available.
+    pub fn with<T, F>(&self, f: F) -> Result<T>
+    where
+        F: FnOnce(&mut Replica) -> Result<T>,
+    {
+        let mut rep = self.acquire()?;
+        let res = f(&mut rep);
+        self.release(rep);
+        res
+    }
+
+    fn acquire(&self) -> Result<Replica> {
+        if let Some(mut rep) = self.inner.idle.lock().unwrap().pop() {
+            // Other instances may have modified the storage since this one was last used.
+            rep.reset_caches();
+            return Ok(rep);
+        }
+        let mut rep = Replica::new((self.inner.make_storage)()?);
+        if let Some(init) = &self.inner.init {
+            init(&mut rep);
+        }
+        Ok(rep)
+    }
+
+    fn release(&self, rep: Replica) {
+        self.inner.idle.lock().unwrap().push(rep);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Operation, Operations, TaskData};
+    use pretty_assertions::assert_eq;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tempfile::TempDir;
+    use uuid::Uuid;
+
+    fn assert_send_sync<T: Send + Sync>() {}
+    fn assert_send<T: Send>() {}
+
+    #[test]
+    fn is_send_sync() {
+        assert_send_sync::<SharedReplica>();
+        assert_send::<Replica>();
+        assert_send::<crate::Task>();
+    }
+
+    #[test]
+    fn concurrent_use() -> Result<()> {
+        let tmp_dir = TempDir::new()?;
+        let created = Arc::new(AtomicUsize::new(0));
+        let created2 = created.clone();
+        let shared = SharedReplica::on_disk(tmp_dir.path(), true)?.with_init(move |_| {
+            created2.fetch_add(1, Ordering::SeqCst);
+        });
+
+        let threads: Vec<_> = (0..4)
+            .map(|_| {
+                let shared = shared.clone();
+                std::thread::spawn(move || {
+                    for _ in 0..10 {
+                        shared
+                            .with(|rep| {
+                                let mut ops = Operations::new();
+                                TaskData::create(Uuid::new_v4(), &mut ops);
+                                rep.commit_operations(ops)
+                            })
+                            .unwrap();
+                    }
+                })
+            })
+            .collect();
+        for t in threads {
+            t.join().unwrap();
+        }
+
+        assert_eq!(shared.with(|rep| Ok(rep.all_task_uuids()?.len()))?, 40);
+        let num_created = created.load(Ordering::SeqCst);
+        assert!((1..=4).contains(&num_created));
+        assert_eq!(shared.inner.idle.lock().unwrap().len(), num_created);
+        Ok(())
+    }
+
+    #[test]
+    fn caches_reset_between_uses() -> Result<()> {
+        let tmp_dir = TempDir::new()?;
+        let shared = SharedReplica::on_disk(tmp_dir.path(), true)?;
+        let (blocker, blocked) = (Uuid::new_v4(), Uuid::new_v4());
+
+        // Hold one instance while another adds a dependency, so that two instances exist.
+        let mut rep1 = shared.acquire()?;
+        let mut ops = Operations::new();
+        for uuid in [blocker, blocked] {
+            rep1.create_task(uuid, &mut ops)?
+                .set_status(crate::Status::Pending, &mut ops)?;
+        }
+        rep1.commit_operations(ops)?;
+        assert!(rep1
+            .dependency_map(false)?
+            .dependencies(blocked)
+            .next()
+            .is_none());
+        shared.with(|rep2| {
+            let mut ops = Operations::new();
+            rep2.get_task(blocked)?
+                .unwrap()
+                .add_dependency(blocker, &mut ops)?;
+            rep2.commit_operations(ops)
+        })?;
+        shared.release(rep1);
+
+        // Both instances reflect the new dependency, including rep1, which cached the
+        // dependency map before it was added.
+        let blocked_tag = crate::Tag::try_from("BLOCKED").unwrap();
+        let reps = [shared.acquire()?, shared.acquire()?];
+        for mut rep in reps {
+            assert_eq!(
+                rep.dependency_map(false)?
+                    .dependencies(blocked)
+                    .collect::<Vec<_>>(),
+                vec![blocker]
+            );
+            assert!(rep.get_task(blocked)?.unwrap().has_tag(&blocked_tag));
+            shared.release(rep);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn redo_stack_reset_between_uses() -> Result<()> {
+        let tmp_dir = TempDir::new()?;
+        let shared = SharedReplica::on_disk(tmp_dir.path(), true)?;
+
+        // Undo a change on one instance, and make another change through a second instance.
+        let mut rep1 = shared.acquire()?;
+        let mut ops = vec![Operation::UndoPoint];
+        TaskData::create(Uuid::new_v4(), &mut ops);
+        rep1.commit_operations(ops)?;
+        assert!(rep1.undo()?);
+        shared.with(|rep2| {
+            let mut ops = vec![Operation::UndoPoint];
+            TaskData::create(Uuid::new_v4(), &mut ops);
+            rep2.commit_operations(ops)
+        })?;
+        shared.release(rep1);
+
+        // The most recently released instance is lent first, so this is rep1 again, and the
+        // undone change can no longer be redone.
+        let mut rep1 = shared.acquire()?;
+        assert!(!rep1.redo()?);
+        shared.release(rep1);
+        Ok(())
+    }
+
+    #[test]
+    fn storage_error() {
+        let shared = SharedReplica::new(|| Err(crate::Error::Database("no storage".into())));
+        assert!(shared.with(|_| Ok(())).is_err());
+    }
+}
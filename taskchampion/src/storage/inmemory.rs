@@ -1,5 +1,6 @@
 #![allow(clippy::new_without_default)]
 
+use crate::changes::{HistoryEntry, SyncConflict};
 use crate::errors::{Error, Result};
 use crate::operation::Operation;
 use crate::storage::{Storage, StorageTxn, TaskMap, VersionId, DEFAULT_BASE_VERSION};
@@ -12,7 +13,11 @@ struct Data {
     tasks: HashMap<Uuid, TaskMap>,
     base_version: VersionId,
     operations: Vec<Operation>,
+    synced_operations: Vec<Operation>,
     working_set: Vec<Option<Uuid>>,
+    /// Task history, if enabled.
+    history: Option<HashMap<Uuid, Vec<HistoryEntry>>>,
+    conflicts: Vec<SyncConflict>,
 }
 
 struct Txn<'t> {
@@ -107,6 +112,15 @@ impl<'t> StorageTxn for Txn<'t> {
         Ok(())
     }
 
+    fn synced_operations(&mut self) -> Result<Vec<Operation>> {
+        Ok(self.data_ref().synced_operations.clone())
+    }
+
+    fn set_synced_operations(&mut self, ops: Vec<Operation>) -> Result<()> {
+        self.mut_data_ref().synced_operations = ops;
+        Ok(())
+    }
+
     fn get_working_set(&mut self) -> Result<Vec<Option<Uuid>>> {
         Ok(self.data_ref().working_set.clone())
     }
@@ -134,6 +148,40 @@ impl<'t> StorageTxn for Txn<'t> {
         Ok(())
     }
 
+    fn add_history(&mut self, uuid: Uuid, entry: HistoryEntry) -> Result<()> {
+        if self.data_ref().history.is_none() {
+            return Ok(());
+        }
+        if let Some(history) = &mut self.mut_data_ref().history {
+            history.entry(uuid).or_default().push(entry);
+        }
+        Ok(())
+    }
+
+    fn get_history(&mut self, uuid: Uuid) -> Result<Vec<HistoryEntry>> {
+        Ok(self
+            .data_ref()
+            .history
+            .as_ref()
+            .and_then(|h| h.get(&uuid))
+            .cloned()
+            .unwrap_or_default())
+    }
+
+    fn conflicts(&mut self) -> Result<Vec<SyncConflict>> {
+        Ok(self.data_ref().conflicts.clone())
+    }
+
+    fn add_conflict(&mut self, conflict: SyncConflict) -> Result<()> {
+        self.mut_data_ref().conflicts.push(conflict);
+        Ok(())
+    }
+
+    fn set_conflicts(&mut self, conflicts: Vec<SyncConflict>) -> Result<()> {
+        self.mut_data_ref().conflicts = conflicts;
+        Ok(())
+    }
+
     fn commit(&mut self) -> Result<()> {
         // copy the new_data back into storage to commit the transaction
         if let Some(data) = self.new_data.take() {
@@ -157,10 +205,20 @@ impl InMemoryStorage {
                 tasks: HashMap::new(),
                 base_version: DEFAULT_BASE_VERSION,
                 operations: vec![],
+                synced_operations: vec![],
                 working_set: vec![None],
+                history: None,
+                conflicts: vec![],
             },
         }
     }
+
+    /// Enable recording of task history, which is disabled by default.
+    pub fn enable_history(&mut self) {
+        if self.data.history.is_none() {
+            self.data.history = Some(HashMap::new());
+        }
+    }
 }
 
 impl Storage for InMemoryStorage {
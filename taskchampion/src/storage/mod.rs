@@ -9,6 +9,7 @@ sufficient. However, users who wish to implement their own storage backends can
 traits defined here and pass the result to [`Replica`](crate::Replica).
 */
 
+use crate::changes::{HistoryEntry, SyncConflict};
 use crate::errors::Result;
 use crate::operation::Operation;
 use std::collections::HashMap;
@@ -38,6 +39,47 @@ pub(crate) fn taskmap_with(mut properties: Vec<(String, String)>) -> TaskMap {
     rv
 }
 
+/// A condition on a single task property, used to look up tasks with
+/// [`StorageTxn::find_tasks`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PropertyCondition {
+    /// The property is set, with any value.
+    Present(String),
+    /// The property is set to exactly the given value.
+    Equals(String, String),
+    /// The property is set to an integer (such as a timestamp) less than the given value.
+    LessThan(String, i64),
+    /// The property is set to an integer (such as a timestamp) greater than the given value.
+    GreaterThan(String, i64),
+}
+
+impl PropertyCondition {
+    /// Get the property this condition applies to.
+    pub fn property(&self) -> &str {
+        match self {
+            PropertyCondition::Present(p)
+            | PropertyCondition::Equals(p, _)
+            | PropertyCondition::LessThan(p, _)
+            | PropertyCondition::GreaterThan(p, _) => p,
+        }
+    }
+
+    /// Determine whether the given task satisfies this condition.
+    pub fn matches(&self, task: &TaskMap) -> bool {
+        let value = task.get(self.property());
+        match self {
+            PropertyCondition::Present(_) => value.is_some(),
+            PropertyCondition::Equals(_, v) => value == Some(v),
+            PropertyCondition::LessThan(_, v) => {
+                matches!(value.and_then(|s| s.parse::<i64>().ok()), Some(i) if i < *v)
+            }
+            PropertyCondition::GreaterThan(_, v) => {
+                matches!(value.and_then(|s| s.parse::<i64>().ok()), Some(i) if i > *v)
+            }
+        }
+    }
+}
+
 /// The type of VersionIds
 pub use crate::server::VersionId;
 
@@ -78,6 +120,17 @@ pub trait StorageTxn {
     /// Get the uuids of all tasks in the storage, in undefined order.
     fn all_task_uuids(&mut self) -> Result<Vec<Uuid>>;
 
+    /// Get the uuids and bodies of all tasks satisfying all of the given conditions, in undefined
+    /// order.
+    ///
+    /// The default implementation scans all tasks.  Implementations may use indexes to answer
+    /// this query more efficiently.
+    fn find_tasks(&mut self, conditions: &[PropertyCondition]) -> Result<Vec<(Uuid, TaskMap)>> {
+        let mut tasks = self.all_tasks()?;
+        tasks.retain(|(_, t)| conditions.iter().all(|c| c.matches(t)));
+        Ok(tasks)
+    }
+
     /// Get the current base_version for this storage -- the last version synced from the server.
     fn base_version(&mut self) -> Result<VersionId>;
 
@@ -99,6 +152,24 @@ pub trait StorageTxn {
     /// Replace the current list of operations with a new list.
     fn set_operations(&mut self, ops: Vec<Operation>) -> Result<()>;
 
+    /// Get the local operations retained after they were synchronized to the server, for use in
+    /// undoing synchronized changes.
+    ///
+    /// The default implementation does not retain synchronized operations, and always returns an
+    /// empty list.
+    fn synced_operations(&mut self) -> Result<Vec<Operation>> {
+        Ok(vec![])
+    }
+
+    /// Replace the list of retained synchronized operations with a new list.  Storage
+    /// implementations that do not retain synchronized operations ignore this call.
+    ///
+    /// The default implementation does not retain synchronized operations.
+    fn set_synced_operations(&mut self, ops: Vec<Operation>) -> Result<()> {
+        let _ = ops;
+        Ok(())
+    }
+
     /// Get the entire working set, with each task UUID at its appropriate (1-based) index.
     /// Element 0 is always None.
     fn get_working_set(&mut self) -> Result<Vec<Option<Uuid>>>;
@@ -115,6 +186,48 @@ pub trait StorageTxn {
     /// Note that this is the only way items are removed from the set.
     fn clear_working_set(&mut self) -> Result<()>;
 
+    /// Record an entry in the history of the given task.  Storage implementations that do not
+    /// retain task history, or in which it is not enabled, ignore this call.
+    ///
+    /// The default implementation does not retain history.
+    fn add_history(&mut self, uuid: Uuid, entry: HistoryEntry) -> Result<()> {
+        let _ = (uuid, entry);
+        Ok(())
+    }
+
+    /// Get the recorded history of the given task, oldest first.
+    ///
+    /// The default implementation does not retain history, and always returns an empty list.
+    fn get_history(&mut self, uuid: Uuid) -> Result<Vec<HistoryEntry>> {
+        let _ = uuid;
+        Ok(vec![])
+    }
+
+    /// Get the conflicts recorded during sync, oldest first.
+    ///
+    /// The default implementation does not retain conflicts, and always returns an empty list.
+    fn conflicts(&mut self) -> Result<Vec<SyncConflict>> {
+        Ok(vec![])
+    }
+
+    /// Record a conflict resolved during sync.  Storage implementations that do not retain
+    /// conflicts ignore this call.
+    ///
+    /// The default implementation does not retain conflicts.
+    fn add_conflict(&mut self, conflict: SyncConflict) -> Result<()> {
+        let _ = conflict;
+        Ok(())
+    }
+
+    /// Replace the recorded conflicts with a new list.  Storage implementations that do not
+    /// retain conflicts ignore this call.
+    ///
+    /// The default implementation does not retain conflicts.
+    fn set_conflicts(&mut self, conflicts: Vec<SyncConflict>) -> Result<()> {
+        let _ = conflicts;
+        Ok(())
+    }
+
     /// Check whether this storage is entirely empty
     #[allow(clippy::wrong_self_convention)] // mut is required here for storage access
     fn is_empty(&mut self) -> Result<bool> {
@@ -133,7 +246,10 @@ pub trait StorageTxn {
 
 /// A trait for objects able to act as task storage.  Most of the interesting behavior is in the
 /// [`crate::storage::StorageTxn`] trait.
-pub trait Storage {
+///
+/// Storage must be `Send`, so that a [`Replica`](crate::Replica) can be moved between threads.
+/// Transactions need not be `Send`.
+pub trait Storage: Send {
     /// Begin a transaction
     fn txn<'a>(&'a mut self) -> Result<Box<dyn StorageTxn + 'a>>;
 }
@@ -1,10 +1,17 @@
+use crate::changes::{HistoryEntry, SyncConflict};
 use crate::errors::Result;
 use crate::operation::Operation;
-use crate::storage::{Storage, StorageTxn, TaskMap, VersionId, DEFAULT_BASE_VERSION};
+use crate::storage::{
+    PropertyCondition, Storage, StorageTxn, TaskMap, VersionId, DEFAULT_BASE_VERSION,
+};
 use anyhow::Context;
 use rusqlite::types::{FromSql, ToSql};
-use rusqlite::{params, Connection, OpenFlags, OptionalExtension, TransactionBehavior};
+use rusqlite::{
+    params, params_from_iter, Connection, OpenFlags, OptionalExtension, TransactionBehavior,
+};
+use std::collections::HashSet;
 use std::path::Path;
+use std::rc::Rc;
 use uuid::Uuid;
 
 #[derive(Debug, thiserror::Error)]
@@ -72,7 +79,37 @@ impl ToSql for Operation {
     }
 }
 
+/// The set of properties maintained in the `task_index` table.
+struct IndexedProperties {
+    /// UDAs to index, in addition to the properties that are always indexed.
+    udas: HashSet<String>,
+}
+
+impl IndexedProperties {
+    fn contains(&self, property: &str) -> bool {
+        matches!(property, "status" | "due" | "wait")
+            || property.starts_with("tag_")
+            || property.starts_with("dep_")
+            || self.udas.contains(property)
+    }
+}
+
 /// SqliteStorage is an on-disk storage backed by SQLite3.
+///
+/// # Indexes
+///
+/// By default, tasks are stored as opaque blobs and [`StorageTxn::find_tasks`] must scan every
+/// task. Secondary indexes can be enabled with [`SqliteStorage::enable_indexes`], after which the
+/// `status`, `due`, `wait`, `tag_*` and `dep_*` properties, as well as any selected UDAs, are
+/// indexed and conditions on those properties are answered without a full scan.  The index
+/// configuration is stored in the database, so it applies to every subsequent use of the
+/// database, regardless of how it is opened.
+///
+/// # History
+///
+/// Local operations are discarded once they are synchronized.  To retain a record of the changes
+/// made to each task, enable history with [`SqliteStorage::enable_history`].  As with indexes,
+/// this setting is stored in the database.
 pub struct SqliteStorage {
     con: Connection,
 }
@@ -100,9 +137,17 @@ impl SqliteStorage {
 
         let queries = vec![
             "CREATE TABLE IF NOT EXISTS operations (id INTEGER PRIMARY KEY AUTOINCREMENT, data STRING);",
+            "CREATE TABLE IF NOT EXISTS synced_operations (id INTEGER PRIMARY KEY AUTOINCREMENT, data STRING);",
             "CREATE TABLE IF NOT EXISTS sync_meta (key STRING PRIMARY KEY, value STRING);",
             "CREATE TABLE IF NOT EXISTS tasks (uuid STRING PRIMARY KEY, data STRING);",
             "CREATE TABLE IF NOT EXISTS working_set (id INTEGER PRIMARY KEY, uuid STRING);",
+            // (values use TEXT affinity, as STRING would convert numeric-looking values)
+            "CREATE TABLE IF NOT EXISTS task_index (uuid STRING, property TEXT, value TEXT);",
+            "CREATE INDEX IF NOT EXISTS task_index_by_property ON task_index (property, value);",
+            "CREATE INDEX IF NOT EXISTS task_index_by_uuid ON task_index (uuid);",
+            "CREATE TABLE IF NOT EXISTS task_history (id INTEGER PRIMARY KEY AUTOINCREMENT, uuid STRING, data STRING);",
+            "CREATE INDEX IF NOT EXISTS task_history_by_uuid ON task_history (uuid);",
+            "CREATE TABLE IF NOT EXISTS sync_conflicts (id INTEGER PRIMARY KEY AUTOINCREMENT, data STRING);",
         ];
         for q in queries {
             con.execute(q, []).context("Creating table")?;
@@ -110,10 +155,78 @@ impl SqliteStorage {
 
         Ok(SqliteStorage { con })
     }
+
+    /// Enable secondary indexes on this database, indexing the given UDAs in addition to the
+    /// properties that are always indexed.  This replaces any existing index configuration and
+    /// rebuilds the index, which may take some time for large databases.
+    pub fn enable_indexes<S: Into<String>>(
+        &mut self,
+        udas: impl IntoIterator<Item = S>,
+    ) -> Result<()> {
+        let udas: Vec<String> = udas.into_iter().map(Into::into).collect();
+        let mut txn = self.begin()?;
+        txn.get_txn()?
+            .execute(
+                "INSERT OR REPLACE INTO sync_meta (key, value) VALUES (?, ?)",
+                params!["indexed_udas", serde_json::to_string(&udas)?],
+            )
+            .context("Set index configuration")?;
+        txn.rebuild_index()?;
+        txn.commit()
+    }
+
+    /// Disable secondary indexes on this database, removing the index data.
+    pub fn disable_indexes(&mut self) -> Result<()> {
+        let mut txn = self.begin()?;
+        txn.get_txn()?
+            .execute("DELETE FROM sync_meta WHERE key = 'indexed_udas'", [])
+            .context("Clear index configuration")?;
+        txn.rebuild_index()?;
+        txn.commit()
+    }
+
+    /// Enable recording of task history in this database.  History is recorded for changes
+    /// made after this call.
+    pub fn enable_history(&mut self) -> Result<()> {
+        let mut txn = self.begin()?;
+        txn.get_txn()?
+            .execute(
+                "INSERT OR REPLACE INTO sync_meta (key, value) VALUES ('history', 'enabled')",
+                [],
+            )
+            .context("Set history configuration")?;
+        txn.commit()
+    }
+
+    /// Disable recording of task history in this database, removing any recorded history.
+    pub fn disable_history(&mut self) -> Result<()> {
+        let mut txn = self.begin()?;
+        let t = txn.get_txn()?;
+        t.execute("DELETE FROM sync_meta WHERE key = 'history'", [])
+            .context("Clear history configuration")?;
+        t.execute("DELETE FROM task_history", [])
+            .context("Clear task history")?;
+        txn.commit()
+    }
+
+    fn begin(&mut self) -> Result<Txn<'_>> {
+        let txn = self
+            .con
+            .transaction_with_behavior(TransactionBehavior::Immediate)?;
+        Ok(Txn {
+            txn: Some(txn),
+            indexed: None,
+            history_enabled: None,
+        })
+    }
 }
 
 struct Txn<'t> {
     txn: Option<rusqlite::Transaction<'t>>,
+    /// The indexed properties, loaded on first use.  `Some(None)` indicates indexing is disabled.
+    indexed: Option<Option<Rc<IndexedProperties>>>,
+    /// Whether history is enabled, loaded on first use.
+    history_enabled: Option<bool>,
 }
 
 impl<'t> Txn<'t> {
@@ -136,14 +249,84 @@ impl<'t> Txn<'t> {
 
         Ok(next_id.unwrap_or(0))
     }
+
+    /// Get the set of indexed properties, or None if indexing is disabled.
+    fn indexed_properties(&mut self) -> Result<Option<Rc<IndexedProperties>>> {
+        if let Some(indexed) = &self.indexed {
+            return Ok(indexed.clone());
+        }
+        let t = self.get_txn()?;
+        let udas: Option<String> = t
+            .query_row(
+                "SELECT value FROM sync_meta WHERE key = 'indexed_udas'",
+                [],
+                |r| r.get("value"),
+            )
+            .optional()
+            .context("Get index configuration")?;
+        let indexed = match udas {
+            Some(udas) => Some(Rc::new(IndexedProperties {
+                udas: serde_json::from_str(&udas)?,
+            })),
+            None => None,
+        };
+        self.indexed = Some(indexed.clone());
+        Ok(indexed)
+    }
+
+    /// Determine whether history is enabled.
+    fn history_enabled(&mut self) -> Result<bool> {
+        if let Some(enabled) = self.history_enabled {
+            return Ok(enabled);
+        }
+        let t = self.get_txn()?;
+        let enabled = t
+            .query_row("SELECT 1 FROM sync_meta WHERE key = 'history'", [], |_| {
+                Ok(())
+            })
+            .optional()
+            .context("Get history configuration")?
+            .is_some();
+        self.history_enabled = Some(enabled);
+        Ok(enabled)
+    }
+
+    /// Update the index entries for the given task, removing them if `task` is None.
+    fn update_index(&mut self, uuid: Uuid, task: Option<&TaskMap>) -> Result<()> {
+        let indexed = self.indexed_properties()?;
+        let t = self.get_txn()?;
+        t.execute("DELETE FROM task_index WHERE uuid = ?", [&StoredUuid(uuid)])
+            .context("Clear task index query")?;
+        if let (Some(indexed), Some(task)) = (indexed, task) {
+            for (property, value) in task.iter().filter(|(p, _)| indexed.contains(p)) {
+                t.execute(
+                    "INSERT INTO task_index (uuid, property, value) VALUES (?, ?, ?)",
+                    params![&StoredUuid(uuid), property, value],
+                )
+                .context("Update task index query")?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Rebuild the index from scratch, based on the current configuration.
+    fn rebuild_index(&mut self) -> Result<()> {
+        self.indexed = None;
+        self.get_txn()?
+            .execute("DELETE FROM task_index", [])
+            .context("Clear task index query")?;
+        if self.indexed_properties()?.is_some() {
+            for (uuid, task) in self.all_tasks()? {
+                self.update_index(uuid, Some(&task))?;
+            }
+        }
+        Ok(())
+    }
 }
 
 impl Storage for SqliteStorage {
     fn txn<'a>(&'a mut self) -> Result<Box<dyn StorageTxn + 'a>> {
-        let txn = self
-            .con
-            .transaction_with_behavior(TransactionBehavior::Immediate)?;
-        Ok(Box::new(Txn { txn: Some(txn) }))
+        Ok(Box::new(self.begin()?))
     }
 }
 
@@ -183,6 +366,7 @@ impl<'t> StorageTxn for Txn<'t> {
     }
 
     fn set_task(&mut self, uuid: Uuid, task: TaskMap) -> Result<()> {
+        self.update_index(uuid, Some(&task))?;
         let t = self.get_txn()?;
         t.execute(
             "INSERT OR REPLACE INTO tasks (uuid, data) VALUES (?, ?)",
@@ -193,6 +377,7 @@ impl<'t> StorageTxn for Txn<'t> {
     }
 
     fn delete_task(&mut self, uuid: Uuid) -> Result<bool> {
+        self.update_index(uuid, None)?;
         let t = self.get_txn()?;
         let changed = t
             .execute("DELETE FROM tasks WHERE uuid = ?", [&StoredUuid(uuid)])
@@ -233,6 +418,56 @@ impl<'t> StorageTxn for Txn<'t> {
         Ok(ret)
     }
 
+    fn find_tasks(&mut self, conditions: &[PropertyCondition]) -> Result<Vec<(Uuid, TaskMap)>> {
+        let Some(indexed) = self.indexed_properties()? else {
+            let mut tasks = self.all_tasks()?;
+            tasks.retain(|(_, t)| conditions.iter().all(|c| c.matches(t)));
+            return Ok(tasks);
+        };
+
+        // Narrow the set of tasks using the conditions on indexed properties, then check all
+        // conditions against the resulting tasks.
+        let mut query = String::from("SELECT uuid, data FROM tasks WHERE 1");
+        let mut query_params: Vec<Box<dyn ToSql>> = vec![];
+        for cond in conditions.iter().filter(|c| indexed.contains(c.property())) {
+            query.push_str(" AND uuid IN (SELECT uuid FROM task_index WHERE property = ?");
+            query_params.push(Box::new(cond.property().to_string()));
+            match cond {
+                PropertyCondition::Present(_) => {}
+                PropertyCondition::Equals(_, v) => {
+                    query.push_str(" AND value = ?");
+                    query_params.push(Box::new(v.clone()));
+                }
+                PropertyCondition::LessThan(_, v) => {
+                    query.push_str(" AND CAST(value AS INTEGER) < ?");
+                    query_params.push(Box::new(*v));
+                }
+                PropertyCondition::GreaterThan(_, v) => {
+                    query.push_str(" AND CAST(value AS INTEGER) > ?");
+                    query_params.push(Box::new(*v));
+                }
+            }
+            query.push(')');
+        }
+
+        let t = self.get_txn()?;
+        let mut q = t.prepare(&query)?;
+        let rows = q.query_map(params_from_iter(query_params.iter()), |r| {
+            let uuid: StoredUuid = r.get("uuid")?;
+            let data: StoredTaskMap = r.get("data")?;
+            Ok((uuid.0, data.0))
+        })?;
+
+        let mut ret = vec![];
+        for r in rows {
+            let (uuid, task) = r?;
+            if conditions.iter().all(|c| c.matches(&task)) {
+                ret.push((uuid, task));
+            }
+        }
+        Ok(ret)
+    }
+
     fn base_version(&mut self) -> Result<VersionId> {
         let t = self.get_txn()?;
 
@@ -299,6 +534,33 @@ impl<'t> StorageTxn for Txn<'t> {
         Ok(())
     }
 
+    fn synced_operations(&mut self) -> Result<Vec<Operation>> {
+        let t = self.get_txn()?;
+
+        let mut q = t.prepare("SELECT data FROM synced_operations ORDER BY id ASC")?;
+        let rows = q.query_map([], |r| r.get::<_, Operation>("data"))?;
+
+        let mut ret = vec![];
+        for r in rows {
+            ret.push(r?);
+        }
+        Ok(ret)
+    }
+
+    fn set_synced_operations(&mut self, ops: Vec<Operation>) -> Result<()> {
+        let t = self.get_txn()?;
+        t.execute("DELETE FROM synced_operations", [])
+            .context("Clear synced operations")?;
+        for op in ops {
+            t.execute(
+                "INSERT INTO synced_operations (data) VALUES (?)",
+                params![&op],
+            )
+            .context("Add synced operation query")?;
+        }
+        Ok(())
+    }
+
     fn get_working_set(&mut self) -> Result<Vec<Option<Uuid>>> {
         let t = self.get_txn()?;
 
@@ -363,6 +625,63 @@ impl<'t> StorageTxn for Txn<'t> {
         Ok(())
     }
 
+    fn add_history(&mut self, uuid: Uuid, entry: HistoryEntry) -> Result<()> {
+        if !self.history_enabled()? {
+            return Ok(());
+        }
+        let t = self.get_txn()?;
+        t.execute(
+            "INSERT INTO task_history (uuid, data) VALUES (?, ?)",
+            params![&StoredUuid(uuid), serde_json::to_string(&entry)?],
+        )
+        .context("Add history query")?;
+        Ok(())
+    }
+
+    fn get_history(&mut self, uuid: Uuid) -> Result<Vec<HistoryEntry>> {
+        let t = self.get_txn()?;
+        let mut q = t.prepare("SELECT data FROM task_history WHERE uuid = ? ORDER BY id ASC")?;
+        let rows = q.query_map([&StoredUuid(uuid)], |r| r.get::<_, String>("data"))?;
+
+        let mut ret = vec![];
+        for r in rows {
+            ret.push(serde_json::from_str(&r?)?);
+        }
+        Ok(ret)
+    }
+
+    fn conflicts(&mut self) -> Result<Vec<SyncConflict>> {
+        let t = self.get_txn()?;
+        let mut q = t.prepare("SELECT data FROM sync_conflicts ORDER BY id ASC")?;
+        let rows = q.query_map([], |r| r.get::<_, String>("data"))?;
+
+        let mut ret = vec![];
+        for r in rows {
+            ret.push(serde_json::from_str(&r?)?);
+        }
+        Ok(ret)
+    }
+
+    fn add_conflict(&mut self, conflict: SyncConflict) -> Result<()> {
+        let t = self.get_txn()?;
+        t.execute(
+            "INSERT INTO sync_conflicts (data) VALUES (?)",
+            params![serde_json::to_string(&conflict)?],
+        )
+        .context("Add conflict query")?;
+        Ok(())
+    }
+
+    fn set_conflicts(&mut self, conflicts: Vec<SyncConflict>) -> Result<()> {
+        self.get_txn()?
+            .execute("DELETE FROM sync_conflicts", [])
+            .context("Clear conflicts")?;
+        for conflict in conflicts {
+            self.add_conflict(conflict)?;
+        }
+        Ok(())
+    }
+
     fn commit(&mut self) -> Result<()> {
         let t = self
             .txn
@@ -566,7 +885,7 @@ mod test {
             let mut tasks = txn.all_tasks()?;
 
             // order is nondeterministic, so sort by uuid
-            tasks.sort_by(|a, b| a.0.cmp(&b.0));
+            tasks.sort_by_key(|a| a.0);
 
             let mut exp = vec![
                 (
@@ -578,7 +897,7 @@ mod test {
                     taskmap_with(vec![("num".to_string(), "2".to_string())]),
                 ),
             ];
-            exp.sort_by(|a, b| a.0.cmp(&b.0));
+            exp.sort_by_key(|a| a.0);
 
             assert_eq!(tasks, exp);
         }
@@ -595,6 +914,267 @@ mod test {
         Ok(())
     }
 
+    /// Find tasks matching the given conditions, returning a sorted list of UUIDs.
+    fn find_uuids(
+        storage: &mut SqliteStorage,
+        conditions: &[PropertyCondition],
+    ) -> Result<Vec<Uuid>> {
+        let mut txn = storage.txn()?;
+        let mut uuids: Vec<Uuid> = txn
+            .find_tasks(conditions)?
+            .into_iter()
+            .map(|(u, _)| u)
+            .collect();
+        uuids.sort();
+        Ok(uuids)
+    }
+
+    /// Create three tasks with various indexed and un-indexed properties.
+    fn make_find_tasks(storage: &mut SqliteStorage) -> Result<[Uuid; 3]> {
+        let mut uuids = [Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4()];
+        uuids.sort();
+        let tasks = [
+            vec![("status", "pending"), ("tag_work", ""), ("due", "100")],
+            vec![("status", "pending"), ("priority", "H"), ("due", "200")],
+            vec![("status", "completed"), ("tag_work", ""), ("priority", "H")],
+        ];
+        let mut txn = storage.txn()?;
+        for (uuid, task) in uuids.iter().zip(tasks) {
+            txn.set_task(
+                *uuid,
+                taskmap_with(
+                    task.iter()
+                        .map(|(p, v)| (p.to_string(), v.to_string()))
+                        .collect(),
+                ),
+            )?;
+        }
+        txn.commit()?;
+        Ok(uuids)
+    }
+
+    /// Check the results of `find_tasks` on the tasks from `make_find_tasks`.
+    fn check_find_tasks(storage: &mut SqliteStorage, uuids: [Uuid; 3]) -> Result<()> {
+        use PropertyCondition::*;
+        let [uuid1, uuid2, uuid3] = uuids;
+        let eq = |p: &str, v: &str| Equals(p.into(), v.into());
+        assert_eq!(find_uuids(storage, &[])?, vec![uuid1, uuid2, uuid3]);
+        assert_eq!(
+            find_uuids(storage, &[eq("status", "pending")])?,
+            vec![uuid1, uuid2]
+        );
+        assert_eq!(
+            find_uuids(
+                storage,
+                &[eq("status", "pending"), Present("tag_work".into())]
+            )?,
+            vec![uuid1]
+        );
+        assert_eq!(
+            find_uuids(storage, &[eq("priority", "H"), Present("tag_work".into())])?,
+            vec![uuid3]
+        );
+        assert_eq!(
+            find_uuids(storage, &[LessThan("due".into(), 150)])?,
+            vec![uuid1]
+        );
+        assert_eq!(
+            find_uuids(storage, &[GreaterThan("due".into(), 50)])?,
+            vec![uuid1, uuid2]
+        );
+        assert_eq!(find_uuids(storage, &[Present("nosuch".into())])?, vec![]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_find_tasks_no_index() -> Result<()> {
+        let tmp_dir = TempDir::new()?;
+        let mut storage = SqliteStorage::new(tmp_dir.path(), true)?;
+        let uuids = make_find_tasks(&mut storage)?;
+        check_find_tasks(&mut storage, uuids)
+    }
+
+    #[test]
+    fn test_find_tasks_indexed() -> Result<()> {
+        let tmp_dir = TempDir::new()?;
+        let mut storage = SqliteStorage::new(tmp_dir.path(), true)?;
+        storage.enable_indexes(["priority"])?;
+        let uuids = make_find_tasks(&mut storage)?;
+        check_find_tasks(&mut storage, uuids)
+    }
+
+    #[test]
+    fn test_find_tasks_index_backfilled() -> Result<()> {
+        let tmp_dir = TempDir::new()?;
+        let mut storage = SqliteStorage::new(tmp_dir.path(), true)?;
+        let uuids = make_find_tasks(&mut storage)?;
+        storage.enable_indexes(["priority"])?;
+        check_find_tasks(&mut storage, uuids)
+    }
+
+    /// Get the sorted contents of the task_index table.
+    fn index_rows(storage: &mut SqliteStorage) -> Result<Vec<(Uuid, String, String)>> {
+        let mut q = storage
+            .con
+            .prepare("SELECT uuid, property, value FROM task_index")?;
+        let mut rows: Vec<(Uuid, String, String)> = q
+            .query_map([], |r| {
+                let uuid: StoredUuid = r.get("uuid")?;
+                Ok((uuid.0, r.get("property")?, r.get("value")?))
+            })?
+            .collect::<std::result::Result<_, _>>()?;
+        rows.sort();
+        Ok(rows)
+    }
+
+    #[test]
+    fn test_index_maintained() -> Result<()> {
+        let tmp_dir = TempDir::new()?;
+        let uuid = Uuid::new_v4();
+        {
+            let mut storage = SqliteStorage::new(tmp_dir.path(), true)?;
+            storage.enable_indexes(["estimate"])?;
+        }
+
+        // the index configuration persists when the database is re-opened
+        let mut storage = SqliteStorage::new(tmp_dir.path(), true)?;
+        {
+            let mut txn = storage.txn()?;
+            txn.set_task(
+                uuid,
+                taskmap_with(vec![
+                    ("status".into(), "pending".into()),
+                    ("description".into(), "unindexed".into()),
+                    ("estimate".into(), "3".into()),
+                    ("tag_x".into(), "".into()),
+                ]),
+            )?;
+            txn.commit()?;
+        }
+        assert_eq!(
+            index_rows(&mut storage)?,
+            vec![
+                (uuid, "estimate".into(), "3".into()),
+                (uuid, "status".into(), "pending".into()),
+                (uuid, "tag_x".into(), "".into()),
+            ]
+        );
+
+        {
+            let mut txn = storage.txn()?;
+            txn.set_task(
+                uuid,
+                taskmap_with(vec![("status".into(), "completed".into())]),
+            )?;
+            txn.commit()?;
+        }
+        assert_eq!(
+            index_rows(&mut storage)?,
+            vec![(uuid, "status".into(), "completed".into())]
+        );
+
+        {
+            let mut txn = storage.txn()?;
+            txn.delete_task(uuid)?;
+            txn.commit()?;
+        }
+        assert_eq!(index_rows(&mut storage)?, vec![]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_disable_indexes() -> Result<()> {
+        let tmp_dir = TempDir::new()?;
+        let mut storage = SqliteStorage::new(tmp_dir.path(), true)?;
+        storage.enable_indexes(Vec::<String>::new())?;
+        let uuids = make_find_tasks(&mut storage)?;
+        assert_eq!(index_rows(&mut storage)?.len(), 7);
+
+        storage.disable_indexes()?;
+        assert_eq!(index_rows(&mut storage)?, vec![]);
+        check_find_tasks(&mut storage, uuids)
+    }
+
+    fn history_entry(property: &str) -> HistoryEntry {
+        HistoryEntry {
+            timestamp: chrono::Utc::now(),
+            kind: crate::ChangeKind::Updated {
+                property: property.into(),
+                old_value: None,
+                value: Some("v".into()),
+            },
+            origin: crate::ChangeOrigin::Local,
+        }
+    }
+
+    #[test]
+    fn test_history() -> Result<()> {
+        let tmp_dir = TempDir::new()?;
+        let mut storage = SqliteStorage::new(tmp_dir.path(), true)?;
+        let uuid1 = Uuid::new_v4();
+        let uuid2 = Uuid::new_v4();
+
+        // history is not recorded by default
+        {
+            let mut txn = storage.txn()?;
+            txn.add_history(uuid1, history_entry("a"))?;
+            txn.commit()?;
+        }
+        assert_eq!(storage.txn()?.get_history(uuid1)?, vec![]);
+
+        storage.enable_history()?;
+        let (e1, e2, e3) = (history_entry("b"), history_entry("c"), history_entry("d"));
+        {
+            let mut txn = storage.txn()?;
+            txn.add_history(uuid1, e1.clone())?;
+            txn.add_history(uuid2, e2.clone())?;
+            txn.add_history(uuid1, e3.clone())?;
+            txn.commit()?;
+        }
+        {
+            let mut txn = storage.txn()?;
+            assert_eq!(txn.get_history(uuid1)?, vec![e1, e3]);
+            assert_eq!(txn.get_history(uuid2)?, vec![e2]);
+        }
+
+        storage.disable_history()?;
+        assert_eq!(storage.txn()?.get_history(uuid1)?, vec![]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_conflicts() -> Result<()> {
+        let tmp_dir = TempDir::new()?;
+        let mut storage = SqliteStorage::new(tmp_dir.path(), true)?;
+        let conflict = |property: &str| SyncConflict {
+            uuid: Uuid::new_v4(),
+            property: property.into(),
+            losing_value: Some("lose".into()),
+            losing_timestamp: chrono::Utc::now(),
+            losing_origin: crate::ChangeOrigin::Remote,
+            winning_value: None,
+            winning_timestamp: chrono::Utc::now(),
+        };
+        let (c1, c2, c3) = (conflict("a"), conflict("b"), conflict("c"));
+
+        {
+            let mut txn = storage.txn()?;
+            assert_eq!(txn.conflicts()?, vec![]);
+            txn.add_conflict(c1.clone())?;
+            txn.add_conflict(c2.clone())?;
+            txn.commit()?;
+        }
+        assert_eq!(storage.txn()?.conflicts()?, vec![c1.clone(), c2]);
+
+        {
+            let mut txn = storage.txn()?;
+            txn.set_conflicts(vec![c3.clone(), c1.clone()])?;
+            txn.commit()?;
+        }
+        assert_eq!(storage.txn()?.conflicts()?, vec![c3, c1]);
+        Ok(())
+    }
+
     #[test]
     fn test_base_version_default() -> Result<()> {
         let tmp_dir = TempDir::new()?;
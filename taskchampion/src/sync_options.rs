@@ -0,0 +1,125 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+type ProgressFn<'a> = dyn FnMut(SyncProgress) + Send + 'a;
+
+/// Options controlling a sync, for use with [`Replica::sync_with_options`] and
+/// [`Replica::sync_async_with_options`].
+///
+/// [`Replica::sync_with_options`]: crate::Replica::sync_with_options
+/// [`Replica::sync_async_with_options`]: crate::Replica::sync_async_with_options
+#[derive(Default)]
+pub struct SyncOptions<'a> {
+    pub(crate) avoid_snapshots: bool,
+    progress: Option<Box<ProgressFn<'a>>>,
+    cancellation: Option<CancellationToken>,
+}
+
+impl<'a> SyncOptions<'a> {
+    /// Create a new set of options with the default values.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// If true, the sync produces a snapshot only when the server indicates it is urgent
+    /// (snapshot urgency "high").  This allows time for other replicas to create a snapshot
+    /// before this one does.
+    ///
+    /// Set this to true on systems more constrained in CPU, memory, or bandwidth than a typical
+    /// desktop system.
+    pub fn avoid_snapshots(mut self, avoid_snapshots: bool) -> Self {
+        self.avoid_snapshots = avoid_snapshots;
+        self
+    }
+
+    /// Call the given function as the sync progresses.  The function is called synchronously,
+    /// so it should return quickly.
+    pub fn on_progress<F>(mut self, progress: F) -> Self
+    where
+        F: FnMut(SyncProgress) + Send + 'a,
+    {
+        self.progress = Some(Box::new(progress));
+        self
+    }
+
+    /// Check the given token during the sync, stopping with [`Error::Cancelled`] once it is
+    /// cancelled.
+    ///
+    /// [`Error::Cancelled`]: crate::Error::Cancelled
+    pub fn cancellation_token(mut self, token: CancellationToken) -> Self {
+        self.cancellation = Some(token);
+        self
+    }
+
+    /// Report progress to the callback, if any.
+    pub(crate) fn progress(&mut self, progress: SyncProgress) {
+        if let Some(f) = &mut self.progress {
+            f(progress);
+        }
+    }
+
+    /// Determine whether the sync has been cancelled.
+    pub(crate) fn is_cancelled(&self) -> bool {
+        self.cancellation
+            .as_ref()
+            .map(CancellationToken::is_cancelled)
+            .unwrap_or(false)
+    }
+}
+
+/// A stage in the progress of a sync, reported to the function given to
+/// [`SyncOptions::on_progress`].
+///
+/// A sync may begin again, if another replica or another sync of the same replica made changes in
+/// the interim, in which case the counts begin again from zero.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum SyncProgress {
+    /// A snapshot was fetched from the server and applied to the (empty) replica.
+    SnapshotApplied,
+    /// A version was fetched from the server.  `count` is the number of versions fetched so far.
+    VersionFetched { count: usize },
+    /// The local operations were transformed to apply after the versions fetched from the server.
+    OperationsTransformed {
+        /// The number of operations fetched from the server.
+        remote_operations: usize,
+        /// The number of local operations remaining to send to the server.
+        local_operations: usize,
+    },
+    /// A batch of local operations was accepted by the server, as a new version.  The `batch`
+    /// counts from 1 to `batches`.
+    BatchUploaded { batch: usize, batches: usize },
+    /// A snapshot is being uploaded to the server.  This occurs after all changes have been
+    /// committed to storage.
+    SnapshotUploading,
+}
+
+/// A token used to cancel a sync in progress, from another thread or task.
+///
+/// Clones of a token share the same state, so one clone can be given to
+/// [`SyncOptions::cancellation_token`] while another is used to cancel the sync.
+///
+/// The token is checked between each exchange with the server.  When the sync is cancelled, any
+/// changes fetched from the server and not yet applied are discarded.  Versions are applied as
+/// they are fetched only when the replica has no local changes to send; otherwise, the replica's
+/// storage is unchanged.  Local changes that the server had already accepted will be fetched
+/// again on the next sync.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    /// Create a new token, not yet cancelled.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cancel any sync using this token.
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    /// Determine whether this token has been cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
@@ -0,0 +1,96 @@
+use crate::changes::{ChangeKind, TaskChange};
+use crate::server::VersionId;
+use std::collections::HashSet;
+
+/// A summary of a sync, as returned from [`Replica::sync_with_report`].
+///
+/// If the sync began again, because another replica or another sync of the same replica made
+/// changes in the interim, this describes only the final, successful, attempt.
+///
+/// [`Replica::sync_with_report`]: crate::Replica::sync_with_report
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct SyncReport {
+    /// The number of versions fetched from the server and applied to the replica, not including
+    /// any snapshot.
+    pub versions_applied: usize,
+    /// The number of local operations sent to the server.
+    pub operations_pushed: usize,
+    /// The number of tasks created by remote changes.
+    pub tasks_created: usize,
+    /// The number of existing tasks modified by remote changes.  This does not include tasks
+    /// that were also created or deleted in the same sync.
+    pub tasks_modified: usize,
+    /// The number of tasks deleted by remote changes.
+    pub tasks_deleted: usize,
+    /// True if the replica was empty and was initialized from a snapshot fetched from the server.
+    pub snapshot_applied: bool,
+    /// True if a snapshot of the replica was sent to the server.
+    pub snapshot_uploaded: bool,
+    /// The number of conflicts between local and remote operations that were resolved by
+    /// preferring one operation over the other, such as two updates to the same property.
+    pub conflicts_resolved: usize,
+    /// The number of pairs of conflicting updates to the same property that were combined with a
+    /// text merge, as configured with
+    /// [`Replica::set_merge_strategy`](crate::Replica::set_merge_strategy).
+    pub updates_merged: usize,
+    /// The base version of the replica after the sync.
+    pub base_version: VersionId,
+}
+
+impl SyncReport {
+    /// Count the tasks created, modified, and deleted by the given changes.
+    pub(crate) fn count_changes(&mut self, changes: &[TaskChange]) {
+        let mut created = HashSet::new();
+        let mut deleted = HashSet::new();
+        let mut modified = HashSet::new();
+        for change in changes {
+            match change.kind {
+                ChangeKind::Created => created.insert(change.uuid),
+                ChangeKind::Deleted => deleted.insert(change.uuid),
+                ChangeKind::Updated { .. } => modified.insert(change.uuid),
+            };
+        }
+        self.tasks_created = created.len();
+        self.tasks_deleted = deleted.len();
+        self.tasks_modified = modified
+            .iter()
+            .filter(|uuid| !created.contains(uuid) && !deleted.contains(uuid))
+            .count();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::changes::ChangeOrigin;
+    use pretty_assertions::assert_eq;
+    use uuid::Uuid;
+
+    #[test]
+    fn count_changes() {
+        let (created, modified, deleted) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        let update = |uuid| {
+            TaskChange::updated(
+                uuid,
+                "description".into(),
+                None,
+                Some("x".into()),
+                ChangeOrigin::Remote,
+            )
+        };
+        let changes = vec![
+            TaskChange::created(created, ChangeOrigin::Remote),
+            update(created),
+            update(modified),
+            update(modified),
+            update(deleted),
+            TaskChange::deleted(deleted, ChangeOrigin::Remote),
+        ];
+        let mut report = SyncReport::default();
+        report.count_changes(&changes);
+        assert_eq!(report.tasks_created, 1);
+        assert_eq!(report.tasks_modified, 1);
+        assert_eq!(report.tasks_deleted, 1);
+    }
+}
@@ -0,0 +1,567 @@
+//! Conversion between TaskChampion's key/value representation of tasks and iCalendar (RFC 5545)
+//! `VTODO` components.
+//!
+//! Properties with a natural iCalendar representation are mapped to standard iCalendar
+//! properties, and all other properties (including UDAs and annotations) are represented as
+//! `X-TASKCHAMPION-PROPERTY` properties, so that a task survives a round-trip unchanged.
+
+use crate::errors::{Error, Result};
+use crate::storage::TaskMap;
+use crate::TaskData;
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+use uuid::Uuid;
+
+/// Task properties represented as iCalendar date-time properties.
+const DATE_PROPS: &[(&str, &str)] = &[
+    ("entry", "CREATED"),
+    ("modified", "LAST-MODIFIED"),
+    ("start", "DTSTART"),
+    ("due", "DUE"),
+];
+
+/// The property used for a task status that has no iCalendar equivalent.
+const X_STATUS: &str = "X-TASKCHAMPION-STATUS";
+
+/// The property used for task properties that have no iCalendar equivalent.  Its value is the
+/// escaped property name and value, separated by a comma.
+const X_PROPERTY: &str = "X-TASKCHAMPION-PROPERTY";
+
+/// The iCalendar UTC date-time format.
+const DATE_TIME_FORMAT: &str = "%Y%m%dT%H%M%SZ";
+
+/// Write the beginning of a `VCALENDAR` object.
+pub(crate) fn begin_vcalendar(out: &mut String) {
+    content_line(out, "BEGIN", "VCALENDAR");
+    content_line(out, "VERSION", "2.0");
+    content_line(out, "PRODID", "-//GothenburgBitFactory//TaskChampion//EN");
+}
+
+/// Write the end of a `VCALENDAR` object.
+pub(crate) fn end_vcalendar(out: &mut String) {
+    content_line(out, "END", "VCALENDAR");
+}
+
+/// Write the given task as a `VTODO` component.  The `DTSTAMP` property is set to the task's
+/// modification time, if it has one, or to `now` otherwise.
+pub(crate) fn export_vtodo(out: &mut String, data: &TaskData, now: DateTime<Utc>) {
+    let mut props: Vec<_> = data.iter().collect();
+    props.sort();
+
+    content_line(out, "BEGIN", "VTODO");
+    content_line(out, "UID", &data.get_uuid().to_string());
+    let dtstamp = data
+        .get("modified")
+        .and_then(parse_timestamp)
+        .unwrap_or(now);
+    content_line(
+        out,
+        "DTSTAMP",
+        &dtstamp.format(DATE_TIME_FORMAT).to_string(),
+    );
+
+    let mut categories = vec![];
+    let mut related = vec![];
+    let mut extra = vec![];
+    for (key, value) in props {
+        if let Some(tag) = key.strip_prefix("tag_") {
+            categories.push(escape(tag));
+        } else if let Some(dep) = key.strip_prefix("dep_") {
+            related.push(escape(dep));
+        } else if key == "description" {
+            content_line(out, "SUMMARY", &escape(value));
+        } else if key == "status" {
+            let status = match value.as_str() {
+                "pending" => "NEEDS-ACTION",
+                "completed" => "COMPLETED",
+                "deleted" => "CANCELLED",
+                _ => {
+                    content_line(out, X_STATUS, &escape(value));
+                    "NEEDS-ACTION"
+                }
+            };
+            content_line(out, "STATUS", status);
+        } else if let Some((_, name)) = DATE_PROPS.iter().find(|(k, _)| k == key) {
+            match parse_timestamp(value) {
+                Some(ts) => content_line(out, name, &ts.format(DATE_TIME_FORMAT).to_string()),
+                None => extra.push((key, value)),
+            }
+        } else {
+            extra.push((key, value));
+        }
+    }
+    if !categories.is_empty() {
+        content_line(out, "CATEGORIES", &categories.join(","));
+    }
+    for dep in related {
+        content_line(out, "RELATED-TO;RELTYPE=DEPENDS-ON", &dep);
+    }
+    for (key, value) in extra {
+        content_line(
+            out,
+            X_PROPERTY,
+            &format!("{},{}", escape(key), escape(value)),
+        );
+    }
+    content_line(out, "END", "VTODO");
+}
+
+/// Parse all `VTODO` components in the given iCalendar data, returning the UUID and key/value
+/// map for each.
+///
+/// The task UUID is taken from the component's `UID`.  A `UID` that is not a UUID, such as one
+/// generated by another calendar application, is converted to a version-5 UUID so that
+/// re-importing the same component updates the same task.
+pub(crate) fn import_vtodos(input: &str) -> Result<Vec<(Uuid, TaskMap)>> {
+    let mut tasks = vec![];
+    // The stack of components containing the current line.
+    let mut components: Vec<String> = vec![];
+    let mut current: Option<(Option<Uuid>, TaskMap)> = None;
+
+    for line in unfold(input) {
+        let line = parse_content_line(&line)?;
+        match line.name.as_str() {
+            "BEGIN" => {
+                let name = line.value.to_ascii_uppercase();
+                if name == "VTODO" && current.is_none() {
+                    current = Some((None, TaskMap::new()));
+                }
+                components.push(name);
+                continue;
+            }
+            "END" => {
+                let name = line.value.to_ascii_uppercase();
+                if components.pop().as_ref() != Some(&name) {
+                    return Err(Error::Usage(format!("Unexpected END:{}", line.value)));
+                }
+                if name == "VTODO" && !components.iter().any(|c| c == "VTODO") {
+                    let (uuid, taskmap) = current.take().expect("VTODO was begun");
+                    let uuid =
+                        uuid.ok_or_else(|| Error::Usage("VTODO has no UID property".into()))?;
+                    tasks.push((uuid, taskmap));
+                }
+                continue;
+            }
+            _ => {}
+        }
+
+        // Only consider properties of the VTODO itself, not of nested components like VALARM.
+        if components.last().map(String::as_str) != Some("VTODO") {
+            continue;
+        }
+        let Some((uuid, taskmap)) = current.as_mut() else {
+            continue;
+        };
+        match line.name.as_str() {
+            "UID" => {
+                let uid = unescape(&line.value);
+                *uuid = Some(
+                    Uuid::parse_str(&uid)
+                        .unwrap_or_else(|_| Uuid::new_v5(&Uuid::NAMESPACE_OID, uid.as_bytes())),
+                );
+            }
+            "SUMMARY" => {
+                taskmap.insert("description".into(), unescape(&line.value));
+            }
+            "STATUS" => {
+                let status = match line.value.to_ascii_uppercase().as_str() {
+                    "COMPLETED" => "completed",
+                    "CANCELLED" => "deleted",
+                    _ => "pending",
+                };
+                // an X-TASKCHAMPION-STATUS takes precedence
+                taskmap
+                    .entry("status".into())
+                    .or_insert_with(|| status.into());
+            }
+            X_STATUS => {
+                taskmap.insert("status".into(), unescape(&line.value));
+            }
+            "CATEGORIES" => {
+                for tag in split_list(&line.value) {
+                    taskmap.insert(format!("tag_{}", unescape(tag)), "".into());
+                }
+            }
+            "RELATED-TO" => {
+                if line.param("RELTYPE").map(|r| r.to_ascii_uppercase())
+                    == Some("DEPENDS-ON".into())
+                {
+                    let dep = unescape(&line.value);
+                    let dep = Uuid::parse_str(&dep)
+                        .map_err(|e| Error::Usage(format!("Invalid dependency {dep}: {e}")))?;
+                    taskmap.insert(format!("dep_{dep}"), "".into());
+                }
+            }
+            X_PROPERTY => {
+                let mut parts = split_list(&line.value);
+                let (Some(key), Some(value), None) = (parts.next(), parts.next(), parts.next())
+                else {
+                    return Err(Error::Usage(format!(
+                        "Invalid {X_PROPERTY}: {}",
+                        line.value
+                    )));
+                };
+                taskmap.insert(unescape(key), unescape(value));
+            }
+            name => {
+                if let Some((key, _)) = DATE_PROPS.iter().find(|(_, n)| *n == name) {
+                    let ts = parse_date_time(&line.value)?;
+                    taskmap.insert(key.to_string(), ts.timestamp().to_string());
+                }
+                // other properties are ignored
+            }
+        }
+    }
+
+    if !components.is_empty() {
+        return Err(Error::Usage(format!(
+            "Unterminated component {}",
+            components.join(", ")
+        )));
+    }
+    Ok(tasks)
+}
+
+/// Parse a TaskChampion timestamp value.
+fn parse_timestamp(value: &str) -> Option<DateTime<Utc>> {
+    DateTime::from_timestamp(value.parse().ok()?, 0)
+}
+
+/// Parse an iCalendar DATE or DATE-TIME value.  Floating and zoned times are treated as UTC, and
+/// dates as midnight UTC.
+fn parse_date_time(value: &str) -> Result<DateTime<Utc>> {
+    let naive = value.strip_suffix('Z').unwrap_or(value);
+    if let Ok(dt) = NaiveDateTime::parse_from_str(naive, "%Y%m%dT%H%M%S") {
+        return Ok(dt.and_utc());
+    }
+    if let Ok(d) = NaiveDate::parse_from_str(value, "%Y%m%d") {
+        return Ok(d.and_hms_opt(0, 0, 0).expect("midnight is valid").and_utc());
+    }
+    Err(Error::Usage(format!("Invalid date-time {value}")))
+}
+
+/// Write a content line, folding it to lines of at most 75 octets.
+fn content_line(out: &mut String, name: &str, value: &str) {
+    let line = format!("{name}:{value}");
+    let mut len = 0;
+    for c in line.chars() {
+        if len + c.len_utf8() > 75 {
+            out.push_str("\r\n ");
+            len = 1;
+        }
+        out.push(c);
+        len += c.len_utf8();
+    }
+    out.push_str("\r\n");
+}
+
+/// Unfold the lines of iCalendar data, skipping empty lines.
+fn unfold(input: &str) -> Vec<String> {
+    let mut lines: Vec<String> = vec![];
+    for line in input.split('\n') {
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        if let Some(cont) = line.strip_prefix([' ', '\t']) {
+            if let Some(last) = lines.last_mut() {
+                last.push_str(cont);
+                continue;
+            }
+        }
+        if !line.is_empty() {
+            lines.push(line.into());
+        }
+    }
+    lines
+}
+
+/// A parsed content line.
+struct ContentLine {
+    /// The property name, in upper case.
+    name: String,
+    /// Parameters, with names in upper case and quotes removed from values.
+    params: Vec<(String, String)>,
+    /// The raw (still escaped) value.
+    value: String,
+}
+
+impl ContentLine {
+    fn param(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+/// Parse a content line of the form `NAME;PARAM=VALUE:value`.
+fn parse_content_line(line: &str) -> Result<ContentLine> {
+    let mut in_quotes = false;
+    let mut segments = vec![];
+    let mut start = 0;
+    for (i, c) in line.char_indices() {
+        match c {
+            '"' => in_quotes = !in_quotes,
+            ';' if !in_quotes => {
+                segments.push(&line[start..i]);
+                start = i + 1;
+            }
+            ':' if !in_quotes => {
+                segments.push(&line[start..i]);
+                let mut segments = segments.into_iter();
+                let name = segments.next().expect("one segment").to_ascii_uppercase();
+                let params = segments
+                    .map(|p| {
+                        let (name, value) = p.split_once('=').unwrap_or((p, ""));
+                        (name.to_ascii_uppercase(), value.replace('"', ""))
+                    })
+                    .collect();
+                return Ok(ContentLine {
+                    name,
+                    params,
+                    value: line[i + 1..].into(),
+                });
+            }
+            _ => {}
+        }
+    }
+    Err(Error::Usage(format!("Invalid content line {line:?}")))
+}
+
+/// Escape a TEXT value.
+fn escape(value: &str) -> String {
+    let mut res = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\\' | ';' | ',' => {
+                res.push('\\');
+                res.push(c);
+            }
+            '\n' => res.push_str("\\n"),
+            c => res.push(c),
+        }
+    }
+    res
+}
+
+/// Unescape a TEXT value.
+fn unescape(value: &str) -> String {
+    let mut res = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            match chars.next() {
+                Some('n') | Some('N') => res.push('\n'),
+                Some(c) => res.push(c),
+                None => res.push('\\'),
+            }
+        } else {
+            res.push(c);
+        }
+    }
+    res
+}
+
+/// Split a list of escaped TEXT values on unescaped commas.
+fn split_list(value: &str) -> impl Iterator<Item = &str> {
+    let mut items = vec![];
+    let mut start = 0;
+    let mut escaped = false;
+    for (i, c) in value.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            ',' => {
+                items.push(&value[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    items.push(&value[start..]);
+    items.into_iter()
+}
+
+/// Write the given tasks as a complete iCalendar object.
+#[cfg(test)]
+fn export_vcalendar(tasks: &[TaskData], now: DateTime<Utc>) -> String {
+    let mut out = String::new();
+    begin_vcalendar(&mut out);
+    for task in tasks {
+        export_vtodo(&mut out, task, now);
+    }
+    end_vcalendar(&mut out);
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chrono::TimeZone;
+    use pretty_assertions::assert_eq;
+    use rstest::rstest;
+
+    const UUID: &str = "a3ac8b5e-3f3b-4f4f-9c71-1ad2b2b7e0e4";
+    const DEP: &str = "0e23fd06-36e1-41a4-a3b4-08a5d0fdbb2b";
+
+    fn taskmap(props: &[(&str, &str)]) -> TaskMap {
+        props
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    fn now() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 2, 1, 0, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn export() {
+        let data = TaskData::new(
+            Uuid::parse_str(UUID).unwrap(),
+            taskmap(&[
+                ("description", "pay rent; then relax"),
+                ("status", "pending"),
+                ("entry", "1704110400"),
+                ("due", "1704412800"),
+                ("project", "home"),
+                ("tag_bills", ""),
+                ("tag_money", ""),
+                (&format!("dep_{DEP}"), ""),
+            ]),
+        );
+        assert_eq!(
+            export_vcalendar(&[data], now()),
+            [
+                "BEGIN:VCALENDAR",
+                "VERSION:2.0",
+                "PRODID:-//GothenburgBitFactory//TaskChampion//EN",
+                "BEGIN:VTODO",
+                &format!("UID:{UUID}"),
+                "DTSTAMP:20240201T000000Z",
+                "SUMMARY:pay rent\\; then relax",
+                "DUE:20240105T000000Z",
+                "CREATED:20240101T120000Z",
+                "STATUS:NEEDS-ACTION",
+                "CATEGORIES:bills,money",
+                &format!("RELATED-TO;RELTYPE=DEPENDS-ON:{DEP}"),
+                "X-TASKCHAMPION-PROPERTY:project,home",
+                "END:VTODO",
+                "END:VCALENDAR",
+                "",
+            ]
+            .join("\r\n")
+        );
+    }
+
+    #[test]
+    fn import() {
+        let input = format!(
+            "BEGIN:VCALENDAR\r
+VERSION:2.0\r
+BEGIN:VTODO\r
+UID:{UUID}\r
+DTSTAMP:20240201T000000Z\r
+SUMMARY:a very long summary that will certainly need to be folded onto a \r
+ second line\r
+STATUS:COMPLETED\r
+DUE;VALUE=DATE:20240105\r
+DTSTART;TZID=Europe/Berlin:20240102T080000\r
+CATEGORIES:a,b\\,c\r
+CATEGORIES:d\r
+RELATED-TO:{DEP}\r
+X-TASKCHAMPION-PROPERTY:devsync.github.issue-id,123\r
+DESCRIPTION:ignored\r
+BEGIN:VALARM\r
+SUMMARY:not the task\r
+END:VALARM\r
+END:VTODO\r
+BEGIN:VEVENT\r
+UID:not-a-task\r
+END:VEVENT\r
+END:VCALENDAR\r
+"
+        );
+        assert_eq!(
+            import_vtodos(&input).unwrap(),
+            vec![(
+                Uuid::parse_str(UUID).unwrap(),
+                taskmap(&[
+                    (
+                        "description",
+                        "a very long summary that will certainly need to be folded onto a second line"
+                    ),
+                    ("status", "completed"),
+                    ("due", "1704412800"),
+                    ("start", "1704182400"),
+                    ("tag_a", ""),
+                    ("tag_b,c", ""),
+                    ("tag_d", ""),
+                    ("devsync.github.issue-id", "123"),
+                ])
+            )]
+        );
+    }
+
+    #[test]
+    fn import_non_uuid_uid() {
+        let input = "BEGIN:VTODO\nUID:1234@example.com\nSUMMARY:x\nEND:VTODO\n";
+        let tasks = import_vtodos(input).unwrap();
+        assert_eq!(
+            tasks[0].0,
+            Uuid::new_v5(&Uuid::NAMESPACE_OID, b"1234@example.com")
+        );
+        // importing again gives the same UUID
+        assert_eq!(import_vtodos(input).unwrap()[0].0, tasks[0].0);
+    }
+
+    #[rstest]
+    #[case::no_uid("BEGIN:VTODO\nSUMMARY:x\nEND:VTODO\n")]
+    #[case::unterminated(&format!("BEGIN:VTODO\nUID:{UUID}\n"))]
+    #[case::mismatched(&format!("BEGIN:VTODO\nUID:{UUID}\nEND:VEVENT\n"))]
+    #[case::bad_line(&format!("BEGIN:VTODO\nUID:{UUID}\nNOCOLON\nEND:VTODO\n"))]
+    #[case::bad_date(&format!("BEGIN:VTODO\nUID:{UUID}\nDUE:tomorrow\nEND:VTODO\n"))]
+    #[case::bad_dep(&format!(
+        "BEGIN:VTODO\nUID:{UUID}\nRELATED-TO;RELTYPE=DEPENDS-ON:x\nEND:VTODO\n"
+    ))]
+    fn import_errors(#[case] input: &str) {
+        assert!(import_vtodos(input).is_err());
+    }
+
+    #[rstest]
+    #[case::pending("pending")]
+    #[case::completed("completed")]
+    #[case::deleted("deleted")]
+    #[case::recurring("recurring")]
+    #[case::unknown("wishful")]
+    fn round_trip_status(#[case] status: &str) {
+        let original = taskmap(&[("status", status)]);
+        let data = TaskData::new(Uuid::parse_str(UUID).unwrap(), original.clone());
+        let tasks = import_vtodos(&export_vcalendar(&[data], now())).unwrap();
+        assert_eq!(tasks[0].1, original);
+    }
+
+    #[test]
+    fn round_trip() {
+        let original = taskmap(&[
+            (
+                "description",
+                "multi\nline, with \\ and ; and a long tail "
+                    .repeat(4)
+                    .as_str(),
+            ),
+            ("status", "pending"),
+            ("modified", "1704110400"),
+            ("entry", "not-a-timestamp"),
+            ("wait", "1704110400"),
+            ("tag_a", ""),
+            ("tag_ünïcödé", ""),
+            (&format!("dep_{DEP}"), ""),
+            ("annotation_1704110400", "note, with comma"),
+            ("devsync.github.issue-id", "123"),
+            ("key,with=odd\"chars", "ok"),
+        ]);
+        let data = TaskData::new(Uuid::parse_str(UUID).unwrap(), original.clone());
+        let exported = export_vcalendar(&[data], now());
+        assert!(exported.split("\r\n").all(|l| l.len() <= 75));
+        let tasks = import_vtodos(&exported).unwrap();
+        assert_eq!(tasks, vec![(Uuid::parse_str(UUID).unwrap(), original)]);
+    }
+}
@@ -1,14 +1,20 @@
 #![allow(clippy::module_inception)]
 mod annotation;
 mod data;
+mod icalendar;
+mod recurrence;
 mod status;
 mod tag;
 mod task;
+mod taskwarrior;
 mod time;
 
 pub use annotation::Annotation;
 pub use data::TaskData;
+pub(crate) use icalendar::{begin_vcalendar, end_vcalendar, export_vtodo, import_vtodos};
+pub use recurrence::{Recurrence, RecurrencePeriod};
 pub use status::Status;
 pub use tag::Tag;
 pub use task::Task;
+pub(crate) use taskwarrior::{export_task, import_task};
 pub use time::{utc_timestamp, Timestamp};
@@ -0,0 +1,222 @@
+use super::Timestamp;
+use chrono::{Duration, Months};
+use std::fmt;
+use std::str::FromStr;
+use uuid::Uuid;
+
+/// The interval between instances of a recurring task, as given in its `recur` property.
+///
+/// This accepts the common Taskwarrior forms: the named periods `daily`, `weekly`, `biweekly`,
+/// `fortnight`, `monthly`, `quarterly`, `semiannual`, `annual` / `yearly` and `biannual` /
+/// `biyearly`; a count followed by a unit such as `3d`, `2w`, `6mo`, `1q` or `1y`; and simple ISO
+/// 8601 durations such as `P1D`, `P2W`, `P1M` or `P1Y`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecurrencePeriod {
+    /// Recur every given number of days.
+    Days(u32),
+    /// Recur every given number of months.  Instances that would fall on a day that does not
+    /// exist in a month (such as the 31st) fall on the last day of that month instead.
+    Months(u32),
+}
+
+impl RecurrencePeriod {
+    /// Calculate the due time of the `index`'th instance, given the due time of the first
+    /// instance.  This returns None if the result is not representable.
+    pub fn nth(&self, first: Timestamp, index: usize) -> Option<Timestamp> {
+        let index = u32::try_from(index).ok()?;
+        match self {
+            RecurrencePeriod::Days(n) => {
+                first.checked_add_signed(Duration::days(i64::from(n.checked_mul(index)?)))
+            }
+            RecurrencePeriod::Months(n) => {
+                first.checked_add_months(Months::new(n.checked_mul(index)?))
+            }
+        }
+    }
+}
+
+impl FromStr for RecurrencePeriod {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<RecurrencePeriod, anyhow::Error> {
+        use RecurrencePeriod::*;
+        let period = match value {
+            "daily" | "day" => Days(1),
+            "weekly" | "week" => Days(7),
+            "biweekly" | "fortnight" => Days(14),
+            "monthly" | "month" => Months(1),
+            "quarterly" | "quarter" => Months(3),
+            "semiannual" => Months(6),
+            "annual" | "yearly" | "year" => Months(12),
+            "biannual" | "biyearly" => Months(24),
+            _ => {
+                let (count, unit) = match value.strip_prefix('P') {
+                    // ISO 8601 durations put the unit after the count, like `P3D`
+                    Some(rest) => {
+                        let split = rest
+                            .find(|c: char| !c.is_ascii_digit())
+                            .unwrap_or(rest.len());
+                        let (count, unit) = rest.split_at(split);
+                        let unit = match unit {
+                            "D" => "d",
+                            "W" => "w",
+                            "M" => "mo",
+                            "Y" => "y",
+                            _ => anyhow::bail!("invalid recurrence period {:?}", value),
+                        };
+                        (count, unit)
+                    }
+                    None => {
+                        let split = value
+                            .find(|c: char| !c.is_ascii_digit())
+                            .unwrap_or(value.len());
+                        value.split_at(split)
+                    }
+                };
+                let count: u32 = if count.is_empty() { 1 } else { count.parse()? };
+                let period = match unit {
+                    "d" | "day" | "days" => Days(count),
+                    "w" | "wk" | "wks" | "week" | "weeks" => Days(count.saturating_mul(7)),
+                    "mo" | "mos" | "month" | "months" => Months(count),
+                    "q" | "qtr" | "qtrs" | "quarter" | "quarters" => {
+                        Months(count.saturating_mul(3))
+                    }
+                    "y" | "yr" | "yrs" | "year" | "years" => Months(count.saturating_mul(12)),
+                    _ => anyhow::bail!("invalid recurrence period {:?}", value),
+                };
+                if matches!(period, Days(0) | Months(0)) {
+                    anyhow::bail!("recurrence period {:?} is empty", value);
+                }
+                period
+            }
+        };
+        Ok(period)
+    }
+}
+
+impl fmt::Display for RecurrencePeriod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RecurrencePeriod::Days(n) => write!(f, "{}d", n),
+            RecurrencePeriod::Months(n) => write!(f, "{}mo", n),
+        }
+    }
+}
+
+/// The recurrence information for a task, as returned from [`crate::Task::recurrence`].
+///
+/// Recurring tasks follow the Taskwarrior model: a "template" task with status `recurring`
+/// describes the recurrence, and pending "instances" are generated from it by
+/// [`crate::Replica::generate_recurring_instances`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Recurrence {
+    /// This task is a recurrence template.
+    Template {
+        /// The interval between instances (the `recur` property).
+        period: RecurrencePeriod,
+        /// The due time of the first instance (the `due` property).
+        due: Timestamp,
+        /// The time after which no more instances are generated (the `until` property).
+        until: Option<Timestamp>,
+        /// The state of each instance generated so far (the `mask` property).  Each character
+        /// corresponds to an instance: `-` for pending, `+` for completed, `X` for deleted, and
+        /// `W` for waiting.
+        mask: String,
+    },
+    /// This task is an instance of a recurring task.
+    Instance {
+        /// The UUID of the template task (the `parent` property).
+        parent: Uuid,
+        /// The index of this instance, starting at zero (the `imask` property).
+        index: usize,
+    },
+}
+
+impl Recurrence {
+    /// Get the UUID of the `index`'th instance of the template with the given UUID.  This is
+    /// deterministic, so that replicas independently generating the same instance agree on its
+    /// identity.
+    pub(crate) fn instance_uuid(template: Uuid, index: usize) -> Uuid {
+        Uuid::new_v5(&template, index.to_string().as_bytes())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chrono::{TimeZone, Utc};
+    use pretty_assertions::assert_eq;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case::daily("daily", RecurrencePeriod::Days(1))]
+    #[case::weekly("weekly", RecurrencePeriod::Days(7))]
+    #[case::fortnight("fortnight", RecurrencePeriod::Days(14))]
+    #[case::monthly("monthly", RecurrencePeriod::Months(1))]
+    #[case::quarterly("quarterly", RecurrencePeriod::Months(3))]
+    #[case::yearly("yearly", RecurrencePeriod::Months(12))]
+    #[case::days("3d", RecurrencePeriod::Days(3))]
+    #[case::weeks("2wks", RecurrencePeriod::Days(14))]
+    #[case::months("6mo", RecurrencePeriod::Months(6))]
+    #[case::quarters("2q", RecurrencePeriod::Months(6))]
+    #[case::years("1y", RecurrencePeriod::Months(12))]
+    #[case::bare_unit("week", RecurrencePeriod::Days(7))]
+    #[case::iso_days("P3D", RecurrencePeriod::Days(3))]
+    #[case::iso_weeks("P1W", RecurrencePeriod::Days(7))]
+    #[case::iso_months("P2M", RecurrencePeriod::Months(2))]
+    #[case::iso_years("P1Y", RecurrencePeriod::Months(12))]
+    fn test_parse_period(#[case] value: &'static str, #[case] expected: RecurrencePeriod) {
+        assert_eq!(value.parse::<RecurrencePeriod>().unwrap(), expected);
+    }
+
+    #[rstest]
+    #[case::empty("")]
+    #[case::zero("0d")]
+    #[case::unknown_unit("3x")]
+    #[case::weekdays("weekdays")]
+    #[case::iso_time("PT1H")]
+    fn test_parse_period_invalid(#[case] value: &'static str) {
+        assert!(value.parse::<RecurrencePeriod>().is_err());
+    }
+
+    #[test]
+    fn test_nth_days() {
+        let first = Utc.with_ymd_and_hms(2024, 2, 27, 12, 0, 0).unwrap();
+        assert_eq!(
+            RecurrencePeriod::Days(2).nth(first, 2),
+            Some(Utc.with_ymd_and_hms(2024, 3, 2, 12, 0, 0).unwrap())
+        );
+    }
+
+    #[test]
+    fn test_nth_months_clamped() {
+        let first = Utc.with_ymd_and_hms(2024, 1, 31, 12, 0, 0).unwrap();
+        let period = RecurrencePeriod::Months(1);
+        assert_eq!(
+            period.nth(first, 1),
+            Some(Utc.with_ymd_and_hms(2024, 2, 29, 12, 0, 0).unwrap())
+        );
+        // later instances are still calculated from the first instance
+        assert_eq!(
+            period.nth(first, 2),
+            Some(Utc.with_ymd_and_hms(2024, 3, 31, 12, 0, 0).unwrap())
+        );
+    }
+
+    #[test]
+    fn test_instance_uuid() {
+        let template = Uuid::new_v4();
+        assert_eq!(
+            Recurrence::instance_uuid(template, 3),
+            Recurrence::instance_uuid(template, 3)
+        );
+        assert_ne!(
+            Recurrence::instance_uuid(template, 3),
+            Recurrence::instance_uuid(template, 4)
+        );
+        assert_ne!(
+            Recurrence::instance_uuid(template, 3),
+            Recurrence::instance_uuid(Uuid::new_v4(), 3)
+        );
+    }
+}
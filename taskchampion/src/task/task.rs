@@ -1,5 +1,5 @@
 use super::tag::{SyntheticTag, TagInner};
-use super::{utc_timestamp, Annotation, Status, Tag, Timestamp};
+use super::{utc_timestamp, Annotation, Recurrence, Status, Tag, Timestamp};
 use crate::depmap::DependencyMap;
 use crate::errors::{Error, Result};
 use crate::storage::TaskMap;
@@ -8,8 +8,8 @@ use chrono::prelude::*;
 use log::trace;
 use std::convert::AsRef;
 use std::convert::TryInto;
-use std::rc::Rc;
 use std::str::FromStr;
+use std::sync::Arc;
 use uuid::Uuid;
 
 /// A task, with a high-level interface.
@@ -28,7 +28,7 @@ pub struct Task {
     data: TaskData,
 
     // The dependency map for this replica, for rapidly computing synthetic tags.
-    depmap: Rc<DependencyMap>,
+    depmap: Arc<DependencyMap>,
 
     // True if an operation has alredy been emitted to update the `modified` property.
     updated_modified: bool,
@@ -55,6 +55,11 @@ enum Prop {
     Wait,
     End,
     Entry,
+    Recur,
+    Until,
+    Mask,
+    Imask,
+    Parent,
 }
 
 #[allow(clippy::ptr_arg)]
@@ -80,7 +85,7 @@ fn uda_tuple_to_string(namespace: impl AsRef<str>, key: impl AsRef<str>) -> Stri
 }
 
 impl Task {
-    pub(crate) fn new(data: TaskData, depmap: Rc<DependencyMap>) -> Task {
+    pub(crate) fn new(data: TaskData, depmap: Arc<DependencyMap>) -> Task {
         Task {
             data,
             depmap,
@@ -257,6 +262,36 @@ impl Task {
         self.get_timestamp(Prop::Due.as_ref())
     }
 
+    /// Get the recurrence information for this task, if any.
+    ///
+    /// A task with status `recurring` is a template if it has a valid `recur` period and a `due`
+    /// time.  Any other task is an instance if it has a valid `parent` and `imask`.
+    pub fn recurrence(&self) -> Option<Recurrence> {
+        if self.get_status() == Status::Recurring {
+            Some(Recurrence::Template {
+                period: self.data.get(Prop::Recur.as_ref())?.parse().ok()?,
+                due: self.get_due()?,
+                until: self.get_timestamp(Prop::Until.as_ref()),
+                mask: self.data.get(Prop::Mask.as_ref()).unwrap_or("").into(),
+            })
+        } else {
+            let parent = Uuid::parse_str(self.data.get(Prop::Parent.as_ref())?).ok()?;
+            // Taskwarrior may write `imask` as a floating-point value
+            let index = self.data.get(Prop::Imask.as_ref())?;
+            let index = match index.parse() {
+                Ok(index) => index,
+                Err(_) => {
+                    let index: f64 = index.parse().ok()?;
+                    if index < 0.0 || index.fract() != 0.0 {
+                        return None;
+                    }
+                    index as usize
+                }
+            };
+            Some(Recurrence::Instance { parent, index })
+        }
+    }
+
     /// Get the UUIDs of tasks on which this task depends.
     ///
     /// This includes all dependencies, regardless of their status.  In fact, it may include
@@ -284,17 +319,13 @@ impl Task {
     /// This also updates the task's "end" property appropriately.
     pub fn set_status(&mut self, status: Status, ops: &mut Operations) -> Result<()> {
         match status {
-            Status::Pending | Status::Recurring => {
-                // clear "end" when a task becomes "pending" or "recurring"
-                if self.data.has(Prop::End.as_ref()) {
-                    self.set_timestamp(Prop::End.as_ref(), None, ops)?;
-                }
+            // clear "end" when a task becomes "pending" or "recurring"
+            Status::Pending | Status::Recurring if self.data.has(Prop::End.as_ref()) => {
+                self.set_timestamp(Prop::End.as_ref(), None, ops)?;
             }
-            Status::Completed | Status::Deleted => {
-                // set "end" when a task is deleted or completed
-                if !self.data.has(Prop::End.as_ref()) {
-                    self.set_timestamp(Prop::End.as_ref(), Some(Utc::now()), ops)?;
-                }
+            // set "end" when a task is deleted or completed
+            Status::Completed | Status::Deleted if !self.data.has(Prop::End.as_ref()) => {
+                self.set_timestamp(Prop::End.as_ref(), Some(Utc::now()), ops)?;
             }
             _ => {}
         }
@@ -534,12 +565,13 @@ impl Task {
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::task::RecurrencePeriod;
     use crate::Replica;
     use pretty_assertions::assert_eq;
     use std::collections::HashSet;
 
-    fn dm() -> Rc<DependencyMap> {
-        Rc::new(DependencyMap::new())
+    fn dm() -> Arc<DependencyMap> {
+        Arc::new(DependencyMap::new())
     }
 
     // Test task mutation by modifying a task and checking the assertions both on the
@@ -774,6 +806,82 @@ mod test {
         assert_eq!(task.get_due(), Some(test_time))
     }
 
+    #[test]
+    fn test_recurrence_template() {
+        let due = Utc.with_ymd_and_hms(2033, 1, 1, 0, 0, 0).unwrap();
+        let task = Task::new(
+            TaskData::new(
+                Uuid::new_v4(),
+                vec![
+                    (String::from("status"), String::from("recurring")),
+                    (String::from("recur"), String::from("weekly")),
+                    (String::from("due"), format!("{}", due.timestamp())),
+                    (String::from("mask"), String::from("-+")),
+                ]
+                .drain(..)
+                .collect(),
+            ),
+            dm(),
+        );
+        assert_eq!(
+            task.recurrence(),
+            Some(Recurrence::Template {
+                period: RecurrencePeriod::Days(7),
+                due,
+                until: None,
+                mask: String::from("-+"),
+            })
+        );
+    }
+
+    #[test]
+    fn test_recurrence_template_invalid() {
+        // a template without a due time is not a valid template
+        let task = Task::new(
+            TaskData::new(
+                Uuid::new_v4(),
+                vec![
+                    (String::from("status"), String::from("recurring")),
+                    (String::from("recur"), String::from("weekly")),
+                ]
+                .drain(..)
+                .collect(),
+            ),
+            dm(),
+        );
+        assert_eq!(task.recurrence(), None);
+    }
+
+    #[test]
+    fn test_recurrence_instance() {
+        let parent = Uuid::new_v4();
+        for imask in ["3", "3.000000"] {
+            let task = Task::new(
+                TaskData::new(
+                    Uuid::new_v4(),
+                    vec![
+                        (String::from("status"), String::from("pending")),
+                        (String::from("parent"), parent.to_string()),
+                        (String::from("imask"), String::from(imask)),
+                    ]
+                    .drain(..)
+                    .collect(),
+                ),
+                dm(),
+            );
+            assert_eq!(
+                task.recurrence(),
+                Some(Recurrence::Instance { parent, index: 3 })
+            );
+        }
+    }
+
+    #[test]
+    fn test_recurrence_none() {
+        let task = Task::new(TaskData::new(Uuid::new_v4(), TaskMap::new()), dm());
+        assert_eq!(task.recurrence(), None);
+    }
+
     #[test]
     fn test_get_invalid_due() {
         let task = Task::new(
@@ -0,0 +1,316 @@
+//! Conversion between TaskChampion's key/value representation of tasks and the JSON format used
+//! by Taskwarrior's `task export` and `task import` commands.
+
+use crate::errors::{Error, Result};
+use crate::storage::TaskMap;
+use crate::TaskData;
+use chrono::{DateTime, NaiveDateTime};
+use serde_json::{Map, Number, Value};
+use uuid::Uuid;
+
+/// Keys which Taskwarrior represents as dates, and TaskChampion as UNIX timestamps.
+const DATE_KEYS: &[&str] = &[
+    "entry",
+    "modified",
+    "start",
+    "end",
+    "due",
+    "wait",
+    "until",
+    "scheduled",
+];
+
+/// Keys in Taskwarrior's export that are computed, and not part of the task data.
+const IGNORED_KEYS: &[&str] = &["id", "urgency"];
+
+/// The format Taskwarrior uses for dates in its JSON representation.
+const DATE_FORMAT: &str = "%Y%m%dT%H%M%SZ";
+
+/// Convert a TaskChampion timestamp value to a Taskwarrior date, if possible.
+fn format_date(value: &str) -> Option<String> {
+    let secs: i64 = value.parse().ok()?;
+    let dt = DateTime::from_timestamp(secs, 0)?;
+    Some(dt.format(DATE_FORMAT).to_string())
+}
+
+/// Parse a Taskwarrior date into a TaskChampion timestamp value.  RFC 3339 dates and integer
+/// timestamps are accepted as well.
+fn parse_date(key: &str, value: &Value) -> Result<String> {
+    let invalid = || Error::Usage(format!("Invalid date for {key}: {value}"));
+    match value {
+        Value::String(s) => {
+            if let Ok(dt) = NaiveDateTime::parse_from_str(s, DATE_FORMAT) {
+                Ok(dt.and_utc().timestamp().to_string())
+            } else if let Ok(dt) = DateTime::parse_from_rfc3339(s) {
+                Ok(dt.timestamp().to_string())
+            } else if let Ok(secs) = s.parse::<i64>() {
+                Ok(secs.to_string())
+            } else {
+                Err(invalid())
+            }
+        }
+        Value::Number(n) => n.as_i64().map(|n| n.to_string()).ok_or_else(invalid),
+        _ => Err(invalid()),
+    }
+}
+
+/// Parse a string-valued element of a list such as `tags` or `depends`.
+fn list_item<'a>(key: &str, value: &'a Value) -> Result<&'a str> {
+    value
+        .as_str()
+        .ok_or_else(|| Error::Usage(format!("Invalid item in {key}: {value}")))
+}
+
+/// Represent a TaskChampion value as Taskwarrior would, using a JSON number for numeric values
+/// of the given keys.
+fn export_value(key: &str, value: &str) -> Value {
+    if key == "imask" {
+        if let Ok(n) = value.parse::<i64>() {
+            return Value::Number(n.into());
+        }
+        if let Some(n) = value.parse::<f64>().ok().and_then(Number::from_f64) {
+            return Value::Number(n);
+        }
+    }
+    Value::String(value.into())
+}
+
+/// Convert a task to Taskwarrior's JSON representation.  If given, `id` is the task's index in
+/// the working set.
+pub(crate) fn export_task(data: &TaskData, id: Option<usize>) -> Value {
+    let mut obj = Map::new();
+    let mut tags = vec![];
+    let mut annotations = vec![];
+    let mut depends = vec![];
+
+    if let Some(id) = id {
+        obj.insert("id".into(), Value::Number(id.into()));
+    }
+    obj.insert("uuid".into(), Value::String(data.get_uuid().to_string()));
+
+    let mut props: Vec<_> = data.iter().collect();
+    props.sort();
+    for (key, value) in props {
+        if let Some(tag) = key.strip_prefix("tag_") {
+            tags.push(Value::String(tag.into()));
+        } else if let Some(entry) = key.strip_prefix("annotation_") {
+            let mut ann = Map::new();
+            let entry = format_date(entry).unwrap_or_else(|| entry.into());
+            ann.insert("entry".into(), Value::String(entry));
+            ann.insert("description".into(), Value::String(value.clone()));
+            annotations.push(Value::Object(ann));
+        } else if let Some(dep) = key.strip_prefix("dep_") {
+            depends.push(Value::String(dep.into()));
+        } else if DATE_KEYS.contains(&key.as_str()) {
+            let value = format_date(value).unwrap_or_else(|| value.clone());
+            obj.insert(key.clone(), Value::String(value));
+        } else {
+            obj.insert(key.clone(), export_value(key, value));
+        }
+    }
+
+    if !tags.is_empty() {
+        obj.insert("tags".into(), Value::Array(tags));
+    }
+    if !annotations.is_empty() {
+        obj.insert("annotations".into(), Value::Array(annotations));
+    }
+    if !depends.is_empty() {
+        obj.insert("depends".into(), Value::Array(depends));
+    }
+    Value::Object(obj)
+}
+
+/// Convert a task in Taskwarrior's JSON representation to its UUID and key/value map.
+pub(crate) fn import_task(value: &Value) -> Result<(Uuid, TaskMap)> {
+    let Value::Object(obj) = value else {
+        return Err(Error::Usage(format!("Expected a JSON object, got {value}")));
+    };
+    let uuid = obj
+        .get("uuid")
+        .and_then(Value::as_str)
+        .ok_or_else(|| Error::Usage("Task has no uuid".into()))?;
+    let uuid = Uuid::parse_str(uuid).map_err(|e| Error::Usage(format!("Invalid uuid: {e}")))?;
+
+    let mut taskmap = TaskMap::new();
+    for (key, value) in obj {
+        match key.as_str() {
+            "uuid" => {}
+            k if IGNORED_KEYS.contains(&k) => {}
+            "tags" => {
+                for tag in value.as_array().into_iter().flatten() {
+                    taskmap.insert(format!("tag_{}", list_item(key, tag)?), "".into());
+                }
+            }
+            "depends" => {
+                // Older versions of Taskwarrior represent dependencies as a comma-separated
+                // string.
+                let deps: Vec<&str> = match value {
+                    Value::String(s) => s.split(',').filter(|s| !s.is_empty()).collect(),
+                    Value::Array(a) => a
+                        .iter()
+                        .map(|dep| list_item(key, dep))
+                        .collect::<Result<_>>()?,
+                    _ => return Err(Error::Usage(format!("Invalid depends: {value}"))),
+                };
+                for dep in deps {
+                    let dep = Uuid::parse_str(dep)
+                        .map_err(|e| Error::Usage(format!("Invalid dependency {dep}: {e}")))?;
+                    taskmap.insert(format!("dep_{dep}"), "".into());
+                }
+            }
+            "annotations" => {
+                for ann in value.as_array().into_iter().flatten() {
+                    let entry = ann.get("entry").unwrap_or(&Value::Null);
+                    let mut entry: i64 = parse_date("annotation", entry)?
+                        .parse()
+                        .expect("parse_date returns an integer");
+                    let description = ann
+                        .get("description")
+                        .and_then(Value::as_str)
+                        .unwrap_or_default();
+                    // Annotations are keyed by their entry time, so move annotations with the
+                    // same entry time to the next free second, as Taskwarrior does.
+                    while taskmap.contains_key(&format!("annotation_{entry}")) {
+                        entry += 1;
+                    }
+                    taskmap.insert(format!("annotation_{entry}"), description.into());
+                }
+            }
+            k if DATE_KEYS.contains(&k) => {
+                taskmap.insert(key.clone(), parse_date(key, value)?);
+            }
+            _ => {
+                let value = match value {
+                    Value::Null => continue,
+                    Value::String(s) => s.clone(),
+                    Value::Number(n) => n.to_string(),
+                    Value::Bool(b) => b.to_string(),
+                    _ => return Err(Error::Usage(format!("Invalid value for {key}: {value}"))),
+                };
+                taskmap.insert(key.clone(), value);
+            }
+        }
+    }
+    Ok((uuid, taskmap))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    const UUID: &str = "a3ac8b5e-3f3b-4f4f-9c71-1ad2b2b7e0e4";
+    const DEP: &str = "0e23fd06-36e1-41a4-a3b4-08a5d0fdbb2b";
+
+    fn taskmap(props: &[(&str, &str)]) -> TaskMap {
+        props
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn import() {
+        let (uuid, taskmap_) = import_task(&json!({
+            "id": 3,
+            "uuid": UUID,
+            "description": "pay rent",
+            "status": "pending",
+            "entry": "20240101T120000Z",
+            "due": "2024-01-05T00:00:00+01:00",
+            "project": "home",
+            "estimate": 2.5,
+            "tags": ["bills", "money"],
+            "annotations": [
+                {"entry": "20240101T120000Z", "description": "call landlord"},
+                {"entry": "20240101T120000Z", "description": "check account"},
+            ],
+            "depends": [DEP],
+            "imask": 1,
+            "urgency": 4.2,
+        }))
+        .unwrap();
+        assert_eq!(uuid, Uuid::parse_str(UUID).unwrap());
+        assert_eq!(
+            taskmap_,
+            taskmap(&[
+                ("description", "pay rent"),
+                ("status", "pending"),
+                ("entry", "1704110400"),
+                ("due", "1704409200"),
+                ("project", "home"),
+                ("estimate", "2.5"),
+                ("tag_bills", ""),
+                ("tag_money", ""),
+                ("annotation_1704110400", "call landlord"),
+                ("annotation_1704110401", "check account"),
+                (&format!("dep_{DEP}"), ""),
+                ("imask", "1"),
+            ])
+        );
+    }
+
+    #[test]
+    fn import_depends_string() {
+        let (_, taskmap_) = import_task(&json!({"uuid": UUID, "depends": DEP})).unwrap();
+        assert_eq!(taskmap_, taskmap(&[(&format!("dep_{DEP}"), "")]));
+    }
+
+    #[test]
+    fn import_errors() {
+        assert!(import_task(&json!([])).is_err());
+        assert!(import_task(&json!({"description": "no uuid"})).is_err());
+        assert!(import_task(&json!({"uuid": "not-a-uuid"})).is_err());
+        assert!(import_task(&json!({"uuid": UUID, "due": "tomorrow"})).is_err());
+        assert!(import_task(&json!({"uuid": UUID, "depends": ["x"]})).is_err());
+        assert!(import_task(&json!({"uuid": UUID, "foo": {"bar": 1}})).is_err());
+    }
+
+    #[test]
+    fn export() {
+        let data = TaskData::new(
+            Uuid::parse_str(UUID).unwrap(),
+            taskmap(&[
+                ("description", "pay rent"),
+                ("status", "pending"),
+                ("entry", "1704110400"),
+                ("project", "home"),
+                ("tag_bills", ""),
+                ("annotation_1704110400", "call landlord"),
+                (&format!("dep_{DEP}"), ""),
+                ("imask", "1"),
+            ]),
+        );
+        assert_eq!(
+            export_task(&data, Some(3)),
+            json!({
+                "id": 3,
+                "uuid": UUID,
+                "description": "pay rent",
+                "status": "pending",
+                "entry": "20240101T120000Z",
+                "project": "home",
+                "tags": ["bills"],
+                "annotations": [{"entry": "20240101T120000Z", "description": "call landlord"}],
+                "depends": [DEP],
+                "imask": 1,
+            })
+        );
+    }
+
+    #[test]
+    fn round_trip() {
+        let original = taskmap(&[
+            ("description", "x"),
+            ("wait", "1704110400"),
+            ("tag_a", ""),
+            ("annotation_1704110400", "note"),
+            ("devsync.github.issue-id", "123"),
+        ]);
+        let data = TaskData::new(Uuid::parse_str(UUID).unwrap(), original.clone());
+        let (_, taskmap_) = import_task(&export_task(&data, None)).unwrap();
+        assert_eq!(taskmap_, original);
+    }
+}
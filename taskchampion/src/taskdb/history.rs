@@ -0,0 +1,42 @@
+use crate::changes::{HistoryEntry, TaskChange, TimedChange};
+use crate::errors::Result;
+use crate::storage::StorageTxn;
+use chrono::{DateTime, Utc};
+
+/// Record the given changes, made now, in the history of the affected tasks.  This has no effect
+/// if the storage does not retain history.
+///
+/// The transaction is not committed.
+pub(super) fn record_history(txn: &mut dyn StorageTxn, changes: &[TaskChange]) -> Result<()> {
+    record(txn, changes.iter().map(|change| (change, None)))
+}
+
+/// Record the given changes in the history of the affected tasks, as of the time each was made.
+/// Changes without a time are recorded as made now.
+///
+/// The transaction is not committed.
+pub(super) fn record_timed_history(
+    txn: &mut dyn StorageTxn,
+    changes: &[TimedChange],
+) -> Result<()> {
+    record(
+        txn,
+        changes
+            .iter()
+            .map(|(change, timestamp)| (change, *timestamp)),
+    )
+}
+
+fn record<'a>(
+    txn: &mut dyn StorageTxn,
+    changes: impl Iterator<Item = (&'a TaskChange, Option<DateTime<Utc>>)>,
+) -> Result<()> {
+    let now = Utc::now();
+    for (change, timestamp) in changes {
+        txn.add_history(
+            change.uuid,
+            HistoryEntry::new(change, timestamp.unwrap_or(now)),
+        )?;
+    }
+    Ok(())
+}
@@ -1,13 +1,20 @@
 use std::collections::HashSet;
 
+use crate::changes::{timed_local_changes, HistoryEntry, SyncConflict, TaskChange};
 use crate::errors::Result;
+use crate::merge::{MergeStrategies, MergeStrategy};
 use crate::operation::Operation;
-use crate::server::Server;
+use crate::server::{AsyncAdapter, AsyncServer, Server};
 use crate::storage::{Storage, TaskMap};
+use crate::sync_options::SyncOptions;
+use crate::sync_report::SyncReport;
+use crate::undo::{split_steps, UndoStep};
 use crate::Operations;
+use futures_util::FutureExt;
 use uuid::Uuid;
 
 mod apply;
+mod history;
 mod snapshot;
 mod sync;
 pub(crate) mod undo;
@@ -18,12 +25,25 @@ mod working_set;
 /// properties to the replica and task implementations.
 pub(crate) struct TaskDb {
     storage: Box<dyn Storage>,
+    merge_strategies: MergeStrategies,
 }
 
 impl TaskDb {
     /// Create a new TaskDb with the given backend storage
     pub(crate) fn new(storage: Box<dyn Storage>) -> TaskDb {
-        TaskDb { storage }
+        TaskDb {
+            storage,
+            merge_strategies: MergeStrategies::new(),
+        }
+    }
+
+    /// Set the strategy used to combine concurrent updates to the given property during sync.
+    pub(crate) fn set_merge_strategy(&mut self, property: String, strategy: MergeStrategy) {
+        if strategy == MergeStrategy::default() {
+            self.merge_strategies.remove(&property);
+        } else {
+            self.merge_strategies.insert(property, strategy);
+        }
     }
 
     #[cfg(test)]
@@ -51,6 +71,7 @@ impl TaskDb {
     {
         let mut txn = self.storage.txn()?;
         apply::apply_operations(txn.as_mut(), &operations)?;
+        history::record_timed_history(txn.as_mut(), &timed_local_changes(&operations))?;
 
         // Calculate the task(s) to add to the working set.
         let mut to_add = Vec::new();
@@ -105,6 +126,39 @@ impl TaskDb {
         txn.get_task(uuid)
     }
 
+    /// Get the recorded history of a single task, by uuid.
+    pub(crate) fn task_history(&mut self, uuid: Uuid) -> Result<Vec<HistoryEntry>> {
+        let mut txn = self.storage.txn()?;
+        txn.get_history(uuid)
+    }
+
+    /// Get the conflicts recorded during sync, oldest first.
+    pub(crate) fn sync_conflicts(&mut self) -> Result<Vec<SyncConflict>> {
+        let mut txn = self.storage.txn()?;
+        txn.conflicts()
+    }
+
+    /// Remove the given conflict from those recorded during sync.  Returns false if it was not
+    /// recorded.
+    pub(crate) fn remove_sync_conflict(&mut self, conflict: &SyncConflict) -> Result<bool> {
+        let mut txn = self.storage.txn()?;
+        let mut conflicts = txn.conflicts()?;
+        let Some(i) = conflicts.iter().position(|c| c == conflict) else {
+            return Ok(false);
+        };
+        conflicts.remove(i);
+        txn.set_conflicts(conflicts)?;
+        txn.commit()?;
+        Ok(true)
+    }
+
+    /// Remove all conflicts recorded during sync.
+    pub(crate) fn clear_sync_conflicts(&mut self) -> Result<()> {
+        let mut txn = self.storage.txn()?;
+        txn.set_conflicts(vec![])?;
+        txn.commit()
+    }
+
     /// Rebuild the working set using a function to identify tasks that should be in the set.  This
     /// renumbers the existing working-set tasks to eliminate gaps, and also adds any tasks that
     /// are not already in the working set but should be.  The rebuild occurs in a single
@@ -118,19 +172,92 @@ impl TaskDb {
 
     /// Sync to the given server, pulling remote changes and pushing local changes.
     ///
-    /// If `avoid_snapshots` is true, the sync operations produces a snapshot only when the server
-    /// indicate it is urgent (snapshot urgency "high").  This allows time for other replicas to
-    /// create a snapshot before this one does.
+    /// The changes made to tasks by applying remote changes are appended to `changes`.  This
+    /// occurs even if the sync fails, as versions from the server may have been applied to
+    /// storage before the failure.
     ///
-    /// Set this to true on systems more constrained in CPU, memory, or bandwidth than a typical desktop
-    /// system
+    /// Returns a summary of the sync.
+    pub(crate) fn sync_with_options(
+        &mut self,
+        server: &mut Box<dyn Server>,
+        options: &mut SyncOptions<'_>,
+        changes: &mut Vec<TaskChange>,
+    ) -> Result<SyncReport> {
+        // The adapter's futures are ready as soon as they are polled, so no executor is required.
+        // This allows the server to use an executor of its own.
+        self.sync_async_with_options(&mut AsyncAdapter(server.as_mut()), options, changes)
+            .now_or_never()
+            .expect("sync with a blocking server did not complete")
+    }
+
+    /// Sync to the given asynchronous server.  This is the same as [`TaskDb::sync_with_options`],
+    /// but asynchronous.
+    pub(crate) async fn sync_async_with_options(
+        &mut self,
+        server: &mut dyn AsyncServer,
+        options: &mut SyncOptions<'_>,
+        changes: &mut Vec<TaskChange>,
+    ) -> Result<SyncReport> {
+        sync::sync(
+            server,
+            self.storage.as_mut(),
+            &self.merge_strategies,
+            options,
+            changes,
+        )
+        .await
+    }
+
+    /// Sync to the given server, returning the changes made to tasks and a summary of the sync.
+    #[cfg(test)]
     pub(crate) fn sync(
         &mut self,
         server: &mut Box<dyn Server>,
         avoid_snapshots: bool,
-    ) -> Result<()> {
-        let mut txn = self.storage.txn()?;
-        sync::sync(server, txn.as_mut(), avoid_snapshots)
+    ) -> Result<(Vec<TaskChange>, SyncReport)> {
+        let mut changes = vec![];
+        let report = self.sync_with_options(
+            server,
+            &mut SyncOptions::new().avoid_snapshots(avoid_snapshots),
+            &mut changes,
+        )?;
+        Ok((changes, report))
+    }
+
+    /// Sync to the given asynchronous server, as for [`TaskDb::sync`].
+    #[cfg(test)]
+    pub(crate) async fn sync_async(
+        &mut self,
+        server: &mut dyn AsyncServer,
+        avoid_snapshots: bool,
+    ) -> Result<(Vec<TaskChange>, SyncReport)> {
+        let mut changes = vec![];
+        let report = self
+            .sync_async_with_options(
+                server,
+                &mut SyncOptions::new().avoid_snapshots(avoid_snapshots),
+                &mut changes,
+            )
+            .await?;
+        Ok((changes, report))
+    }
+
+    /// Change the encryption secret used by the given server.  Returns false if the replica or
+    /// the server has changes that must first be synchronized.
+    pub(crate) fn rotate_encryption_secret(
+        &mut self,
+        server: &mut Box<dyn Server>,
+        old_secret: &[u8],
+        new_secret: &[u8],
+    ) -> Result<bool> {
+        sync::rotate_encryption_secret(
+            &mut AsyncAdapter(server.as_mut()),
+            self.storage.as_mut(),
+            old_secret,
+            new_secret,
+        )
+        .now_or_never()
+        .expect("rotation with a blocking server did not complete")
     }
 
     /// Return the operations back to and including the last undo point, or since the last sync if
@@ -143,6 +270,51 @@ impl TaskDb {
         undo::get_undo_operations(txn.as_mut())
     }
 
+    /// Return the synchronized operations back to and including the last undo point.  These can
+    /// be reverted with compensating operations.
+    pub(crate) fn get_synced_undo_operations(&mut self) -> Result<Operations> {
+        let mut txn = self.storage.txn()?;
+        undo::get_synced_undo_operations(txn.as_mut())
+    }
+
+    /// Generate operations reverting the given operations, based on the current state of the
+    /// tasks.
+    pub(crate) fn compensating_operations(&mut self, undo_ops: &[Operation]) -> Result<Operations> {
+        let mut txn = self.storage.txn()?;
+        undo::compensating_operations(txn.as_mut(), undo_ops)
+    }
+
+    /// Remove the given operations from the end of the retained synchronized operations, once they
+    /// have been reverted. Returns false if they are not the latest synchronized operations.
+    pub(crate) fn drop_synced_undo_operations(&mut self, undo_ops: &[Operation]) -> Result<bool> {
+        let mut txn = self.storage.txn()?;
+        undo::drop_synced_undo_operations(txn.as_mut(), undo_ops)
+    }
+
+    /// Summarize the synchronized steps that can be undone with compensating operations, most
+    /// recent first.
+    pub(crate) fn synced_undo_steps(&mut self) -> Result<Vec<UndoStep>> {
+        let mut txn = self.storage.txn()?;
+        let operations = txn.synced_operations()?;
+        split_steps(&operations)
+            .into_iter()
+            .rev()
+            .map(|ops| UndoStep::summarize(ops, |uuid| txn.get_task(uuid)))
+            .collect()
+    }
+
+    /// Summarize the steps that can be undone, most recent first.  Each step corresponds to the
+    /// operations returned from `get_undo_operations`, after undoing all of the following steps.
+    pub(crate) fn undo_steps(&mut self) -> Result<Vec<UndoStep>> {
+        let mut txn = self.storage.txn()?;
+        let operations = txn.operations()?;
+        split_steps(&operations)
+            .into_iter()
+            .rev()
+            .map(|ops| UndoStep::summarize(ops, |uuid| txn.get_task(uuid)))
+            .collect()
+    }
+
     /// Commit the reverse of the given operations, beginning with the last operation in the given
     /// operations and proceeding to the first.
     ///
@@ -203,6 +375,7 @@ impl TaskDb {
 }
 
 #[cfg(test)]
+#[allow(clippy::vec_init_then_push)]
 mod tests {
     use super::*;
     use chrono::Utc;
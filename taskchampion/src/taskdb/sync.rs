@@ -1,7 +1,14 @@
-use super::{apply, snapshot};
+use super::{apply, history, snapshot, undo};
+use crate::changes::{created_properties, ChangeOrigin, SyncConflict, TaskChange, TimedChange};
 use crate::errors::Result;
-use crate::server::{AddVersionResult, GetVersionResult, Server, SnapshotUrgency, SyncOp};
-use crate::storage::StorageTxn;
+use crate::merge::{merge_text, MergeStrategies, MergeStrategy};
+use crate::operation::Operation;
+use crate::server::{
+    AddVersionResult, AsyncServer, GetVersionResult, SnapshotUrgency, SyncOp, VersionId,
+};
+use crate::storage::{Storage, StorageTxn, TaskMap};
+use crate::sync_options::{SyncOptions, SyncProgress};
+use crate::sync_report::SyncReport;
 use crate::Error;
 use log::{info, trace, warn};
 use serde::{Deserialize, Serialize};
@@ -13,131 +20,421 @@ struct Version {
 }
 
 /// Sync to the given server, pulling remote changes and pushing local changes.
-pub(super) fn sync(
-    server: &mut Box<dyn Server>,
-    txn: &mut dyn StorageTxn,
-    avoid_snapshots: bool,
-) -> Result<()> {
+///
+/// No storage transaction is held while waiting for the server, so that the resulting future is
+/// `Send`. If there are no local operations, each version pulled from the server is applied and
+/// committed as it arrives. Otherwise, versions pulled from the server are accumulated in memory,
+/// and applied in a single transaction once the server has accepted the local changes. If the
+/// local operations change in the interim, the process begins again, pulling the pushed versions
+/// back from the server.
+///
+/// Concurrent updates to the same property are combined according to `merge_strategies`.
+///
+/// The cancellation token in `options` is checked after each exchange with the server, before
+/// anything further is committed to storage.
+///
+/// The changes made to tasks in the replica by applying remote changes are appended to `changes`
+/// as they are committed, so they are available even if the sync fails.
+///
+/// Returns a summary of the sync.
+pub(super) async fn sync(
+    server: &mut dyn AsyncServer,
+    storage: &mut dyn Storage,
+    merge_strategies: &MergeStrategies,
+    options: &mut SyncOptions<'_>,
+    changes: &mut Vec<TaskChange>,
+) -> Result<SyncReport> {
+    let mut report = SyncReport::default();
+    let first_change = changes.len();
+
     // if this taskdb is entirely empty, then start by getting and applying a snapshot
-    if txn.is_empty()? {
+    let is_empty = storage.txn()?.is_empty()?;
+    if is_empty {
         trace!("storage is empty; attempting to apply a snapshot");
-        if let Some((version, snap)) = server.get_snapshot()? {
-            snapshot::apply_snapshot(txn, version, snap.as_ref())?;
-            trace!("applied snapshot for version {}", version);
+        if let Some((version, snap)) = server.get_snapshot().await? {
+            check_cancelled(options)?;
+            let mut txn = storage.txn()?;
+            // another sync may have populated the storage in the interim
+            if txn.is_empty()? {
+                snapshot::apply_snapshot(txn.as_mut(), version, snap.as_ref())?;
+                trace!("applied snapshot for version {}", version);
+                let mut created = vec![];
+                for (uuid, task) in txn.all_tasks()? {
+                    created.push(TaskChange::created(uuid, ChangeOrigin::Remote));
+                    created.extend(created_properties(uuid, &task, ChangeOrigin::Remote));
+                }
+                history::record_history(txn.as_mut(), &created)?;
+                txn.commit()?;
+                changes.extend(created);
+                report.snapshot_applied = true;
+                options.progress(SyncProgress::SnapshotApplied);
+            }
         }
     }
 
+    // Versions pulled from the server, following `base_version_id`, which have not yet been
+    // applied to storage.
+    let mut base_version_id = storage.txn()?.base_version()?;
+    let mut versions: Vec<(VersionId, Version)> = vec![];
+    // The number of versions fetched, and of operations in those already applied to storage.
+    let mut fetched = 0;
+    let mut operations_applied = 0;
+
     // retry synchronizing until the server accepts our version (this allows for races between
     // replicas trying to sync to the same server).  If the server insists on the same base
     // version twice, then we have diverged.
     let mut requested_parent_version_id = None;
-    'outer: loop {
-        trace!("beginning sync outer loop");
-        let mut base_version_id = txn.base_version()?;
-
-        let mut local_ops = txn.operations()?;
-        let sync_ops = local_ops.drain(..).filter_map(SyncOp::from_op);
-        let mut sync_ops_peekable = sync_ops.peekable();
-
-        // batch operations into versions of no more than a million bytes to avoid excessively large http requests.
-        let sync_ops_batched = std::iter::from_fn(|| {
-            let mut batch_size = 0;
-            let mut batch = Vec::new();
-
-            while let Some(op) = sync_ops_peekable.next_if(|op| {
-                batch_size += serde_json::to_string(&op).unwrap().len();
-                // include if the batch is empty or if the batch size limit is not exceeded.
-                batch.is_empty() || batch_size <= 1000000
-            }) {
-                batch.push(op);
-            }
-
-            Some(batch)
-        });
-
-        for mut sync_ops_batch in sync_ops_batched {
-            // first pull changes and "rebase" on top of them
-            loop {
-                trace!("beginning sync inner loop");
-                if let GetVersionResult::Version {
-                    version_id,
-                    history_segment,
-                    ..
-                } = server.get_child_version(base_version_id)?
-                {
-                    let version_str = str::from_utf8(&history_segment).unwrap();
-                    let version: Version = serde_json::from_str(version_str).unwrap();
-
-                    // apply this version and update base_version in storage
-                    info!("applying version {:?} from server", version_id);
-                    apply_version(txn, &mut sync_ops_batch, version)?;
-                    txn.set_base_version(version_id)?;
+    loop {
+        trace!("beginning sync loop");
+
+        // first pull changes from the server
+        let mut parent_version_id = versions
+            .last()
+            .map(|(version_id, _)| *version_id)
+            .unwrap_or(base_version_id);
+        while let GetVersionResult::Version {
+            version_id,
+            history_segment,
+            ..
+        } = server.get_child_version(parent_version_id).await?
+        {
+            let version_str = str::from_utf8(&history_segment).unwrap();
+            let version: Version = serde_json::from_str(version_str).unwrap();
+            info!("received version {:?} from server", version_id);
+            parent_version_id = version_id;
+            fetched += 1;
+            options.progress(SyncProgress::VersionFetched { count: fetched });
+            check_cancelled(options)?;
+
+            // With no local operations to rebase, apply and commit each version as it arrives, so
+            // that the progress is kept even if the sync does not complete.
+            if versions.is_empty() {
+                let mut txn = storage.txn()?;
+                if txn.base_version()? == base_version_id && txn.operations()?.is_empty() {
+                    let mut applied = Applied::default();
+                    apply_version(
+                        txn.as_mut(),
+                        &mut vec![],
+                        &version,
+                        merge_strategies,
+                        &mut applied,
+                    )?;
+                    finish(txn, version_id, &applied)?;
+                    info!("applied version {:?}", version_id);
                     base_version_id = version_id;
-                } else {
-                    info!("no child versions of {:?}", base_version_id);
-                    // at the moment, no more child versions, so we can try adding our own
-                    break;
+                    report.versions_applied += 1;
+                    operations_applied += version.operations.len();
+                    report.base_version = version_id;
+                    changes.extend(applied.changes.into_iter().map(|(change, _)| change));
+                    continue;
                 }
             }
-
-            if sync_ops_batch.is_empty() {
+            versions.push((version_id, version));
+        }
+        info!("no child versions of {:?}", parent_version_id);
+
+        // then "rebase" the local operations on top of those versions.  Unless there are no local
+        // changes to push, this transaction is rolled back, and the versions are applied again
+        // once the server has accepted the local changes.
+        let (local_ops, sync_ops) = {
+            let mut txn = storage.txn()?;
+            let current_base_version_id = txn.base_version()?;
+            if current_base_version_id != base_version_id {
+                info!("replica was synchronized concurrently; starting again");
+                base_version_id = current_base_version_id;
+                versions.clear();
+                continue;
+            }
+            let local_ops = txn.operations()?;
+            let mut applied = Applied::default();
+            let sync_ops = apply_versions(
+                txn.as_mut(),
+                &versions,
+                &local_ops,
+                merge_strategies,
+                &mut applied,
+            )?;
+            options.progress(SyncProgress::OperationsTransformed {
+                remote_operations: operations_applied
+                    + versions
+                        .iter()
+                        .map(|(_, v)| v.operations.len())
+                        .sum::<usize>(),
+                local_operations: sync_ops.len(),
+            });
+            if sync_ops.is_empty() {
                 info!("no changes to push to server");
-                // nothing to sync back to the server..
-                break 'outer;
+                check_cancelled(options)?;
+                finish(txn, parent_version_id, &applied)?;
+                report.versions_applied += versions.len();
+                report.conflicts_resolved = applied.conflicts;
+                report.updates_merged = applied.merged;
+                report.base_version = parent_version_id;
+                changes.extend(applied.changes.into_iter().map(|(change, _)| change));
+                break;
             }
-
-            trace!("sending {} operations to the server", sync_ops_batch.len());
-
-            // now make a version of our local changes and push those
-            let new_version = Version {
-                operations: sync_ops_batch,
-            };
+            (local_ops, sync_ops)
+        };
+
+        // now push the local changes, as one or more new versions
+        let mut snapshot_urgency = SnapshotUrgency::None;
+        let mut rejected = false;
+        let num_sync_ops = sync_ops.len();
+        let batches = batch_operations(sync_ops);
+        let num_batches = batches.len();
+        for (i, batch) in batches.into_iter().enumerate() {
+            check_cancelled(options)?;
+            trace!("sending {} operations to the server", batch.len());
+            let new_version = Version { operations: batch };
             let history_segment = serde_json::to_string(&new_version).unwrap().into();
             info!("sending new version to server");
-            let (res, snapshot_urgency) = server.add_version(base_version_id, history_segment)?;
+            let (res, urgency) = server
+                .add_version(parent_version_id, history_segment)
+                .await?;
             match res {
                 AddVersionResult::Ok(new_version_id) => {
                     info!("version {:?} received by server", new_version_id);
-                    txn.set_base_version(new_version_id)?;
-                    base_version_id = new_version_id;
-
-                    // make a snapshot if the server indicates it is urgent enough
-                    let base_urgency = if avoid_snapshots {
-                        SnapshotUrgency::High
-                    } else {
-                        SnapshotUrgency::Low
-                    };
-                    if snapshot_urgency >= base_urgency {
-                        let snapshot = snapshot::make_snapshot(txn)?;
-                        server.add_snapshot(new_version_id, snapshot)?;
-                    }
+                    parent_version_id = new_version_id;
+                    snapshot_urgency = snapshot_urgency.max(urgency);
+                    options.progress(SyncProgress::BatchUploaded {
+                        batch: i + 1,
+                        batches: num_batches,
+                    });
                 }
-                AddVersionResult::ExpectedParentVersion(parent_version_id) => {
+                AddVersionResult::ExpectedParentVersion(expected_parent_version_id) => {
                     info!(
                         "new version rejected; must be based on {:?}",
-                        parent_version_id
+                        expected_parent_version_id
                     );
                     if let Some(requested) = requested_parent_version_id {
-                        if parent_version_id == requested {
+                        if expected_parent_version_id == requested {
                             return Err(Error::OutOfSync);
                         }
                     }
-                    requested_parent_version_id = Some(parent_version_id);
+                    requested_parent_version_id = Some(expected_parent_version_id);
+                    rejected = true;
                     break;
                 }
             }
         }
+        // Any versions accepted before the rejection will be pulled back from the server.
+        if rejected {
+            continue;
+        }
+
+        // finally, apply the pulled versions to storage, now that the server has the local changes
+        let snapshot = {
+            let mut txn = storage.txn()?;
+            if txn.base_version()? != base_version_id || txn.operations()? != local_ops {
+                info!("replica changed during sync; starting again");
+                continue;
+            }
+            let mut applied = Applied::default();
+            apply_versions(
+                txn.as_mut(),
+                &versions,
+                &local_ops,
+                merge_strategies,
+                &mut applied,
+            )?;
+
+            // make a snapshot if the server indicates it is urgent enough
+            let base_urgency = if options.avoid_snapshots {
+                SnapshotUrgency::High
+            } else {
+                SnapshotUrgency::Low
+            };
+            let snapshot = if snapshot_urgency >= base_urgency {
+                Some(snapshot::make_snapshot(txn.as_mut())?)
+            } else {
+                None
+            };
+            check_cancelled(options)?;
+            finish(txn, parent_version_id, &applied)?;
+            report.versions_applied += versions.len();
+            report.operations_pushed = num_sync_ops;
+            report.conflicts_resolved = applied.conflicts;
+            report.updates_merged = applied.merged;
+            report.base_version = parent_version_id;
+            changes.extend(applied.changes.into_iter().map(|(change, _)| change));
+            snapshot
+        };
+        // The sync is complete, so a cancellation now only skips the optional snapshot.
+        if let Some(snapshot) = snapshot {
+            if options.is_cancelled() {
+                info!("sync cancelled; not sending snapshot");
+            } else {
+                options.progress(SyncProgress::SnapshotUploading);
+                server.add_snapshot(parent_version_id, snapshot).await?;
+                report.snapshot_uploaded = true;
+            }
+        }
+        break;
     }
 
-    txn.set_operations(vec![])?;
-    txn.commit()?;
+    report.count_changes(&changes[first_change..]);
+    Ok(report)
+}
+
+/// Change the encryption secret used by the server, by adding an empty version and a snapshot of
+/// the replica's current state, both encrypted with the new secret.
+///
+/// Returns false, without changing anything, if the replica has local operations that have not
+/// been synchronized, or if the server has versions that the replica has not applied. In this
+/// case, sync and try again.
+pub(super) async fn rotate_encryption_secret(
+    server: &mut dyn AsyncServer,
+    storage: &mut dyn Storage,
+    old_secret: &[u8],
+    new_secret: &[u8],
+) -> Result<bool> {
+    let (base_version_id, snapshot) = {
+        let mut txn = storage.txn()?;
+        if !txn.operations()?.is_empty() {
+            info!("replica has local changes; not rotating encryption secret");
+            return Ok(false);
+        }
+        (txn.base_version()?, snapshot::make_snapshot(txn.as_mut())?)
+    };
+
+    let new_version = Version { operations: vec![] };
+    let history_segment = serde_json::to_string(&new_version).unwrap().into();
+    info!("sending new version and snapshot with new encryption secret");
+    match server
+        .rotate_encryption_secret(
+            old_secret,
+            new_secret,
+            base_version_id,
+            history_segment,
+            snapshot,
+        )
+        .await?
+    {
+        AddVersionResult::Ok(new_version_id) => {
+            info!("version {:?} received by server", new_version_id);
+            // The new version contains no operations, so the replica is already up to date with
+            // it, unless it was synchronized concurrently.
+            let mut txn = storage.txn()?;
+            if txn.base_version()? == base_version_id {
+                txn.set_base_version(new_version_id)?;
+                txn.commit()?;
+            }
+            Ok(true)
+        }
+        AddVersionResult::ExpectedParentVersion(expected_parent_version_id) => {
+            info!(
+                "new version rejected; must be based on {:?}",
+                expected_parent_version_id
+            );
+            Ok(false)
+        }
+    }
+}
+
+/// The effects of applying versions from the server to storage.
+#[derive(Default)]
+struct Applied {
+    /// The changes made to tasks, with the time of each update.
+    changes: Vec<TimedChange>,
+    /// The number of conflicting pairs of operations, where one took precedence over the other.
+    conflicts: usize,
+    /// The updates discarded in favor of a conflicting update to the same property.
+    overridden: Vec<SyncConflict>,
+    /// The number of pairs of updates to the same property that were merged.
+    merged: usize,
+}
+
+/// A local operation being rebased onto operations from the server.
+struct LocalOp {
+    op: SyncOp,
+    /// For an update, the value of the property before the update, as the common ancestor for a
+    /// text merge.
+    ancestor: Option<String>,
+}
+
+/// Return [`Error::Cancelled`] if the sync has been cancelled.  Any uncommitted transaction is
+/// rolled back as the error is returned.
+fn check_cancelled(options: &SyncOptions<'_>) -> Result<()> {
+    if options.is_cancelled() {
+        info!("sync cancelled");
+        return Err(Error::Cancelled);
+    }
     Ok(())
 }
 
+/// Apply the given versions, rebasing the local operations on top of them.  Returns the rebased
+/// operations that remain to be sent to the server.
+fn apply_versions(
+    txn: &mut dyn StorageTxn,
+    versions: &[(VersionId, Version)],
+    local_ops: &[Operation],
+    merge_strategies: &MergeStrategies,
+    applied: &mut Applied,
+) -> Result<Vec<SyncOp>> {
+    let mut local_ops = local_ops
+        .iter()
+        .filter_map(|op| {
+            let ancestor = match op {
+                Operation::Update { old_value, .. } => old_value.clone(),
+                _ => None,
+            };
+            SyncOp::from_op(op.clone()).map(|op| LocalOp { op, ancestor })
+        })
+        .collect();
+    for (version_id, version) in versions {
+        trace!("applying version {:?}", version_id);
+        apply_version(txn, &mut local_ops, version, merge_strategies, applied)?;
+    }
+    Ok(local_ops.into_iter().map(|l| l.op).collect())
+}
+
+/// Complete a sync, recording that the replica is at the given version and all local operations
+/// are synchronized, along with the effects of applying versions, and commit the transaction.
+fn finish(
+    mut txn: Box<dyn StorageTxn + '_>,
+    version_id: VersionId,
+    applied: &Applied,
+) -> Result<()> {
+    txn.set_base_version(version_id)?;
+    history::record_timed_history(txn.as_mut(), &applied.changes)?;
+    for conflict in &applied.overridden {
+        txn.add_conflict(conflict.clone())?;
+    }
+    let synced_ops = txn.operations()?;
+    undo::retain_synced_operations(txn.as_mut(), synced_ops)?;
+    txn.set_operations(vec![])?;
+    txn.commit()
+}
+
+/// Batch operations into versions of no more than a million bytes, to avoid excessively large
+/// HTTP requests.  Each batch contains at least one operation.
+fn batch_operations(sync_ops: Vec<SyncOp>) -> Vec<Vec<SyncOp>> {
+    let mut batches = vec![];
+    let mut batch = vec![];
+    let mut batch_size = 0;
+    for op in sync_ops {
+        let op_size = serde_json::to_string(&op).unwrap().len();
+        if !batch.is_empty() && batch_size + op_size > 1000000 {
+            batches.push(std::mem::take(&mut batch));
+            batch_size = 0;
+        }
+        batch_size += op_size;
+        batch.push(op);
+    }
+    if !batch.is_empty() {
+        batches.push(batch);
+    }
+    batches
+}
+
+/// Apply a version from the server, rebasing the local operations on top of it.  The effects are
+/// accumulated in `applied`.
 fn apply_version(
     txn: &mut dyn StorageTxn,
-    local_ops: &mut Vec<SyncOp>,
-    mut version: Version,
+    local_ops: &mut Vec<LocalOp>,
+    version: &Version,
+    merge_strategies: &MergeStrategies,
+    applied: &mut Applied,
 ) -> Result<()> {
     // The situation here is that the server has already applied all server operations, and we
     // have already applied all local operations, so states have diverged by several
@@ -164,7 +461,11 @@ fn apply_version(
     // This is slightly complicated by the fact that the transform function can return None,
     // indicating no operation is required.  If this happens for a local op, we can just omit
     // it.  If it happens for server op, then we must copy the remaining local ops.
-    for server_op in version.operations.drain(..) {
+    //
+    // Where configured, conflicting updates to the same property are merged rather than
+    // transformed. In either case, a local update that survives a server update to the same
+    // property now follows that update, so its value becomes the ancestor for any further merge.
+    for server_op in version.operations.iter().cloned() {
         trace!(
             "rebasing local operations onto server operation {:?}",
             server_op
@@ -173,23 +474,66 @@ fn apply_version(
         let mut svr_op = Some(server_op);
         for local_op in local_ops.drain(..) {
             if let Some(o) = svr_op {
-                let (new_server_op, new_local_op) = SyncOp::transform(o, local_op.clone());
-                trace!("local operation {:?} -> {:?}", local_op, new_local_op);
-                svr_op = new_server_op;
-                if let Some(o) = new_local_op {
-                    new_local_ops.push(o);
+                let (new_server_op, new_local_op) =
+                    if let Some(merged) = merge_updates(&o, &local_op, merge_strategies) {
+                        trace!("merged local operation {:?}", local_op.op);
+                        applied.merged += 1;
+                        merged
+                    } else {
+                        let transformed = SyncOp::transform(o.clone(), local_op.op.clone());
+                        // Only an update discarded in favor of another update or a delete is a
+                        // lost edit.  Updates setting the same value transform to (None, None),
+                        // so are not counted as conflicts.
+                        if conflicting(&o, &local_op.op)
+                            && transformed.0.is_some() != transformed.1.is_some()
+                        {
+                            applied.conflicts += 1;
+                            applied.overridden.extend(overridden_update(
+                                o.clone(),
+                                local_op.op.clone(),
+                                transformed.0.is_some(),
+                            ));
+                        }
+                        transformed
+                    };
+                trace!("local operation {:?} -> {:?}", local_op.op, new_local_op);
+                if let Some(op) = new_local_op {
+                    let ancestor = match (&o, &op) {
+                        (
+                            SyncOp::Update {
+                                uuid: u1,
+                                property: p1,
+                                value,
+                                ..
+                            },
+                            SyncOp::Update {
+                                uuid: u2,
+                                property: p2,
+                                ..
+                            },
+                        ) if u1 == u2 && p1 == p2 => value.clone(),
+                        _ => local_op.ancestor,
+                    };
+                    new_local_ops.push(LocalOp { op, ancestor });
                 }
+                svr_op = new_server_op;
             } else {
                 trace!(
                     "local operation {:?} unchanged (server operation consumed)",
-                    local_op
+                    local_op.op
                 );
                 new_local_ops.push(local_op);
             }
         }
         if let Some(o) = svr_op {
-            if let Err(e) = apply::apply_op(txn, &o) {
-                warn!("Invalid operation when syncing: {} (ignored)", e);
+            // Determine the existing state of a created or updated task, to report the change.
+            let existing = match o {
+                SyncOp::Create { uuid } | SyncOp::Update { uuid, .. } => txn.get_task(uuid)?,
+                SyncOp::Delete { .. } => None,
+            };
+            match apply::apply_op(txn, &o) {
+                Ok(()) => applied.changes.extend(remote_change(o, existing)),
+                Err(e) => warn!("Invalid operation when syncing: {} (ignored)", e),
             }
         }
         *local_ops = new_local_ops;
@@ -197,15 +541,142 @@ fn apply_version(
     Ok(())
 }
 
+/// Merge a server update and a local update to the same property, if the property is configured
+/// for a text merge and the merge succeeds.  Returns the transformed operations, as for
+/// [`SyncOp::transform`].  The merged updates carry the later of the two timestamps.
+fn merge_updates(
+    server_op: &SyncOp,
+    local_op: &LocalOp,
+    merge_strategies: &MergeStrategies,
+) -> Option<(Option<SyncOp>, Option<SyncOp>)> {
+    let (
+        SyncOp::Update {
+            uuid,
+            property,
+            value: Some(remote),
+            timestamp: remote_timestamp,
+        },
+        SyncOp::Update {
+            uuid: local_uuid,
+            property: local_property,
+            value: Some(local),
+            timestamp: local_timestamp,
+        },
+    ) = (server_op, &local_op.op)
+    else {
+        return None;
+    };
+    if uuid != local_uuid || property != local_property || remote == local {
+        return None;
+    }
+    if merge_strategies.get(property) != Some(&MergeStrategy::TextMerge) {
+        return None;
+    }
+    let ancestor = local_op.ancestor.as_deref().unwrap_or("");
+    let merged = merge_text(ancestor, local, remote)?;
+    let op = SyncOp::Update {
+        uuid: *uuid,
+        property: property.clone(),
+        value: Some(merged.clone()),
+        timestamp: *remote_timestamp.max(local_timestamp),
+    };
+    // Each side needs no further operation if the merge is the same as its own value.
+    Some((
+        (merged != *local).then(|| op.clone()),
+        (merged != *remote).then_some(op),
+    ))
+}
+
+/// Determine whether a server and local operation can conflict, with one taking precedence over
+/// the other: two updates, or an update and a delete.
+fn conflicting(server_op: &SyncOp, local_op: &SyncOp) -> bool {
+    matches!(
+        (server_op, local_op),
+        (
+            SyncOp::Update { .. },
+            SyncOp::Update { .. } | SyncOp::Delete { .. }
+        ) | (SyncOp::Delete { .. }, SyncOp::Update { .. })
+    )
+}
+
+/// Describe the update discarded when one of a conflicting server and local operation took
+/// precedence over the other, if both are updates that set different values.
+fn overridden_update(
+    server_op: SyncOp,
+    local_op: SyncOp,
+    server_won: bool,
+) -> Option<SyncConflict> {
+    let (winner, loser, losing_origin) = if server_won {
+        (server_op, local_op, ChangeOrigin::Local)
+    } else {
+        (local_op, server_op, ChangeOrigin::Remote)
+    };
+    match (winner, loser) {
+        (
+            SyncOp::Update {
+                value: winning_value,
+                timestamp: winning_timestamp,
+                ..
+            },
+            SyncOp::Update {
+                uuid,
+                property,
+                value: losing_value,
+                timestamp: losing_timestamp,
+            },
+        ) if winning_value != losing_value => Some(SyncConflict {
+            uuid,
+            property,
+            losing_value,
+            losing_timestamp,
+            losing_origin,
+            winning_value,
+            winning_timestamp,
+        }),
+        _ => None,
+    }
+}
+
+/// Describe the change made by successfully applying a server operation to the given existing
+/// task, with the time of the operation if it has one.  A create of a task that already existed
+/// made no change.
+fn remote_change(op: SyncOp, existing: Option<TaskMap>) -> Option<TimedChange> {
+    match op {
+        SyncOp::Create { uuid } => existing
+            .is_none()
+            .then(|| (TaskChange::created(uuid, ChangeOrigin::Remote), None)),
+        SyncOp::Delete { uuid } => Some((TaskChange::deleted(uuid, ChangeOrigin::Remote), None)),
+        SyncOp::Update {
+            uuid,
+            property,
+            value,
+            timestamp,
+        } => {
+            let old_value = existing.and_then(|mut t| t.remove(&property));
+            (old_value != value).then(|| {
+                (
+                    TaskChange::updated(uuid, property, old_value, value, ChangeOrigin::Remote),
+                    Some(timestamp),
+                )
+            })
+        }
+    }
+}
+
 #[cfg(test)]
+#[allow(clippy::vec_init_then_push)]
 mod test {
     use super::*;
     use crate::server::test::TestServer;
-    use crate::storage::{InMemoryStorage, TaskMap};
+    use crate::server::{AsyncAdapter, HistorySegment, Server, Snapshot};
+    use crate::storage::{InMemoryStorage, SqliteStorage, Storage, TaskMap};
     use crate::taskdb::{snapshot::SnapshotTasks, TaskDb};
-    use crate::{Operation, Operations};
+    use crate::{CancellationToken, MergeStrategy, Operation, Operations};
+    use async_trait::async_trait;
     use chrono::Utc;
+    use futures_executor::block_on;
     use pretty_assertions::assert_eq;
+    use tempfile::TempDir;
     use uuid::Uuid;
 
     fn newdb() -> TaskDb {
@@ -217,10 +688,10 @@ mod test {
         let mut server: Box<dyn Server> = TestServer::new().server();
 
         let mut db1 = newdb();
-        sync(&mut server, db1.storage.txn()?.as_mut(), false).unwrap();
+        db1.sync(&mut server, false).unwrap();
 
         let mut db2 = newdb();
-        sync(&mut server, db2.storage.txn()?.as_mut(), false).unwrap();
+        db2.sync(&mut server, false).unwrap();
 
         // make some changes in parallel to db1 and db2..
         let uuid1 = Uuid::new_v4();
@@ -246,9 +717,9 @@ mod test {
         db1.commit_operations(ops, |_| false)?;
 
         // and synchronize those around
-        sync(&mut server, db1.storage.txn()?.as_mut(), false).unwrap();
-        sync(&mut server, db2.storage.txn()?.as_mut(), false).unwrap();
-        sync(&mut server, db1.storage.txn()?.as_mut(), false).unwrap();
+        db1.sync(&mut server, false).unwrap();
+        db2.sync(&mut server, false).unwrap();
+        db1.sync(&mut server, false).unwrap();
         assert_eq!(db1.sorted_tasks(), db2.sorted_tasks());
 
         // now make updates to the same task on both sides
@@ -273,9 +744,9 @@ mod test {
         db1.commit_operations(ops, |_| false)?;
 
         // and synchronize those around
-        sync(&mut server, db1.storage.txn()?.as_mut(), false).unwrap();
-        sync(&mut server, db2.storage.txn()?.as_mut(), false).unwrap();
-        sync(&mut server, db1.storage.txn()?.as_mut(), false).unwrap();
+        db1.sync(&mut server, false).unwrap();
+        db2.sync(&mut server, false).unwrap();
+        db1.sync(&mut server, false).unwrap();
         assert_eq!(db1.sorted_tasks(), db2.sorted_tasks());
 
         Ok(())
@@ -286,10 +757,10 @@ mod test {
         let mut server: Box<dyn Server> = TestServer::new().server();
 
         let mut db1 = newdb();
-        sync(&mut server, db1.storage.txn()?.as_mut(), false).unwrap();
+        db1.sync(&mut server, false).unwrap();
 
         let mut db2 = newdb();
-        sync(&mut server, db2.storage.txn()?.as_mut(), false).unwrap();
+        db2.sync(&mut server, false).unwrap();
 
         // create and update a task..
         let uuid = Uuid::new_v4();
@@ -305,9 +776,9 @@ mod test {
         db1.commit_operations(ops, |_| false)?;
 
         // and synchronize those around
-        sync(&mut server, db1.storage.txn()?.as_mut(), false).unwrap();
-        sync(&mut server, db2.storage.txn()?.as_mut(), false).unwrap();
-        sync(&mut server, db1.storage.txn()?.as_mut(), false).unwrap();
+        db1.sync(&mut server, false).unwrap();
+        db2.sync(&mut server, false).unwrap();
+        db1.sync(&mut server, false).unwrap();
         assert_eq!(db1.sorted_tasks(), db2.sorted_tasks());
 
         // delete and re-create the task on db1
@@ -337,9 +808,9 @@ mod test {
         });
         db2.commit_operations(ops, |_| false)?;
 
-        sync(&mut server, db1.storage.txn()?.as_mut(), false).unwrap();
-        sync(&mut server, db2.storage.txn()?.as_mut(), false).unwrap();
-        sync(&mut server, db1.storage.txn()?.as_mut(), false).unwrap();
+        db1.sync(&mut server, false).unwrap();
+        db2.sync(&mut server, false).unwrap();
+        db1.sync(&mut server, false).unwrap();
         assert_eq!(db1.sorted_tasks(), db2.sorted_tasks());
 
         Ok(())
@@ -365,7 +836,8 @@ mod test {
         db1.commit_operations(ops, |_| false)?;
 
         test_server.set_snapshot_urgency(SnapshotUrgency::High);
-        sync(&mut server, db1.storage.txn()?.as_mut(), false)?;
+        let (_, report) = db1.sync(&mut server, false)?;
+        assert!(report.snapshot_uploaded);
 
         // assert that a snapshot was added
         let base_version = db1.storage.txn()?.base_version()?;
@@ -387,7 +859,7 @@ mod test {
             timestamp: Utc::now(),
         });
         db1.commit_operations(ops, |_| false)?;
-        sync(&mut server, db1.storage.txn()?.as_mut(), false)?;
+        db1.sync(&mut server, false)?;
 
         // delete the first version, so that db2 *must* initialize from
         // the snapshot
@@ -395,7 +867,10 @@ mod test {
 
         // sync to a new DB and check that we got the expected results
         let mut db2 = newdb();
-        sync(&mut server, db2.storage.txn()?.as_mut(), false)?;
+        let (_, report) = db2.sync(&mut server, false)?;
+        assert!(report.snapshot_applied);
+        assert_eq!(report.versions_applied, 1);
+        assert_eq!(report.tasks_created, 1);
 
         let task = db2.get_task(uuid)?.unwrap();
         assert_eq!(task.get("title").unwrap(), "my first task, updated");
@@ -403,6 +878,45 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn test_rotate_encryption_secret() -> Result<()> {
+        let test_server = TestServer::new();
+        let mut server: Box<dyn Server> = test_server.server();
+        let mut db1 = newdb();
+
+        let uuid = Uuid::new_v4();
+        let mut ops = Operations::new();
+        ops.push(Operation::Create { uuid });
+        db1.commit_operations(ops, |_| false)?;
+
+        // local changes must be synchronized first
+        assert!(!db1.rotate_encryption_secret(&mut server, b"old", b"new")?);
+        assert_eq!(test_server.snapshot(), None);
+
+        db1.sync(&mut server, false)?;
+        assert!(db1.rotate_encryption_secret(&mut server, b"old", b"new")?);
+        let base_version = db1.storage.txn()?.base_version()?;
+        let (v, s) = test_server
+            .snapshot()
+            .ok_or_else(|| anyhow::anyhow!("no snapshot"))?;
+        assert_eq!(v, base_version);
+        let tasks = SnapshotTasks::decode(&s)?.into_inner();
+        assert_eq!(tasks[0].0, uuid);
+
+        // the replica is already at the new version
+        let (_, report) = db1.sync(&mut server, false)?;
+        assert_eq!(report.versions_applied, 0);
+        assert_eq!(report.base_version, base_version);
+
+        // a new replica begins from the snapshot
+        let mut db2 = newdb();
+        let (_, report) = db2.sync(&mut server, false)?;
+        assert!(report.snapshot_applied);
+        assert_eq!(report.versions_applied, 0);
+        assert_eq!(db2.sorted_tasks(), db1.sorted_tasks());
+        Ok(())
+    }
+
     #[test]
     fn test_sync_avoids_snapshot() -> Result<()> {
         let test_server = TestServer::new();
@@ -416,7 +930,7 @@ mod test {
         db1.commit_operations(ops, |_| false)?;
 
         test_server.set_snapshot_urgency(SnapshotUrgency::Low);
-        sync(&mut server, db1.storage.txn()?.as_mut(), true).unwrap();
+        db1.sync(&mut server, true).unwrap();
 
         // assert that a snapshot was not added, because we indicated
         // we wanted to avoid snapshots and it was only low urgency
@@ -432,7 +946,7 @@ mod test {
         let mut server: Box<dyn Server> = test_server.server();
 
         let mut db = newdb();
-        sync(&mut server, db.storage.txn()?.as_mut(), false).unwrap();
+        db.sync(&mut server, false).unwrap();
 
         // add a task to db
         let uuid1 = Uuid::new_v4();
@@ -447,7 +961,7 @@ mod test {
         });
         db.commit_operations(ops, |_| false)?;
 
-        sync(&mut server, db.storage.txn()?.as_mut(), true).unwrap();
+        db.sync(&mut server, true).unwrap();
         assert_eq!(test_server.versions_len(), 1);
 
         // chars are four bytes, but they're only one when converted to a String
@@ -467,7 +981,7 @@ mod test {
         db.commit_operations(ops, |_| false)?;
 
         // this sync batches the operations into two versions.
-        sync(&mut server, db.storage.txn()?.as_mut(), true).unwrap();
+        db.sync(&mut server, true).unwrap();
         assert_eq!(test_server.versions_len(), 3);
 
         Ok(())
@@ -480,7 +994,7 @@ mod test {
         let mut server: Box<dyn Server> = test_server.server();
 
         let mut db = newdb();
-        sync(&mut server, db.storage.txn()?.as_mut(), false).unwrap();
+        db.sync(&mut server, false).unwrap();
 
         // add a task to db
         let uuid1 = Uuid::new_v4();
@@ -495,7 +1009,7 @@ mod test {
         });
         db.commit_operations(ops, |_| false)?;
 
-        sync(&mut server, db.storage.txn()?.as_mut(), true).unwrap();
+        db.sync(&mut server, true).unwrap();
         assert_eq!(test_server.versions_len(), 1);
 
         // add an operation greater than the batch limit
@@ -510,8 +1024,759 @@ mod test {
         });
         db.commit_operations(ops, |_| false)?;
 
-        sync(&mut server, db.storage.txn()?.as_mut(), true).unwrap();
+        db.sync(&mut server, true).unwrap();
+        assert_eq!(test_server.versions_len(), 2);
+
+        Ok(())
+    }
+
+    fn update(uuid: Uuid, property: &str, value: &str) -> Operation {
+        Operation::Update {
+            uuid,
+            property: property.into(),
+            value: Some(value.into()),
+            old_value: None,
+            timestamp: Utc::now(),
+        }
+    }
+
+    /// An AsyncServer which calls a function just before each version is added.
+    struct Intercept<F: FnMut(usize) -> Result<()> + Send> {
+        server: Box<dyn Server>,
+        add_versions: usize,
+        intercept: F,
+    }
+
+    #[async_trait]
+    impl<F: FnMut(usize) -> Result<()> + Send> AsyncServer for Intercept<F> {
+        async fn add_version(
+            &mut self,
+            parent_version_id: VersionId,
+            history_segment: HistorySegment,
+        ) -> Result<(AddVersionResult, SnapshotUrgency)> {
+            self.add_versions += 1;
+            (self.intercept)(self.add_versions)?;
+            self.server.add_version(parent_version_id, history_segment)
+        }
+
+        async fn get_child_version(
+            &mut self,
+            parent_version_id: VersionId,
+        ) -> Result<GetVersionResult> {
+            self.server.get_child_version(parent_version_id)
+        }
+
+        async fn add_snapshot(&mut self, version_id: VersionId, snapshot: Snapshot) -> Result<()> {
+            self.server.add_snapshot(version_id, snapshot)
+        }
+
+        async fn get_snapshot(&mut self) -> Result<Option<(VersionId, Snapshot)>> {
+            self.server.get_snapshot()
+        }
+    }
+
+    #[test]
+    fn test_sync_async() -> Result<()> {
+        fn assert_send<T: Send>(_: &T) {}
+        let test_server = TestServer::new();
+        let mut server = AsyncAdapter(test_server.server());
+
+        let mut db1 = newdb();
+        let uuid = Uuid::new_v4();
+        let mut ops = Operations::new();
+        ops.push(Operation::Create { uuid });
+        ops.push(update(uuid, "title", "my task"));
+        db1.commit_operations(ops, |_| false)?;
+
+        let fut = db1.sync_async(&mut server, false);
+        assert_send(&fut);
+        block_on(fut)?;
+        assert_eq!(db1.num_operations()?, 0);
+
+        let mut db2 = newdb();
+        let (changes, _) = block_on(db2.sync_async(&mut server, false))?;
+        assert_eq!(changes.len(), 2);
+        assert_eq!(db1.sorted_tasks(), db2.sorted_tasks());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_sync_rejected_after_first_batch() -> Result<()> {
+        let test_server = TestServer::new();
+        let mut server = test_server.server();
+
+        let uuid1 = Uuid::new_v4();
+        let uuid2 = Uuid::new_v4();
+        let mut db1 = newdb();
+        let mut ops = Operations::new();
+        ops.push(Operation::Create { uuid: uuid1 });
+        db1.commit_operations(ops, |_| false)?;
+        db1.sync(&mut server, false)?;
+        let mut db2 = newdb();
+        db2.sync(&mut server, false)?;
+
+        // db1 makes changes large enough to need two versions, and db2 makes a small change
+        // which it syncs just before db1 adds its second version.
+        let data: String = vec!['a'; 600000].into_iter().collect();
+        let mut ops = Operations::new();
+        ops.push(update(uuid1, "description", &data));
+        ops.push(update(uuid1, "annotation", &data));
+        db1.commit_operations(ops, |_| false)?;
+        let mut ops = Operations::new();
+        ops.push(Operation::Create { uuid: uuid2 });
+        ops.push(update(uuid2, "title", "from db2"));
+        db2.commit_operations(ops, |_| false)?;
+
+        let mut db2 = Some(db2);
+        let mut intercepting = Intercept {
+            server: test_server.server(),
+            add_versions: 0,
+            intercept: |n| {
+                if n == 2 {
+                    db2.as_mut()
+                        .unwrap()
+                        .sync(&mut test_server.server(), false)?;
+                }
+                Ok(())
+            },
+        };
+        let (changes, _) = block_on(db1.sync_async(&mut intercepting, false))?;
+        assert_eq!(changes.len(), 2); // the creation of uuid2, and its title
+        assert_eq!(db1.num_operations()?, 0);
+        assert_eq!(
+            db1.get_task(uuid2)?.unwrap().get("title").unwrap(),
+            "from db2"
+        );
+
+        let mut db2 = db2.take().unwrap();
+        db2.sync(&mut server, false)?;
+        assert_eq!(db1.sorted_tasks(), db2.sorted_tasks());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_sync_local_change_during_push() -> Result<()> {
+        let test_server = TestServer::new();
+        let tmp_dir = TempDir::new()?;
+        let mut db = TaskDb::new(Box::new(SqliteStorage::new(tmp_dir.path(), true)?));
+        let uuid = Uuid::new_v4();
+        let mut ops = Operations::new();
+        ops.push(Operation::Create { uuid });
+        db.commit_operations(ops, |_| false)?;
+
+        // Another connection to the same storage commits a change while the first is being
+        // pushed.
+        let mut intercepting = Intercept {
+            server: test_server.server(),
+            add_versions: 0,
+            intercept: |n| {
+                if n == 1 {
+                    let mut other =
+                        TaskDb::new(Box::new(SqliteStorage::new(tmp_dir.path(), false)?));
+                    let mut ops = Operations::new();
+                    ops.push(update(uuid, "title", "concurrent"));
+                    other.commit_operations(ops, |_| false)?;
+                }
+                Ok(())
+            },
+        };
+        block_on(db.sync_async(&mut intercepting, false))?;
+
+        // Both changes were pushed, in two versions, and no local operations remain.
         assert_eq!(test_server.versions_len(), 2);
+        assert_eq!(db.num_operations()?, 0);
+        let mut db2 = newdb();
+        db2.sync(&mut test_server.server(), false)?;
+        assert_eq!(db.sorted_tasks(), db2.sorted_tasks());
+        assert_eq!(
+            db2.get_task(uuid)?.unwrap().get("title").unwrap(),
+            "concurrent"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_sync_report() -> Result<()> {
+        let mut server: Box<dyn Server> = TestServer::new().server();
+
+        let uuid1 = Uuid::new_v4();
+        let mut db1 = newdb();
+        let mut ops = Operations::new();
+        ops.push(Operation::Create { uuid: uuid1 });
+        ops.push(update(uuid1, "title", "from db1"));
+        db1.commit_operations(ops, |_| false)?;
+        let (_, report) = db1.sync(&mut server, false)?;
+        let expected = SyncReport {
+            operations_pushed: 2,
+            base_version: db1.storage.txn()?.base_version()?,
+            ..SyncReport::default()
+        };
+        assert_eq!(report, expected);
+
+        let mut db2 = newdb();
+        let (_, report) = db2.sync(&mut server, false)?;
+        let expected = SyncReport {
+            versions_applied: 1,
+            tasks_created: 1,
+            base_version: db1.storage.txn()?.base_version()?,
+            ..SyncReport::default()
+        };
+        assert_eq!(report, expected);
+
+        // db2 updates the title, and db1 later updates it again and creates another task
+        let mut ops = Operations::new();
+        ops.push(Operation::Update {
+            uuid: uuid1,
+            property: "title".into(),
+            value: Some("older".into()),
+            old_value: None,
+            timestamp: Utc::now() - chrono::Duration::seconds(10),
+        });
+        db2.commit_operations(ops, |_| false)?;
+        let uuid2 = Uuid::new_v4();
+        let mut ops = Operations::new();
+        ops.push(update(uuid1, "title", "newer"));
+        ops.push(Operation::Create { uuid: uuid2 });
+        db1.commit_operations(ops, |_| false)?;
+        db1.sync(&mut server, false)?;
+
+        // db2's update loses the conflict, so there is nothing to push
+        let (_, report) = db2.sync(&mut server, false)?;
+        let expected = SyncReport {
+            versions_applied: 1,
+            tasks_created: 1,
+            tasks_modified: 1,
+            conflicts_resolved: 1,
+            base_version: db1.storage.txn()?.base_version()?,
+            ..SyncReport::default()
+        };
+        assert_eq!(report, expected);
+        assert_eq!(db1.sorted_tasks(), db2.sorted_tasks());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_sync_records_conflicts() -> Result<()> {
+        let mut server: Box<dyn Server> = TestServer::new().server();
+
+        let uuid = Uuid::new_v4();
+        let mut db1 = newdb();
+        let mut ops = Operations::new();
+        ops.push(Operation::Create { uuid });
+        db1.commit_operations(ops, |_| false)?;
+        db1.sync(&mut server, false)?;
+        let mut db2 = newdb();
+        db2.sync(&mut server, false)?;
+
+        let t0 = Utc::now() - chrono::Duration::seconds(10);
+        let t1 = t0 + chrono::Duration::seconds(1);
+        let t2 = t0 + chrono::Duration::seconds(2);
+        let t3 = t0 + chrono::Duration::seconds(3);
+        let update_at = |property: &str, value: &str, timestamp| Operation::Update {
+            uuid,
+            property: property.into(),
+            value: Some(value.into()),
+            old_value: None,
+            timestamp,
+        };
+
+        // db2's earlier title loses to db1's title, while db2's later priority wins over
+        // db1's priority; db1's project does not conflict with anything
+        let mut ops = Operations::new();
+        ops.push(update_at("title", "db2 title", t0));
+        ops.push(update_at("priority", "H", t3));
+        db2.commit_operations(ops, |_| false)?;
+        let mut ops = Operations::new();
+        ops.push(update_at("title", "db1 title", t1));
+        ops.push(update_at("priority", "L", t2));
+        ops.push(update_at("project", "p", t2));
+        db1.commit_operations(ops, |_| false)?;
+        db1.sync(&mut server, false)?;
+        let (_, report) = db2.sync(&mut server, false)?;
+        db1.sync(&mut server, false)?;
+        assert_eq!(db1.sorted_tasks(), db2.sorted_tasks());
+
+        assert_eq!(report.conflicts_resolved, 2);
+        assert_eq!(
+            db2.sync_conflicts()?,
+            vec![
+                SyncConflict {
+                    uuid,
+                    property: "title".into(),
+                    losing_value: Some("db2 title".into()),
+                    losing_timestamp: t0,
+                    losing_origin: ChangeOrigin::Local,
+                    winning_value: Some("db1 title".into()),
+                    winning_timestamp: t1,
+                },
+                SyncConflict {
+                    uuid,
+                    property: "priority".into(),
+                    losing_value: Some("L".into()),
+                    losing_timestamp: t2,
+                    losing_origin: ChangeOrigin::Remote,
+                    winning_value: Some("H".into()),
+                    winning_timestamp: t3,
+                },
+            ]
+        );
+        // db1 did not resolve any conflicts itself
+        assert_eq!(db1.sync_conflicts()?, vec![]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_sync_same_value_no_conflict() -> Result<()> {
+        let mut server: Box<dyn Server> = TestServer::new().server();
+
+        let uuid = Uuid::new_v4();
+        let mut db1 = newdb();
+        let mut ops = Operations::new();
+        ops.push(Operation::Create { uuid });
+        db1.commit_operations(ops, |_| false)?;
+        db1.sync(&mut server, false)?;
+        let mut db2 = newdb();
+        db2.sync(&mut server, false)?;
+
+        let t0 = Utc::now() - chrono::Duration::seconds(10);
+        let t1 = t0 + chrono::Duration::seconds(1);
+        let update_at = |value: &str, timestamp| Operation::Update {
+            uuid,
+            property: "status".into(),
+            value: Some(value.into()),
+            old_value: None,
+            timestamp,
+        };
+
+        // both replicas set the same value, in either order of precedence
+        for (t_db1, t_db2) in [(t0, t1), (t1, t0)] {
+            let value = if t_db1 == t0 { "completed" } else { "pending" };
+            db2.commit_operations(vec![update_at(value, t_db2)], |_| false)?;
+            db1.commit_operations(vec![update_at(value, t_db1)], |_| false)?;
+            db1.sync(&mut server, false)?;
+            let (_, report) = db2.sync(&mut server, false)?;
+            db1.sync(&mut server, false)?;
+            assert_eq!(db1.sorted_tasks(), db2.sorted_tasks());
+            assert_eq!(report.conflicts_resolved, 0);
+        }
+        assert_eq!(db2.sync_conflicts()?, vec![]);
+        assert_eq!(db1.sync_conflicts()?, vec![]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_sync_create_update_no_conflict() -> Result<()> {
+        let uuid = Uuid::new_v4();
+        let mut storage = InMemoryStorage::new();
+        let mut txn = storage.txn()?;
+        txn.create_task(uuid)?;
+
+        // a remote create is superseded by a local update to the same task, without conflict
+        let mut local_ops = vec![LocalOp {
+            op: SyncOp::Update {
+                uuid,
+                property: "status".into(),
+                value: Some("pending".into()),
+                timestamp: Utc::now(),
+            },
+            ancestor: None,
+        }];
+        let version = Version {
+            operations: vec![SyncOp::Create { uuid }],
+        };
+        let mut applied = Applied::default();
+        apply_version(
+            txn.as_mut(),
+            &mut local_ops,
+            &version,
+            &MergeStrategies::new(),
+            &mut applied,
+        )?;
+        assert_eq!(local_ops.len(), 1);
+        assert_eq!(applied.conflicts, 0);
+        assert_eq!(applied.overridden, vec![]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_sync_create_existing_task() -> Result<()> {
+        let (existing, new) = (Uuid::new_v4(), Uuid::new_v4());
+        let mut storage = InMemoryStorage::new();
+        let mut txn = storage.txn()?;
+        txn.create_task(existing)?;
+
+        // only the create of a task that did not already exist is reported
+        let version = Version {
+            operations: vec![
+                SyncOp::Create { uuid: existing },
+                SyncOp::Create { uuid: new },
+            ],
+        };
+        let mut applied = Applied::default();
+        apply_version(
+            txn.as_mut(),
+            &mut vec![],
+            &version,
+            &MergeStrategies::new(),
+            &mut applied,
+        )?;
+        assert_eq!(
+            applied.changes,
+            vec![(TaskChange::created(new, ChangeOrigin::Remote), None)]
+        );
+        assert_eq!(
+            remote_change(SyncOp::Create { uuid: existing }, Some(TaskMap::new())),
+            None
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_overridden_update_same_value() {
+        let uuid = Uuid::new_v4();
+        let update = |value: &str| SyncOp::Update {
+            uuid,
+            property: "status".into(),
+            value: Some(value.into()),
+            timestamp: Utc::now(),
+        };
+        for server_won in [true, false] {
+            assert_eq!(
+                overridden_update(update("pending"), update("pending"), server_won),
+                None
+            );
+            assert!(
+                overridden_update(update("pending"), update("completed"), server_won).is_some()
+            );
+        }
+    }
+
+    #[test]
+    fn test_sync_text_merge() -> Result<()> {
+        let mut server: Box<dyn Server> = TestServer::new().server();
+
+        let uuid = Uuid::new_v4();
+        let mut db1 = newdb();
+        let mut ops = Operations::new();
+        ops.push(Operation::Create { uuid });
+        ops.push(update(uuid, "description", "buy milk"));
+        ops.push(update(uuid, "project", "home"));
+        db1.commit_operations(ops, |_| false)?;
+        db1.sync(&mut server, false)?;
+        let mut db2 = newdb();
+        db2.set_merge_strategy("description".into(), MergeStrategy::TextMerge);
+        db2.sync(&mut server, false)?;
+
+        let edit = |property: &str, old: &str, new: &str| Operation::Update {
+            uuid,
+            property: property.into(),
+            value: Some(new.into()),
+            old_value: Some(old.into()),
+            timestamp: Utc::now(),
+        };
+
+        // edits to different parts of the description merge; edits to the project do not
+        let mut ops = Operations::new();
+        ops.push(edit("description", "buy milk", "buy oat milk"));
+        ops.push(edit("project", "home", "errands"));
+        db2.commit_operations(ops, |_| false)?;
+        let mut ops = Operations::new();
+        ops.push(edit("description", "buy milk", "buy milk today"));
+        ops.push(edit("project", "home", "shopping"));
+        db1.commit_operations(ops, |_| false)?;
+        db1.sync(&mut server, false)?;
+        let (_, report) = db2.sync(&mut server, false)?;
+        db1.sync(&mut server, false)?;
+
+        assert_eq!(report.updates_merged, 1);
+        assert_eq!(report.conflicts_resolved, 1);
+        assert_eq!(db1.sorted_tasks(), db2.sorted_tasks());
+        let task = db1.get_task(uuid)?.unwrap();
+        assert_eq!(task.get("description").unwrap(), "buy oat milk today");
+        assert_eq!(task.get("project").unwrap(), "shopping");
+
+        // a second edit merges with the merged value as the ancestor
+        let mut ops = Operations::new();
+        ops.push(edit(
+            "description",
+            "buy oat milk today",
+            "buy oat milk today, early",
+        ));
+        db2.commit_operations(ops, |_| false)?;
+        let mut ops = Operations::new();
+        ops.push(edit(
+            "description",
+            "buy oat milk today",
+            "please buy oat milk today",
+        ));
+        db1.commit_operations(ops, |_| false)?;
+        db1.sync(&mut server, false)?;
+        db2.sync(&mut server, false)?;
+        db1.sync(&mut server, false)?;
+        assert_eq!(db1.sorted_tasks(), db2.sorted_tasks());
+        let task = db1.get_task(uuid)?.unwrap();
+        assert_eq!(
+            task.get("description").unwrap(),
+            "please buy oat milk today, early"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_sync_text_merge_conflict() -> Result<()> {
+        let mut server: Box<dyn Server> = TestServer::new().server();
+
+        let uuid = Uuid::new_v4();
+        let mut db1 = newdb();
+        let mut ops = Operations::new();
+        ops.push(Operation::Create { uuid });
+        ops.push(update(uuid, "description", "buy milk"));
+        db1.commit_operations(ops, |_| false)?;
+        db1.sync(&mut server, false)?;
+        let mut db2 = newdb();
+        db2.set_merge_strategy("description".into(), MergeStrategy::TextMerge);
+        db2.sync(&mut server, false)?;
+
+        // both replicas change the same word, so the later update wins
+        let edit = |new: &str, timestamp| Operation::Update {
+            uuid,
+            property: "description".into(),
+            value: Some(new.into()),
+            old_value: Some("buy milk".into()),
+            timestamp,
+        };
+        let mut ops = Operations::new();
+        ops.push(edit(
+            "buy bread",
+            Utc::now() - chrono::Duration::seconds(10),
+        ));
+        db2.commit_operations(ops, |_| false)?;
+        let mut ops = Operations::new();
+        ops.push(edit("buy eggs", Utc::now()));
+        db1.commit_operations(ops, |_| false)?;
+        db1.sync(&mut server, false)?;
+        let (_, report) = db2.sync(&mut server, false)?;
+
+        assert_eq!(report.updates_merged, 0);
+        assert_eq!(report.conflicts_resolved, 1);
+        let task = db2.get_task(uuid)?.unwrap();
+        assert_eq!(task.get("description").unwrap(), "buy eggs");
+        assert_eq!(db2.sync_conflicts()?.len(), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_sync_progress() -> Result<()> {
+        let mut server: Box<dyn Server> = TestServer::new().server();
+
+        // db1 makes changes large enough to need two versions
+        let uuid = Uuid::new_v4();
+        let data: String = vec!['a'; 600000].into_iter().collect();
+        let mut db1 = newdb();
+        let mut ops = Operations::new();
+        ops.push(Operation::Create { uuid });
+        ops.push(update(uuid, "description", &data));
+        ops.push(update(uuid, "annotation", &data));
+        db1.commit_operations(ops, |_| false)?;
+
+        let mut progress = vec![];
+        db1.sync_with_options(
+            &mut server,
+            &mut SyncOptions::new().on_progress(|p| progress.push(p)),
+            &mut vec![],
+        )?;
+        assert_eq!(
+            progress,
+            vec![
+                SyncProgress::OperationsTransformed {
+                    remote_operations: 0,
+                    local_operations: 3
+                },
+                SyncProgress::BatchUploaded {
+                    batch: 1,
+                    batches: 2
+                },
+                SyncProgress::BatchUploaded {
+                    batch: 2,
+                    batches: 2
+                },
+            ]
+        );
+
+        let mut progress = vec![];
+        let mut db2 = newdb();
+        db2.sync_with_options(
+            &mut server,
+            &mut SyncOptions::new().on_progress(|p| progress.push(p)),
+            &mut vec![],
+        )?;
+        assert_eq!(
+            progress,
+            vec![
+                SyncProgress::VersionFetched { count: 1 },
+                SyncProgress::VersionFetched { count: 2 },
+                SyncProgress::OperationsTransformed {
+                    remote_operations: 3,
+                    local_operations: 0
+                },
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_sync_cancelled_while_fetching() -> Result<()> {
+        let mut server: Box<dyn Server> = TestServer::new().server();
+
+        let mut db1 = newdb();
+        let uuid = Uuid::new_v4();
+        let mut ops = Operations::new();
+        ops.push(Operation::Create { uuid });
+        db1.commit_operations(ops, |_| false)?;
+        db1.sync(&mut server, false)?;
+
+        let mut db2 = newdb();
+        db2.sync(&mut server, false)?;
+        let mut ops = Operations::new();
+        ops.push(update(uuid, "title", "from db2"));
+        db2.commit_operations(ops, |_| false)?;
+
+        for title in ["one", "two", "three"] {
+            let mut ops = Operations::new();
+            ops.push(update(uuid, "description", title));
+            db1.commit_operations(ops, |_| false)?;
+            db1.sync(&mut server, false)?;
+        }
+
+        // cancel db2's sync after it has fetched two of the three new versions
+        let before = db2.sorted_tasks();
+        let token = CancellationToken::new();
+        let res = db2.sync_with_options(
+            &mut server,
+            &mut SyncOptions::new()
+                .cancellation_token(token.clone())
+                .on_progress(|p| {
+                    if p == (SyncProgress::VersionFetched { count: 2 }) {
+                        token.cancel();
+                    }
+                }),
+            &mut vec![],
+        );
+        assert!(matches!(res, Err(Error::Cancelled)));
+
+        // nothing has changed locally
+        assert_eq!(db2.sorted_tasks(), before);
+        assert_eq!(db2.num_operations()?, 1);
+
+        // and a subsequent sync completes normally
+        db2.sync(&mut server, false)?;
+        db1.sync(&mut server, false)?;
+        assert_eq!(db1.sorted_tasks(), db2.sorted_tasks());
+        assert_eq!(
+            db1.get_task(uuid)?.unwrap().get("description").unwrap(),
+            "three"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_sync_cancelled_while_fetching_no_local_ops() -> Result<()> {
+        let mut server: Box<dyn Server> = TestServer::new().server();
+
+        let mut db1 = newdb();
+        let uuid = Uuid::new_v4();
+        let mut ops = Operations::new();
+        ops.push(Operation::Create { uuid });
+        db1.commit_operations(ops, |_| false)?;
+        db1.sync(&mut server, false)?;
+
+        let mut db2 = newdb();
+        db2.sync(&mut server, false)?;
+
+        for title in ["one", "two", "three"] {
+            let mut ops = Operations::new();
+            ops.push(update(uuid, "description", title));
+            db1.commit_operations(ops, |_| false)?;
+            db1.sync(&mut server, false)?;
+        }
+
+        // cancel db2's sync after it has fetched two of the three new versions
+        let token = CancellationToken::new();
+        let res = db2.sync_with_options(
+            &mut server,
+            &mut SyncOptions::new()
+                .cancellation_token(token.clone())
+                .on_progress(|p| {
+                    if p == (SyncProgress::VersionFetched { count: 2 }) {
+                        token.cancel();
+                    }
+                }),
+            &mut vec![],
+        );
+        assert!(matches!(res, Err(Error::Cancelled)));
+
+        // the first version was committed before the cancellation
+        assert_eq!(
+            db2.get_task(uuid)?.unwrap().get("description").unwrap(),
+            "one"
+        );
+
+        // and a subsequent sync applies the rest
+        let (_, report) = db2.sync(&mut server, false)?;
+        assert_eq!(report.versions_applied, 2);
+        assert_eq!(db1.sorted_tasks(), db2.sorted_tasks());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_sync_cancelled_between_batches() -> Result<()> {
+        let test_server = TestServer::new();
+        let mut server = test_server.server();
+
+        let uuid = Uuid::new_v4();
+        let data: String = vec!['a'; 600000].into_iter().collect();
+        let mut db1 = newdb();
+        let mut ops = Operations::new();
+        ops.push(Operation::Create { uuid });
+        ops.push(update(uuid, "description", &data));
+        ops.push(update(uuid, "annotation", &data));
+        db1.commit_operations(ops, |_| false)?;
+
+        let token = CancellationToken::new();
+        let res = db1.sync_with_options(
+            &mut server,
+            &mut SyncOptions::new()
+                .cancellation_token(token.clone())
+                .on_progress(|p| {
+                    if matches!(p, SyncProgress::BatchUploaded { batch: 1, .. }) {
+                        token.cancel();
+                    }
+                }),
+            &mut vec![],
+        );
+        assert!(matches!(res, Err(Error::Cancelled)));
+
+        // the first batch reached the server, but the local operations remain
+        assert_eq!(test_server.versions_len(), 1);
+        assert_eq!(db1.num_operations()?, 3);
+
+        // a subsequent sync fetches the first batch and sends the remainder
+        db1.sync(&mut server, false)?;
+        assert_eq!(db1.num_operations()?, 0);
+        let mut db2 = newdb();
+        db2.sync(&mut server, false)?;
+        assert_eq!(db1.sorted_tasks(), db2.sorted_tasks());
 
         Ok(())
     }
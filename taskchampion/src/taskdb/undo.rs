@@ -1,10 +1,18 @@
-use super::apply;
+use super::{apply, history};
+use crate::changes::reversed_changes;
 use crate::errors::Result;
 use crate::operation::{Operation, Operations};
 use crate::server::SyncOp;
-use crate::storage::StorageTxn;
+use crate::storage::{StorageTxn, TaskMap};
+use crate::undo::split_steps;
 use chrono::Utc;
 use log::{debug, info, trace};
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use uuid::Uuid;
+
+/// The number of undo steps retained after local operations are synchronized.
+const SYNCED_UNDO_STEPS: usize = 20;
 
 /// Return the operations back to and including the last undo point, or since the last sync if no
 /// undo point is found.
@@ -85,6 +93,7 @@ pub fn commit_reversed_operations(txn: &mut dyn StorageTxn, undo_ops: Operations
         );
         return Ok(applied);
     }
+    let changes = reversed_changes(&undo_ops);
     undo_ops.reverse();
     local_ops.truncate(new_len);
 
@@ -99,6 +108,7 @@ pub fn commit_reversed_operations(txn: &mut dyn StorageTxn, undo_ops: Operations
     }
 
     if undo_len != 0 {
+        history::record_history(txn, &changes)?;
         txn.set_operations(local_ops)?;
         txn.commit()?;
     }
@@ -106,10 +116,135 @@ pub fn commit_reversed_operations(txn: &mut dyn StorageTxn, undo_ops: Operations
     Ok(applied)
 }
 
+/// Retain the given local operations, which have just been synchronized, so that they can be
+/// undone with compensating operations.  Only the most recent `SYNCED_UNDO_STEPS` undo steps are
+/// retained.
+///
+/// The transaction is not committed.
+pub(super) fn retain_synced_operations(txn: &mut dyn StorageTxn, ops: Operations) -> Result<()> {
+    if ops.is_empty() {
+        return Ok(());
+    }
+    let mut synced_ops = txn.synced_operations()?;
+    synced_ops.extend(ops);
+    let steps = split_steps(&synced_ops);
+    if steps.len() > SYNCED_UNDO_STEPS {
+        let drop: usize = steps[..steps.len() - SYNCED_UNDO_STEPS]
+            .iter()
+            .map(|s| s.len())
+            .sum();
+        synced_ops.drain(..drop);
+    }
+    txn.set_synced_operations(synced_ops)
+}
+
+/// Return the synchronized operations back to and including the last undo point, as retained by
+/// `retain_synced_operations`.
+pub fn get_synced_undo_operations(txn: &mut dyn StorageTxn) -> Result<Operations> {
+    let synced_ops = txn.synced_operations()?;
+    Ok(split_steps(&synced_ops)
+        .last()
+        .map(|s| s.to_vec())
+        .unwrap_or_default())
+}
+
+/// Remove the given operations from the end of the retained synchronized operations, returning
+/// false if they are not the latest synchronized operations.
+pub fn drop_synced_undo_operations(
+    txn: &mut dyn StorageTxn,
+    undo_ops: &[Operation],
+) -> Result<bool> {
+    let mut synced_ops = txn.synced_operations()?;
+    if !synced_ops.ends_with(undo_ops) {
+        return Ok(false);
+    }
+    synced_ops.truncate(synced_ops.len() - undo_ops.len());
+    txn.set_synced_operations(synced_ops)?;
+    txn.commit()?;
+    Ok(true)
+}
+
+/// Generate new operations that revert the effects of the given operations, beginning with the
+/// last and proceeding to the first, based on the current state of the tasks.
+///
+/// Changes that have since been superseded are not reverted: an update is reverted only if the
+/// property still has the value it was updated to, and a deletion is reverted only if the task
+/// has not been re-created.  The result begins with an undo point.
+pub fn compensating_operations(
+    txn: &mut dyn StorageTxn,
+    undo_ops: &[Operation],
+) -> Result<Operations> {
+    // Current state of each task, updated as compensating operations are generated.
+    let mut tasks: HashMap<Uuid, Option<TaskMap>> = HashMap::new();
+    let mut ops = vec![Operation::UndoPoint];
+    let now = Utc::now();
+    for op in undo_ops.iter().rev() {
+        let uuid = match op {
+            Operation::Create { uuid }
+            | Operation::Delete { uuid, .. }
+            | Operation::Update { uuid, .. } => *uuid,
+            Operation::UndoPoint => continue,
+        };
+        let task = match tasks.entry(uuid) {
+            Entry::Occupied(e) => e.into_mut(),
+            Entry::Vacant(e) => e.insert(txn.get_task(uuid)?),
+        };
+        match op {
+            Operation::Create { .. } => {
+                if let Some(old_task) = task.take() {
+                    ops.push(Operation::Delete { uuid, old_task });
+                }
+            }
+            Operation::Delete { old_task, .. } => {
+                if task.is_none() {
+                    ops.push(Operation::Create { uuid });
+                    let mut props: Vec<_> = old_task.iter().collect();
+                    props.sort();
+                    for (property, value) in props {
+                        ops.push(Operation::Update {
+                            uuid,
+                            property: property.clone(),
+                            old_value: None,
+                            value: Some(value.clone()),
+                            timestamp: now,
+                        });
+                    }
+                    *task = Some(old_task.clone());
+                }
+            }
+            Operation::Update {
+                property,
+                old_value,
+                value,
+                ..
+            } => {
+                if let Some(task) = task {
+                    if task.get(property) == value.as_ref() && value != old_value {
+                        ops.push(Operation::Update {
+                            uuid,
+                            property: property.clone(),
+                            old_value: value.clone(),
+                            value: old_value.clone(),
+                            timestamp: now,
+                        });
+                        match old_value {
+                            Some(v) => task.insert(property.clone(), v.clone()),
+                            None => task.remove(property),
+                        };
+                    }
+                }
+            }
+            Operation::UndoPoint => {}
+        }
+    }
+    Ok(ops)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{storage::taskmap_with, taskdb::TaskDb};
+    use crate::storage::{taskmap_with, TaskMap};
+    use crate::taskdb::TaskDb;
     use crate::{Operation, Operations};
     use chrono::Utc;
     use pretty_assertions::assert_eq;
@@ -272,4 +407,127 @@ mod tests {
     fn test_reverse_undo_point() {
         assert_eq!(reverse_ops(Operation::UndoPoint), vec![]);
     }
+
+    fn update(
+        uuid: Uuid,
+        property: &str,
+        old_value: Option<&str>,
+        value: Option<&str>,
+    ) -> Operation {
+        Operation::Update {
+            uuid,
+            property: property.into(),
+            old_value: old_value.map(String::from),
+            value: value.map(String::from),
+            timestamp: Utc::now(),
+        }
+    }
+
+    /// Set the timestamps of all updates to the given value, for comparison.
+    fn with_timestamp(mut ops: Operations, ts: chrono::DateTime<Utc>) -> Operations {
+        for op in &mut ops {
+            if let Operation::Update { timestamp, .. } = op {
+                *timestamp = ts;
+            }
+        }
+        ops
+    }
+
+    #[test]
+    fn test_compensating_operations() -> Result<()> {
+        let mut db = TaskDb::new_inmemory();
+        let uuid1 = Uuid::new_v4();
+        let uuid2 = Uuid::new_v4();
+        let uuid3 = Uuid::new_v4();
+        let ts = Utc::now();
+        db.commit_operations(
+            vec![
+                Operation::Create { uuid: uuid2 },
+                update(uuid2, "title", None, Some("two")),
+                Operation::Create { uuid: uuid3 },
+            ],
+            |_| false,
+        )?;
+
+        let undo_ops = vec![
+            Operation::UndoPoint,
+            Operation::Create { uuid: uuid1 },
+            update(uuid1, "title", None, Some("one")),
+            update(uuid2, "title", Some("old"), Some("two")),
+            update(uuid2, "priority", None, Some("H")),
+            Operation::Delete {
+                uuid: uuid3,
+                old_task: taskmap_with(vec![("title".into(), "three".into())]),
+            },
+        ];
+        db.commit_operations(undo_ops[..3].to_vec(), |_| false)?;
+        // priority was since changed to "L", so that change is not reverted, and uuid3 was
+        // re-created, so it is not re-created again.
+        db.commit_operations(vec![update(uuid2, "priority", None, Some("L"))], |_| false)?;
+
+        let ops = db.compensating_operations(&undo_ops)?;
+        assert_eq!(
+            with_timestamp(ops, ts),
+            with_timestamp(
+                vec![
+                    Operation::UndoPoint,
+                    update(uuid2, "title", Some("two"), Some("old")),
+                    update(uuid1, "title", Some("one"), None),
+                    Operation::Delete {
+                        uuid: uuid1,
+                        old_task: TaskMap::new(),
+                    },
+                ],
+                ts
+            )
+        );
+
+        // undoing a deletion of a task that does not exist re-creates it
+        let uuid4 = Uuid::new_v4();
+        let ops = db.compensating_operations(&[Operation::Delete {
+            uuid: uuid4,
+            old_task: taskmap_with(vec![("title".into(), "four".into())]),
+        }])?;
+        assert_eq!(
+            with_timestamp(ops, ts),
+            with_timestamp(
+                vec![
+                    Operation::UndoPoint,
+                    Operation::Create { uuid: uuid4 },
+                    update(uuid4, "title", None, Some("four")),
+                ],
+                ts
+            )
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_retain_synced_operations() -> Result<()> {
+        let mut db = TaskDb::new_inmemory();
+        let uuid = Uuid::new_v4();
+        let mut txn = db.storage.txn()?;
+        let mut all_ops = vec![];
+        for i in 0..SYNCED_UNDO_STEPS + 2 {
+            let ops = vec![
+                Operation::UndoPoint,
+                update(uuid, "count", None, Some(&i.to_string())),
+            ];
+            all_ops.extend(ops.iter().cloned());
+            retain_synced_operations(txn.as_mut(), ops)?;
+        }
+        assert_eq!(txn.synced_operations()?, all_ops[4..].to_vec());
+        assert_eq!(
+            get_synced_undo_operations(txn.as_mut())?,
+            all_ops[all_ops.len() - 2..].to_vec()
+        );
+
+        // only the latest operations can be dropped
+        assert!(!drop_synced_undo_operations(txn.as_mut(), &all_ops[4..6])?);
+        assert!(drop_synced_undo_operations(
+            txn.as_mut(),
+            &all_ops[all_ops.len() - 2..]
+        )?);
+        Ok(())
+    }
 }
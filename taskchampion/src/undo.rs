@@ -0,0 +1,239 @@
+use crate::errors::Result;
+use crate::operation::Operation;
+use crate::storage::TaskMap;
+use chrono::{DateTime, Utc};
+use std::fmt;
+use uuid::Uuid;
+
+/// A summary of a single step that can be undone or redone, as returned from
+/// [`Replica::undo_steps`](crate::Replica::undo_steps) and
+/// [`Replica::redo_steps`](crate::Replica::redo_steps).
+///
+/// The `Display` implementation gives a brief human-readable description of the step, such as
+/// `modified "Buy milk" (description, due)`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UndoStep {
+    /// The latest timestamp of the property updates in this step, if it contains any.
+    pub timestamp: Option<DateTime<Utc>>,
+    /// The number of operations in this step, not including the undo point.
+    pub num_operations: usize,
+    /// The tasks affected by this step, in the order they were first changed.
+    pub tasks: Vec<UndoTaskSummary>,
+}
+
+/// A summary of the changes to a single task in an [`UndoStep`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UndoTaskSummary {
+    /// The task that was changed.
+    pub uuid: Uuid,
+    /// The task's description, if known.
+    pub description: Option<String>,
+    /// True if the task was created in this step.
+    pub created: bool,
+    /// True if the task was deleted in this step.
+    pub deleted: bool,
+    /// The properties updated in this step, sorted by name.
+    pub properties: Vec<String>,
+}
+
+impl UndoStep {
+    /// Summarize the given operations.  The `get_task` function gets the current state of a task,
+    /// used to find its description.
+    pub(crate) fn summarize<F>(operations: &[Operation], mut get_task: F) -> Result<Self>
+    where
+        F: FnMut(Uuid) -> Result<Option<TaskMap>>,
+    {
+        let mut step = UndoStep {
+            timestamp: None,
+            num_operations: 0,
+            tasks: vec![],
+        };
+        for op in operations {
+            let uuid = match op {
+                Operation::Create { uuid }
+                | Operation::Delete { uuid, .. }
+                | Operation::Update { uuid, .. } => *uuid,
+                Operation::UndoPoint => continue,
+            };
+            step.num_operations += 1;
+            let task = match step.tasks.iter().position(|t| t.uuid == uuid) {
+                Some(i) => &mut step.tasks[i],
+                None => {
+                    step.tasks.push(UndoTaskSummary {
+                        uuid,
+                        description: None,
+                        created: false,
+                        deleted: false,
+                        properties: vec![],
+                    });
+                    step.tasks.last_mut().unwrap()
+                }
+            };
+            match op {
+                Operation::Create { .. } => task.created = true,
+                Operation::Delete { old_task, .. } => {
+                    task.deleted = true;
+                    if let Some(d) = old_task.get("description") {
+                        task.description = Some(d.clone());
+                    }
+                }
+                Operation::Update {
+                    property,
+                    value,
+                    timestamp,
+                    ..
+                } => {
+                    if property == "description" {
+                        if let Some(v) = value {
+                            task.description = Some(v.clone());
+                        }
+                    }
+                    if !task.properties.contains(property) {
+                        task.properties.push(property.clone());
+                    }
+                    step.timestamp = step.timestamp.max(Some(*timestamp));
+                }
+                Operation::UndoPoint => {}
+            }
+        }
+        for task in &mut step.tasks {
+            task.properties.sort();
+            if task.description.is_none() {
+                task.description = get_task(task.uuid)?.and_then(|t| t.get("description").cloned());
+            }
+        }
+        Ok(step)
+    }
+}
+
+impl fmt::Display for UndoStep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.tasks.is_empty() {
+            return write!(f, "no changes");
+        }
+        for (i, task) in self.tasks.iter().enumerate() {
+            if i > 0 {
+                write!(f, "; ")?;
+            }
+            write!(f, "{}", task)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for UndoTaskSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let verb = match (self.created, self.deleted) {
+            (true, false) => "created",
+            (_, true) => "deleted",
+            (false, false) => "modified",
+        };
+        match &self.description {
+            Some(d) => write!(f, "{} {:?}", verb, d)?,
+            None => write!(f, "{} {}", verb, self.uuid)?,
+        }
+        if verb == "modified" && !self.properties.is_empty() {
+            write!(f, " ({})", self.properties.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+/// Split the given local operations into undo steps, each beginning with an undo point (except
+/// possibly the first), in the order they were applied.
+pub(crate) fn split_steps(operations: &[Operation]) -> Vec<&[Operation]> {
+    let mut steps = vec![];
+    let mut start = 0;
+    for (i, op) in operations.iter().enumerate() {
+        if op.is_undo_point() && i > start {
+            steps.push(&operations[start..i]);
+            start = i;
+        }
+    }
+    if start < operations.len() {
+        steps.push(&operations[start..]);
+    }
+    steps
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn update(uuid: Uuid, property: &str, value: &str) -> Operation {
+        Operation::Update {
+            uuid,
+            property: property.into(),
+            old_value: None,
+            value: Some(value.into()),
+            timestamp: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn split() {
+        let uuid = Uuid::new_v4();
+        let ops = vec![
+            Operation::Create { uuid },
+            Operation::UndoPoint,
+            update(uuid, "a", "1"),
+            Operation::UndoPoint,
+            Operation::UndoPoint,
+            update(uuid, "b", "1"),
+        ];
+        assert_eq!(
+            split_steps(&ops),
+            vec![&ops[0..1], &ops[1..3], &ops[3..4], &ops[4..6]]
+        );
+        assert_eq!(split_steps(&ops[1..3]), vec![&ops[1..3]]);
+        assert!(split_steps(&[]).is_empty());
+    }
+
+    #[test]
+    fn summarize() {
+        let uuid1 = Uuid::new_v4();
+        let uuid2 = Uuid::new_v4();
+        let uuid3 = Uuid::new_v4();
+        let ops = vec![
+            Operation::UndoPoint,
+            Operation::Create { uuid: uuid1 },
+            update(uuid1, "description", "new task"),
+            update(uuid1, "entry", "1"),
+            update(uuid2, "due", "2"),
+            update(uuid2, "priority", "H"),
+            update(uuid2, "due", "3"),
+            Operation::Delete {
+                uuid: uuid3,
+                old_task: [("description".to_string(), "old task".to_string())].into(),
+            },
+        ];
+        let step = UndoStep::summarize(&ops, |uuid| {
+            assert_eq!(uuid, uuid2);
+            Ok(Some(
+                [("description".to_string(), "existing".to_string())].into(),
+            ))
+        })
+        .unwrap();
+        assert_eq!(step.num_operations, 7);
+        assert_eq!(step.tasks.len(), 3);
+        assert_eq!(step.tasks[1].properties, vec!["due", "priority"]);
+        assert_eq!(
+            step.to_string(),
+            r#"created "new task"; modified "existing" (due, priority); deleted "old task""#
+        );
+    }
+
+    #[test]
+    fn summarize_unknown_description() {
+        let uuid = Uuid::new_v4();
+        let step = UndoStep::summarize(&[update(uuid, "due", "2")], |_| Ok(None)).unwrap();
+        assert_eq!(step.to_string(), format!("modified {} (due)", uuid));
+        assert_eq!(
+            UndoStep::summarize(&[Operation::UndoPoint], |_| Ok(None))
+                .unwrap()
+                .to_string(),
+            "no changes"
+        );
+    }
+}
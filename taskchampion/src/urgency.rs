@@ -0,0 +1,375 @@
+use crate::task::{utc_timestamp, Task, Timestamp};
+use chrono::Utc;
+use std::collections::HashMap;
+
+/// Urgency calculates a score for a task, indicating how urgently it should be addressed.
+///
+/// The score is a weighted sum of terms, following [Taskwarrior's urgency
+/// model](https://taskwarrior.org/docs/urgency/).  Each term has a value between 0.0 and 1.0,
+/// which is multiplied by the corresponding coefficient.  The `Default` implementation uses
+/// Taskwarrior's default coefficients; any of them can be adjusted, and coefficients can be
+/// added for specific tags, projects, and UDAs.
+///
+/// The blocking and blocked terms use the [`crate::DependencyMap`] cached with each task, so
+/// it is cheap to calculate urgency for a large number of tasks from the same replica.
+///
+/// ```
+/// # use taskchampion::{Replica, Status, StorageConfig, Urgency};
+/// # let mut replica = Replica::new(StorageConfig::InMemory.into_storage().unwrap());
+/// # let task = replica.new_task(Status::Pending, "foo".into()).unwrap();
+/// let mut urgency = Urgency::default();
+/// urgency.tag_coefficients.insert("next".into(), 15.0);
+/// urgency.uda_coefficients.insert("estimate".into(), 0.5);
+/// let score = urgency.calculate(&task);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Urgency {
+    /// Coefficient for the proximity of the task's due time.  The term ramps from 0.2, for
+    /// tasks due two weeks or more in the future, to 1.0, for tasks a week or more overdue.
+    pub due: f64,
+    /// Coefficients for values of the `priority` property.
+    pub priority: HashMap<String, f64>,
+    /// Coefficient for the age of the task, measured from its `entry` time.
+    pub age: f64,
+    /// The age, in days, at which the age term reaches its maximum.
+    pub age_max_days: f64,
+    /// Coefficient for the presence of user tags.  The term is 0.8 for one tag, 0.9 for two,
+    /// and 1.0 for three or more.
+    pub tags: f64,
+    /// Coefficient for the presence of annotations, counted like tags.
+    pub annotations: f64,
+    /// Coefficient for the presence of a `project` property.
+    pub project: f64,
+    /// Coefficient for a task that is active (started).
+    pub active: f64,
+    /// Coefficient for a task with a `scheduled` time in the past.
+    pub scheduled: f64,
+    /// Coefficient for a task that is waiting.
+    pub waiting: f64,
+    /// Coefficient for a task that is blocking another task.
+    pub blocking: f64,
+    /// Coefficient for a task that is blocked by another task.
+    pub blocked: f64,
+    /// Coefficients for specific user tags, keyed by tag name.
+    pub tag_coefficients: HashMap<String, f64>,
+    /// Coefficients for specific projects, keyed by project name.  Subprojects match their
+    /// parent projects, so an entry for `home` also applies to `home.garden`.
+    pub project_coefficients: HashMap<String, f64>,
+    /// Coefficients for the presence of UDAs, keyed by the full property name.
+    pub uda_coefficients: HashMap<String, f64>,
+    /// Coefficients for specific UDA values, keyed by full property name and value.
+    pub uda_value_coefficients: HashMap<(String, String), f64>,
+}
+
+impl Default for Urgency {
+    fn default() -> Self {
+        Self {
+            due: 12.0,
+            priority: [("H", 6.0), ("M", 3.9), ("L", 1.8)]
+                .into_iter()
+                .map(|(p, c)| (p.into(), c))
+                .collect(),
+            age: 2.0,
+            age_max_days: 365.0,
+            tags: 1.0,
+            annotations: 1.0,
+            project: 1.0,
+            active: 4.0,
+            scheduled: 5.0,
+            waiting: -3.0,
+            blocking: 8.0,
+            blocked: -5.0,
+            tag_coefficients: [("next".into(), 15.0)].into_iter().collect(),
+            project_coefficients: HashMap::new(),
+            uda_coefficients: HashMap::new(),
+            uda_value_coefficients: HashMap::new(),
+        }
+    }
+}
+
+impl Urgency {
+    /// Calculate the urgency of the given task, as of now.
+    pub fn calculate(&self, task: &Task) -> f64 {
+        self.calculate_at(task, Utc::now())
+    }
+
+    /// Calculate the urgency of the given task, as of the given time.
+    pub fn calculate_at(&self, task: &Task, now: Timestamp) -> f64 {
+        let mut urgency = 0.0;
+
+        if let Some(due) = task.get_due() {
+            urgency += self.due * due_term(due, now);
+        }
+
+        if let Some(c) = self.priority.get(task.get_priority()) {
+            urgency += c;
+        }
+
+        if let Some(entry) = task.get_entry() {
+            if self.age_max_days > 0.0 {
+                let age_days = (now - entry).num_seconds() as f64 / 86400.0;
+                urgency += self.age * (age_days / self.age_max_days).clamp(0.0, 1.0);
+            }
+        }
+
+        let mut num_tags = 0;
+        for tag in task.get_tags().filter(|t| t.is_user()) {
+            num_tags += 1;
+            if let Some(c) = self.tag_coefficients.get(&tag.to_string()) {
+                urgency += c;
+            }
+        }
+        urgency += self.tags * count_term(num_tags);
+        urgency += self.annotations * count_term(task.get_annotations().count());
+
+        if let Some(project) = task.get_value("project") {
+            urgency += self.project;
+            for (name, c) in &self.project_coefficients {
+                if project == name
+                    || project
+                        .strip_prefix(name.as_str())
+                        .is_some_and(|rest| rest.starts_with('.'))
+                {
+                    urgency += c;
+                }
+            }
+        }
+
+        if task.is_active() {
+            urgency += self.active;
+        }
+        if timestamp_value(task, "scheduled").is_some_and(|s| s < now) {
+            urgency += self.scheduled;
+        }
+        if task.get_wait().is_some_and(|w| w > now) {
+            urgency += self.waiting;
+        }
+        if task.is_blocking() {
+            urgency += self.blocking;
+        }
+        if task.is_blocked() {
+            urgency += self.blocked;
+        }
+
+        for (name, c) in &self.uda_coefficients {
+            if task.get_value(name.as_str()).is_some() {
+                urgency += c;
+            }
+        }
+        for ((name, value), c) in &self.uda_value_coefficients {
+            if task.get_value(name.as_str()) == Some(value.as_str()) {
+                urgency += c;
+            }
+        }
+
+        urgency
+    }
+}
+
+/// Calculate the due term, ramping linearly from 0.2 at 14 days before the due time to 1.0 at 7
+/// days after it.
+fn due_term(due: Timestamp, now: Timestamp) -> f64 {
+    let days_overdue = (now - due).num_seconds() as f64 / 86400.0;
+    if days_overdue >= 7.0 {
+        1.0
+    } else if days_overdue >= -14.0 {
+        ((days_overdue + 14.0) * 0.8 / 21.0) + 0.2
+    } else {
+        0.2
+    }
+}
+
+/// Calculate the term for a count of items such as tags or annotations.
+fn count_term(count: usize) -> f64 {
+    match count {
+        0 => 0.0,
+        1 => 0.8,
+        2 => 0.9,
+        _ => 1.0,
+    }
+}
+
+fn timestamp_value(task: &Task, property: &str) -> Option<Timestamp> {
+    task.get_value(property)?.parse().ok().map(utc_timestamp)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Annotation, Operations, Replica, Status, Tag};
+    use chrono::{Duration, TimeZone};
+    use pretty_assertions::assert_eq;
+    use rstest::rstest;
+    use uuid::Uuid;
+
+    fn now() -> Timestamp {
+        Utc.with_ymd_and_hms(2024, 6, 1, 12, 0, 0).unwrap()
+    }
+
+    /// Create a pending task, modify it, and return its urgency.
+    fn urgency_of<F: Fn(&mut Task, &mut Operations)>(urgency: &Urgency, modify: F) -> f64 {
+        let mut rep = Replica::new_inmemory();
+        let mut ops = Operations::new();
+        let mut task = rep.create_task(Uuid::new_v4(), &mut ops).unwrap();
+        task.set_status(Status::Pending, &mut ops).unwrap();
+        modify(&mut task, &mut ops);
+        rep.commit_operations(ops).unwrap();
+        let task = rep.get_task(task.get_uuid()).unwrap().unwrap();
+        urgency.calculate_at(&task, now())
+    }
+
+    fn assert_close(a: f64, b: f64) {
+        assert!((a - b).abs() < 0.0001, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn test_empty() {
+        assert_close(urgency_of(&Urgency::default(), |_, _| {}), 0.0);
+    }
+
+    #[rstest]
+    #[case::overdue(Duration::days(-10), 12.0)]
+    #[case::due_now(Duration::zero(), 12.0 * (14.0 * 0.8 / 21.0 + 0.2))]
+    #[case::far_future(Duration::days(30), 12.0 * 0.2)]
+    fn test_due(#[case] offset: Duration, #[case] expected: f64) {
+        let urgency = urgency_of(&Urgency::default(), |t, ops| {
+            t.set_due(Some(now() + offset), ops).unwrap();
+        });
+        assert_close(urgency, expected);
+    }
+
+    #[rstest]
+    #[case::high("H", 6.0)]
+    #[case::medium("M", 3.9)]
+    #[case::low("L", 1.8)]
+    #[case::unknown("X", 0.0)]
+    fn test_priority(#[case] priority: &'static str, #[case] expected: f64) {
+        let urgency = urgency_of(&Urgency::default(), |t, ops| {
+            t.set_priority(priority.into(), ops).unwrap();
+        });
+        assert_close(urgency, expected);
+    }
+
+    #[test]
+    fn test_age() {
+        let urgency = urgency_of(&Urgency::default(), |t, ops| {
+            t.set_entry(Some(now() - Duration::days(73)), ops).unwrap();
+        });
+        assert_close(urgency, 2.0 * 0.2);
+        let urgency = urgency_of(&Urgency::default(), |t, ops| {
+            t.set_entry(Some(now() - Duration::days(1000)), ops)
+                .unwrap();
+        });
+        assert_close(urgency, 2.0);
+    }
+
+    #[test]
+    fn test_tags() {
+        let urgency = urgency_of(&Urgency::default(), |t, ops| {
+            t.add_tag(&Tag::try_from("a").unwrap(), ops).unwrap();
+            t.add_tag(&Tag::try_from("b").unwrap(), ops).unwrap();
+        });
+        assert_close(urgency, 0.9);
+        let urgency = urgency_of(&Urgency::default(), |t, ops| {
+            t.add_tag(&Tag::try_from("next").unwrap(), ops).unwrap();
+        });
+        assert_close(urgency, 0.8 + 15.0);
+    }
+
+    #[test]
+    fn test_annotations() {
+        let urgency = urgency_of(&Urgency::default(), |t, ops| {
+            t.add_annotation(
+                Annotation {
+                    entry: now(),
+                    description: "note".into(),
+                },
+                ops,
+            )
+            .unwrap();
+        });
+        assert_close(urgency, 0.8);
+    }
+
+    #[test]
+    fn test_project() {
+        let mut coefficients = Urgency::default();
+        coefficients.project_coefficients.insert("home".into(), 3.0);
+        let urgency = urgency_of(&coefficients, |t, ops| {
+            t.set_value("project", Some("home.garden".into()), ops)
+                .unwrap();
+        });
+        assert_close(urgency, 1.0 + 3.0);
+        let urgency = urgency_of(&coefficients, |t, ops| {
+            t.set_value("project", Some("homework".into()), ops)
+                .unwrap();
+        });
+        assert_close(urgency, 1.0);
+    }
+
+    #[test]
+    fn test_active_scheduled_waiting() {
+        let urgency = urgency_of(&Urgency::default(), |t, ops| {
+            t.start(ops).unwrap();
+        });
+        assert_close(urgency, 4.0);
+        let urgency = urgency_of(&Urgency::default(), |t, ops| {
+            let scheduled = now() - Duration::days(1);
+            t.set_value("scheduled", Some(scheduled.timestamp().to_string()), ops)
+                .unwrap();
+        });
+        assert_close(urgency, 5.0);
+        let urgency = urgency_of(&Urgency::default(), |t, ops| {
+            t.set_wait(Some(now() + Duration::days(1)), ops).unwrap();
+        });
+        assert_close(urgency, -3.0);
+    }
+
+    #[test]
+    fn test_dependencies() {
+        let mut rep = Replica::new_inmemory();
+        let mut ops = Operations::new();
+        let mut t1 = rep.create_task(Uuid::new_v4(), &mut ops).unwrap();
+        t1.set_status(Status::Pending, &mut ops).unwrap();
+        let mut t2 = rep.create_task(Uuid::new_v4(), &mut ops).unwrap();
+        t2.set_status(Status::Pending, &mut ops).unwrap();
+        t2.add_dependency(t1.get_uuid(), &mut ops).unwrap();
+        rep.commit_operations(ops).unwrap();
+
+        let urgency = Urgency::default();
+        let t1 = rep.get_task(t1.get_uuid()).unwrap().unwrap();
+        let t2 = rep.get_task(t2.get_uuid()).unwrap().unwrap();
+        assert_close(urgency.calculate_at(&t1, now()), 8.0);
+        assert_close(urgency.calculate_at(&t2, now()), -5.0);
+    }
+
+    #[test]
+    fn test_udas() {
+        let mut coefficients = Urgency::default();
+        coefficients
+            .uda_coefficients
+            .insert("devsync.issue".into(), 2.0);
+        coefficients
+            .uda_value_coefficients
+            .insert(("size".into(), "large".into()), -1.5);
+        let urgency = urgency_of(&coefficients, |t, ops| {
+            t.set_uda("devsync", "issue", "123", ops).unwrap();
+            t.set_legacy_uda("size", "large", ops).unwrap();
+        });
+        assert_close(urgency, 2.0 - 1.5);
+    }
+
+    #[test]
+    fn test_zero_coefficients() {
+        let coefficients = Urgency {
+            due: 0.0,
+            priority: HashMap::new(),
+            ..Urgency::default()
+        };
+        let urgency = urgency_of(&coefficients, |t, ops| {
+            t.set_due(Some(now()), ops).unwrap();
+            t.set_priority("H".into(), ops).unwrap();
+        });
+        assert_eq!(urgency, 0.0);
+    }
+}
@@ -1,4 +1,4 @@
-use chrono::Utc;
+use chrono::{TimeZone, Utc};
 use pretty_assertions::assert_eq;
 use taskchampion::{Operations, Replica, ServerConfig, Status, StorageConfig, Uuid};
 use tempfile::TempDir;
@@ -67,3 +67,149 @@ fn cross_sync() -> anyhow::Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn cross_sync_recurrence() -> anyhow::Result<()> {
+    // two replicas generating the same recurring instances before syncing do not create
+    // duplicate tasks
+    let mut rep1 = Replica::new(StorageConfig::InMemory.into_storage()?);
+    let mut rep2 = Replica::new(StorageConfig::InMemory.into_storage()?);
+
+    let tmp_dir = TempDir::new().expect("TempDir failed");
+    let server_config = ServerConfig::Local {
+        server_dir: tmp_dir.path().to_path_buf(),
+    };
+    let mut server = server_config.into_server()?;
+
+    let due = Utc.with_ymd_and_hms(2024, 1, 1, 12, 0, 0).unwrap();
+    let until = Utc.with_ymd_and_hms(2024, 1, 10, 0, 0, 0).unwrap();
+    let uuid = Uuid::new_v4();
+    let mut ops = Operations::new();
+    let mut t = rep1.create_task(uuid, &mut ops)?;
+    t.set_description("take out the trash".into(), &mut ops)?;
+    t.set_status(Status::Recurring, &mut ops)?;
+    t.set_value("recur", Some("weekly".into()), &mut ops)?;
+    t.set_due(Some(due), &mut ops)?;
+    rep1.commit_operations(ops)?;
+
+    rep1.sync(&mut server, false)?;
+    rep2.sync(&mut server, false)?;
+
+    for rep in [&mut rep1, &mut rep2] {
+        let mut ops = Operations::new();
+        rep.generate_recurring_instances(until, &mut ops)?;
+        rep.commit_operations(ops)?;
+    }
+
+    rep1.sync(&mut server, false)?;
+    rep2.sync(&mut server, false)?;
+    rep1.sync(&mut server, false)?;
+
+    for rep in [&mut rep1, &mut rep2] {
+        let tasks = rep.all_tasks()?;
+        assert_eq!(tasks.len(), 3);
+        assert_eq!(tasks[&uuid].get_value("mask"), Some("--"));
+        assert_eq!(
+            tasks
+                .values()
+                .filter(|t| t.get_status() == Status::Pending)
+                .count(),
+            2
+        );
+    }
+
+    Ok(())
+}
+
+#[cfg(feature = "server-directory")]
+#[test]
+fn cross_sync_directory() -> anyhow::Result<()> {
+    // two replicas, each with its own server instance, sync through a shared directory
+    let mut rep1 = Replica::new(StorageConfig::InMemory.into_storage()?);
+    let mut rep2 = Replica::new(StorageConfig::InMemory.into_storage()?);
+
+    let tmp_dir = TempDir::new().expect("TempDir failed");
+    let server_config = ServerConfig::Directory {
+        path: tmp_dir.path().join("shared"),
+        encryption_secret: b"abc123".to_vec(),
+    };
+    let mut server1 = server_config.clone().into_server()?;
+    let mut server2 = server_config.into_server()?;
+
+    let uuid = Uuid::new_v4();
+    let mut ops = Operations::new();
+    let mut t = rep1.create_task(uuid, &mut ops)?;
+    t.set_description("shared".into(), &mut ops)?;
+    t.set_status(Status::Pending, &mut ops)?;
+    rep1.commit_operations(ops)?;
+
+    rep1.sync(&mut server1, false)?;
+    rep2.sync(&mut server2, false)?;
+
+    let mut t2 = rep2.get_task(uuid)?.expect("expected task on rep2");
+    assert_eq!(t2.get_description(), "shared");
+
+    let mut ops = Operations::new();
+    t2.set_status(Status::Completed, &mut ops)?;
+    rep2.commit_operations(ops)?;
+
+    rep2.sync(&mut server2, false)?;
+    rep1.sync(&mut server1, false)?;
+
+    let t = rep1.get_task(uuid)?.expect("expected task on rep1");
+    assert_eq!(t.get_status(), Status::Completed);
+
+    Ok(())
+}
+
+#[cfg(feature = "server-directory")]
+#[test]
+fn rotate_encryption_secret_directory() -> anyhow::Result<()> {
+    let mut rep1 = Replica::new(StorageConfig::InMemory.into_storage()?);
+    let mut rep2 = Replica::new(StorageConfig::InMemory.into_storage()?);
+
+    let tmp_dir = TempDir::new().expect("TempDir failed");
+    let server_config = |secret: &[u8]| ServerConfig::Directory {
+        path: tmp_dir.path().join("shared"),
+        encryption_secret: secret.to_vec(),
+    };
+    let mut server1 = server_config(b"old").into_server()?;
+    let mut server2 = server_config(b"old").into_server()?;
+
+    let uuid = Uuid::new_v4();
+    let mut ops = Operations::new();
+    let mut t = rep1.create_task(uuid, &mut ops)?;
+    t.set_description("secret".into(), &mut ops)?;
+    rep1.commit_operations(ops)?;
+    rep1.sync(&mut server1, false)?;
+    rep2.sync(&mut server2, false)?;
+
+    rep1.rotate_encryption_secret(&mut server1, b"old", b"new")?;
+
+    // rep2 cannot sync until it is given the new secret
+    assert!(matches!(
+        rep2.sync(&mut server2, false),
+        Err(taskchampion::Error::WrongEncryptionSecret)
+    ));
+    let mut server2 = server_config(b"new").into_server()?;
+    rep2.sync(&mut server2, false)?;
+
+    // rep1 continues to sync with the new secret
+    let mut ops = Operations::new();
+    t.set_status(Status::Completed, &mut ops)?;
+    rep1.commit_operations(ops)?;
+    rep1.sync(&mut server1, false)?;
+    rep2.sync(&mut server2, false)?;
+    let t2 = rep2.get_task(uuid)?.expect("expected task on rep2");
+    assert_eq!(t2.get_status(), Status::Completed);
+
+    // a new replica begins from the snapshot made with the new secret
+    let mut rep3 = Replica::new(StorageConfig::InMemory.into_storage()?);
+    let mut server3 = server_config(b"new").into_server()?;
+    rep3.sync(&mut server3, false)?;
+    let t3 = rep3.get_task(uuid)?.expect("expected task on rep3");
+    assert_eq!(t3.get_description(), "secret");
+    assert_eq!(t3.get_status(), Status::Completed);
+
+    Ok(())
+}
@@ -0,0 +1,159 @@
+#![cfg(feature = "server-sync")]
+
+use pretty_assertions::assert_eq;
+use taskchampion::{Operations, Replica, ServerConfig, Status, StorageConfig, Uuid};
+use taskchampion_sync_server::storage::{InMemoryStorage, SqliteStorage};
+use taskchampion_sync_server::{RunningServer, WebServer};
+use tempfile::TempDir;
+
+fn remote(
+    server: &RunningServer,
+    client_id: Uuid,
+) -> anyhow::Result<Box<dyn taskchampion::Server>> {
+    Ok(ServerConfig::Remote {
+        url: server.url(),
+        client_id,
+        encryption_secret: b"abc".to_vec(),
+    }
+    .into_server()?)
+}
+
+#[test]
+fn sync_via_sync_server() -> anyhow::Result<()> {
+    let sync_server = WebServer::new(Default::default(), Box::new(InMemoryStorage::new()))
+        .spawn("127.0.0.1:0")?;
+    let client_id = Uuid::new_v4();
+    let mut server1 = remote(&sync_server, client_id)?;
+    let mut server2 = remote(&sync_server, client_id)?;
+
+    let mut rep1 = Replica::new(StorageConfig::InMemory.into_storage()?);
+    let mut rep2 = Replica::new(StorageConfig::InMemory.into_storage()?);
+
+    let uuid = Uuid::new_v4();
+    let mut ops = Operations::new();
+    let mut t = rep1.create_task(uuid, &mut ops)?;
+    t.set_description("from rep1".into(), &mut ops)?;
+    t.set_status(Status::Pending, &mut ops)?;
+    rep1.commit_operations(ops)?;
+
+    rep1.sync(&mut server1, false)?;
+    rep2.sync(&mut server2, false)?;
+    let mut t2 = rep2.get_task(uuid)?.expect("task should sync to rep2");
+    assert_eq!(t2.get_description(), "from rep1");
+
+    // conflicting changes are resolved consistently
+    let mut ops = Operations::new();
+    t2.set_description("from rep2".into(), &mut ops)?;
+    rep2.commit_operations(ops)?;
+    let mut ops = Operations::new();
+    t.set_status(Status::Completed, &mut ops)?;
+    rep1.commit_operations(ops)?;
+
+    rep1.sync(&mut server1, false)?;
+    rep2.sync(&mut server2, false)?;
+    rep1.sync(&mut server1, false)?;
+
+    let t1 = rep1.get_task(uuid)?.unwrap();
+    let t2 = rep2.get_task(uuid)?.unwrap();
+    assert_eq!(t1.get_description(), "from rep2");
+    assert_eq!(t1.get_status(), Status::Completed);
+    assert_eq!(t1.into_task_data(), t2.into_task_data());
+    Ok(())
+}
+
+#[test]
+fn sync_server_isolates_clients() -> anyhow::Result<()> {
+    let tmp_dir = TempDir::new()?;
+    let sync_server = WebServer::new(
+        Default::default(),
+        Box::new(SqliteStorage::new(tmp_dir.path())?),
+    )
+    .spawn("127.0.0.1:0")?;
+    let mut server1 = remote(&sync_server, Uuid::new_v4())?;
+    let mut server2 = remote(&sync_server, Uuid::new_v4())?;
+
+    let mut rep1 = Replica::new(StorageConfig::InMemory.into_storage()?);
+    let mut rep2 = Replica::new(StorageConfig::InMemory.into_storage()?);
+
+    let mut ops = Operations::new();
+    rep1.create_task(Uuid::new_v4(), &mut ops)?;
+    rep1.commit_operations(ops)?;
+
+    rep1.sync(&mut server1, false)?;
+    rep2.sync(&mut server2, false)?;
+    assert_eq!(rep2.all_task_uuids()?.len(), 0);
+    Ok(())
+}
+
+#[test]
+fn sync_server_snapshots() -> anyhow::Result<()> {
+    let sync_server = WebServer::new(Default::default(), Box::new(InMemoryStorage::new()))
+        .spawn("127.0.0.1:0")?;
+    let client_id = Uuid::new_v4();
+    let mut server1 = remote(&sync_server, client_id)?;
+
+    let mut rep1 = Replica::new(StorageConfig::InMemory.into_storage()?);
+    let mut ops = Operations::new();
+    let uuid = Uuid::new_v4();
+    rep1.create_task(uuid, &mut ops)?;
+    rep1.commit_operations(ops)?;
+
+    // the server requests a snapshot urgently, as it has none
+    rep1.sync(&mut server1, false)?;
+    let (version_id, _) = server1
+        .get_snapshot()?
+        .expect("replica should have uploaded a snapshot");
+
+    // a new replica can sync from the snapshot
+    let mut server2 = remote(&sync_server, client_id)?;
+    let mut rep2 = Replica::new(StorageConfig::InMemory.into_storage()?);
+    rep2.sync(&mut server2, false)?;
+    assert!(rep2.get_task(uuid)?.is_some());
+    assert!(!version_id.is_nil());
+    Ok(())
+}
+
+#[test]
+fn sync_server_rotate_encryption_secret() -> anyhow::Result<()> {
+    let sync_server = WebServer::new(Default::default(), Box::new(InMemoryStorage::new()))
+        .spawn("127.0.0.1:0")?;
+    let client_id = Uuid::new_v4();
+    let mut server1 = remote(&sync_server, client_id)?;
+    let mut server2 = remote(&sync_server, client_id)?;
+
+    let mut rep1 = Replica::new(StorageConfig::InMemory.into_storage()?);
+    let mut rep2 = Replica::new(StorageConfig::InMemory.into_storage()?);
+    let uuid = Uuid::new_v4();
+    let mut ops = Operations::new();
+    rep1.create_task(uuid, &mut ops)?
+        .set_description("secret".into(), &mut ops)?;
+    rep1.commit_operations(ops)?;
+    rep1.sync(&mut server1, false)?;
+    rep2.sync(&mut server2, false)?;
+
+    rep1.rotate_encryption_secret(&mut server1, b"abc", b"def")?;
+
+    // rep2 cannot sync until it is given the new secret
+    assert!(matches!(
+        rep2.sync(&mut server2, false),
+        Err(taskchampion::Error::WrongEncryptionSecret)
+    ));
+    let new_remote = || {
+        ServerConfig::Remote {
+            url: sync_server.url(),
+            client_id,
+            encryption_secret: b"def".to_vec(),
+        }
+        .into_server()
+    };
+    let mut server2 = new_remote()?;
+    rep2.sync(&mut server2, false)?;
+
+    // a new replica begins from the snapshot made with the new secret
+    let mut rep3 = Replica::new(StorageConfig::InMemory.into_storage()?);
+    let mut server3 = new_remote()?;
+    rep3.sync(&mut server3, false)?;
+    let t3 = rep3.get_task(uuid)?.expect("expected task on rep3");
+    assert_eq!(t3.get_description(), "secret");
+    Ok(())
+}
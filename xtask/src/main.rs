@@ -23,6 +23,7 @@ const MSRV_PATH_REGEX: &[(&str, &str)] = &[
         r#"Rust version [0-9.]* and higher"#,
     ),
     ("taskchampion/Cargo.toml", r#"^rust-version = "[0-9.]"#),
+    ("sync-server/Cargo.toml", r#"^rust-version = "[0-9.]"#),
 ];
 
 pub fn main() -> anyhow::Result<()> {
@@ -31,15 +32,26 @@ pub fn main() -> anyhow::Result<()> {
     let arguments: Vec<String> = env::args().collect();
 
     if arguments.len() < 2 {
-        anyhow::bail!("xtask: Valid arguments are: `msrv <version x.y>`");
+        anyhow::bail!("xtask: Valid arguments are: `msrv <version x.y>`, `codegen`");
     }
 
     match arguments[1].as_str() {
         "msrv" => msrv(arguments, workspace_dir),
+        "codegen" => codegen(workspace_dir),
         _ => anyhow::bail!("xtask: unknown xtask"),
     }
 }
 
+/// `cargo xtask codegen`
+///
+/// This regenerates the C header for taskchampion-lib.
+fn codegen(workspace_dir: &Path) -> anyhow::Result<()> {
+    let path = workspace_dir.join("lib").join("taskchampion.h");
+    let mut file = File::create(path)?;
+    write!(&mut file, "{}", ::taskchampion_lib::generate_header())?;
+    Ok(())
+}
+
 /// `cargo xtask msrv (X.Y)`
 ///
 /// This checks and updates the Minimum Supported Rust Version for all files specified in MSRV_PATH_REGEX`.